@@ -0,0 +1,42 @@
+//! Render a few effects with the standard test signals to target/wav for listening.
+//! Run with `cargo run --example render`.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::*;
+use embedded_dsp::chain;
+use embedded_dsp::distortion::Distortion;
+use embedded_dsp::filter::Biquad;
+use embedded_dsp::modulation::Wah;
+use embedded_dsp::synthesis::{Oscillator, WaveType};
+
+const SAMPLE_RATE_F: f32 = 48000.0;
+
+fn main() {
+    let mut filter = Biquad::new(SAMPLE_RATE_F);
+    filter.set_low_pass(4000.0, 0.707);
+    let mut amp = chain!(Distortion::new(SAMPLE_RATE_F), filter);
+    render_wav(
+        "amp_sine",
+        &mut amp,
+        TestSignal::Sine(110.0),
+        SAMPLE_RATE_F,
+        2.0,
+    );
+    render_wav("amp_noise", &mut amp, TestSignal::Noise, SAMPLE_RATE_F, 2.0);
+
+    let mut wah = Wah::new(SAMPLE_RATE_F);
+    wah.set_position(1.0);
+    render_wav(
+        "wah_sweep",
+        &mut wah,
+        TestSignal::Sweep(50.0, 5000.0),
+        SAMPLE_RATE_F,
+        2.0,
+    );
+
+    let mut oscillator = Oscillator::new(WaveType::PolyBLEPSquare, SAMPLE_RATE_F, 220.0);
+    render_voice_wav("square_voice", || oscillator.process(), SAMPLE_RATE_F, 2.0);
+    println!("wrote renders to {}", wav_dir().display());
+}
@@ -0,0 +1,1025 @@
+float_math!();
+
+use crate::dynamics::EnvelopeFollower;
+use crate::fft::{real_magnitudes, Fft};
+use crate::filter::Biquad;
+use crate::units::{db_to_linear, power_to_db};
+use crate::windows::{self, Symmetry, Window};
+
+// Center frequency of bin `k` of a magnitude frame of `len` bins from DC to Nyquist.
+fn bin_freq(k: usize, len: usize, sample_rate: f32) -> f32 {
+    k as f32 * sample_rate * 0.5 / (len.max(2) - 1) as f32
+}
+
+/// Spectral centroid in Hz, the magnitude weighted mean frequency, correlating with brightness.
+/// `magnitudes` holds the bins from DC to Nyquist as produced by `fft::real_magnitudes`.
+/// Returns 0.0 for a silent frame.
+pub fn spectral_centroid(magnitudes: &[f32], sample_rate: f32) -> f32 {
+    let len = magnitudes.len();
+    let (weighted, total) =
+        magnitudes
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(weighted, total), (k, magnitude)| {
+                (
+                    weighted + bin_freq(k, len, sample_rate) * magnitude,
+                    total + magnitude,
+                )
+            });
+    if total > 0.0 {
+        weighted / total
+    } else {
+        0.0
+    }
+}
+
+/// Spectral flux, the summed increase in magnitude from the previous frame.
+/// Only rising bins count so the result peaks at note onsets rather than releases.
+pub fn spectral_flux(magnitudes: &[f32], previous: &[f32]) -> f32 {
+    magnitudes
+        .iter()
+        .zip(previous.iter())
+        .map(|(current, previous)| (current - previous).max(0.0))
+        .sum()
+}
+
+/// Spectral rolloff in Hz, the frequency below which `fraction` of the energy lies.
+/// A fraction of 0.85 is typical, it is clamped to [0.0-1.0]. Returns 0.0 for a silent frame.
+pub fn spectral_rolloff(magnitudes: &[f32], sample_rate: f32, fraction: f32) -> f32 {
+    let total: f32 = magnitudes
+        .iter()
+        .map(|magnitude| magnitude * magnitude)
+        .sum();
+    if total <= 0.0 {
+        return 0.0;
+    }
+    let target = total * fraction.clamp(0.0, 1.0);
+    let mut energy = 0.0;
+    for (k, magnitude) in magnitudes.iter().enumerate() {
+        energy += magnitude * magnitude;
+        if energy >= target {
+            return bin_freq(k, magnitudes.len(), sample_rate);
+        }
+    }
+    sample_rate * 0.5
+}
+
+/// Real cepstrum of a windowed frame in place, the inverse FFT of the log magnitude spectrum.
+/// Index `n` of the result is the quefrency of `n` samples, periodic signals peak at their period.
+pub fn real_cepstrum<const N: usize>(fft: &Fft<N>, frame: &mut [f32; N]) {
+    fft.process_real(frame);
+    // Packed DC and Nyquist bins are both real.
+    frame[0] = frame[0].abs().max(1e-10).ln();
+    frame[1] = frame[1].abs().max(1e-10).ln();
+    for bin in frame[2..].chunks_exact_mut(2) {
+        let magnitude = (bin[0] * bin[0] + bin[1] * bin[1]).sqrt();
+        bin[0] = magnitude.max(1e-10).ln();
+        bin[1] = 0.0;
+    }
+    fft.inverse_real(frame);
+}
+
+/// Estimate the fundamental frequency in Hz from the strongest cepstrum peak with a period in
+/// the given frequency range. Returns 0.0 if the range doesn't fit the cepstrum.
+pub fn cepstrum_pitch(cepstrum: &[f32], sample_rate: f32, min_freq: f32, max_freq: f32) -> f32 {
+    let min_lag = ((sample_rate / max_freq.max(1.0)) as usize).max(1);
+    let max_lag = ((sample_rate / min_freq.max(1.0)) as usize).min(cepstrum.len() / 2);
+    if min_lag >= max_lag {
+        return 0.0;
+    }
+    let mut best_lag = min_lag;
+    for lag in min_lag..=max_lag {
+        if cepstrum[lag] > cepstrum[best_lag] {
+            best_lag = lag;
+        }
+    }
+    sample_rate / best_lag as f32
+}
+
+/// Smoothed log magnitude spectral envelope from a cepstrum, keeping quefrencies below `lifter`
+/// samples. The lifter should be below the pitch period so the harmonics are removed.
+/// `envelope` receives the natural log magnitude of bins from DC to Nyquist. The cepstrum is
+/// overwritten.
+pub fn cepstrum_envelope<const N: usize>(
+    fft: &Fft<N>,
+    cepstrum: &mut [f32; N],
+    lifter: usize,
+    envelope: &mut [f32],
+) {
+    let lifter = lifter.clamp(1, N / 2);
+    for (n, x) in cepstrum.iter_mut().enumerate() {
+        // The cepstrum of a real spectrum is symmetric, lifter both ends. A tapered lifter avoids
+        // ripple in the envelope that could be mistaken for peaks.
+        let quefrency = n.min(N - n);
+        *x *= if quefrency < lifter {
+            0.5 + 0.5 * (core::f32::consts::PI * quefrency as f32 / lifter as f32).cos()
+        } else {
+            0.0
+        };
+    }
+    fft.process_real(cepstrum);
+    for (k, value) in envelope.iter_mut().enumerate().take(N / 2 + 1) {
+        *value = match k {
+            0 => cepstrum[0],
+            k if k == N / 2 => cepstrum[1],
+            k => cepstrum[2 * k],
+        };
+    }
+}
+
+/// Find the frequencies in Hz of local maxima in a spectral envelope of bins from DC to Nyquist,
+/// such as rough formants from `cepstrum_envelope`. Peaks are written lowest first and the
+/// number found is returned.
+pub fn find_peaks(envelope: &[f32], sample_rate: f32, peaks: &mut [f32]) -> usize {
+    let mut count = 0;
+    for k in 1..envelope.len().saturating_sub(1) {
+        if count == peaks.len() {
+            break;
+        }
+        if envelope[k] > envelope[k - 1] && envelope[k] >= envelope[k + 1] {
+            peaks[count] = bin_freq(k, envelope.len(), sample_rate);
+            count += 1;
+        }
+    }
+    count
+}
+
+// Lowest level reported by the spectrum analyzer in dBFS.
+const SPECTRUM_FLOOR: f32 = -120.0;
+
+/// Spectrum analyzer for LED and display spectrum meters.
+/// Input is Hann windowed and transformed every `N / 2` samples, then the bins are mapped onto
+/// `BANDS` log spaced bands in dBFS, each showing its loudest bin. Bands rise instantly and fall
+/// at the decay rate.
+pub struct SpectrumAnalyzer<const N: usize, const BANDS: usize> {
+    sample_rate: f32,
+    fft: Fft<N>,
+    window: [f32; N],
+    scale: f32,
+    input: [f32; N],
+    write_index: usize,
+    counter: usize,
+    frame: [f32; N],
+    // Bin magnitudes, DC to Nyquist in the first `N / 2 + 1`.
+    magnitudes: [f32; N],
+    band_start: [usize; BANDS],
+    band_end: [usize; BANDS],
+    decay: f32,
+    bands: [f32; BANDS],
+}
+
+impl<const N: usize, const BANDS: usize> SpectrumAnalyzer<N, BANDS> {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut window = [0.0; N];
+        windows::fill(Window::Hann, Symmetry::Periodic, &mut window);
+        // A full scale sine reads 0 dB.
+        let scale = 2.0 / window.iter().sum::<f32>();
+        let mut analyzer = Self {
+            sample_rate,
+            fft: Fft::new(),
+            window,
+            scale,
+            input: [0.0; N],
+            write_index: 0,
+            counter: 0,
+            frame: [0.0; N],
+            magnitudes: [0.0; N],
+            band_start: [0; BANDS],
+            band_end: [0; BANDS],
+            decay: 0.0,
+            bands: [SPECTRUM_FLOOR; BANDS],
+        };
+        analyzer.set_freq_range(40.0, 16000.0);
+        analyzer.set_decay(30.0);
+        analyzer
+    }
+
+    pub fn process(&mut self, input: f32) {
+        self.input[self.write_index] = input;
+        self.write_index = (self.write_index + 1) % N;
+        self.counter += 1;
+        if self.counter >= N / 2 {
+            self.counter = 0;
+            self.analyze();
+        }
+    }
+
+    pub fn process_block(&mut self, block: &[f32]) {
+        for sample in block {
+            self.process(*sample);
+        }
+    }
+
+    fn analyze(&mut self) {
+        for (i, x) in self.frame.iter_mut().enumerate() {
+            *x = self.input[(self.write_index + i) % N] * self.window[i];
+        }
+        self.fft.process_real(&mut self.frame);
+        real_magnitudes(&self.frame, &mut self.magnitudes);
+
+        let decay = self.decay * (N / 2) as f32 / self.sample_rate;
+        for band in 0..BANDS {
+            let peak = self.magnitudes[self.band_start[band]..self.band_end[band]]
+                .iter()
+                .fold(0.0_f32, |peak, magnitude| peak.max(*magnitude));
+            let level = (20.0 * (peak * self.scale).max(1e-10).log10()).max(SPECTRUM_FLOOR);
+            self.bands[band] = level.max(self.bands[band] - decay);
+        }
+    }
+
+    /// Set the frequency range covered by the bands in Hz.
+    /// Bands narrower than a bin show the nearest bin.
+    pub fn set_freq_range(&mut self, min_freq: f32, max_freq: f32) {
+        let nyquist = self.sample_rate * 0.5;
+        let min_freq = min_freq.clamp(1.0, nyquist);
+        let max_freq = max_freq.clamp(min_freq, nyquist);
+        let bin_width = self.sample_rate / N as f32;
+        let ratio = max_freq / min_freq;
+        for band in 0..BANDS {
+            let low = min_freq * ratio.powf(band as f32 / BANDS as f32);
+            let high = min_freq * ratio.powf((band + 1) as f32 / BANDS as f32);
+            let start = ((low / bin_width + 0.5) as usize).min(N / 2);
+            let end = ((high / bin_width + 0.5) as usize).min(N / 2 + 1);
+            self.band_start[band] = start;
+            self.band_end[band] = end.max(start + 1).min(N / 2 + 1);
+        }
+    }
+
+    /// Set how fast the bands fall in dB per second.
+    pub fn set_decay(&mut self, decay: f32) {
+        self.decay = decay.max(0.0);
+    }
+
+    /// Get the band levels in dBFS, lowest frequency first.
+    pub fn get_bands(&self) -> &[f32; BANDS] {
+        &self.bands
+    }
+
+    /// Clear the input and drop the bands to the floor.
+    pub fn reset(&mut self) {
+        self.input = [0.0; N];
+        self.write_index = 0;
+        self.counter = 0;
+        self.bands = [SPECTRUM_FLOOR; BANDS];
+    }
+}
+
+const ONSET_BANDS: usize = 3;
+
+/// Onset detector for drum triggers and beat reactive effects.
+/// The input is split into low, mid and high bands and a fast envelope is compared against a slow
+/// one in each, a sudden rise in any band counts towards the detection function. For spectral
+/// frames `spectral_flux` gives a similar detection function.
+pub struct OnsetDetector {
+    sample_rate: f32,
+    bands: [Biquad; ONSET_BANDS],
+    fast: [EnvelopeFollower; ONSET_BANDS],
+    slow: [EnvelopeFollower; ONSET_BANDS],
+    threshold: f32,
+    floor: f32,
+    refractory: u32,
+    remaining: u32,
+    armed: bool,
+    detection: f32,
+}
+
+impl OnsetDetector {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut bands = [
+            Biquad::new(sample_rate),
+            Biquad::new(sample_rate),
+            Biquad::new(sample_rate),
+        ];
+        bands[0].set_low_pass(200.0, 0.707);
+        bands[1].set_band_pass(1000.0, 0.5);
+        bands[2].set_high_pass(4000.0, 0.707);
+        let fast = core::array::from_fn(|_| {
+            let mut envelope = EnvelopeFollower::new(sample_rate);
+            envelope.set_attack(0.001);
+            envelope.set_release(0.02);
+            envelope
+        });
+        let slow = core::array::from_fn(|_| {
+            let mut envelope = EnvelopeFollower::new(sample_rate);
+            envelope.set_attack(0.1);
+            envelope.set_release(0.1);
+            envelope
+        });
+        let mut detector = Self {
+            sample_rate,
+            bands,
+            fast,
+            slow,
+            threshold: 0.0,
+            floor: 0.0,
+            refractory: 0,
+            remaining: 0,
+            armed: true,
+            detection: 0.0,
+        };
+        detector.set_sensitivity(0.5);
+        detector.set_min_level(-60.0);
+        detector.set_refractory(0.05);
+        detector
+    }
+
+    /// Returns true on the sample an onset is detected.
+    pub fn process(&mut self, input: f32) -> bool {
+        let mut detection = 0.0;
+        for band in 0..ONSET_BANDS {
+            let x = self.bands[band].process(input);
+            let fast = self.fast[band].process(x);
+            // Smoothing the fast envelope keeps steady signals at 0 dB regardless of crest factor.
+            let slow = self.slow[band].process(fast).max(self.floor);
+            let fast = fast.max(self.floor);
+            // Rise of the fast envelope over the slow one in dB.
+            detection += (20.0 * (fast / slow).log10()).max(0.0);
+        }
+        self.detection = detection;
+
+        if self.remaining > 0 {
+            self.remaining -= 1;
+        }
+        if detection < self.threshold * 0.5 {
+            self.armed = true;
+        }
+        if self.armed && self.remaining == 0 && detection > self.threshold {
+            self.armed = false;
+            self.remaining = self.refractory;
+            return true;
+        }
+        false
+    }
+
+    /// Set the sensitivity, clamped to [0.0-1.0].
+    /// Higher values detect softer transients, scaling the threshold from 24 dB to 6 dB.
+    pub fn set_sensitivity(&mut self, sensitivity: f32) {
+        self.threshold = 6.0 + 18.0 * (1.0 - sensitivity.clamp(0.0, 1.0));
+    }
+
+    /// Set the minimum time in seconds between onsets.
+    pub fn set_refractory(&mut self, refractory: f32) {
+        self.refractory = (refractory.max(0.0) * self.sample_rate) as u32;
+    }
+
+    /// Set the level in dB below which signals are ignored.
+    pub fn set_min_level(&mut self, level: f32) {
+        self.floor = db_to_linear(level);
+    }
+
+    /// Get the detection function, the summed envelope rise in dB across the bands.
+    pub fn get_detection(&self) -> f32 {
+        self.detection
+    }
+
+    pub fn reset(&mut self) {
+        for band in self.bands.iter_mut() {
+            band.reset();
+        }
+        for envelope in self.fast.iter_mut().chain(self.slow.iter_mut()) {
+            envelope.reset();
+        }
+        self.remaining = 0;
+        self.armed = true;
+        self.detection = 0.0;
+    }
+}
+
+// Onset envelope history of the beat tracker in frames, about 5 seconds at 100 frames per second.
+const BEAT_FRAMES: usize = 512;
+const BEAT_FRAME_RATE: f32 = 100.0;
+// Frames between tempo estimates.
+const BEAT_UPDATE: usize = 50;
+// Beats combined when estimating the beat phase.
+const BEAT_COMB: usize = 4;
+// Slowest tempo that can be tracked, and the autocorrelation lags it needs around its period.
+const MIN_BEAT_BPM: f32 = 30.0;
+const BEAT_LAGS: usize = (60.0 * BEAT_FRAME_RATE / MIN_BEAT_BPM) as usize + 3;
+
+/// Tempo and beat tracker for syncing delays and LFOs to a live input.
+/// The onset detection function is recorded at 100 frames per second, its autocorrelation gives
+/// the beat period and a comb over the recent onsets gives the beat phase. Both are re-estimated
+/// twice a second and drive a free running beat clock between estimates. The autocorrelation is
+/// spread over the frames in between, so no single sample pays for the whole search.
+pub struct BeatTracker {
+    sample_rate: f32,
+    onsets: OnsetDetector,
+    hop: usize,
+    counter: usize,
+    frame_peak: f32,
+    envelope: [f32; BEAT_FRAMES],
+    index: usize,
+    frames: usize,
+    update_counter: usize,
+    // Autocorrelation by lag of the search in progress, and its range of beat periods in frames.
+    correlation: [f32; BEAT_LAGS],
+    mean: f32,
+    lags: [usize; 2],
+    next_lag: usize,
+    min_bpm: f32,
+    max_bpm: f32,
+    bpm: f32,
+    phase: f32,
+    since_tick: u32,
+}
+
+impl BeatTracker {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            onsets: OnsetDetector::new(sample_rate),
+            hop: ((sample_rate / BEAT_FRAME_RATE) as usize).max(1),
+            counter: 0,
+            frame_peak: 0.0,
+            envelope: [0.0; BEAT_FRAMES],
+            index: 0,
+            frames: 0,
+            update_counter: 0,
+            correlation: [0.0; BEAT_LAGS],
+            mean: 0.0,
+            lags: [0; 2],
+            next_lag: 0,
+            min_bpm: 70.0,
+            max_bpm: 180.0,
+            bpm: 0.0,
+            phase: 0.0,
+            since_tick: 0,
+        }
+    }
+
+    /// Returns true on the sample of each beat, once a tempo has been found.
+    pub fn process(&mut self, input: f32) -> bool {
+        self.onsets.process(input);
+        self.frame_peak = self.frame_peak.max(self.onsets.get_detection());
+        self.counter += 1;
+        if self.counter >= self.hop {
+            self.counter = 0;
+            self.envelope[self.index] = self.frame_peak;
+            self.index = (self.index + 1) % BEAT_FRAMES;
+            self.frames = (self.frames + 1).min(BEAT_FRAMES);
+            self.frame_peak = 0.0;
+            self.update_counter += 1;
+            if self.update_counter >= BEAT_UPDATE {
+                self.update_counter = 0;
+                self.estimate();
+                self.start_search();
+            } else {
+                self.search();
+            }
+        }
+
+        if self.bpm <= 0.0 {
+            return false;
+        }
+        let period = 60.0 * self.sample_rate / self.bpm;
+        self.since_tick = self.since_tick.saturating_add(1);
+        self.phase += 1.0 / period;
+        if self.phase >= 1.0 {
+            self.phase -= 1.0;
+            // Phase corrections can move the clock back, don't tick twice for one beat.
+            if self.since_tick as f32 > period * 0.5 {
+                self.since_tick = 0;
+                return true;
+            }
+        }
+        false
+    }
+
+    // Onset envelope `frames_ago` frames back, 0 is the most recent frame.
+    fn frame(&self, frames_ago: usize) -> f32 {
+        self.envelope[(self.index + 2 * BEAT_FRAMES - 1 - frames_ago) % BEAT_FRAMES]
+    }
+
+    // Autocorrelation of the onset envelope at `lag` frames, around the mean of the search.
+    fn autocorrelation(&self, lag: usize) -> f32 {
+        let count = self.frames.saturating_sub(lag);
+        if count == 0 {
+            return 0.0;
+        }
+        (0..count)
+            .map(|i| (self.frame(i) - self.mean) * (self.frame(i + lag) - self.mean))
+            .sum::<f32>()
+            / count as f32
+    }
+
+    fn start_search(&mut self) {
+        let min_lag = ((60.0 * BEAT_FRAME_RATE / self.max_bpm) as usize).max(2);
+        let max_lag = (60.0 * BEAT_FRAME_RATE / self.min_bpm) as usize + 1;
+        self.lags = [min_lag, max_lag];
+        self.mean = (0..self.frames).map(|i| self.frame(i)).sum::<f32>() / self.frames as f32;
+        // One lag either side of the range for interpolating the peak.
+        self.next_lag = min_lag - 1;
+    }
+
+    // Fill the next few lags, finishing the search by the time of the next estimate.
+    fn search(&mut self) {
+        let [min_lag, max_lag] = self.lags;
+        let end = max_lag + 2;
+        let per_frame = (end - min_lag + BEAT_UPDATE - 1) / (BEAT_UPDATE - 1);
+        let stop = (self.next_lag + per_frame).min(end);
+        for lag in self.next_lag..stop {
+            self.correlation[lag] = self.autocorrelation(lag);
+        }
+        self.next_lag = self.next_lag.max(stop);
+    }
+
+    fn estimate(&mut self) {
+        let [min_lag, max_lag] = self.lags;
+        if self.next_lag < max_lag + 2 || self.frames < max_lag * BEAT_COMB || min_lag + 2 > max_lag
+        {
+            return;
+        }
+
+        let mut best_lag = 0;
+        let mut best = 0.0;
+        for lag in min_lag..=max_lag {
+            let value = self.correlation[lag];
+            if value > best {
+                best = value;
+                best_lag = lag;
+            }
+        }
+        if best_lag == 0 {
+            return;
+        }
+        // Parabolic interpolation of the peak.
+        let (previous, next) = (
+            self.correlation[best_lag - 1],
+            self.correlation[best_lag + 1],
+        );
+        let denominator = previous - 2.0 * best + next;
+        let offset = if denominator.abs() > f32::EPSILON {
+            (0.5 * (previous - next) / denominator).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+        let period = best_lag as f32 + offset;
+
+        // Find how long ago the last beat was from the strongest comb of onsets.
+        let mut best_offset = 0;
+        let mut best_score = f32::MIN;
+        for beat_offset in 0..(period as usize).max(1) {
+            let score: f32 = (0..BEAT_COMB)
+                .map(|k| self.frame(beat_offset + (k as f32 * period + 0.5) as usize))
+                .sum();
+            if score > best_score {
+                best_score = score;
+                best_offset = beat_offset;
+            }
+        }
+
+        self.bpm = 60.0 * BEAT_FRAME_RATE / period;
+        self.phase = ((best_offset as f32 + 0.5) / period).fract();
+    }
+
+    /// Set the range of tempos in beats per minute that can be detected.
+    /// A narrow range avoids locking onto half or double the tempo.
+    pub fn set_tempo_range(&mut self, min_bpm: f32, max_bpm: f32) {
+        self.min_bpm = min_bpm.max(MIN_BEAT_BPM);
+        self.max_bpm = max_bpm.max(self.min_bpm + 1.0);
+    }
+
+    /// Set the sensitivity of the onset detector, clamped to [0.0-1.0].
+    pub fn set_sensitivity(&mut self, sensitivity: f32) {
+        self.onsets.set_sensitivity(sensitivity);
+    }
+
+    /// Get the detected tempo in beats per minute, 0.0 until a tempo is found.
+    pub fn get_bpm(&self) -> f32 {
+        self.bpm
+    }
+
+    /// Get the position within the current beat, [0.0-1.0).
+    pub fn get_phase(&self) -> f32 {
+        self.phase
+    }
+
+    /// Forget the onset history and the tempo.
+    pub fn reset(&mut self) {
+        self.onsets.reset();
+        self.counter = 0;
+        self.frame_peak = 0.0;
+        self.envelope = [0.0; BEAT_FRAMES];
+        self.index = 0;
+        self.frames = 0;
+        self.update_counter = 0;
+        self.lags = [0; 2];
+        self.next_lag = 0;
+        self.bpm = 0.0;
+        self.phase = 0.0;
+        self.since_tick = 0;
+    }
+}
+
+/// Measures the long term DC offset and noise floor of an input, for calibrating ADC offsets and
+/// detecting hardware faults. The noise floor is the minimum of the short term level with the DC
+/// removed, allowed to rise slowly so it recovers after the floor changes.
+pub struct DcNoiseAnalyzer {
+    sample_rate: f32,
+    dc_coef: f32,
+    level_coef: f32,
+    rise: f32,
+    dc: f32,
+    level: f32,
+    floor: f32,
+    warmup: u32,
+}
+
+impl DcNoiseAnalyzer {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut analyzer = Self {
+            sample_rate,
+            dc_coef: 0.0,
+            level_coef: (-1.0 / (0.05 * sample_rate)).exp(),
+            rise: 0.0,
+            dc: 0.0,
+            level: 0.0,
+            floor: f32::MAX,
+            // Let the short term level settle before tracking its minimum.
+            warmup: (0.25 * sample_rate) as u32,
+        };
+        analyzer.set_dc_time(1.0);
+        analyzer.set_rise_rate(1.0);
+        analyzer
+    }
+
+    pub fn process(&mut self, input: f32) {
+        self.dc = input + self.dc_coef * (self.dc - input);
+        let ac = input - self.dc;
+        let square = ac * ac;
+        self.level = square + self.level_coef * (self.level - square);
+        if self.warmup > 0 {
+            self.warmup -= 1;
+            return;
+        }
+        self.floor = (self.floor * self.rise).min(self.level);
+    }
+
+    pub fn process_block(&mut self, block: &[f32]) {
+        for sample in block {
+            self.process(*sample);
+        }
+    }
+
+    /// Set the averaging time in seconds of the DC offset measurement.
+    pub fn set_dc_time(&mut self, time: f32) {
+        self.dc_coef = (-1.0 / (time.max(1e-6) * self.sample_rate)).exp();
+    }
+
+    /// Set how fast the noise floor can rise in dB per second.
+    pub fn set_rise_rate(&mut self, rate: f32) {
+        // The floor is tracked as power.
+        self.rise = 10.0_f32.powf(rate.max(0.0) / (10.0 * self.sample_rate));
+    }
+
+    /// Get the DC offset in linear units of full scale.
+    pub fn get_dc_offset(&self) -> f32 {
+        self.dc
+    }
+
+    /// Get the RMS noise floor in dBFS, or None until enough input has been measured.
+    pub fn get_noise_floor(&self) -> Option<f32> {
+        if self.floor == f32::MAX {
+            None
+        } else {
+            Some(power_to_db(self.floor))
+        }
+    }
+
+    /// Restart the measurement.
+    pub fn reset(&mut self) {
+        self.dc = 0.0;
+        self.level = 0.0;
+        self.floor = f32::MAX;
+        self.warmup = (0.25 * self.sample_rate) as u32;
+    }
+}
+
+/// Energy based voice activity detector for keying transmission or recording.
+/// The input is band limited to the voice range and each block's energy is compared against an
+/// adaptive noise floor, which falls quickly and rises slowly. Activity is held for a hangover time
+/// so gaps between words don't cut off.
+pub struct VoiceActivityDetector {
+    sample_rate: f32,
+    high_pass: Biquad,
+    low_pass: Biquad,
+    threshold: f32,
+    rise: f32,
+    hangover: f32,
+    floor: Option<f32>,
+    since_voice: f32,
+    active: bool,
+    confidence: f32,
+}
+
+impl VoiceActivityDetector {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut high_pass = Biquad::new(sample_rate);
+        high_pass.set_high_pass(200.0, 0.707);
+        let mut low_pass = Biquad::new(sample_rate);
+        low_pass.set_low_pass(4000.0, 0.707);
+        Self {
+            sample_rate,
+            high_pass,
+            low_pass,
+            threshold: 9.0,
+            rise: 3.0,
+            hangover: 0.3,
+            floor: None,
+            since_voice: f32::MAX,
+            active: false,
+            confidence: 0.0,
+        }
+    }
+
+    /// Analyse a block of input, returning true while voice is active.
+    pub fn process_block(&mut self, block: &[f32]) -> bool {
+        if block.is_empty() {
+            return self.active;
+        }
+        let mut energy = 0.0;
+        for x in block {
+            let filtered = self.low_pass.process(self.high_pass.process(*x));
+            energy += filtered * filtered;
+        }
+        let level = power_to_db(energy / block.len() as f32);
+        let duration = block.len() as f32 / self.sample_rate;
+
+        let floor = match self.floor {
+            Some(floor) if level > floor => floor + self.rise * duration,
+            // Follow drops in the noise quickly.
+            Some(floor) => floor + (level - floor) * 0.5,
+            None => level,
+        };
+        self.floor = Some(floor);
+
+        let snr = level - floor;
+        self.confidence = (snr / (2.0 * self.threshold)).clamp(0.0, 1.0);
+        if snr > self.threshold {
+            self.since_voice = 0.0;
+        } else {
+            self.since_voice += duration;
+        }
+        self.active = self.since_voice <= self.hangover;
+        self.active
+    }
+
+    /// Set the level above the noise floor in dB counted as voice.
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold.max(0.0);
+    }
+
+    /// Set the time in seconds activity is held after the voice stops.
+    pub fn set_hangover(&mut self, hangover: f32) {
+        self.hangover = hangover.max(0.0);
+    }
+
+    /// Set how fast the noise floor estimate can rise in dB per second.
+    pub fn set_floor_rise(&mut self, rate: f32) {
+        self.rise = rate.max(0.0);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Get the confidence of the last block containing voice, [0.0-1.0].
+    /// A block at the threshold reads 0.5.
+    pub fn get_confidence(&self) -> f32 {
+        self.confidence
+    }
+
+    /// Clear the filters and forget the noise floor.
+    pub fn reset(&mut self) {
+        self.high_pass.reset();
+        self.low_pass.reset();
+        self.floor = None;
+        self.since_voice = f32::MAX;
+        self.active = false;
+        self.confidence = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const SAMPLE_RATE_F: f32 = 44100.0;
+
+    use super::*;
+    use crate::random::{Random, XorShift32};
+    use crate::synthesis::{Oscillator, WaveType};
+    use crate::windows::fill;
+
+    fn magnitude_frame(fft: &Fft<1024>, oscillator: &mut Oscillator) -> [f32; 513] {
+        let mut data = [0.0; 1024];
+        // Hann window, leakage of a rectangular window would pull the centroid upwards.
+        fill(Window::Hann, Symmetry::Periodic, &mut data);
+        for x in data.iter_mut() {
+            *x *= oscillator.process();
+        }
+        fft.process_real(&mut data);
+        let mut magnitudes = [0.0; 513];
+        real_magnitudes(&data, &mut magnitudes);
+        magnitudes
+    }
+
+    #[test]
+    fn test_centroid_and_rolloff_of_sine() {
+        let fft = Fft::new();
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 1000.0);
+        let magnitudes = magnitude_frame(&fft, &mut oscillator);
+        assert!((spectral_centroid(&magnitudes, SAMPLE_RATE_F) - 1000.0).abs() < 50.0);
+        assert!((spectral_rolloff(&magnitudes, SAMPLE_RATE_F, 0.85) - 1000.0).abs() < 100.0);
+        assert_eq!(spectral_centroid(&[0.0; 513], SAMPLE_RATE_F), 0.0);
+    }
+
+    #[test]
+    fn test_brighter_spectrum() {
+        let fft = Fft::new();
+        let mut sine = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 220.0);
+        let mut saw = Oscillator::new(WaveType::PolyBLEPSaw, SAMPLE_RATE_F, 220.0);
+        let sine = magnitude_frame(&fft, &mut sine);
+        let saw = magnitude_frame(&fft, &mut saw);
+        assert!(
+            spectral_centroid(&saw, SAMPLE_RATE_F) > 2.0 * spectral_centroid(&sine, SAMPLE_RATE_F)
+        );
+        assert!(
+            spectral_rolloff(&saw, SAMPLE_RATE_F, 0.85)
+                > spectral_rolloff(&sine, SAMPLE_RATE_F, 0.85)
+        );
+    }
+
+    fn noise_bursts(detector: &mut OnsetDetector, interval: usize, count: usize) -> usize {
+        let mut random = XorShift32::new(7);
+        let mut onsets = 0;
+        for i in 0..interval * count {
+            let noise = random.next_f32_unit() - 0.5;
+            // Exponentially decaying burst at the start of each interval.
+            let envelope = (-((i % interval) as f32) / 400.0).exp();
+            if detector.process(noise * envelope) {
+                onsets += 1;
+            }
+        }
+        onsets
+    }
+
+    #[test]
+    fn test_onsets_of_bursts() {
+        let mut detector = OnsetDetector::new(SAMPLE_RATE_F);
+        assert_eq!(noise_bursts(&mut detector, 22050, 8), 8);
+
+        // A second burst inside the refractory period is ignored.
+        let mut detector = OnsetDetector::new(SAMPLE_RATE_F);
+        detector.set_refractory(0.15);
+        assert_eq!(noise_bursts(&mut detector, 4410, 10), 5);
+    }
+
+    #[test]
+    fn test_beat_tracker_tempo_and_phase() {
+        let mut tracker = BeatTracker::new(SAMPLE_RATE_F);
+        // 120 BPM bursts starting 0.1 s in.
+        let interval = 22050;
+        let start = 4410;
+        let mut random = XorShift32::new(3);
+        let mut ticks = Vec::new();
+        for i in 0..(44100 * 12) {
+            let noise = random.next_f32_unit() - 0.5;
+            let position = (i + interval - start) % interval;
+            let envelope = (-(position as f32) / 400.0).exp();
+            if tracker.process(noise * envelope) {
+                ticks.push(i);
+            }
+        }
+        assert!(
+            (tracker.get_bpm() - 120.0).abs() < 2.0,
+            "{}",
+            tracker.get_bpm()
+        );
+        let late: Vec<usize> = ticks.into_iter().filter(|i| *i > 44100 * 8).collect();
+        assert!(late.len() >= 7 && late.len() <= 9);
+        for tick in late {
+            // Ticks land within 30 ms of the bursts.
+            let position = (tick + interval - start) % interval;
+            let error = position.min(interval - position);
+            assert!(error < 1323, "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_dc_and_noise_floor() {
+        let mut analyzer = DcNoiseAnalyzer::new(SAMPLE_RATE_F);
+        assert_eq!(analyzer.get_noise_floor(), None);
+        let mut random = XorShift32::new(11);
+        for i in 0..(44100 * 8) {
+            // Uniform noise with an RMS of 0.001, -60 dBFS.
+            let noise = (random.next_f32_unit() - 0.5) * 0.001 * 12.0_f32.sqrt();
+            // Loud bursts must not lift the floor.
+            let burst = if i % 44100 < 4410 {
+                0.5 * (i as f32 * 0.1).sin()
+            } else {
+                0.0
+            };
+            analyzer.process(0.01 + noise + burst);
+        }
+        assert!((analyzer.get_dc_offset() - 0.01).abs() < 0.001);
+        let floor = analyzer.get_noise_floor().unwrap();
+        assert!((floor + 60.0).abs() < 2.0, "{}", floor);
+    }
+
+    #[test]
+    fn test_voice_activity() {
+        let mut detector = VoiceActivityDetector::new(SAMPLE_RATE_F);
+        let mut voice = Oscillator::new(WaveType::PolyBLEPSaw, SAMPLE_RATE_F, 150.0);
+        voice.set_amplitude(0.1);
+        let mut random = XorShift32::new(5);
+        let mut block = [0.0; 441];
+        // Two seconds of noise, one of voice and two of noise in 10 ms blocks.
+        for i in 0..500 {
+            let voiced = (200..300).contains(&i);
+            for x in block.iter_mut() {
+                *x = (random.next_f32_unit() - 0.5) * 0.003;
+                if voiced {
+                    *x += voice.process();
+                }
+            }
+            let active = detector.process_block(&block);
+            match i {
+                10..=199 => assert!(!active, "{}", i),
+                205..=299 => assert!(active && detector.get_confidence() > 0.9, "{}", i),
+                // Hangover
+                300..=325 => assert!(active, "{}", i),
+                340..=499 => assert!(!active, "{}", i),
+                _ => (),
+            }
+        }
+    }
+
+    #[test]
+    fn test_no_onsets_in_steady_tone() {
+        let mut detector = OnsetDetector::new(SAMPLE_RATE_F);
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 440.0);
+        let onsets = (0..44100)
+            .filter(|_| detector.process(oscillator.process()))
+            .count();
+        // Only the start of the tone.
+        assert_eq!(onsets, 1);
+    }
+
+    #[test]
+    fn test_cepstrum_pitch_and_formants() {
+        let fft: Fft<2048> = Fft::new();
+        // Pulse train through two resonances, a rough /a/ vowel.
+        let mut formants = [Biquad::new(SAMPLE_RATE_F), Biquad::new(SAMPLE_RATE_F)];
+        formants[0].set_band_pass(700.0, 5.0);
+        formants[1].set_band_pass(1200.0, 5.0);
+        let mut frame = [0.0; 2048];
+        fill(Window::Hann, Symmetry::Periodic, &mut frame);
+        for (i, x) in frame.iter_mut().enumerate() {
+            let pulse = if i % 294 == 0 { 1.0 } else { 0.0 };
+            *x *= formants[0].process(pulse) + 0.5 * formants[1].process(pulse);
+        }
+
+        real_cepstrum(&fft, &mut frame);
+        let pitch = cepstrum_pitch(&frame, SAMPLE_RATE_F, 60.0, 500.0);
+        assert!((pitch - 150.0).abs() < 2.0, "{}", pitch);
+
+        let mut envelope = [0.0; 1025];
+        cepstrum_envelope(&fft, &mut frame, 100, &mut envelope);
+        let mut peaks = [0.0; 4];
+        let count = find_peaks(&envelope, SAMPLE_RATE_F, &mut peaks);
+        assert!(count >= 2);
+        assert!((peaks[0] - 700.0).abs() < 150.0, "{:?}", peaks);
+        assert!((peaks[1] - 1200.0).abs() < 150.0, "{:?}", peaks);
+    }
+
+    #[test]
+    fn test_spectrum_analyzer_bands() {
+        let mut analyzer: SpectrumAnalyzer<1024, 16> = SpectrumAnalyzer::new(SAMPLE_RATE_F);
+        analyzer.set_freq_range(50.0, 12800.0);
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 1000.0);
+        // Long enough for the start of the tone to decay away.
+        for _ in 0..88200 {
+            analyzer.process(oscillator.process());
+        }
+        // Bands are half an octave wide, 1 kHz falls in band 8 from 800 to 1131 Hz.
+        let bands = *analyzer.get_bands();
+        assert!(bands[8].abs() < 1.0, "{:?}", bands);
+        for (band, level) in bands.iter().enumerate() {
+            if (band as i32 - 8).abs() > 2 {
+                assert!(*level < -60.0, "{:?}", bands);
+            }
+        }
+
+        // Half a second of silence drops the band by 15 dB at the default decay.
+        analyzer.process_block(&[0.0; 22016]);
+        let level = analyzer.get_bands()[8];
+        assert!((level + 15.0).abs() < 1.0, "{}", level);
+    }
+
+    #[test]
+    fn test_flux_rises_only() {
+        let quiet = [0.1; 8];
+        let loud = [1.0; 8];
+        assert!((spectral_flux(&loud, &quiet) - 7.2).abs() < 1e-5);
+        assert_eq!(spectral_flux(&quiet, &loud), 0.0);
+    }
+}
@@ -0,0 +1,133 @@
+//! Measuring the processing time of blocks on the target hardware, to check effects fit the CPU
+//! budget. Counts come from the DWT cycle counter on Cortex-M and a monotonic clock in
+//! nanoseconds on hosted targets.
+
+use crate::processor::Processor;
+
+/// A free running counter that wraps around, read before and after the code being measured.
+pub trait CycleCounter {
+    fn count(&mut self) -> u32;
+
+    /// Counts from `start` to now, correct across one wrap of the counter.
+    fn elapsed(&mut self, start: u32) -> u32 {
+        self.count().wrapping_sub(start)
+    }
+}
+
+// Cortex-M debug registers, the same addresses on every core with a DWT.
+#[cfg(target_arch = "arm")]
+const DEMCR: *mut u32 = 0xe000_edfc as *mut u32;
+#[cfg(target_arch = "arm")]
+const DWT_CTRL: *mut u32 = 0xe000_1000 as *mut u32;
+#[cfg(target_arch = "arm")]
+const DWT_CYCCNT: *const u32 = 0xe000_1004 as *const u32;
+
+/// Core clock cycles from the DWT CYCCNT register on Cortex-M3 and up.
+#[cfg(target_arch = "arm")]
+pub struct DwtCycleCounter {
+    _private: (),
+}
+
+#[cfg(target_arch = "arm")]
+impl DwtCycleCounter {
+    /// Enable tracing and the cycle counter.
+    ///
+    /// # Safety
+    /// Must run on a Cortex-M core with a DWT unit, and nothing else may be using the DWT or
+    /// writing DEMCR at the same time, for example a debugger.
+    pub unsafe fn new() -> Self {
+        core::ptr::write_volatile(DEMCR, core::ptr::read_volatile(DEMCR) | 1 << 24);
+        core::ptr::write_volatile(DWT_CTRL, core::ptr::read_volatile(DWT_CTRL) | 1);
+        Self { _private: () }
+    }
+}
+
+#[cfg(target_arch = "arm")]
+impl CycleCounter for DwtCycleCounter {
+    fn count(&mut self) -> u32 {
+        // SAFETY: the register exists as checked by the constructor, and reading it has no side
+        // effects.
+        unsafe { core::ptr::read_volatile(DWT_CYCCNT) }
+    }
+}
+
+/// Nanoseconds from `std::time::Instant` on hosted targets, wrapping after about 4 seconds.
+#[cfg(not(target_os = "none"))]
+pub struct InstantCounter {
+    start: std::time::Instant,
+}
+
+#[cfg(not(target_os = "none"))]
+impl InstantCounter {
+    pub fn new() -> Self {
+        Self {
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+impl Default for InstantCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+impl CycleCounter for InstantCounter {
+    fn count(&mut self) -> u32 {
+        self.start.elapsed().as_nanos() as u32
+    }
+}
+
+/// Measure processors in place, implemented for every `Processor`.
+pub trait Measure: Processor {
+    /// Process a block in place and return the counts it took.
+    fn measure_block<C: CycleCounter>(&mut self, counter: &mut C, block: &mut [f32]) -> u32 {
+        let start = counter.count();
+        self.process_block(block);
+        counter.elapsed(start)
+    }
+}
+
+impl<P: Processor + ?Sized> Measure for P {}
+
+/// Fraction of the CPU used by a block taking `cycles` at `clock` Hz, 1.0 uses the whole time
+/// between blocks. With `InstantCounter` pass a clock of 1e9 for nanoseconds.
+pub fn cpu_load(cycles: u32, block_len: usize, sample_rate: f32, clock: f32) -> f32 {
+    let available = clock * block_len as f32 / sample_rate;
+    cycles as f32 / available
+}
+
+#[cfg(test)]
+mod tests {
+    const SAMPLE_RATE_F: f32 = 48000.0;
+
+    use super::*;
+    use crate::filter::Biquad;
+
+    struct FakeCounter(u32);
+
+    impl CycleCounter for FakeCounter {
+        fn count(&mut self) -> u32 {
+            self.0 = self.0.wrapping_add(100);
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_measure_block() {
+        let mut filter = Biquad::new(SAMPLE_RATE_F);
+        filter.set_low_pass(1000.0, 0.707);
+        let mut block = [1.0; 48];
+        // Counts across the wrap of the counter.
+        let mut counter = FakeCounter(u32::MAX - 50);
+        assert_eq!(filter.measure_block(&mut counter, &mut block), 100);
+        assert!(block[47] > 0.0);
+        let mut counter = InstantCounter::new();
+        filter.measure_block(&mut counter, &mut block);
+
+        // 480 MHz with 48 samples at 48 kHz has 480000 cycles available.
+        assert_eq!(cpu_load(120000, 48, SAMPLE_RATE_F, 480e6), 0.25);
+    }
+}
@@ -0,0 +1,70 @@
+#[cfg(all(feature = "cmsis", target_arch = "arm"))]
+use crate::cmsis;
+
+/// Number of samples or voices processed together by the block helpers and the `*4` processors.
+/// Loops are written over fixed arrays of this width so they vectorize on targets with SIMD and
+/// keep independent operations in flight on superscalar FPUs.
+pub const LANES: usize = 4;
+
+/// Sum of the products of two slices, up to the length of the shorter one.
+pub fn dot_product(a: &[f32], b: &[f32]) -> f32 {
+    #[cfg(all(feature = "cmsis", target_arch = "arm"))]
+    {
+        cmsis::dot_product(a, b)
+    }
+    #[cfg(not(all(feature = "cmsis", target_arch = "arm")))]
+    {
+        let len = a.len().min(b.len());
+        let (a, b) = (&a[..len], &b[..len]);
+        // Separate accumulators break the dependency between additions.
+        let mut sums = [0.0; LANES];
+        let mut a_chunks = a.chunks_exact(LANES);
+        let mut b_chunks = b.chunks_exact(LANES);
+        for (a, b) in (&mut a_chunks).zip(&mut b_chunks) {
+            for lane in 0..LANES {
+                sums[lane] += a[lane] * b[lane];
+            }
+        }
+        let tail: f32 = a_chunks
+            .remainder()
+            .iter()
+            .zip(b_chunks.remainder().iter())
+            .map(|(a, b)| a * b)
+            .sum();
+        (sums[0] + sums[1]) + (sums[2] + sums[3]) + tail
+    }
+}
+
+/// Multiply a block by a gain in place.
+pub fn scale(block: &mut [f32], gain: f32) {
+    let mut chunks = block.chunks_exact_mut(LANES);
+    for chunk in &mut chunks {
+        for sample in chunk.iter_mut() {
+            *sample *= gain;
+        }
+    }
+    for sample in chunks.into_remainder() {
+        *sample *= gain;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dot_product() {
+        assert_eq!(dot_product(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]), 32.0);
+        assert_eq!(dot_product(&[1.0, 2.0, 3.0], &[4.0]), 4.0);
+        assert_eq!(dot_product(&[], &[4.0]), 0.0);
+        let a: Vec<f32> = (0..11).map(|i| i as f32).collect();
+        assert_eq!(dot_product(&a, &a), 385.0);
+    }
+
+    #[test]
+    fn test_scale() {
+        let mut block: Vec<f32> = (0..7).map(|i| i as f32).collect();
+        scale(&mut block, 2.0);
+        assert_eq!(block, [0.0, 2.0, 4.0, 6.0, 8.0, 10.0, 12.0]);
+    }
+}
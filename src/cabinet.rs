@@ -0,0 +1,217 @@
+use crate::delay::DelayLine;
+use crate::filter::Fir;
+use crate::preset::Preset;
+use crate::processor::Processor;
+use crate::units::db_to_linear;
+
+/// Longest impulse response the cabinet simulator will convolve.
+pub const MAX_IR_LEN: usize = 2048;
+
+/// Sample rate the built in impulse responses were generated at.
+pub const IR_SAMPLE_RATE: f32 = 48000.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct CabSimParams {
+    /// Output level in dB.
+    pub level: f32,
+}
+
+/// Speaker cabinet simulation, convolving the input with a short impulse response.
+/// Use one of the built in responses or any user supplied one up to `MAX_IR_LEN` taps. The
+/// impulse response is borrowed, so it isn't part of the preset.
+pub struct CabSim<'a> {
+    fir: Fir<'a>,
+    level: f32,
+    gain: f32,
+}
+
+impl<'a> CabSim<'a> {
+    /// The delay line must be at least as long as the impulse response, longer responses are truncated.
+    pub fn new(impulse_response: &'a [f32], delay_line: DelayLine<'a>) -> Self {
+        let len = impulse_response.len().min(MAX_IR_LEN);
+        Self {
+            fir: Fir::new(&impulse_response[..len], delay_line),
+            level: 0.0,
+            gain: 1.0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.fir.process(input) * self.gain
+    }
+
+    /// Load a different impulse response.
+    pub fn set_impulse_response(&mut self, impulse_response: &'a [f32]) {
+        let len = impulse_response.len().min(MAX_IR_LEN);
+        self.fir.set_coefs(&impulse_response[..len]);
+    }
+
+    /// Set the output level in dB.
+    pub fn set_level(&mut self, level: f32) {
+        self.level = level;
+        self.gain = db_to_linear(level);
+    }
+
+    /// Clear the convolution history.
+    pub fn reset(&mut self) {
+        self.fir.reset();
+    }
+}
+
+impl Processor for CabSim<'_> {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+impl Preset for CabSim<'_> {
+    type Params = CabSimParams;
+
+    fn get_params(&self) -> CabSimParams {
+        CabSimParams { level: self.level }
+    }
+
+    fn set_params(&mut self, params: &CabSimParams) {
+        self.set_level(params.level);
+    }
+}
+
+/// Open back 1x12 combo.
+pub const IR_1X12: [f32; 256] = [
+    0.003735, 0.023329, 0.067045, 0.120422, 0.155380, 0.155537, 0.124194, 0.075947, 0.026524,
+    -0.013278, -0.039055, -0.051361, -0.053345, -0.048725, -0.040609, -0.031115, -0.021492,
+    -0.012432, -0.004360, 0.002397, 0.007561, 0.010928, 0.012417, 0.012106, 0.010236, 0.007180,
+    0.003392, -0.000655, -0.004525, -0.007859, -0.010406, -0.012033, -0.012718, -0.012539,
+    -0.011645, -0.010236, -0.008530, -0.006743, -0.005065, -0.003649, -0.002597, -0.001961,
+    -0.001744, -0.001909, -0.002384, -0.003079, -0.003897, -0.004740, -0.005524, -0.006184,
+    -0.006675, -0.006976, -0.007087, -0.007028, -0.006832, -0.006540, -0.006197, -0.005845,
+    -0.005521, -0.005256, -0.005067, -0.004962, -0.004942, -0.004995, -0.005107, -0.005259,
+    -0.005431, -0.005603, -0.005760, -0.005888, -0.005980, -0.006033, -0.006046, -0.006026,
+    -0.005979, -0.005913, -0.005838, -0.005763, -0.005694, -0.005637, -0.005596, -0.005571,
+    -0.005562, -0.005568, -0.005583, -0.005606, -0.005630, -0.005654, -0.005673, -0.005685,
+    -0.005689, -0.005684, -0.005671, -0.005651, -0.005625, -0.005595, -0.005562, -0.005530,
+    -0.005498, -0.005469, -0.005442, -0.005418, -0.005397, -0.005377, -0.005359, -0.005342,
+    -0.005324, -0.005306, -0.005286, -0.005264, -0.005239, -0.005213, -0.005184, -0.005153,
+    -0.005121, -0.005087, -0.005053, -0.005018, -0.004983, -0.004948, -0.004913, -0.004878,
+    -0.004843, -0.004808, -0.004773, -0.004737, -0.004701, -0.004664, -0.004627, -0.004589,
+    -0.004550, -0.004510, -0.004469, -0.004427, -0.004385, -0.004342, -0.004299, -0.004255,
+    -0.004211, -0.004166, -0.004121, -0.004076, -0.004031, -0.003985, -0.003939, -0.003893,
+    -0.003846, -0.003798, -0.003751, -0.003703, -0.003654, -0.003605, -0.003556, -0.003506,
+    -0.003456, -0.003406, -0.003355, -0.003304, -0.003253, -0.003201, -0.003150, -0.003098,
+    -0.003046, -0.002993, -0.002941, -0.002888, -0.002835, -0.002782, -0.002729, -0.002675,
+    -0.002621, -0.002568, -0.002514, -0.002459, -0.002405, -0.002351, -0.002296, -0.002242,
+    -0.002187, -0.002133, -0.002078, -0.002023, -0.001968, -0.001913, -0.001859, -0.001804,
+    -0.001749, -0.001694, -0.001639, -0.001584, -0.001530, -0.001475, -0.001420, -0.001365,
+    -0.001308, -0.001250, -0.001191, -0.001131, -0.001070, -0.001010, -0.000949, -0.000888,
+    -0.000827, -0.000767, -0.000707, -0.000648, -0.000591, -0.000534, -0.000479, -0.000425,
+    -0.000373, -0.000323, -0.000275, -0.000229, -0.000185, -0.000143, -0.000103, -0.000066,
+    -0.000032, 0.000000, 0.000030, 0.000057, 0.000081, 0.000103, 0.000122, 0.000139, 0.000153,
+    0.000165, 0.000175, 0.000182, 0.000187, 0.000190, 0.000191, 0.000190, 0.000187, 0.000182,
+    0.000177, 0.000169, 0.000161, 0.000152, 0.000141, 0.000130, 0.000119, 0.000107, 0.000095,
+    0.000083, 0.000071, 0.000060, 0.000049, 0.000039, 0.000029, 0.000021, 0.000014, 0.000008,
+    0.000004, 0.000001,
+];
+
+/// Closed back 4x12, tight low end and strong upper mid.
+pub const IR_4X12: [f32; 256] = [
+    0.002279, 0.014881, 0.045469, 0.088701, 0.127457, 0.146424, 0.140297, 0.113119, 0.074066,
+    0.033052, -0.002258, -0.027669, -0.042286, -0.047559, -0.046103, -0.040704, -0.033666,
+    -0.026535, -0.020110, -0.014622, -0.009966, -0.005916, -0.002267, 0.001093, 0.004167, 0.006883,
+    0.009133, 0.010813, 0.011858, 0.012251, 0.012028, 0.011266, 0.010072, 0.008566, 0.006869,
+    0.005093, 0.003334, 0.001670, 0.000160, -0.001154, -0.002250, -0.003119, -0.003763, -0.004197,
+    -0.004443, -0.004527, -0.004481, -0.004338, -0.004131, -0.003891, -0.003645, -0.003418,
+    -0.003228, -0.003088, -0.003008, -0.002991, -0.003037, -0.003141, -0.003296, -0.003493,
+    -0.003722, -0.003970, -0.004228, -0.004485, -0.004732, -0.004960, -0.005165, -0.005342,
+    -0.005487, -0.005602, -0.005685, -0.005740, -0.005768, -0.005774, -0.005761, -0.005735,
+    -0.005699, -0.005657, -0.005613, -0.005570, -0.005531, -0.005498, -0.005471, -0.005452,
+    -0.005441, -0.005438, -0.005442, -0.005452, -0.005468, -0.005487, -0.005509, -0.005532,
+    -0.005556, -0.005580, -0.005602, -0.005623, -0.005642, -0.005658, -0.005672, -0.005683,
+    -0.005693, -0.005701, -0.005707, -0.005713, -0.005718, -0.005723, -0.005727, -0.005733,
+    -0.005738, -0.005745, -0.005752, -0.005761, -0.005769, -0.005779, -0.005789, -0.005800,
+    -0.005811, -0.005822, -0.005832, -0.005843, -0.005852, -0.005862, -0.005870, -0.005877,
+    -0.005884, -0.005889, -0.005894, -0.005897, -0.005899, -0.005901, -0.005901, -0.005900,
+    -0.005899, -0.005896, -0.005893, -0.005888, -0.005883, -0.005878, -0.005871, -0.005864,
+    -0.005856, -0.005847, -0.005838, -0.005828, -0.005817, -0.005805, -0.005793, -0.005780,
+    -0.005766, -0.005751, -0.005735, -0.005719, -0.005702, -0.005684, -0.005665, -0.005645,
+    -0.005625, -0.005604, -0.005582, -0.005560, -0.005537, -0.005513, -0.005488, -0.005463,
+    -0.005437, -0.005410, -0.005383, -0.005355, -0.005327, -0.005298, -0.005268, -0.005238,
+    -0.005207, -0.005176, -0.005144, -0.005111, -0.005078, -0.005044, -0.005010, -0.004975,
+    -0.004939, -0.004903, -0.004867, -0.004830, -0.004792, -0.004754, -0.004715, -0.004676,
+    -0.004636, -0.004596, -0.004555, -0.004514, -0.004472, -0.004427, -0.004377, -0.004321,
+    -0.004259, -0.004193, -0.004122, -0.004046, -0.003965, -0.003881, -0.003792, -0.003700,
+    -0.003605, -0.003507, -0.003406, -0.003302, -0.003197, -0.003089, -0.002980, -0.002870,
+    -0.002759, -0.002648, -0.002536, -0.002424, -0.002313, -0.002202, -0.002092, -0.001983,
+    -0.001875, -0.001769, -0.001665, -0.001563, -0.001463, -0.001366, -0.001271, -0.001180,
+    -0.001091, -0.001005, -0.000923, -0.000845, -0.000769, -0.000698, -0.000630, -0.000565,
+    -0.000505, -0.000448, -0.000395, -0.000346, -0.000300, -0.000258, -0.000220, -0.000185,
+    -0.000154, -0.000126, -0.000101, -0.000079, -0.000061, -0.000045, -0.000032, -0.000021,
+    -0.000013, -0.000007, -0.000003, -0.000001,
+];
+
+/// Bright 2x10.
+pub const IR_2X10: [f32; 256] = [
+    0.007698, 0.044224, 0.112879, 0.171477, 0.174865, 0.124130, 0.052877, -0.008355, -0.046664,
+    -0.063296, -0.065028, -0.058221, -0.046943, -0.033487, -0.019420, -0.006196, 0.004790,
+    0.012435, 0.016167, 0.016040, 0.012668, 0.007048, 0.000345, -0.006313, -0.011994, -0.016059,
+    -0.018204, -0.018444, -0.017057, -0.014503, -0.011326, -0.008068, -0.005192, -0.003031,
+    -0.001764, -0.001413, -0.001870, -0.002931, -0.004343, -0.005845, -0.007205, -0.008251,
+    -0.008883, -0.009075, -0.008866, -0.008346, -0.007632, -0.006848, -0.006110, -0.005506,
+    -0.005091, -0.004885, -0.004874, -0.005021, -0.005272, -0.005566, -0.005850, -0.006076,
+    -0.006216, -0.006257, -0.006202, -0.006066, -0.005875, -0.005656, -0.005437, -0.005240,
+    -0.005082, -0.004970, -0.004904, -0.004878, -0.004880, -0.004898, -0.004919, -0.004930,
+    -0.004924, -0.004896, -0.004846, -0.004776, -0.004691, -0.004597, -0.004500, -0.004406,
+    -0.004320, -0.004243, -0.004178, -0.004122, -0.004073, -0.004030, -0.003988, -0.003946,
+    -0.003900, -0.003850, -0.003795, -0.003735, -0.003671, -0.003605, -0.003537, -0.003470,
+    -0.003405, -0.003341, -0.003280, -0.003222, -0.003166, -0.003111, -0.003057, -0.003004,
+    -0.002950, -0.002895, -0.002840, -0.002784, -0.002726, -0.002669, -0.002611, -0.002553,
+    -0.002496, -0.002440, -0.002384, -0.002330, -0.002276, -0.002222, -0.002170, -0.002117,
+    -0.002065, -0.002013, -0.001961, -0.001909, -0.001858, -0.001806, -0.001755, -0.001704,
+    -0.001653, -0.001603, -0.001554, -0.001505, -0.001456, -0.001408, -0.001360, -0.001313,
+    -0.001266, -0.001220, -0.001174, -0.001128, -0.001083, -0.001038, -0.000993, -0.000949,
+    -0.000905, -0.000862, -0.000819, -0.000777, -0.000735, -0.000694, -0.000653, -0.000612,
+    -0.000572, -0.000533, -0.000494, -0.000455, -0.000417, -0.000379, -0.000342, -0.000305,
+    -0.000268, -0.000232, -0.000197, -0.000162, -0.000127, -0.000093, -0.000060, -0.000027,
+    0.000006, 0.000038, 0.000070, 0.000101, 0.000132, 0.000162, 0.000192, 0.000221, 0.000250,
+    0.000279, 0.000307, 0.000334, 0.000361, 0.000388, 0.000414, 0.000440, 0.000465, 0.000490,
+    0.000514, 0.000538, 0.000561, 0.000584, 0.000607, 0.000628, 0.000649, 0.000668, 0.000686,
+    0.000702, 0.000717, 0.000730, 0.000741, 0.000751, 0.000760, 0.000766, 0.000771, 0.000775,
+    0.000776, 0.000776, 0.000774, 0.000771, 0.000766, 0.000759, 0.000751, 0.000741, 0.000730,
+    0.000718, 0.000704, 0.000688, 0.000672, 0.000654, 0.000636, 0.000616, 0.000595, 0.000574,
+    0.000552, 0.000529, 0.000506, 0.000482, 0.000458, 0.000433, 0.000409, 0.000384, 0.000360,
+    0.000335, 0.000311, 0.000288, 0.000264, 0.000241, 0.000219, 0.000198, 0.000177, 0.000157,
+    0.000138, 0.000120, 0.000103, 0.000087, 0.000072, 0.000059, 0.000047, 0.000036, 0.000027,
+    0.000018, 0.000012, 0.000007, 0.000003, 0.000001,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cab_sim_impulse() {
+        let mut buffer = [0.0; 256];
+        let mut cab_sim = CabSim::new(&IR_4X12, DelayLine::new(&mut buffer));
+        for (i, coef) in IR_4X12.iter().enumerate() {
+            let output = cab_sim.process(if i == 0 { 1.0 } else { 0.0 });
+            assert!((output - coef).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_cab_sim_user_ir() {
+        let impulse_response = [0.0, 1.0];
+        let mut buffer = [0.0; 2];
+        let mut cab_sim = CabSim::new(&impulse_response, DelayLine::new(&mut buffer));
+        cab_sim.set_level(-6.0);
+        assert_eq!(cab_sim.get_params().level, -6.0);
+        assert_eq!(cab_sim.process(1.0), 0.0);
+        assert!((cab_sim.process(0.0) - 0.501).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,143 @@
+// Bindings to the CMSIS-DSP library used when the `cmsis` feature is enabled on Arm targets.
+// The application links the prebuilt library for its core, for example libarm_cortexM4lf_math.a,
+// so only the few functions used are declared here instead of depending on a bindings crate.
+
+#[repr(C)]
+struct BiquadCascadeDf2TInstance {
+    num_stages: u8,
+    state: *mut f32,
+    coefs: *const f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CfftInstance {
+    fft_len: u16,
+    twiddle: *const f32,
+    bit_rev_table: *const u16,
+    bit_rev_length: u16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct RfftFastInstance {
+    inner: CfftInstance,
+    fft_len: u16,
+    twiddle: *const f32,
+}
+
+// arm_status, ARM_MATH_SUCCESS is 0.
+type Status = i32;
+
+extern "C" {
+    fn arm_dot_prod_f32(a: *const f32, b: *const f32, block_size: u32, result: *mut f32);
+    fn arm_biquad_cascade_df2T_f32(
+        instance: *const BiquadCascadeDf2TInstance,
+        input: *const f32,
+        output: *mut f32,
+        block_size: u32,
+    );
+    fn arm_cfft_init_f32(instance: *mut CfftInstance, fft_len: u16) -> Status;
+    fn arm_cfft_f32(instance: *const CfftInstance, data: *mut f32, inverse: u8, bit_reverse: u8);
+    fn arm_rfft_fast_init_f32(instance: *mut RfftFastInstance, fft_len: u16) -> Status;
+    fn arm_rfft_fast_f32(
+        instance: *const RfftFastInstance,
+        input: *mut f32,
+        output: *mut f32,
+        inverse: u8,
+    );
+}
+
+pub(crate) fn dot_product(a: &[f32], b: &[f32]) -> f32 {
+    let len = a.len().min(b.len());
+    let mut result = 0.0;
+    // SAFETY: both pointers are valid for `len` reads.
+    unsafe { arm_dot_prod_f32(a.as_ptr(), b.as_ptr(), len as u32, &mut result) };
+    result
+}
+
+/// Run one transposed direct form II stage in place, `coefs` are b0, b1, b2, -a1, -a2.
+pub(crate) fn biquad_block(coefs: &[f32; 5], state: &mut [f32; 2], block: &mut [f32]) {
+    let instance = BiquadCascadeDf2TInstance {
+        num_stages: 1,
+        state: state.as_mut_ptr(),
+        coefs: coefs.as_ptr(),
+    };
+    // SAFETY: the instance points at live coefficient and state arrays of the sizes CMSIS expects
+    // for one stage, and the biquad functions support processing in place.
+    unsafe {
+        arm_biquad_cascade_df2T_f32(
+            &instance,
+            block.as_ptr(),
+            block.as_mut_ptr(),
+            block.len() as u32,
+        )
+    };
+}
+
+/// CMSIS FFT instances, only created for the sizes CMSIS has tables for.
+pub(crate) struct FftInstances {
+    complex: Option<CfftInstance>,
+    real: Option<RfftFastInstance>,
+}
+
+impl FftInstances {
+    pub(crate) fn new(len: usize) -> Self {
+        let len = if len <= u16::MAX as usize {
+            len as u16
+        } else {
+            0
+        };
+        let mut complex = CfftInstance {
+            fft_len: 0,
+            twiddle: core::ptr::null(),
+            bit_rev_table: core::ptr::null(),
+            bit_rev_length: 0,
+        };
+        let mut real = RfftFastInstance {
+            inner: complex,
+            fft_len: 0,
+            twiddle: core::ptr::null(),
+        };
+        // SAFETY: the init functions only write the instance structs, pointing them at static
+        // tables, and report unsupported sizes through their status.
+        let complex_ok = unsafe { arm_cfft_init_f32(&mut complex, len) } == 0;
+        let real_ok = unsafe { arm_rfft_fast_init_f32(&mut real, len) } == 0;
+        Self {
+            complex: if complex_ok { Some(complex) } else { None },
+            real: if real_ok { Some(real) } else { None },
+        }
+    }
+
+    /// Complex transform of interleaved data in place, returns false if the size isn't supported.
+    pub(crate) fn complex(&self, data: &mut [f32], inverse: bool) -> bool {
+        match &self.complex {
+            Some(instance) => {
+                // SAFETY: the data holds fft_len complex values as the instance was created with.
+                unsafe { arm_cfft_f32(instance, data.as_mut_ptr(), inverse as u8, 1) };
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Real transform from `input` into `output`, `input` is used as scratch space.
+    /// Returns false if the size isn't supported.
+    pub(crate) fn real(&self, input: &mut [f32], output: &mut [f32], inverse: bool) -> bool {
+        match &self.real {
+            Some(instance) => {
+                // SAFETY: both buffers hold fft_len values as the instance was created with.
+                unsafe {
+                    arm_rfft_fast_f32(
+                        instance,
+                        input.as_mut_ptr(),
+                        output.as_mut_ptr(),
+                        inverse as u8,
+                    )
+                };
+                true
+            }
+            None => false,
+        }
+    }
+}
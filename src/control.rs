@@ -0,0 +1,985 @@
+float_math!();
+
+/// Limits how fast a signal can rise and fall, for control voltage style smoothing and lo-fi effects.
+pub struct SlewLimiter {
+    sample_rate: f32,
+    rise: f32,
+    fall: f32,
+    value: f32,
+}
+
+impl SlewLimiter {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            rise: f32::INFINITY,
+            fall: f32::INFINITY,
+            value: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let delta = (input - self.value).clamp(-self.fall, self.rise);
+        self.value += delta;
+        self.value
+    }
+
+    /// Set the maximum rise rate in units per second.
+    pub fn set_rise(&mut self, rate: f32) {
+        self.rise = rate.abs() / self.sample_rate;
+    }
+
+    /// Set the maximum fall rate in units per second.
+    pub fn set_fall(&mut self, rate: f32) {
+        self.fall = rate.abs() / self.sample_rate;
+    }
+
+    pub fn get_value(&self) -> f32 {
+        self.value
+    }
+
+    pub fn reset(&mut self) {
+        self.value = 0.0;
+    }
+}
+
+// Distance from the target at which a one-pole smoother snaps to it.
+const SMOOTHER_SETTLED: f32 = 1e-5;
+
+/// How a `Smoother` moves to a new target.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SmootherMode {
+    /// Exponential approach, reaching ~63% of a change in the smoothing time. Good for noisy
+    /// inputs such as pots read from an ADC.
+    OnePole,
+    /// Straight line reaching the target in exactly the smoothing time.
+    Linear,
+}
+
+/// Smooths parameter changes to avoid zipper noise, call `next` once per sample or per control
+/// rate tick with the matching sample rate.
+pub struct Smoother {
+    sample_rate: f32,
+    mode: SmootherMode,
+    time: f32,
+    coef: f32,
+    value: f32,
+    target: f32,
+    step: f32,
+    remaining: u32,
+}
+
+impl Smoother {
+    /// Linear mode with a 20 ms smoothing time.
+    pub const fn new(sample_rate: f32) -> Self {
+        Self::with_value(sample_rate, 0.0)
+    }
+
+    /// Linear mode with a 20 ms smoothing time, settled at `value`.
+    pub const fn with_value(sample_rate: f32, value: f32) -> Self {
+        Self {
+            sample_rate,
+            mode: SmootherMode::Linear,
+            time: 0.02,
+            coef: 0.0,
+            value,
+            target: value,
+            step: 0.0,
+            remaining: 0,
+        }
+    }
+
+    /// Advance one sample or tick and return the smoothed value.
+    // Never ends, so it isn't an iterator.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> f32 {
+        match self.mode {
+            SmootherMode::OnePole => {
+                self.value = self.target + self.coef * (self.value - self.target);
+                if (self.value - self.target).abs() < SMOOTHER_SETTLED {
+                    self.value = self.target;
+                }
+            }
+            SmootherMode::Linear => {
+                if self.remaining > 0 {
+                    self.remaining -= 1;
+                    self.value = if self.remaining == 0 {
+                        self.target
+                    } else {
+                        self.value + self.step
+                    };
+                }
+            }
+        }
+        self.value
+    }
+
+    /// Set the value to move to.
+    pub fn set_target(&mut self, target: f32) {
+        self.target = target;
+        let samples = (self.time * self.sample_rate) as u32;
+        if samples == 0 {
+            self.value = target;
+            self.remaining = 0;
+        } else {
+            self.step = (target - self.value) / samples as f32;
+            self.remaining = samples;
+        }
+    }
+
+    /// Jump to a value without smoothing.
+    pub const fn set_immediate(&mut self, value: f32) {
+        self.target = value;
+        self.value = value;
+        self.remaining = 0;
+    }
+
+    /// Set the smoothing time in seconds, changes in progress keep their old time.
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time.max(0.0);
+        self.calc_coef();
+    }
+
+    pub fn set_mode(&mut self, mode: SmootherMode) {
+        self.mode = mode;
+        self.calc_coef();
+        self.set_target(self.target);
+    }
+
+    fn calc_coef(&mut self) {
+        self.coef = if self.time > 0.0 {
+            (-1.0 / (self.time * self.sample_rate)).exp()
+        } else {
+            0.0
+        };
+    }
+
+    /// True once the value has reached the target.
+    pub fn is_settled(&self) -> bool {
+        self.value == self.target
+    }
+
+    pub fn get_value(&self) -> f32 {
+        self.value
+    }
+
+    pub fn get_target(&self) -> f32 {
+        self.target
+    }
+
+    /// Jump to the target, ending any ramp.
+    pub fn reset(&mut self) {
+        self.set_immediate(self.target);
+    }
+}
+
+/// Connects a hardware pot to a parameter that can also change elsewhere, for example from a
+/// preset. The pot only takes over once it reaches the stored value, either by coming within the
+/// pickup window or by crossing it, so loading a preset doesn't make the parameter jump.
+/// Readings within the deadband of the last accepted reading are ignored as ADC noise.
+pub struct SoftTakeover {
+    value: f32,
+    last_raw: Option<f32>,
+    window: f32,
+    deadband: f32,
+    picked_up: bool,
+}
+
+impl SoftTakeover {
+    /// Takes over immediately, with a 0.02 pickup window and a 0.005 deadband.
+    pub const fn new() -> Self {
+        Self {
+            value: 0.0,
+            last_raw: None,
+            window: 0.02,
+            deadband: 0.005,
+            picked_up: true,
+        }
+    }
+
+    /// Feed a raw reading normalized to [0.0-1.0].
+    /// Returns the new parameter value if the pot is in control and has moved.
+    pub fn process(&mut self, raw: f32) -> Option<f32> {
+        let last = self.last_raw;
+        if let Some(last) = last {
+            if (raw - last).abs() < self.deadband {
+                return None;
+            }
+        }
+        self.last_raw = Some(raw);
+        if !self.picked_up {
+            let crossed = match last {
+                Some(last) => (last - self.value) * (raw - self.value) <= 0.0,
+                None => false,
+            };
+            if !crossed && (raw - self.value).abs() > self.window {
+                return None;
+            }
+            self.picked_up = true;
+        }
+        self.value = raw;
+        Some(raw)
+    }
+
+    /// Store a value set elsewhere, the pot has to pick it up again before taking over.
+    pub fn set_value(&mut self, value: f32) {
+        self.value = value;
+        self.picked_up = false;
+    }
+
+    /// Set how close the pot has to come to the stored value to take over.
+    pub fn set_window(&mut self, window: f32) {
+        self.window = window.max(0.0);
+    }
+
+    /// Set the smallest change in reading that is accepted, larger values filter more noise but
+    /// lower the resolution.
+    pub fn set_deadband(&mut self, deadband: f32) {
+        self.deadband = deadband.max(0.0);
+    }
+
+    pub fn get_value(&self) -> f32 {
+        self.value
+    }
+
+    /// True while the pot controls the parameter.
+    pub fn is_picked_up(&self) -> bool {
+        self.picked_up
+    }
+
+    /// Forget the last reading, the pot takes over again from its next reading.
+    pub fn reset(&mut self) {
+        self.last_raw = None;
+        self.picked_up = true;
+    }
+}
+
+impl Default for SoftTakeover {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Response of a `CcMap` over the controller range.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CcCurve {
+    Linear,
+    /// Equal ratios per step, for frequencies and times. Both ends of the range must be positive.
+    Log,
+    /// Squared, finer control at the low end, for levels and depths.
+    Exp,
+    /// The minimum below the middle of the controller range and the maximum above it.
+    Switched,
+}
+
+/// Maps MIDI CC values to a parameter range through a curve, smoothing the steps between values.
+/// Call `next` once per sample or per control rate tick with the matching sample rate.
+pub struct CcMap {
+    min: f32,
+    max: f32,
+    curve: CcCurve,
+    smoother: Smoother,
+}
+
+impl CcMap {
+    /// Linear curve starting at `min`, smoothed over 20 ms.
+    pub const fn new(sample_rate: f32, min: f32, max: f32) -> Self {
+        Self {
+            min,
+            max,
+            curve: CcCurve::Linear,
+            smoother: Smoother::with_value(sample_rate, min),
+        }
+    }
+
+    /// Map a normalized controller position in [0.0-1.0] to the parameter range.
+    pub fn map(&self, position: f32) -> f32 {
+        let position = position.clamp(0.0, 1.0);
+        let range = self.max - self.min;
+        match self.curve {
+            CcCurve::Linear => self.min + range * position,
+            CcCurve::Log => self.min * (self.max / self.min).powf(position),
+            CcCurve::Exp => self.min + range * position * position,
+            CcCurve::Switched => {
+                if position < 0.5 {
+                    self.min
+                } else {
+                    self.max
+                }
+            }
+        }
+    }
+
+    /// Set from a 7 bit CC value.
+    pub fn set_cc(&mut self, value: u8) {
+        self.set_position(value.min(127) as f32 / 127.0);
+    }
+
+    /// Set from a 14 bit CC value, the MSB and LSB controller pair combined.
+    pub fn set_cc14(&mut self, value: u16) {
+        self.set_position(value.min(16383) as f32 / 16383.0);
+    }
+
+    /// Set from the MSB and LSB of a 14 bit controller pair.
+    pub fn set_cc_pair(&mut self, msb: u8, lsb: u8) {
+        self.set_cc14(((msb.min(127) as u16) << 7) | lsb.min(127) as u16);
+    }
+
+    fn set_position(&mut self, position: f32) {
+        let value = self.map(position);
+        // Switches jump instead of sweeping through the values between.
+        if self.curve == CcCurve::Switched {
+            self.smoother.set_immediate(value);
+        } else {
+            self.smoother.set_target(value);
+        }
+    }
+
+    /// Advance the smoothing and return the parameter value.
+    // Never ends, so it isn't an iterator.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> f32 {
+        self.smoother.next()
+    }
+
+    pub fn set_curve(&mut self, curve: CcCurve) {
+        self.curve = curve;
+    }
+
+    pub fn set_range(&mut self, min: f32, max: f32) {
+        self.min = min;
+        self.max = max;
+    }
+
+    /// Set the smoothing time in seconds, 0.0 follows the controller immediately.
+    pub fn set_smoothing_time(&mut self, time: f32) {
+        self.smoother.set_time(time);
+    }
+
+    pub fn get_value(&self) -> f32 {
+        self.smoother.get_value()
+    }
+
+    /// The value the smoothing is moving to.
+    pub fn get_target(&self) -> f32 {
+        self.smoother.get_target()
+    }
+
+    /// Jump to the target, ending any smoothing.
+    pub fn reset(&mut self) {
+        self.smoother.reset();
+    }
+}
+
+/// Per note expression of an MPE controller for one voice: pitch bend, pressure and timbre (CC 74)
+/// from the note's own channel, each smoothed. Call `tick` once per sample or per control rate
+/// tick with the matching sample rate, and `start_note` when the voice starts a note. A
+/// `VoiceAllocator` does the routing and `start_note` for voices that return one from
+/// `Voice::expression`.
+pub struct NoteExpression {
+    bend_range: f32,
+    // Last bend message as a fraction of full deflection, to rescale on range changes.
+    bend_position: f32,
+    bend: Smoother,
+    pressure: Smoother,
+    timbre: Smoother,
+}
+
+impl NoteExpression {
+    /// 48 semitone bend range, the MPE default, smoothed over 10 ms.
+    pub fn new(sample_rate: f32) -> Self {
+        let smoother = || {
+            let mut smoother = Smoother::new(sample_rate);
+            smoother.set_mode(SmootherMode::OnePole);
+            smoother.set_time(0.01);
+            smoother
+        };
+        let mut expression = Self {
+            bend_range: 48.0,
+            bend_position: 0.0,
+            bend: smoother(),
+            pressure: smoother(),
+            timbre: smoother(),
+        };
+        expression.timbre.set_immediate(0.5);
+        expression
+    }
+
+    /// Advance the smoothing of all three dimensions.
+    pub fn tick(&mut self) {
+        self.bend.next();
+        self.pressure.next();
+        self.timbre.next();
+    }
+
+    /// Jump to the current values so a new note doesn't sweep from the last note's expression.
+    /// Controllers send the initial bend and timbre before the note on.
+    pub fn start_note(&mut self) {
+        self.bend.reset();
+        self.pressure.reset();
+        self.timbre.reset();
+    }
+
+    /// Set from a 14 bit pitch bend message, centred at 8192.
+    pub fn set_bend(&mut self, value: u16) {
+        self.bend_position = (value.min(16383) as f32 - 8192.0) / 8192.0;
+        self.bend.set_target(self.bend_position * self.bend_range);
+    }
+
+    /// Set from a 7 bit channel pressure message.
+    pub fn set_pressure(&mut self, value: u8) {
+        self.pressure.set_target(value.min(127) as f32 / 127.0);
+    }
+
+    /// Set from a 7 bit CC 74 message.
+    pub fn set_timbre(&mut self, value: u8) {
+        self.timbre.set_target(value.min(127) as f32 / 127.0);
+    }
+
+    /// Set the bend at full deflection in semitones, moving a held bend into the new range.
+    pub fn set_bend_range(&mut self, semitones: f32) {
+        self.bend_range = semitones;
+        self.bend.set_target(self.bend_position * self.bend_range);
+    }
+
+    /// Set the smoothing time in seconds, 0.0 follows the controller immediately.
+    pub fn set_smoothing_time(&mut self, time: f32) {
+        self.bend.set_time(time);
+        self.pressure.set_time(time);
+        self.timbre.set_time(time);
+    }
+
+    /// Get the pitch bend in semitones, added to the note number.
+    pub fn get_bend(&self) -> f32 {
+        self.bend.get_value()
+    }
+
+    /// Get the pressure, [0.0-1.0].
+    pub fn get_pressure(&self) -> f32 {
+        self.pressure.get_value()
+    }
+
+    /// Get the timbre, [0.0-1.0].
+    pub fn get_timbre(&self) -> f32 {
+        self.timbre.get_value()
+    }
+
+    /// Centre the bend, release the pressure and return the timbre to the middle.
+    pub fn reset(&mut self) {
+        self.bend_position = 0.0;
+        self.bend.set_immediate(0.0);
+        self.pressure.set_immediate(0.0);
+        self.timbre.set_immediate(0.5);
+    }
+}
+
+/// Shape of a `Glide` between notes.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GlideCurve {
+    /// Constant rate in semitones.
+    Linear,
+    /// Fast at first and slowing into the new note, like the RC portamento of analog synths.
+    Convex,
+    /// Slow at first and speeding up into the new note.
+    Concave,
+}
+
+// Notes a `Glide` remembers to return to when the sounding one is released.
+const GLIDE_HELD_NOTES: usize = 8;
+
+/// When a `Glide` moves between notes.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GlideMode {
+    /// Every note glides from the last one.
+    Always,
+    /// Fingered portamento, only notes played legato while another is held glide.
+    Legato,
+}
+
+/// Portamento between note numbers, taking the glide time whatever the interval. Call `next`
+/// once per sample or per control rate tick with the matching sample rate, and convert the note
+/// to a frequency with `units::note_to_freq` or a `Tuning`.
+pub struct Glide {
+    sample_rate: f32,
+    time: f32,
+    curve: GlideCurve,
+    mode: GlideMode,
+    start: f32,
+    target: f32,
+    position: f32,
+    step: f32,
+    remaining: u32,
+    // Held notes from oldest to newest, the newest is the one sounding.
+    held: [f32; GLIDE_HELD_NOTES],
+    held_count: usize,
+}
+
+impl Glide {
+    /// Linear glides over 100 ms on every note.
+    pub const fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            time: 0.1,
+            curve: GlideCurve::Linear,
+            mode: GlideMode::Always,
+            start: 0.0,
+            target: 0.0,
+            position: 1.0,
+            step: 0.0,
+            remaining: 0,
+            held: [0.0; GLIDE_HELD_NOTES],
+            held_count: 0,
+        }
+    }
+
+    /// Start a note, `legato` is true when another note is still held. With a `VoiceAllocator`
+    /// that's `held_count() > 0` before the note is passed to it, a mono synth can use this
+    /// glide's own `held_count`.
+    pub fn note_on(&mut self, note: f32, legato: bool) {
+        self.release(note);
+        if self.held_count == GLIDE_HELD_NOTES {
+            self.held.copy_within(1.., 0);
+            self.held_count -= 1;
+        }
+        self.held[self.held_count] = note;
+        self.held_count += 1;
+        self.glide_to(note, legato);
+    }
+
+    /// Release a note. Releasing the sounding note while others are still held glides back to
+    /// the newest of them, in either mode.
+    pub fn note_off(&mut self, note: f32) {
+        let sounding = self.held_count > 0 && self.held[self.held_count - 1] == note;
+        self.release(note);
+        if sounding && self.held_count > 0 {
+            self.glide_to(self.held[self.held_count - 1], true);
+        }
+    }
+
+    /// Get the number of notes held, at most 8 are remembered.
+    pub fn held_count(&self) -> usize {
+        self.held_count
+    }
+
+    // Forget a held note.
+    fn release(&mut self, note: f32) {
+        let held = &self.held[..self.held_count];
+        if let Some(index) = held.iter().position(|held| *held == note) {
+            self.held.copy_within(index + 1..self.held_count, index);
+            self.held_count -= 1;
+        }
+    }
+
+    fn glide_to(&mut self, note: f32, legato: bool) {
+        let samples = (self.time * self.sample_rate) as u32;
+        if samples == 0 || (self.mode == GlideMode::Legato && !legato) {
+            self.set_immediate(note);
+            return;
+        }
+        // Glides starting mid glide leave from where the pitch is now.
+        self.start = self.get_value();
+        self.target = note;
+        self.position = 0.0;
+        self.step = 1.0 / samples as f32;
+        self.remaining = samples;
+    }
+
+    /// Advance one sample or tick and return the note number.
+    // Never ends, so it isn't an iterator.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> f32 {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            self.position = if self.remaining == 0 {
+                1.0
+            } else {
+                self.position + self.step
+            };
+        }
+        self.get_value()
+    }
+
+    /// Jump to a note without gliding.
+    pub const fn set_immediate(&mut self, note: f32) {
+        self.start = note;
+        self.target = note;
+        self.position = 1.0;
+        self.remaining = 0;
+    }
+
+    /// Set the glide time in seconds, glides in progress keep their old time.
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time.max(0.0);
+    }
+
+    pub fn set_curve(&mut self, curve: GlideCurve) {
+        self.curve = curve;
+    }
+
+    pub fn set_mode(&mut self, mode: GlideMode) {
+        self.mode = mode;
+    }
+
+    /// Get the current note number.
+    pub fn get_value(&self) -> f32 {
+        let x = self.position;
+        let shaped = match self.curve {
+            GlideCurve::Linear => x,
+            GlideCurve::Convex => 1.0 - (1.0 - x) * (1.0 - x) * (1.0 - x),
+            GlideCurve::Concave => x * x * x,
+        };
+        self.start + (self.target - self.start) * shaped
+    }
+
+    /// True once the glide has reached the note.
+    pub fn is_settled(&self) -> bool {
+        self.remaining == 0
+    }
+
+    /// Jump to the note, ending any glide.
+    pub fn reset(&mut self) {
+        self.set_immediate(self.target);
+    }
+}
+
+/// Shape applied to a modulation source before it is scaled by the route depth.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ModCurve {
+    Linear,
+    /// Sign preserving square, finer control around zero.
+    Squared,
+    /// Maps a bipolar source in [-1.0, 1.0] to [0.0, 1.0].
+    Unipolar,
+    Inverted,
+}
+
+impl ModCurve {
+    pub fn apply(self, value: f32) -> f32 {
+        match self {
+            ModCurve::Linear => value,
+            ModCurve::Squared => value * value.abs(),
+            ModCurve::Unipolar => (value + 1.0) * 0.5,
+            ModCurve::Inverted => -value,
+        }
+    }
+}
+
+/// Connection from a modulation source to a destination.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ModRoute {
+    pub source: usize,
+    pub destination: usize,
+    pub depth: f32,
+    pub curve: ModCurve,
+}
+
+impl ModRoute {
+    pub fn new(source: usize, destination: usize, depth: f32) -> Self {
+        Self {
+            source,
+            destination,
+            depth,
+            curve: ModCurve::Linear,
+        }
+    }
+}
+
+/// Routes modulation sources such as LFOs, envelopes, CVs and MIDI CCs to destinations through
+/// up to `ROUTES` connections. Sources are written as they change and `update` is called at
+/// control rate, each destination then reads the sum of its routes.
+pub struct ModMatrix<const SOURCES: usize, const DESTINATIONS: usize, const ROUTES: usize> {
+    sources: [f32; SOURCES],
+    routes: [Option<ModRoute>; ROUTES],
+    outputs: [f32; DESTINATIONS],
+}
+
+impl<const SOURCES: usize, const DESTINATIONS: usize, const ROUTES: usize>
+    ModMatrix<SOURCES, DESTINATIONS, ROUTES>
+{
+    pub fn new() -> Self {
+        Self {
+            sources: [0.0; SOURCES],
+            routes: [None; ROUTES],
+            outputs: [0.0; DESTINATIONS],
+        }
+    }
+
+    /// Recalculate every destination from the current sources.
+    pub fn update(&mut self) {
+        self.outputs = [0.0; DESTINATIONS];
+        for route in self.routes.iter().flatten() {
+            let value = route.curve.apply(self.sources[route.source]);
+            self.outputs[route.destination] += value * route.depth;
+        }
+    }
+
+    /// Set the current value of a source, out of range sources are ignored.
+    pub fn set_source(&mut self, source: usize, value: f32) {
+        if let Some(slot) = self.sources.get_mut(source) {
+            *slot = value;
+        }
+    }
+
+    /// Add a route in the first free slot, returning the slot.
+    /// Returns None if the matrix is full or the route refers to a missing source or destination.
+    pub fn add_route(&mut self, route: ModRoute) -> Option<usize> {
+        if route.source >= SOURCES || route.destination >= DESTINATIONS {
+            return None;
+        }
+        let slot = self.routes.iter().position(|route| route.is_none())?;
+        self.routes[slot] = Some(route);
+        Some(slot)
+    }
+
+    pub fn remove_route(&mut self, slot: usize) {
+        if let Some(route) = self.routes.get_mut(slot) {
+            *route = None;
+        }
+    }
+
+    pub fn set_depth(&mut self, slot: usize, depth: f32) {
+        if let Some(Some(route)) = self.routes.get_mut(slot) {
+            route.depth = depth;
+        }
+    }
+
+    pub fn get_route(&self, slot: usize) -> Option<ModRoute> {
+        self.routes.get(slot).copied().flatten()
+    }
+
+    /// Get the total modulation of a destination as of the last `update`.
+    pub fn get(&self, destination: usize) -> f32 {
+        self.outputs.get(destination).copied().unwrap_or(0.0)
+    }
+}
+
+impl<const SOURCES: usize, const DESTINATIONS: usize, const ROUTES: usize> Default
+    for ModMatrix<SOURCES, DESTINATIONS, ROUTES>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slew_limiter_rates() {
+        let mut slew = SlewLimiter::new(1000.0);
+        slew.set_rise(100.0);
+        slew.set_fall(1000.0);
+        // Rising at 0.1 per sample takes 10 samples to reach 1.0.
+        for i in 1..=10 {
+            assert!((slew.process(1.0) - i as f32 * 0.1).abs() < 1e-5);
+        }
+        assert!((slew.process(1.0) - 1.0).abs() < 1e-6);
+        // Falling at 1.0 per sample.
+        assert!(slew.process(-1.0).abs() < 1e-6);
+        assert!((slew.process(-1.0) + 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_slew_limiter_unlimited() {
+        let mut slew = SlewLimiter::new(1000.0);
+        assert_eq!(slew.process(0.7), 0.7);
+        assert_eq!(slew.process(-0.3), -0.3);
+    }
+
+    #[test]
+    fn test_smoother_linear_ramp() {
+        let mut smoother = Smoother::new(1000.0);
+        smoother.set_time(0.01);
+        smoother.set_target(1.0);
+        assert!(!smoother.is_settled());
+        for i in 1..=10 {
+            assert!((smoother.next() - i as f32 * 0.1).abs() < 1e-5);
+        }
+        assert!(smoother.is_settled());
+        assert_eq!(smoother.get_value(), 1.0);
+        smoother.set_immediate(-1.0);
+        assert_eq!(smoother.next(), -1.0);
+    }
+
+    #[test]
+    fn test_smoother_one_pole() {
+        let mut smoother = Smoother::new(1000.0);
+        smoother.set_mode(SmootherMode::OnePole);
+        smoother.set_time(0.01);
+        smoother.set_target(1.0);
+        let mut value = 0.0;
+        for _ in 0..10 {
+            value = smoother.next();
+        }
+        assert!((value - 0.632).abs() < 0.01, "{}", value);
+        for _ in 0..1000 {
+            smoother.next();
+        }
+        assert!(smoother.is_settled());
+    }
+
+    #[test]
+    fn test_soft_takeover_pickup() {
+        let mut takeover = SoftTakeover::new();
+        assert_eq!(takeover.process(0.2), Some(0.2));
+        // Noise within the deadband is ignored.
+        assert_eq!(takeover.process(0.203), None);
+        // A preset moves the parameter away from the pot.
+        takeover.set_value(0.8);
+        assert_eq!(takeover.process(0.5), None);
+        assert_eq!(takeover.get_value(), 0.8);
+        // Jumping past the stored value between readings still picks it up.
+        assert_eq!(takeover.process(0.85), Some(0.85));
+        assert!(takeover.is_picked_up());
+
+        takeover.set_value(0.3);
+        assert_eq!(takeover.process(0.7), None);
+        assert_eq!(takeover.process(0.31), Some(0.31));
+    }
+
+    #[test]
+    fn test_cc_map_curves() {
+        let mut cutoff = CcMap::new(1000.0, 20.0, 20000.0);
+        cutoff.set_curve(CcCurve::Log);
+        assert!((cutoff.map(0.5) - 632.46).abs() < 0.1);
+        cutoff.set_smoothing_time(0.0);
+        cutoff.set_cc(127);
+        assert!((cutoff.next() - 20000.0).abs() < 0.1);
+
+        let mut level = CcMap::new(1000.0, 0.0, 1.0);
+        level.set_curve(CcCurve::Exp);
+        level.set_cc_pair(64, 0);
+        assert!((level.get_target() - 0.25).abs() < 0.01);
+        // Smoothed over 20 samples at the default 20 ms.
+        assert!(level.next() > 0.0 && level.get_value() < 0.25);
+        for _ in 0..19 {
+            level.next();
+        }
+        assert_eq!(level.get_value(), level.get_target());
+
+        level.set_curve(CcCurve::Switched);
+        level.set_cc(63);
+        assert_eq!(level.next(), 0.0);
+        level.set_cc14(16383);
+        assert_eq!(level.next(), 1.0);
+    }
+
+    #[test]
+    fn test_note_expression() {
+        let mut expression = NoteExpression::new(1000.0);
+        assert_eq!(expression.get_timbre(), 0.5);
+        // Values sent before the note on apply without smoothing.
+        expression.set_bend(16383);
+        expression.set_timbre(0);
+        expression.start_note();
+        assert!((expression.get_bend() - 48.0).abs() < 0.01);
+        assert_eq!(expression.get_timbre(), 0.0);
+
+        // Later changes are smoothed.
+        expression.set_bend_range(2.0);
+        expression.set_bend(0);
+        expression.set_pressure(127);
+        expression.tick();
+        assert!(expression.get_bend() > 0.0);
+        assert!(expression.get_pressure() < 0.5);
+        for _ in 0..200 {
+            expression.tick();
+        }
+        assert!((expression.get_bend() + 2.0).abs() < 1e-3);
+        assert!((expression.get_pressure() - 1.0).abs() < 1e-3);
+        // A held bend follows a range change.
+        expression.set_bend_range(12.0);
+        for _ in 0..200 {
+            expression.tick();
+        }
+        assert!((expression.get_bend() + 12.0).abs() < 1e-3);
+
+        expression.reset();
+        assert_eq!(expression.get_bend(), 0.0);
+        assert_eq!(expression.get_pressure(), 0.0);
+    }
+
+    #[test]
+    fn test_glide() {
+        let mut glide = Glide::new(1000.0);
+        glide.set_immediate(60.0);
+        glide.note_on(72.0, false);
+        // Half way in time is half way in pitch, the curves bow either side.
+        let glided = |glide: &mut Glide| {
+            for _ in 0..49 {
+                glide.next();
+            }
+            glide.next()
+        };
+        assert!((glided(&mut glide) - 66.0).abs() < 1e-3);
+        glide.set_curve(GlideCurve::Convex);
+        assert!((glide.get_value() - 70.5).abs() < 1e-3);
+        glide.set_curve(GlideCurve::Concave);
+        assert!((glide.get_value() - 61.5).abs() < 1e-3);
+        assert!((glided(&mut glide) - 72.0).abs() < 1e-3);
+        assert!(glide.is_settled());
+
+        // Fingered portamento only glides between held notes.
+        glide.set_mode(GlideMode::Legato);
+        glide.note_on(48.0, false);
+        assert_eq!(glide.next(), 48.0);
+        glide.note_on(50.0, true);
+        assert!(glide.next() < 49.0);
+        glide.reset();
+        assert_eq!(glide.get_value(), 50.0);
+
+        // Releasing the sounding note glides back to the newest note still held, releasing
+        // one that isn't sounding changes nothing.
+        let mut glide = Glide::new(1000.0);
+        glide.set_mode(GlideMode::Legato);
+        glide.note_on(60.0, glide.held_count() > 0);
+        glide.note_on(64.0, glide.held_count() > 0);
+        glide.note_on(67.0, glide.held_count() > 0);
+        glide.reset();
+        glide.note_off(64.0);
+        assert_eq!(glide.next(), 67.0);
+        glide.note_off(67.0);
+        assert_eq!(glide.held_count(), 1);
+        let back = glide.next();
+        assert!(back < 67.0 && back > 60.0);
+        glided(&mut glide);
+        assert!((glided(&mut glide) - 60.0).abs() < 1e-3);
+        glide.note_off(60.0);
+        assert_eq!(glide.held_count(), 0);
+        assert_eq!(glide.next(), 60.0);
+        glide.note_on(72.0, glide.held_count() > 0);
+        assert_eq!(glide.next(), 72.0);
+    }
+
+    #[test]
+    fn test_mod_matrix_routes() {
+        const LFO: usize = 0;
+        const ENVELOPE: usize = 1;
+        const CUTOFF: usize = 0;
+        const PITCH: usize = 1;
+        let mut matrix: ModMatrix<2, 3, 4> = ModMatrix::new();
+        let lfo_to_pitch = matrix.add_route(ModRoute::new(LFO, PITCH, 0.1)).unwrap();
+        matrix.add_route(ModRoute::new(ENVELOPE, CUTOFF, 0.5));
+        matrix.add_route(ModRoute {
+            curve: ModCurve::Unipolar,
+            ..ModRoute::new(LFO, CUTOFF, 0.25)
+        });
+        assert_eq!(matrix.add_route(ModRoute::new(2, CUTOFF, 1.0)), None);
+
+        matrix.set_source(LFO, -1.0);
+        matrix.set_source(ENVELOPE, 0.8);
+        matrix.update();
+        assert!((matrix.get(CUTOFF) - 0.4).abs() < 1e-6);
+        assert!((matrix.get(PITCH) + 0.1).abs() < 1e-6);
+        assert_eq!(matrix.get(2), 0.0);
+
+        matrix.set_depth(lfo_to_pitch, 0.2);
+        matrix.remove_route(1);
+        matrix.set_source(LFO, 1.0);
+        matrix.update();
+        assert!((matrix.get(CUTOFF) - 0.25).abs() < 1e-6);
+        assert!((matrix.get(PITCH) - 0.2).abs() < 1e-6);
+        assert_eq!(matrix.get_route(1), None);
+    }
+}
@@ -0,0 +1,307 @@
+//! Conversions between codec sample formats and f32, and between interleaved and planar stereo.
+//! Integer samples are scaled so full scale maps to [-1.0-1.0), conversions to integers round to
+//! the nearest value and saturate instead of wrapping. `Quantizer` adds dither and noise shaping
+//! for reducing the final output to 16 or 24 bits.
+
+float_math!();
+
+use crate::block::dot_product;
+use crate::random::{Random, XorShift32};
+
+const I16_SCALE: f32 = 32768.0;
+const I24_SCALE: f32 = 8388608.0;
+const I32_SCALE: f32 = 2147483648.0;
+
+pub fn i16_to_f32(sample: i16) -> f32 {
+    sample as f32 * (1.0 / I16_SCALE)
+}
+
+pub fn f32_to_i16(sample: f32) -> i16 {
+    (sample * I16_SCALE)
+        .round()
+        .clamp(-I16_SCALE, I16_SCALE - 1.0) as i16
+}
+
+/// Convert a 24 bit sample right aligned in an i32, the upper byte is ignored.
+pub fn i24_to_f32(sample: i32) -> f32 {
+    // Sign extend from bit 23.
+    ((sample << 8) >> 8) as f32 * (1.0 / I24_SCALE)
+}
+
+/// Convert to a 24 bit sample right aligned and sign extended in an i32.
+pub fn f32_to_i24(sample: f32) -> i32 {
+    (sample * I24_SCALE)
+        .round()
+        .clamp(-I24_SCALE, I24_SCALE - 1.0) as i32
+}
+
+/// Convert a 32 bit sample, also used for 24 bit samples left aligned in 32 bits.
+pub fn i32_to_f32(sample: i32) -> f32 {
+    sample as f32 * (1.0 / I32_SCALE)
+}
+
+pub fn f32_to_i32(sample: f32) -> i32 {
+    // Float to int casts saturate, and f32 can't hold the fractional part at this scale.
+    (sample * I32_SCALE) as i32
+}
+
+pub fn i16_to_f32_slice(input: &[i16], output: &mut [f32]) {
+    for (output, input) in output.iter_mut().zip(input.iter()) {
+        *output = i16_to_f32(*input);
+    }
+}
+
+pub fn f32_to_i16_slice(input: &[f32], output: &mut [i16]) {
+    for (output, input) in output.iter_mut().zip(input.iter()) {
+        *output = f32_to_i16(*input);
+    }
+}
+
+pub fn i24_to_f32_slice(input: &[i32], output: &mut [f32]) {
+    for (output, input) in output.iter_mut().zip(input.iter()) {
+        *output = i24_to_f32(*input);
+    }
+}
+
+pub fn f32_to_i24_slice(input: &[f32], output: &mut [i32]) {
+    for (output, input) in output.iter_mut().zip(input.iter()) {
+        *output = f32_to_i24(*input);
+    }
+}
+
+pub fn i32_to_f32_slice(input: &[i32], output: &mut [f32]) {
+    for (output, input) in output.iter_mut().zip(input.iter()) {
+        *output = i32_to_f32(*input);
+    }
+}
+
+pub fn f32_to_i32_slice(input: &[f32], output: &mut [i32]) {
+    for (output, input) in output.iter_mut().zip(input.iter()) {
+        *output = f32_to_i32(*input);
+    }
+}
+
+/// Split interleaved stereo frames into left and right channels.
+/// Stops at whichever of the buffers runs out first.
+pub fn deinterleave<T: Copy>(interleaved: &[T], left: &mut [T], right: &mut [T]) {
+    for ((frame, left), right) in interleaved
+        .chunks_exact(2)
+        .zip(left.iter_mut())
+        .zip(right.iter_mut())
+    {
+        *left = frame[0];
+        *right = frame[1];
+    }
+}
+
+/// Combine left and right channels into interleaved stereo frames.
+/// Stops at whichever of the buffers runs out first.
+pub fn interleave<T: Copy>(left: &[T], right: &[T], interleaved: &mut [T]) {
+    for ((frame, left), right) in interleaved
+        .chunks_exact_mut(2)
+        .zip(left.iter())
+        .zip(right.iter())
+    {
+        frame[0] = *left;
+        frame[1] = *right;
+    }
+}
+
+/// Spectral shape of the requantization noise of a `Quantizer`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum NoiseShaping {
+    /// White noise.
+    Off,
+    /// Rising 6 dB per octave, the error of the last sample is subtracted from the next.
+    FirstOrder,
+    /// Wannamaker's 9 tap F-weighted filter, pushing the noise to where hearing is least
+    /// sensitive. Designed for 44.1 kHz and close enough at 48 kHz, the total noise power is
+    /// higher so it suits final 16 bit output.
+    Psychoacoustic,
+}
+
+// Error feedback filters, the error of the last sample first.
+const FIRST_ORDER_SHAPING: [f32; 1] = [1.0];
+const PSYCHOACOUSTIC_SHAPING: [f32; 9] = [
+    2.412, -3.370, 3.937, -4.174, 3.353, -2.205, 1.281, -0.569, 0.0847,
+];
+const SHAPING_TAPS: usize = PSYCHOACOUSTIC_SHAPING.len();
+const QUANTIZER_SEED: u32 = 0x2545_f491;
+
+/// Reduces f32 samples to integers of 8 to 24 bits with TPDF dither, two LSBs peak to peak of
+/// triangular noise that makes the error independent of the signal, and optional noise shaping.
+/// Use one per channel.
+pub struct Quantizer {
+    bits: u32,
+    scale: f32,
+    dither: bool,
+    shaping: NoiseShaping,
+    random: XorShift32,
+    // Past errors in LSBs, the newest first.
+    errors: [f32; SHAPING_TAPS],
+}
+
+impl Quantizer {
+    /// Dithered without noise shaping.
+    pub fn new(bits: u32) -> Self {
+        let mut quantizer = Self {
+            bits: 0,
+            scale: 0.0,
+            dither: true,
+            shaping: NoiseShaping::Off,
+            random: XorShift32::new(QUANTIZER_SEED),
+            errors: [0.0; SHAPING_TAPS],
+        };
+        quantizer.set_bits(bits);
+        quantizer
+    }
+
+    /// Quantize one sample to a right aligned integer of the configured bit depth.
+    pub fn process(&mut self, sample: f32) -> i32 {
+        let coefs: &[f32] = match self.shaping {
+            NoiseShaping::Off => &[],
+            NoiseShaping::FirstOrder => &FIRST_ORDER_SHAPING,
+            NoiseShaping::Psychoacoustic => &PSYCHOACOUSTIC_SHAPING,
+        };
+        let feedback = dot_product(coefs, &self.errors);
+        let shaped = sample * self.scale - feedback;
+        let dither = if self.dither {
+            self.random.next_f32_unit() - self.random.next_f32_unit()
+        } else {
+            0.0
+        };
+        let quantized = (shaped + dither).round();
+        // The error is taken before saturating so clipping can't make the feedback run away.
+        self.errors.copy_within(..SHAPING_TAPS - 1, 1);
+        self.errors[0] = quantized - shaped;
+        quantized.clamp(-self.scale, self.scale - 1.0) as i32
+    }
+
+    /// Quantize to 16 bit samples, the quantizer should be set to 16 bits.
+    pub fn process_i16_slice(&mut self, input: &[f32], output: &mut [i16]) {
+        for (output, input) in output.iter_mut().zip(input.iter()) {
+            *output = self.process(*input) as i16;
+        }
+    }
+
+    /// Quantize to right aligned samples of the configured bit depth.
+    pub fn process_i32_slice(&mut self, input: &[f32], output: &mut [i32]) {
+        for (output, input) in output.iter_mut().zip(input.iter()) {
+            *output = self.process(*input);
+        }
+    }
+
+    /// Set the bit depth, [8-24]. Clears the error history.
+    pub fn set_bits(&mut self, bits: u32) {
+        self.bits = bits.clamp(8, 24);
+        self.scale = (1_u32 << (self.bits - 1)) as f32;
+        self.errors = [0.0; SHAPING_TAPS];
+    }
+
+    pub fn get_bits(&self) -> u32 {
+        self.bits
+    }
+
+    pub fn set_dither(&mut self, enabled: bool) {
+        self.dither = enabled;
+    }
+
+    pub fn set_noise_shaping(&mut self, shaping: NoiseShaping) {
+        self.shaping = shaping;
+        self.errors = [0.0; SHAPING_TAPS];
+    }
+
+    /// Clear the error history and restart the dither sequence.
+    pub fn reset(&mut self) {
+        self.errors = [0.0; SHAPING_TAPS];
+        self.random = XorShift32::new(QUANTIZER_SEED);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integer_round_trip() {
+        for sample in [i16::MIN, -12345, -1, 0, 1, 12345, i16::MAX].iter() {
+            assert_eq!(f32_to_i16(i16_to_f32(*sample)), *sample);
+        }
+        for sample in [-8388608, -1, 0, 1, 4321987, 8388607].iter() {
+            assert_eq!(f32_to_i24(i24_to_f32(*sample)), *sample);
+        }
+        for sample in [i32::MIN, -256, 0, 256, 1 << 30].iter() {
+            assert_eq!(f32_to_i32(i32_to_f32(*sample)), *sample);
+        }
+    }
+
+    #[test]
+    fn test_saturation_and_scaling() {
+        assert_eq!(f32_to_i16(1.5), i16::MAX);
+        assert_eq!(f32_to_i16(-1.5), i16::MIN);
+        assert_eq!(f32_to_i16(0.5), 16384);
+        assert_eq!(f32_to_i24(2.0), 8388607);
+        assert_eq!(f32_to_i24(-1.0), -8388608);
+        assert_eq!(f32_to_i32(1.0), i32::MAX);
+        assert_eq!(f32_to_i32(-3.0), i32::MIN);
+        assert_eq!(i16_to_f32(i16::MIN), -1.0);
+        // Upper byte of a 24 bit sample is ignored and bit 23 is the sign.
+        assert_eq!(i24_to_f32(0x7f80_0000), -1.0);
+        assert_eq!(i24_to_f32(0x0040_0000), 0.5);
+    }
+
+    #[test]
+    fn test_interleave() {
+        let interleaved = [1, -1, 2, -2, 3, -3];
+        let mut left = [0; 3];
+        let mut right = [0; 3];
+        deinterleave(&interleaved, &mut left, &mut right);
+        assert_eq!(left, [1, 2, 3]);
+        assert_eq!(right, [-1, -2, -3]);
+
+        let mut output = [0; 6];
+        interleave(&left, &right, &mut output);
+        assert_eq!(output, interleaved);
+
+        let mut samples = [0.0; 3];
+        i16_to_f32_slice(&left.map(|x| x as i16 * 8192), &mut samples);
+        assert_eq!(samples, [0.25, 0.5, 0.75]);
+    }
+
+    #[test]
+    fn test_quantizer() {
+        // Without dither it rounds like the plain conversion.
+        let mut quantizer = Quantizer::new(16);
+        quantizer.set_dither(false);
+        for sample in [-1.5, -0.3, 0.0, 0.12345, 0.99999].iter() {
+            assert_eq!(quantizer.process(*sample) as i16, f32_to_i16(*sample));
+        }
+
+        // Dither makes a quarter of an LSB come out right on average instead of as silence.
+        let mut quantizer = Quantizer::new(24);
+        let lsb = 1.0 / I24_SCALE;
+        let sum: i32 = (0..10000).map(|_| quantizer.process(0.25 * lsb)).sum();
+        assert!((sum as f32 / 10000.0 - 0.25).abs() < 0.05);
+
+        // Psychoacoustic shaping moves the noise out of the lower frequencies.
+        let low_noise = |shaping| {
+            let mut quantizer = Quantizer::new(16);
+            quantizer.set_noise_shaping(shaping);
+            let mut low_pass = crate::filter::Biquad::new(44100.0);
+            low_pass.set_low_pass(2000.0, core::f32::consts::FRAC_1_SQRT_2);
+            let mut energy = 0.0;
+            for n in 0..44100 {
+                let x = 0.5 * (0.05 * n as f32).sin();
+                let error = quantizer.process(x) as f32 / I16_SCALE - x;
+                energy += low_pass.process(error).powi(2);
+            }
+            energy
+        };
+        let flat = low_noise(NoiseShaping::Off);
+        assert!(low_noise(NoiseShaping::FirstOrder) < 0.5 * flat);
+        assert!(low_noise(NoiseShaping::Psychoacoustic) < 0.5 * flat);
+    }
+}
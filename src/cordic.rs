@@ -0,0 +1,135 @@
+float_math!();
+
+use core::f64::consts::PI;
+
+/// Source of sine and cosine for oscillators, so the math backend can be swapped for a software
+/// CORDIC or a hardware CORDIC peripheral such as the one on STM32G4 and STM32H7 parts.
+/// Takes `&mut self` so peripheral drivers can own their registers.
+pub trait SinCos {
+    /// Sine and cosine of `x` in radians.
+    fn sin_cos(&mut self, x: f32) -> (f32, f32);
+}
+
+/// Sine and cosine from the selected math backend.
+pub struct MathBackend;
+
+impl SinCos for MathBackend {
+    fn sin_cos(&mut self, x: f32) -> (f32, f32) {
+        (x.sin(), x.cos())
+    }
+}
+
+const ITERATIONS: usize = 24;
+
+// Angles of atan(2^-i) where 2^31 is pi, matching the q1.31 angles of hardware CORDIC units.
+const ANGLES: [i32; ITERATIONS] = {
+    let mut angles = [0; ITERATIONS];
+    let mut i = 0;
+    while i < ITERATIONS {
+        angles[i] = (atan(1.0 / (1u32 << i) as f64) / PI * 2147483648.0 + 0.5) as i32;
+        i += 1;
+    }
+    angles
+};
+
+// Start vector length that cancels the gain of the rotations, in Q2.30.
+const START: i32 = {
+    let mut gain = 1.0;
+    let mut i = 0;
+    while i < ITERATIONS {
+        gain *= sqrt(1.0 + 1.0 / (1u64 << (2 * i)) as f64);
+        i += 1;
+    }
+    (1073741824.0 / gain + 0.5) as i32
+};
+
+/// Software CORDIC sin and cos in 32 bit fixed point, accurate to about 2e-7.
+/// Only needs shifts and adds, useful as a reference for the hardware version or on cores
+/// without an FPU.
+pub struct Cordic;
+
+impl Cordic {
+    /// Sine and cosine in Q2.30 of a q1.31 angle where `i32::MIN` is -pi.
+    pub fn sin_cos_fixed(angle: i32) -> (i32, i32) {
+        // Rotations only converge within about +-pi/2, so rotate the rest by pi first.
+        let (mut z, sign) = if !(i32::MIN / 2..=i32::MAX / 2).contains(&angle) {
+            (angle.wrapping_add(i32::MIN), -1)
+        } else {
+            (angle, 1)
+        };
+        let mut x = START;
+        let mut y = 0;
+        for (i, angle) in ANGLES.iter().enumerate() {
+            let (dx, dy) = (y >> i, x >> i);
+            if z >= 0 {
+                x -= dx;
+                y += dy;
+                z -= angle;
+            } else {
+                x += dx;
+                y -= dy;
+                z += angle;
+            }
+        }
+        (sign * y, sign * x)
+    }
+}
+
+impl SinCos for Cordic {
+    fn sin_cos(&mut self, x: f32) -> (f32, f32) {
+        // Wrap to a single turn before converting so large phases don't overflow.
+        let turns = x * (0.5 / core::f32::consts::PI);
+        let turns = turns - turns as i32 as f32;
+        let angle = (turns * 4294967296.0) as i64 as i32;
+        let (sin, cos) = Cordic::sin_cos_fixed(angle);
+        (sin as f32 / 1073741824.0, cos as f32 / 1073741824.0)
+    }
+}
+
+// atan for 0 < x <= 1, only used for the angle table.
+const fn atan(x: f64) -> f64 {
+    if x == 1.0 {
+        return PI / 4.0;
+    }
+    // Alternating series, converges quickly for x <= 0.5.
+    let mut sum = 0.0;
+    let mut power = x;
+    let mut n = 0;
+    while n < 40 {
+        let term = power / (2 * n + 1) as f64;
+        sum += if n % 2 == 0 { term } else { -term };
+        power *= x * x;
+        n += 1;
+    }
+    sum
+}
+
+// Newton's method square root for values near 1, only used for the gain.
+const fn sqrt(x: f64) -> f64 {
+    let mut guess = x;
+    let mut i = 0;
+    while i < 20 {
+        guess = 0.5 * (guess + x / guess);
+        i += 1;
+    }
+    guess
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cordic_sin_cos() {
+        let mut cordic = Cordic;
+        for i in -2000..2000 {
+            let x = i as f32 * 0.01;
+            let (sin, cos) = cordic.sin_cos(x);
+            assert!((sin - x.sin()).abs() < 1e-5, "sin {}", x);
+            assert!((cos - x.cos()).abs() < 1e-5, "cos {}", x);
+        }
+        assert!(Cordic::sin_cos_fixed(0).0.abs() < 256);
+        assert!((Cordic::sin_cos_fixed(i32::MIN).1 + (1 << 30)).abs() < 256);
+        assert!((Cordic::sin_cos_fixed(1 << 30).0 - (1 << 30)).abs() < 256);
+    }
+}
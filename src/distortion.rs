@@ -0,0 +1,347 @@
+use crate::filter::{BandSplit, Biquad, ToneStack};
+use crate::preset::Preset;
+use crate::processor::Processor;
+use crate::units::{db_to_linear, linear_to_db};
+
+/// Smooth saturating curve approximating tanh, reaching +/-1.0 at +/-3.0.
+pub fn soft_clip(input: f32) -> f32 {
+    let x = input.clamp(-3.0, 3.0);
+    x * (27.0 + x * x) / (27.0 + 9.0 * x * x)
+}
+
+/// Clipping curve of the distortion.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum ClipType {
+    /// Smooth tanh like saturation.
+    Soft,
+    /// Hard limiting at +/-1.0.
+    Hard,
+    /// Soft clipping with a lower threshold on the negative half, adding even harmonics.
+    Asymmetric,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct DistortionParams {
+    /// Input gain in dB.
+    pub gain: f32,
+    /// Output level in dB.
+    pub level: f32,
+    pub clip_type: ClipType,
+    pub bass: f32,
+    pub mid: f32,
+    pub treble: f32,
+}
+
+/// Amp style drive channel, input gain into a waveshaper followed by a passive tone stack.
+pub struct Distortion {
+    clip_type: ClipType,
+    gain: f32,
+    level: f32,
+    dc_block: Biquad,
+    tone_stack: ToneStack,
+}
+
+impl Distortion {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut dc_block = Biquad::new(sample_rate);
+        dc_block.set_high_pass(20.0, 0.707);
+        Self {
+            clip_type: ClipType::Soft,
+            gain: 10.0,
+            level: 1.0,
+            dc_block,
+            tone_stack: ToneStack::new(sample_rate),
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let x = input * self.gain;
+        let shaped = match self.clip_type {
+            ClipType::Soft => soft_clip(x),
+            ClipType::Hard => x.clamp(-1.0, 1.0),
+            ClipType::Asymmetric => {
+                if x < 0.0 {
+                    soft_clip(x * 2.0) * 0.5
+                } else {
+                    soft_clip(x)
+                }
+            }
+        };
+        let shaped = self.dc_block.process(shaped);
+        self.tone_stack.process(shaped) * self.level
+    }
+
+    /// Set the input gain in dB.
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = db_to_linear(gain);
+    }
+
+    /// Set the output level in dB.
+    pub fn set_level(&mut self, level: f32) {
+        self.level = db_to_linear(level);
+    }
+
+    pub fn set_clip_type(&mut self, clip_type: ClipType) {
+        self.clip_type = clip_type;
+    }
+
+    /// Set the tone stack bass, clamped to [0.0-1.0].
+    pub fn set_bass(&mut self, bass: f32) {
+        self.tone_stack.set_bass(bass);
+    }
+
+    /// Set the tone stack mid, clamped to [0.0-1.0].
+    pub fn set_mid(&mut self, mid: f32) {
+        self.tone_stack.set_mid(mid);
+    }
+
+    /// Set the tone stack treble, clamped to [0.0-1.0].
+    pub fn set_treble(&mut self, treble: f32) {
+        self.tone_stack.set_treble(treble);
+    }
+
+    pub fn reset(&mut self) {
+        self.dc_block.reset();
+        self.tone_stack.reset();
+    }
+}
+
+impl Processor for Distortion {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+impl Preset for Distortion {
+    type Params = DistortionParams;
+
+    fn get_params(&self) -> DistortionParams {
+        DistortionParams {
+            gain: linear_to_db(self.gain),
+            level: linear_to_db(self.level),
+            clip_type: self.clip_type,
+            bass: self.tone_stack.get_bass(),
+            mid: self.tone_stack.get_mid(),
+            treble: self.tone_stack.get_treble(),
+        }
+    }
+
+    fn set_params(&mut self, params: &DistortionParams) {
+        self.set_gain(params.gain);
+        self.set_level(params.level);
+        self.set_clip_type(params.clip_type);
+        self.set_bass(params.bass);
+        self.set_mid(params.mid);
+        self.set_treble(params.treble);
+    }
+}
+
+/// Nonlinearity used to generate harmonics.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum HarmonicMode {
+    /// Odd harmonics from symmetric soft clipping.
+    SoftClip,
+    /// Even harmonics from full wave rectification.
+    Rectify,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct ExciterParams {
+    pub freq: f32,
+    pub drive: f32,
+    pub amount: f32,
+    pub mode: HarmonicMode,
+}
+
+/// Harmonic exciter, a two band `BandSplit` generating harmonics from the high band and adding
+/// them back to the recombined bands.
+pub struct Exciter {
+    split: BandSplit<2>,
+    harmonic_filter: Biquad,
+    mode: HarmonicMode,
+    drive: f32,
+    amount: f32,
+}
+
+impl Exciter {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut exciter = Self {
+            split: BandSplit::new(sample_rate),
+            harmonic_filter: Biquad::new(sample_rate),
+            mode: HarmonicMode::SoftClip,
+            drive: 4.0,
+            amount: 0.2,
+        };
+        exciter.set_freq(3000.0);
+        exciter
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let bands = self.split.process(input);
+        let high = bands[1] * self.drive;
+        let shaped = match self.mode {
+            HarmonicMode::SoftClip => soft_clip(high),
+            HarmonicMode::Rectify => high.abs(),
+        };
+        // Remove the DC and low products of the nonlinearity so only new harmonics are added.
+        let harmonics = self.harmonic_filter.process(shaped);
+        BandSplit::recombine(&bands) + harmonics * self.amount
+    }
+
+    /// Set the frequency above which harmonics are generated.
+    pub fn set_freq(&mut self, freq: f32) {
+        self.split.set_crossover_freq(0, freq);
+        self.harmonic_filter.set_high_pass(freq, 0.707);
+    }
+
+    /// Set the gain into the nonlinearity, higher values generate more harmonics.
+    pub fn set_drive(&mut self, drive: f32) {
+        self.drive = drive.max(0.0);
+    }
+
+    /// Set the level of the harmonics mixed with the input.
+    pub fn set_amount(&mut self, amount: f32) {
+        self.amount = amount.max(0.0);
+    }
+
+    pub fn set_mode(&mut self, mode: HarmonicMode) {
+        self.mode = mode;
+    }
+
+    pub fn reset(&mut self) {
+        self.split.reset();
+        self.harmonic_filter.reset();
+    }
+}
+
+impl Processor for Exciter {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+impl Preset for Exciter {
+    type Params = ExciterParams;
+
+    fn get_params(&self) -> ExciterParams {
+        ExciterParams {
+            freq: self.split.get_crossover_freq(0),
+            drive: self.drive,
+            amount: self.amount,
+            mode: self.mode,
+        }
+    }
+
+    fn set_params(&mut self, params: &ExciterParams) {
+        self.set_freq(params.freq);
+        self.set_drive(params.drive);
+        self.set_amount(params.amount);
+        self.set_mode(params.mode);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const SAMPLE_RATE_F: f32 = 44100.0;
+
+    use super::*;
+    use crate::synthesis::{Oscillator, WaveType};
+
+    #[test]
+    fn test_distortion_preset() {
+        let mut distortion = Distortion::new(SAMPLE_RATE_F);
+        distortion.set_gain(30.0);
+        distortion.set_level(-6.0);
+        distortion.set_clip_type(ClipType::Hard);
+        distortion.set_treble(0.9);
+        let params = distortion.get_params();
+        assert!((params.gain - 30.0).abs() < 1e-4);
+        assert!((params.level + 6.0).abs() < 1e-4);
+        assert_eq!(params.clip_type, ClipType::Hard);
+        assert_eq!(params.treble, 0.9);
+
+        let mut restored = Distortion::new(SAMPLE_RATE_F);
+        restored.set_params(&params);
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 220.0);
+        for _ in 0..1000 {
+            let x = oscillator.process();
+            assert!((distortion.process(x) - restored.process(x)).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_soft_clip() {
+        assert_eq!(soft_clip(0.0), 0.0);
+        assert!((soft_clip(3.0) - 1.0).abs() < 1e-6);
+        assert!((soft_clip(10.0) - 1.0).abs() < 1e-6);
+        assert!((soft_clip(-10.0) + 1.0).abs() < 1e-6);
+        assert!((soft_clip(0.1) - 0.1).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_distortion_bounded() {
+        for clip_type in [ClipType::Soft, ClipType::Hard, ClipType::Asymmetric].iter() {
+            let mut distortion = Distortion::new(SAMPLE_RATE_F);
+            distortion.set_clip_type(*clip_type);
+            distortion.set_gain(40.0);
+            let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 110.0);
+            let mut peak = 0.0_f32;
+            for _ in 0..44100 {
+                peak = peak.max(distortion.process(oscillator.process()).abs());
+            }
+            // The clipper limits the level into the passive tone stack.
+            assert!(peak > 0.1 && peak < 1.5);
+        }
+    }
+
+    #[test]
+    fn test_exciter_ignores_low_band() {
+        let mut exciter = Exciter::new(SAMPLE_RATE_F);
+        exciter.set_amount(1.0);
+        // Without harmonics the output is the all pass response of the recombined bands.
+        let mut split: BandSplit<2> = BandSplit::new(SAMPLE_RATE_F);
+        split.set_crossover_freq(0, 3000.0);
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 100.0);
+        let mut error = 0.0_f32;
+        for _ in 0..44100 {
+            let input = oscillator.process();
+            let dry = BandSplit::recombine(&split.process(input));
+            error = error.max((exciter.process(input) - dry).abs());
+        }
+        assert!(error < 0.05);
+    }
+
+    #[test]
+    fn test_exciter_adds_harmonics() {
+        let mut exciter = Exciter::new(SAMPLE_RATE_F);
+        exciter.set_mode(HarmonicMode::Rectify);
+        exciter.set_amount(1.0);
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 5000.0);
+        let mut difference = 0.0;
+        for _ in 0..44100 {
+            let input = oscillator.process();
+            difference += (exciter.process(input) - input).abs();
+        }
+        assert!(difference > 1000.0);
+    }
+}
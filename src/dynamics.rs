@@ -0,0 +1,1211 @@
+float_math!();
+
+use crate::delay::DelayLine;
+use crate::filter::{BandSplit, Biquad};
+use crate::preset::Preset;
+use crate::processor::Processor;
+use crate::units::{db_to_linear, linear_to_db};
+
+// Coefficient of a one-pole smoother reaching ~63% of a step in `time` seconds.
+fn time_coef(sample_rate: f32, time: f32) -> f32 {
+    if time <= 0.0 {
+        0.0
+    } else {
+        (-1.0 / (time * sample_rate)).exp()
+    }
+}
+
+/// Peak envelope follower with separate attack and release times.
+pub struct EnvelopeFollower {
+    sample_rate: f32,
+    attack_coef: f32,
+    release_coef: f32,
+    envelope: f32,
+}
+
+impl EnvelopeFollower {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            attack_coef: time_coef(sample_rate, 0.005),
+            release_coef: time_coef(sample_rate, 0.05),
+            envelope: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let rectified = input.abs();
+        let coef = if rectified > self.envelope {
+            self.attack_coef
+        } else {
+            self.release_coef
+        };
+        self.envelope = rectified + coef * (self.envelope - rectified);
+        self.envelope
+    }
+
+    /// Set the attack time in seconds.
+    pub fn set_attack(&mut self, attack: f32) {
+        self.attack_coef = time_coef(self.sample_rate, attack);
+    }
+
+    /// Set the release time in seconds.
+    pub fn set_release(&mut self, release: f32) {
+        self.release_coef = time_coef(self.sample_rate, release);
+    }
+
+    pub fn get_envelope(&self) -> f32 {
+        self.envelope
+    }
+
+    pub fn reset(&mut self) {
+        self.envelope = 0.0;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct CompressorParams {
+    /// Threshold in dB.
+    pub threshold: f32,
+    pub ratio: f32,
+    /// Knee width in dB.
+    pub knee: f32,
+    /// Make-up gain in dB.
+    pub makeup: f32,
+    /// Attack time in seconds.
+    pub attack: f32,
+    /// Release time in seconds.
+    pub release: f32,
+    /// Program dependent release, see `Compressor::set_auto_release`.
+    pub auto_release: bool,
+    /// Make-up gain from the threshold and ratio, see `Compressor::set_auto_makeup`.
+    pub auto_makeup: bool,
+}
+
+// Time constant of the slow release stage as a multiple of the release time.
+const AUTO_RELEASE_SLOW: f32 = 10.0;
+
+/// Feed forward peak compressor with a soft knee, smoothing applied to the gain reduction.
+pub struct Compressor {
+    sample_rate: f32,
+    threshold: f32,
+    ratio: f32,
+    knee: f32,
+    makeup: f32,
+    attack: f32,
+    release: f32,
+    attack_coef: f32,
+    release_coef: f32,
+    slow_coef: f32,
+    auto_release: bool,
+    auto_makeup: bool,
+    makeup_gain: f32,
+    gain_reduction: f32,
+    slow_reduction: f32,
+    peak_reduction: f32,
+    meter: f32,
+    control_rate: bool,
+    // Loudest key since the last control rate tick.
+    key_peak: f32,
+    gain: f32,
+    gain_step: f32,
+    remaining: usize,
+}
+
+impl Compressor {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            threshold: -20.0,
+            ratio: 4.0,
+            knee: 6.0,
+            makeup: 1.0,
+            attack: 0.01,
+            release: 0.1,
+            attack_coef: time_coef(sample_rate, 0.01),
+            release_coef: time_coef(sample_rate, 0.1),
+            slow_coef: time_coef(sample_rate, 0.1 * AUTO_RELEASE_SLOW),
+            auto_release: false,
+            auto_makeup: false,
+            makeup_gain: 1.0,
+            gain_reduction: 0.0,
+            slow_reduction: 0.0,
+            peak_reduction: 0.0,
+            meter: 0.0,
+            control_rate: false,
+            key_peak: 0.0,
+            gain: 1.0,
+            gain_step: 0.0,
+            remaining: 0,
+        }
+    }
+
+    // Static curve, returns the gain change in dB (<= 0.0) for an input level in dB.
+    fn gain_computer(&self, level: f32) -> f32 {
+        let over = level - self.threshold;
+        let slope = 1.0 / self.ratio - 1.0;
+        if 2.0 * over < -self.knee {
+            0.0
+        } else if 2.0 * over.abs() <= self.knee {
+            let x = over + self.knee * 0.5;
+            slope * x * x / (2.0 * self.knee)
+        } else {
+            slope * over
+        }
+    }
+
+    /// Update the detector with a key signal and return the linear gain to apply, including make-up.
+    pub fn compute_gain(&mut self, key: f32) -> f32 {
+        if self.control_rate {
+            self.key_peak = self.key_peak.max(key.abs());
+            if self.remaining > 0 {
+                self.remaining -= 1;
+                self.gain += self.gain_step;
+            }
+            return self.gain;
+        }
+        self.detect(
+            key.abs(),
+            self.attack_coef,
+            self.release_coef,
+            self.slow_coef,
+        )
+    }
+
+    // Move the gain reduction towards the curve for a key level with the given smoothing
+    // coefficients, returning the linear gain.
+    fn detect(&mut self, level: f32, attack_coef: f32, release_coef: f32, slow_coef: f32) -> f32 {
+        let target = self.gain_computer(linear_to_db(level));
+        let coef = if target < self.gain_reduction {
+            attack_coef
+        } else {
+            release_coef
+        };
+        self.gain_reduction = target + coef * (self.gain_reduction - target);
+        let mut reduction = self.gain_reduction;
+        if self.auto_release {
+            // The slow stage only builds up under sustained compression, holding the release
+            // back afterwards while short peaks still recover at the release time.
+            self.slow_reduction =
+                self.gain_reduction + slow_coef * (self.slow_reduction - self.gain_reduction);
+            reduction = reduction.min(self.slow_reduction);
+        }
+        self.peak_reduction = self.peak_reduction.max(-reduction);
+        db_to_linear(reduction) * self.makeup_gain
+    }
+
+    /// Control rate tick, once called the gain curve is only evaluated here, for the loudest
+    /// key since the last tick, and the gain is interpolated over the next `samples` samples.
+    pub fn update_params(&mut self, samples: usize) {
+        if !self.control_rate {
+            self.control_rate = true;
+            self.gain = db_to_linear(self.gain_reduction) * self.makeup_gain;
+        }
+        let samples = samples.max(1);
+        // The smoothing over a whole tick.
+        let n = samples as i32;
+        let target = self.detect(
+            self.key_peak,
+            self.attack_coef.powi(n),
+            self.release_coef.powi(n),
+            self.slow_coef.powi(n),
+        );
+        self.key_peak = 0.0;
+        self.gain_step = (target - self.gain) / samples as f32;
+        self.remaining = samples;
+    }
+
+    // Recompute the applied make-up gain after a change to the curve or the make-up settings.
+    fn update_makeup(&mut self) {
+        self.makeup_gain = if self.auto_makeup {
+            // Half the gain reduction of a full scale input, louder without pinning peaks at 0 dBFS.
+            db_to_linear(-0.5 * self.gain_computer(0.0))
+        } else {
+            self.makeup
+        };
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        input * self.compute_gain(input)
+    }
+
+    /// Process a block in place and update the gain reduction meter.
+    pub fn process_block(&mut self, block: &mut [f32]) {
+        for sample in block.iter_mut() {
+            *sample = self.process(*sample);
+        }
+        self.latch_meter();
+    }
+
+    // Move the peak reduction since the last call into the meter.
+    fn latch_meter(&mut self) {
+        self.meter = self.peak_reduction;
+        self.peak_reduction = 0.0;
+    }
+
+    /// Get the peak gain reduction in dB (positive) during the last `process_block` call.
+    pub fn get_gain_reduction(&self) -> f32 {
+        self.meter
+    }
+
+    /// Compress the input using a separate side chain signal for detection.
+    pub fn process_sidechain(&mut self, input: f32, key: f32) -> f32 {
+        input * self.compute_gain(key)
+    }
+
+    /// Set the threshold in dB.
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
+        self.update_makeup();
+    }
+
+    /// Set the ratio, at least 1.0.
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.ratio = ratio.max(1.0);
+        self.update_makeup();
+    }
+
+    /// Set the knee width in dB.
+    pub fn set_knee(&mut self, knee: f32) {
+        self.knee = knee.max(0.0);
+        self.update_makeup();
+    }
+
+    /// Set the make-up gain in dB, ignored while auto make-up is on.
+    pub fn set_makeup(&mut self, makeup: f32) {
+        self.makeup = db_to_linear(makeup);
+        self.update_makeup();
+    }
+
+    /// Compute the make-up gain from the threshold, ratio and knee instead of `set_makeup`,
+    /// restoring half the gain reduction of a full scale input.
+    pub fn set_auto_makeup(&mut self, auto_makeup: bool) {
+        self.auto_makeup = auto_makeup;
+        self.update_makeup();
+    }
+
+    /// Get the make-up gain applied in dB, manual or automatic.
+    pub fn get_makeup(&self) -> f32 {
+        linear_to_db(self.makeup_gain)
+    }
+
+    /// Set the attack time in seconds.
+    pub fn set_attack(&mut self, attack: f32) {
+        self.attack = attack;
+        self.attack_coef = time_coef(self.sample_rate, attack);
+    }
+
+    /// Set the release time in seconds.
+    pub fn set_release(&mut self, release: f32) {
+        self.release = release;
+        self.release_coef = time_coef(self.sample_rate, release);
+        self.slow_coef = time_coef(self.sample_rate, release * AUTO_RELEASE_SLOW);
+    }
+
+    /// Program dependent release with two time constants. Short peaks recover at the release
+    /// time, sustained compression releases up to ten times slower to avoid pumping.
+    pub fn set_auto_release(&mut self, auto_release: bool) {
+        self.auto_release = auto_release;
+        self.slow_reduction = self.gain_reduction;
+    }
+
+    /// Release the gain reduction immediately and clear the meter.
+    pub fn reset(&mut self) {
+        self.gain_reduction = 0.0;
+        self.slow_reduction = 0.0;
+        self.peak_reduction = 0.0;
+        self.meter = 0.0;
+        self.key_peak = 0.0;
+        self.gain = self.makeup_gain;
+        self.remaining = 0;
+    }
+}
+
+impl Processor for Compressor {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn process_block(&mut self, block: &mut [f32]) {
+        self.process_block(block)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+
+    fn update_params(&mut self, samples: usize) {
+        self.update_params(samples)
+    }
+}
+
+impl Preset for Compressor {
+    type Params = CompressorParams;
+
+    fn get_params(&self) -> CompressorParams {
+        CompressorParams {
+            threshold: self.threshold,
+            ratio: self.ratio,
+            knee: self.knee,
+            makeup: linear_to_db(self.makeup),
+            attack: self.attack,
+            release: self.release,
+            auto_release: self.auto_release,
+            auto_makeup: self.auto_makeup,
+        }
+    }
+
+    fn set_params(&mut self, params: &CompressorParams) {
+        self.set_threshold(params.threshold);
+        self.set_ratio(params.ratio);
+        self.set_knee(params.knee);
+        self.set_makeup(params.makeup);
+        self.set_attack(params.attack);
+        self.set_release(params.release);
+        self.set_auto_release(params.auto_release);
+        self.set_auto_makeup(params.auto_makeup);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct DuckerParams {
+    /// Key threshold in dB.
+    pub threshold: f32,
+    /// Attenuation in dB (positive).
+    pub range: f32,
+    /// Attack time in seconds.
+    pub attack: f32,
+    /// Hold time in seconds.
+    pub hold: f32,
+    /// Release time in seconds.
+    pub release: f32,
+}
+
+/// Ducker, drops the input by a fixed range while a key signal is above the threshold.
+pub struct Ducker {
+    sample_rate: f32,
+    detector: EnvelopeFollower,
+    threshold: f32,
+    range: f32,
+    attack: f32,
+    release: f32,
+    attack_coef: f32,
+    release_coef: f32,
+    hold: u32,
+    hold_counter: u32,
+    gain: f32,
+}
+
+impl Ducker {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut detector = EnvelopeFollower::new(sample_rate);
+        detector.set_attack(0.001);
+        detector.set_release(0.01);
+        let mut ducker = Self {
+            sample_rate,
+            detector,
+            threshold: db_to_linear(-30.0),
+            range: db_to_linear(-12.0),
+            attack: 0.01,
+            release: 0.3,
+            attack_coef: time_coef(sample_rate, 0.01),
+            release_coef: time_coef(sample_rate, 0.3),
+            hold: 0,
+            hold_counter: 0,
+            gain: 1.0,
+        };
+        ducker.set_hold(0.2);
+        ducker
+    }
+
+    /// Process one sample of the input, ducked by the key.
+    pub fn process(&mut self, input: f32, key: f32) -> f32 {
+        if self.detector.process(key) > self.threshold {
+            self.hold_counter = self.hold;
+        } else if self.hold_counter > 0 {
+            self.hold_counter -= 1;
+        }
+        let (target, coef) = if self.hold_counter > 0 {
+            (self.range, self.attack_coef)
+        } else {
+            (1.0, self.release_coef)
+        };
+        self.gain = target + coef * (self.gain - target);
+        input * self.gain
+    }
+
+    /// Set the key level in dB above which the input is ducked.
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = db_to_linear(threshold);
+    }
+
+    /// Set the attenuation in dB (positive) applied while ducking.
+    pub fn set_range(&mut self, range: f32) {
+        self.range = db_to_linear(-range.abs());
+    }
+
+    /// Set the time in seconds to duck once the key passes the threshold.
+    pub fn set_attack(&mut self, attack: f32) {
+        self.attack = attack;
+        self.attack_coef = time_coef(self.sample_rate, attack);
+    }
+
+    /// Set the time in seconds the input stays ducked after the key falls below the threshold.
+    pub fn set_hold(&mut self, hold: f32) {
+        self.hold = (hold.max(0.0) * self.sample_rate) as u32;
+    }
+
+    /// Set the time in seconds to recover after the hold.
+    pub fn set_release(&mut self, release: f32) {
+        self.release = release;
+        self.release_coef = time_coef(self.sample_rate, release);
+    }
+
+    /// Get the current gain reduction in dB (positive).
+    pub fn get_gain_reduction(&self) -> f32 {
+        -linear_to_db(self.gain)
+    }
+
+    /// Stop ducking immediately.
+    pub fn reset(&mut self) {
+        self.detector.reset();
+        self.hold_counter = 0;
+        self.gain = 1.0;
+    }
+}
+
+impl Preset for Ducker {
+    type Params = DuckerParams;
+
+    fn get_params(&self) -> DuckerParams {
+        DuckerParams {
+            threshold: linear_to_db(self.threshold),
+            range: -linear_to_db(self.range),
+            attack: self.attack,
+            hold: self.hold as f32 / self.sample_rate,
+            release: self.release,
+        }
+    }
+
+    fn set_params(&mut self, params: &DuckerParams) {
+        self.set_threshold(params.threshold);
+        self.set_range(params.range);
+        self.set_attack(params.attack);
+        self.set_hold(params.hold);
+        self.set_release(params.release);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct GateParams {
+    /// Opening threshold in dB.
+    pub threshold: f32,
+    /// Drop below the threshold in dB before closing.
+    pub hysteresis: f32,
+    /// Attenuation in dB (positive) while closed.
+    pub range: f32,
+    /// Attack time in seconds.
+    pub attack: f32,
+    /// Hold time in seconds.
+    pub hold: f32,
+    /// Release time in seconds.
+    pub release: f32,
+    /// Lookahead in seconds.
+    pub lookahead: f32,
+    /// Detector high pass frequency in Hz, 0.0 when off.
+    pub low_cut: f32,
+    /// Detector low pass frequency in Hz, 0.0 when off.
+    pub high_cut: f32,
+}
+
+/// Noise gate, attenuating the input by the range while its level stays below the threshold.
+/// The detector can be band limited so rumble or cymbal spill don't hold the gate open, and a
+/// few milliseconds of lookahead open it ahead of fast transients such as drum hits, at the
+/// cost of delaying the audio. Lookahead needs a delay line, see `with_lookahead`.
+pub struct Gate<'a> {
+    sample_rate: f32,
+    delay_line: Option<DelayLine<'a>>,
+    lookahead: usize,
+    detector: EnvelopeFollower,
+    low_cut: f32,
+    high_cut: f32,
+    high_pass: Biquad,
+    low_pass: Biquad,
+    threshold: f32,
+    hysteresis: f32,
+    close_threshold: f32,
+    range: f32,
+    attack: f32,
+    release: f32,
+    attack_coef: f32,
+    release_coef: f32,
+    hold: u32,
+    hold_counter: u32,
+    open: bool,
+    gain: f32,
+}
+
+impl Gate<'static> {
+    /// Gate without lookahead.
+    pub fn new(sample_rate: f32) -> Self {
+        Self::build(sample_rate, None)
+    }
+}
+
+impl<'a> Gate<'a> {
+    /// Gate with up to the delay line's length of lookahead, none until `set_lookahead`.
+    pub fn with_lookahead(sample_rate: f32, delay_line: DelayLine<'a>) -> Self {
+        Self::build(sample_rate, Some(delay_line))
+    }
+
+    fn build(sample_rate: f32, delay_line: Option<DelayLine<'a>>) -> Self {
+        let mut detector = EnvelopeFollower::new(sample_rate);
+        detector.set_attack(0.0);
+        detector.set_release(0.01);
+        let mut gate = Self {
+            sample_rate,
+            delay_line,
+            lookahead: 0,
+            detector,
+            low_cut: 0.0,
+            high_cut: 0.0,
+            high_pass: Biquad::new(sample_rate),
+            low_pass: Biquad::new(sample_rate),
+            threshold: 0.0,
+            hysteresis: 0.0,
+            close_threshold: 0.0,
+            range: db_to_linear(-80.0),
+            attack: 0.0,
+            release: 0.0,
+            attack_coef: 0.0,
+            release_coef: 0.0,
+            hold: 0,
+            hold_counter: 0,
+            open: false,
+            gain: 0.0,
+        };
+        gate.gain = gate.range;
+        gate.set_hysteresis(6.0);
+        gate.set_threshold(-50.0);
+        gate.set_attack(0.0005);
+        gate.set_hold(0.05);
+        gate.set_release(0.1);
+        gate
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.process_sidechain(input, input)
+    }
+
+    /// Gate the input by the level of a separate key signal, such as a drum's own close mic.
+    pub fn process_sidechain(&mut self, input: f32, key: f32) -> f32 {
+        let mut key = key;
+        if self.low_cut > 0.0 {
+            key = self.high_pass.process(key);
+        }
+        if self.high_cut > 0.0 {
+            key = self.low_pass.process(key);
+        }
+        let level = self.detector.process(key);
+        if level > self.threshold {
+            self.open = true;
+        } else if level < self.close_threshold {
+            self.open = false;
+        }
+        if self.open {
+            self.hold_counter = self.hold;
+        } else if self.hold_counter > 0 {
+            self.hold_counter -= 1;
+        }
+        let (target, coef) = if self.open || self.hold_counter > 0 {
+            (1.0, self.attack_coef)
+        } else {
+            (self.range, self.release_coef)
+        };
+        self.gain = target + coef * (self.gain - target);
+
+        // Keep writing without lookahead, so turning it on doesn't play stale audio.
+        let delayed = match &mut self.delay_line {
+            Some(delay_line) => {
+                delay_line.write(input);
+                if self.lookahead > 0 {
+                    delay_line.tap(self.lookahead + 1)
+                } else {
+                    input
+                }
+            }
+            None => input,
+        };
+        delayed * self.gain
+    }
+
+    pub fn process_block(&mut self, block: &mut [f32]) {
+        for sample in block.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
+
+    /// Set the level in dB above which the gate opens.
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = db_to_linear(threshold);
+        self.close_threshold = db_to_linear(threshold - self.hysteresis);
+    }
+
+    /// Set how far in dB the level must fall below the threshold to close the gate, so a level
+    /// hovering around the threshold doesn't chatter.
+    pub fn set_hysteresis(&mut self, hysteresis: f32) {
+        self.hysteresis = hysteresis.max(0.0);
+        self.set_threshold(linear_to_db(self.threshold));
+    }
+
+    /// Set the attenuation in dB (positive) while closed.
+    pub fn set_range(&mut self, range: f32) {
+        self.range = db_to_linear(-range.abs());
+    }
+
+    /// Set the time in seconds to open.
+    pub fn set_attack(&mut self, attack: f32) {
+        self.attack = attack;
+        self.attack_coef = time_coef(self.sample_rate, attack);
+    }
+
+    /// Set the time in seconds the gate stays open after the level falls below the threshold.
+    pub fn set_hold(&mut self, hold: f32) {
+        self.hold = (hold.max(0.0) * self.sample_rate) as u32;
+    }
+
+    /// Set the time in seconds to close after the hold.
+    pub fn set_release(&mut self, release: f32) {
+        self.release = release;
+        self.release_coef = time_coef(self.sample_rate, release);
+    }
+
+    /// Set the lookahead in seconds, limited by the delay line, none without one.
+    pub fn set_lookahead(&mut self, lookahead: f32) {
+        let max = self
+            .delay_line
+            .as_ref()
+            .map_or(0, |delay_line| delay_line.len().saturating_sub(1));
+        self.lookahead = ((lookahead.max(0.0) * self.sample_rate) as usize).min(max);
+    }
+
+    /// Band limit the detector to the range of the source, 0.0 leaves either side open.
+    pub fn set_detector_band(&mut self, low_cut: f32, high_cut: f32) {
+        self.low_cut = low_cut.max(0.0);
+        self.high_cut = high_cut.max(0.0);
+        if self.low_cut > 0.0 {
+            self.high_pass
+                .set_high_pass(self.low_cut, core::f32::consts::FRAC_1_SQRT_2);
+        }
+        if self.high_cut > 0.0 {
+            self.low_pass
+                .set_low_pass(self.high_cut, core::f32::consts::FRAC_1_SQRT_2);
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open || self.hold_counter > 0
+    }
+
+    /// Get the current gain reduction in dB (positive).
+    pub fn get_gain_reduction(&self) -> f32 {
+        -linear_to_db(self.gain)
+    }
+
+    /// Delay of the audio in samples from the lookahead.
+    pub fn latency_samples(&self) -> usize {
+        self.lookahead
+    }
+
+    /// Close the gate and clear the lookahead and detector.
+    pub fn reset(&mut self) {
+        if let Some(delay_line) = &mut self.delay_line {
+            delay_line.reset();
+        }
+        self.detector.reset();
+        self.high_pass.reset();
+        self.low_pass.reset();
+        self.open = false;
+        self.hold_counter = 0;
+        self.gain = self.range;
+    }
+}
+
+impl Processor for Gate<'_> {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.latency_samples()
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+impl Preset for Gate<'_> {
+    type Params = GateParams;
+
+    fn get_params(&self) -> GateParams {
+        GateParams {
+            threshold: linear_to_db(self.threshold),
+            hysteresis: self.hysteresis,
+            range: -linear_to_db(self.range),
+            attack: self.attack,
+            hold: self.hold as f32 / self.sample_rate,
+            release: self.release,
+            lookahead: self.lookahead as f32 / self.sample_rate,
+            low_cut: self.low_cut,
+            high_cut: self.high_cut,
+        }
+    }
+
+    fn set_params(&mut self, params: &GateParams) {
+        self.set_hysteresis(params.hysteresis);
+        self.set_threshold(params.threshold);
+        self.set_range(params.range);
+        self.set_attack(params.attack);
+        self.set_hold(params.hold);
+        self.set_release(params.release);
+        self.set_lookahead(params.lookahead);
+        self.set_detector_band(params.low_cut, params.high_cut);
+    }
+}
+
+/// Most bands supported by the multiband compressor.
+pub const MAX_BANDS: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct MultibandCompressorParams {
+    /// Crossover frequencies in Hz, lowest first.
+    pub crossover_freqs: [f32; MAX_BANDS - 1],
+    /// The compressor of each band, including those above the number in use.
+    pub bands: [CompressorParams; MAX_BANDS],
+}
+
+/// Multiband compressor, a `BandSplit` feeding a compressor per band so the bands recombine with
+/// a flat magnitude response.
+pub struct MultibandCompressor {
+    bands: usize,
+    split: BandSplit<MAX_BANDS>,
+    compressors: [Compressor; MAX_BANDS],
+}
+
+impl MultibandCompressor {
+    /// Create a compressor with `bands` bands, clamped to [2, MAX_BANDS].
+    pub fn new(sample_rate: f32, bands: usize) -> Self {
+        let bands = bands.clamp(2, MAX_BANDS);
+        let mut split = BandSplit::new(sample_rate);
+        split.set_bands(bands);
+        let mut compressor = Self {
+            bands,
+            split,
+            compressors: core::array::from_fn(|_| Compressor::new(sample_rate)),
+        };
+        let freqs = [200.0, 2000.0, 8000.0];
+        for (band, freq) in freqs.iter().enumerate() {
+            compressor.set_crossover_freq(band, *freq);
+        }
+        compressor
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let bands = self.split.process(input);
+        bands[..self.bands]
+            .iter()
+            .zip(self.compressors.iter_mut())
+            .map(|(band, compressor)| compressor.process(*band))
+            .sum()
+    }
+
+    /// Process a block in place and update the gain reduction meter of each band.
+    pub fn process_block(&mut self, block: &mut [f32]) {
+        for sample in block.iter_mut() {
+            *sample = self.process(*sample);
+        }
+        for compressor in self.compressors.iter_mut() {
+            compressor.latch_meter();
+        }
+    }
+
+    /// Get the peak gain reduction in dB of `band` during the last `process_block` call.
+    pub fn get_gain_reduction(&self, band: usize) -> f32 {
+        self.compressors[..self.bands]
+            .get(band)
+            .map_or(0.0, |compressor| compressor.get_gain_reduction())
+    }
+
+    /// Set the frequency of the crossover between `band` and `band + 1`.
+    pub fn set_crossover_freq(&mut self, band: usize, freq: f32) {
+        self.split.set_crossover_freq(band, freq);
+    }
+
+    /// Access one band's compressor to set its parameters independently.
+    pub fn band(&mut self, band: usize) -> Option<&mut Compressor> {
+        self.compressors[..self.bands].get_mut(band)
+    }
+
+    /// Set the threshold of all bands in dB.
+    pub fn set_threshold(&mut self, threshold: f32) {
+        for compressor in self.compressors.iter_mut() {
+            compressor.set_threshold(threshold);
+        }
+    }
+
+    /// Set the ratio of all bands.
+    pub fn set_ratio(&mut self, ratio: f32) {
+        for compressor in self.compressors.iter_mut() {
+            compressor.set_ratio(ratio);
+        }
+    }
+
+    /// Set the attack time of all bands in seconds.
+    pub fn set_attack(&mut self, attack: f32) {
+        for compressor in self.compressors.iter_mut() {
+            compressor.set_attack(attack);
+        }
+    }
+
+    /// Set the release time of all bands in seconds.
+    pub fn set_release(&mut self, release: f32) {
+        for compressor in self.compressors.iter_mut() {
+            compressor.set_release(release);
+        }
+    }
+
+    /// Set the make-up gain of all bands in dB.
+    pub fn set_makeup(&mut self, makeup: f32) {
+        for compressor in self.compressors.iter_mut() {
+            compressor.set_makeup(makeup);
+        }
+    }
+
+    /// Set program dependent release on all bands.
+    pub fn set_auto_release(&mut self, auto_release: bool) {
+        for compressor in self.compressors.iter_mut() {
+            compressor.set_auto_release(auto_release);
+        }
+    }
+
+    /// Set automatic make-up gain on all bands.
+    pub fn set_auto_makeup(&mut self, auto_makeup: bool) {
+        for compressor in self.compressors.iter_mut() {
+            compressor.set_auto_makeup(auto_makeup);
+        }
+    }
+
+    /// Control rate tick for every band, see `Compressor::update_params`.
+    pub fn update_params(&mut self, samples: usize) {
+        for compressor in self.compressors.iter_mut() {
+            compressor.update_params(samples);
+        }
+    }
+
+    /// Clear the crossovers and release every band.
+    pub fn reset(&mut self) {
+        self.split.reset();
+        for compressor in self.compressors.iter_mut() {
+            compressor.reset();
+        }
+    }
+}
+
+impl Processor for MultibandCompressor {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn process_block(&mut self, block: &mut [f32]) {
+        self.process_block(block)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+
+    fn update_params(&mut self, samples: usize) {
+        self.update_params(samples)
+    }
+}
+
+impl Preset for MultibandCompressor {
+    type Params = MultibandCompressorParams;
+
+    fn get_params(&self) -> MultibandCompressorParams {
+        MultibandCompressorParams {
+            crossover_freqs: core::array::from_fn(|band| self.split.get_crossover_freq(band)),
+            bands: core::array::from_fn(|band| self.compressors[band].get_params()),
+        }
+    }
+
+    fn set_params(&mut self, params: &MultibandCompressorParams) {
+        for (band, freq) in params.crossover_freqs.iter().enumerate() {
+            self.set_crossover_freq(band, *freq);
+        }
+        for (compressor, params) in self.compressors.iter_mut().zip(params.bands.iter()) {
+            compressor.set_params(params);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const SAMPLE_RATE_F: f32 = 44100.0;
+
+    use super::*;
+    use crate::synthesis::{Oscillator, WaveType};
+
+    fn steady_peak<F: FnMut(f32) -> f32>(freq: f32, amplitude: f32, mut process: F) -> f32 {
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, freq);
+        oscillator.set_amplitude(amplitude);
+        let mut peak = 0.0_f32;
+        for i in 0..44100 {
+            let output = process(oscillator.process());
+            if i > 22050 {
+                peak = peak.max(output.abs());
+            }
+        }
+        peak
+    }
+
+    #[test]
+    fn test_compressor_and_ducker_presets() {
+        let mut compressor = Compressor::new(SAMPLE_RATE_F);
+        compressor.set_threshold(-12.0);
+        compressor.set_makeup(3.0);
+        compressor.set_release(0.25);
+        let params = compressor.get_params();
+        assert!((params.makeup - 3.0).abs() < 1e-4);
+        let mut restored = Compressor::new(SAMPLE_RATE_F);
+        restored.set_params(&params);
+        assert_eq!(restored.get_params(), params);
+
+        let mut ducker = Ducker::new(SAMPLE_RATE_F);
+        ducker.set_range(20.0);
+        ducker.set_hold(0.5);
+        let params = ducker.get_params();
+        assert!((params.range - 20.0).abs() < 1e-4);
+        assert!((params.hold - 0.5).abs() < 1e-4);
+        let mut restored = Ducker::new(SAMPLE_RATE_F);
+        restored.set_params(&params);
+        assert_eq!(restored.get_params(), params);
+    }
+
+    #[test]
+    fn test_envelope_follower() {
+        let mut follower = EnvelopeFollower::new(SAMPLE_RATE_F);
+        follower.set_attack(0.001);
+        follower.set_release(0.1);
+        for _ in 0..441 {
+            follower.process(-0.5);
+        }
+        assert!((follower.get_envelope() - 0.5).abs() < 0.01);
+        // One release time constant later the envelope has fallen to 1/e.
+        for _ in 0..4410 {
+            follower.process(0.0);
+        }
+        assert!((follower.get_envelope() - 0.5 / core::f32::consts::E).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_compressor_ratio() {
+        let mut compressor = Compressor::new(SAMPLE_RATE_F);
+        compressor.set_threshold(-20.0);
+        compressor.set_ratio(4.0);
+        compressor.set_knee(0.0);
+        compressor.set_release(1.0);
+        // 0 dBFS in, 20 dB over the threshold comes out 5 dB over.
+        let peak = steady_peak(1000.0, 1.0, |x| compressor.process(x));
+        assert!((linear_to_db(peak) + 15.0).abs() < 1.0);
+        // The same at control rate.
+        compressor.reset();
+        let mut control = crate::processor::ControlRate::new(compressor, 32);
+        let control_peak = steady_peak(1000.0, 1.0, |x| control.process(x));
+        assert!((linear_to_db(control_peak) - linear_to_db(peak)).abs() < 0.5);
+
+        // Below the threshold the signal is untouched.
+        let mut compressor = Compressor::new(SAMPLE_RATE_F);
+        let peak = steady_peak(1000.0, 0.01, |x| compressor.process(x));
+        assert!((peak - 0.01).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_compressor_gain_reduction_meter() {
+        let mut compressor = Compressor::new(SAMPLE_RATE_F);
+        compressor.set_threshold(-20.0);
+        compressor.set_ratio(4.0);
+        compressor.set_knee(0.0);
+        compressor.set_attack(0.0);
+        let mut block = [1.0; 64];
+        compressor.process_block(&mut block);
+        assert!((compressor.get_gain_reduction() - 15.0).abs() < 0.1);
+
+        let mut block = [0.0; 64];
+        compressor.set_release(0.0);
+        compressor.process_block(&mut block);
+        assert!(compressor.get_gain_reduction() < 1e-3);
+    }
+
+    #[test]
+    fn test_compressor_auto_makeup_and_release() {
+        let mut compressor = Compressor::new(SAMPLE_RATE_F);
+        compressor.set_threshold(-20.0);
+        compressor.set_ratio(4.0);
+        compressor.set_knee(0.0);
+        compressor.set_makeup(3.0);
+        compressor.set_auto_makeup(true);
+        // 15 dB of reduction at full scale, half of it made up.
+        assert!((compressor.get_makeup() - 7.5).abs() < 1e-3);
+        compressor.set_ratio(2.0);
+        assert!((compressor.get_makeup() - 5.0).abs() < 1e-3);
+        compressor.set_auto_makeup(false);
+        assert!((compressor.get_makeup() - 3.0).abs() < 1e-3);
+
+        // Reduction left 50 ms after a burst of `len` samples at full scale.
+        let remaining = |len: usize| {
+            let mut compressor = Compressor::new(SAMPLE_RATE_F);
+            compressor.set_knee(0.0);
+            compressor.set_attack(0.001);
+            compressor.set_release(0.05);
+            compressor.set_auto_release(true);
+            let mut block: Vec<f32> = vec![1.0; len];
+            block.resize(len + 2205, 0.0);
+            compressor.process_block(&mut block);
+            compressor.compute_gain(0.0).recip()
+        };
+        // A short peak recovers at the release time, sustained compression more slowly.
+        let short = linear_to_db(remaining(441));
+        let sustained = linear_to_db(remaining(88200));
+        // One release time constant brings the 15 dB of reduction down to 15 / e.
+        assert!(short < 6.0, "{}", short);
+        assert!(sustained > 12.0, "{}", sustained);
+    }
+
+    #[test]
+    fn test_gate() {
+        // A hit out of silence, the first sample full scale.
+        let hit = |gate: &mut Gate| {
+            let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 100.0);
+            oscillator.set_phase(0.25);
+            for _ in 0..4410 {
+                gate.process(0.0);
+            }
+            let latency = gate.latency_samples();
+            let output: Vec<f32> = (0..4410)
+                .map(|_| gate.process(oscillator.process()))
+                .collect();
+            output[latency]
+        };
+        let mut gate = Gate::new(SAMPLE_RATE_F);
+        gate.set_attack(0.001);
+        assert!(hit(&mut gate) < 0.1);
+        assert!(gate.is_open());
+
+        let mut buffer = [0.0; 256];
+        let mut gate = Gate::with_lookahead(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+        gate.set_attack(0.001);
+        gate.set_lookahead(0.005);
+        assert_eq!(gate.latency_samples(), 220);
+        assert!(hit(&mut gate) > 0.99);
+        let params = gate.get_params();
+        let mut buffer = [0.0; 256];
+        let mut restored = Gate::with_lookahead(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+        restored.set_params(&params);
+        assert_eq!(restored.get_params(), params);
+
+        // Turning the lookahead on plays the audio from before it was on.
+        let mut buffer = [0.0; 256];
+        let mut gate = Gate::with_lookahead(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+        gate.set_threshold(-100.0);
+        gate.set_attack(0.0);
+        let input = |n: usize| 0.1 + n as f32 * 0.001;
+        for n in 0..100 {
+            assert!((gate.process(input(n)) - input(n)).abs() < 1e-3);
+        }
+        gate.set_lookahead(0.001);
+        let latency = gate.latency_samples();
+        assert!((gate.process(input(100)) - input(100 - latency)).abs() < 1e-3);
+
+        // Rumble holds the gate open unless the detector cuts it.
+        let mut gate = Gate::new(SAMPLE_RATE_F);
+        gate.set_threshold(-40.0);
+        let peak = steady_peak(30.0, 0.03, |x| gate.process(x));
+        assert!((peak - 0.03).abs() < 1e-3);
+        gate.set_detector_band(100.0, 0.0);
+        gate.reset();
+        let peak = steady_peak(30.0, 0.03, |x| gate.process(x));
+        assert!(peak < 0.03 * db_to_linear(-60.0), "{}", peak);
+    }
+
+    #[test]
+    fn test_ducker() {
+        let mut ducker = Ducker::new(SAMPLE_RATE_F);
+        ducker.set_range(12.0);
+        ducker.set_hold(0.1);
+        for _ in 0..4410 {
+            assert_eq!(ducker.process(1.0, 0.0), 1.0);
+        }
+        let mut output = 0.0;
+        for _ in 0..4410 {
+            output = ducker.process(1.0, 0.5);
+        }
+        assert!((linear_to_db(output) + 12.0).abs() < 0.1);
+        // Still ducked during the hold time.
+        for _ in 0..2205 {
+            output = ducker.process(1.0, 0.0);
+        }
+        assert!((ducker.get_gain_reduction() - 12.0).abs() < 0.1);
+        for _ in 0..88200 {
+            output = ducker.process(1.0, 0.0);
+        }
+        assert!((output - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_multiband_flat_below_threshold() {
+        for bands in 2..=MAX_BANDS {
+            let mut compressor = MultibandCompressor::new(SAMPLE_RATE_F, bands);
+            compressor.set_threshold(0.0);
+            for freq in [100.0, 1000.0, 5000.0, 12000.0].iter() {
+                let peak = steady_peak(*freq, 0.5, |x| compressor.process(x));
+                assert!((peak - 0.5).abs() < 0.01, "{} {} {}", bands, freq, peak);
+            }
+        }
+    }
+
+    #[test]
+    fn test_multiband_independent_bands() {
+        let mut compressor = MultibandCompressor::new(SAMPLE_RATE_F, 3);
+        compressor.set_threshold(0.0);
+        compressor.band(0).unwrap().set_threshold(-40.0);
+        compressor.band(0).unwrap().set_ratio(10.0);
+        assert!(compressor.band(3).is_none());
+        let low = steady_peak(50.0, 0.5, |x| compressor.process(x));
+        let high = steady_peak(10000.0, 0.5, |x| compressor.process(x));
+        assert!(low < 0.1);
+        assert!((high - 0.5).abs() < 0.01);
+
+        let params = compressor.get_params();
+        assert_eq!(params.crossover_freqs, [200.0, 2000.0, 8000.0]);
+        assert_eq!(params.bands[0].threshold, -40.0);
+        let mut restored = MultibandCompressor::new(SAMPLE_RATE_F, 3);
+        restored.set_params(&params);
+        assert_eq!(restored.get_params(), params);
+        let low = steady_peak(50.0, 0.5, |x| restored.process(x));
+        assert!(low < 0.1);
+    }
+}
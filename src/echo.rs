@@ -0,0 +1,244 @@
+use crate::control::{Smoother, SmootherMode};
+use crate::delay::DelayLine;
+use crate::distortion::soft_clip;
+use crate::filter::{Biquad, OnePoleLowPass};
+use crate::mix::{CrossfadeCurve, Mix};
+use crate::preset::Preset;
+use crate::processor::Processor;
+use crate::random::{Random, XorShift32};
+use crate::synthesis::{Oscillator, WaveType};
+
+// Peak delay time deviation at full wow and flutter depth, in seconds.
+const WOW_DEPTH: f32 = 0.002;
+const FLUTTER_DEPTH: f32 = 0.0002;
+// Time constant of the smoothing that turns noise into slow drift, about a 1 Hz cutoff.
+const DRIFT_TIME: f32 = 0.16;
+const RANDOM_SEED: u32 = 0x1234_5678;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct TapeDelayParams {
+    /// Delay time in seconds.
+    pub delay: f32,
+    pub feedback: f32,
+    pub mix: f32,
+    pub mix_curve: CrossfadeCurve,
+    pub drive: f32,
+    pub wow: f32,
+    pub flutter: f32,
+    /// Tape loss cutoff in Hz.
+    pub tone: f32,
+}
+
+/// Tape echo, a feedback delay with wow and flutter, head bump EQ and saturation in the loop.
+pub struct TapeDelay<'a> {
+    sample_rate: f32,
+    delay_line: DelayLine<'a>,
+    delay: f32,
+    feedback: f32,
+    mix: Mix,
+    drive: f32,
+    wow: f32,
+    flutter: f32,
+    tone: f32,
+    wow_lfo: Oscillator,
+    flutter_lfo: Oscillator,
+    drift: Smoother,
+    random: XorShift32,
+    head_bump: Biquad,
+    tape_loss: OnePoleLowPass,
+}
+
+impl<'a> TapeDelay<'a> {
+    pub fn new(sample_rate: f32, delay_line: DelayLine<'a>) -> Self {
+        let mut drift = Smoother::new(sample_rate);
+        drift.set_mode(SmootherMode::OnePole);
+        drift.set_time(DRIFT_TIME);
+        let mut head_bump = Biquad::new(sample_rate);
+        head_bump.set_peak(100.0, 1.0, 3.0);
+        let mut tape_loss = OnePoleLowPass::new(sample_rate);
+        tape_loss.set_freq(5000.0);
+        let mut tape_delay = Self {
+            sample_rate,
+            delay_line,
+            delay: 1.0,
+            feedback: 0.4,
+            mix: Mix::new(CrossfadeCurve::Linear),
+            drive: 1.0,
+            wow: 0.2,
+            flutter: 0.2,
+            tone: 5000.0,
+            wow_lfo: Oscillator::new(WaveType::Sine, sample_rate, 0.7),
+            flutter_lfo: Oscillator::new(WaveType::Sine, sample_rate, 7.0),
+            drift,
+            random: XorShift32::new(RANDOM_SEED),
+            head_bump,
+            tape_loss,
+        };
+        tape_delay.set_delay(0.3);
+        tape_delay
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        // Slow random drift combined with the periodic wow.
+        self.drift.set_target(self.random.next_f32_bipolar());
+        let drift = self.drift.next() * 4.0;
+        let wow = (self.wow_lfo.process() + drift) * 0.5 * self.wow * WOW_DEPTH;
+        let flutter = self.flutter_lfo.process() * self.flutter * FLUTTER_DEPTH;
+        let delay = self.delay + (wow + flutter) * self.sample_rate;
+
+        let delayed = self.delay_line.read_delayed(delay);
+        let feedback = self.tape_loss.process(self.head_bump.process(delayed));
+        let record = soft_clip((input + feedback * self.feedback) * self.drive) / self.drive;
+        self.delay_line.write(record);
+
+        self.mix.process(input, delayed)
+    }
+
+    /// Set the delay time in seconds, limited by the delay line length.
+    pub fn set_delay(&mut self, delay: f32) {
+        let max_modulation = (WOW_DEPTH + FLUTTER_DEPTH) * self.sample_rate + 1.0;
+        let max_delay = self.delay_line.len() as f32 - 2.0 - max_modulation;
+        self.delay =
+            (delay * self.sample_rate).clamp(max_modulation, max_delay.max(max_modulation));
+    }
+
+    /// Set the feedback, clamped to [0.0-1.0]. The saturation keeps high settings from running away.
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.feedback = feedback.clamp(0.0, 1.0);
+    }
+
+    /// Set the dry/wet mix, clamped to [0.0-1.0].
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix.set_mix(mix);
+    }
+
+    pub fn set_mix_curve(&mut self, curve: CrossfadeCurve) {
+        self.mix.set_curve(curve);
+    }
+
+    /// Set the gain into the tape saturation.
+    pub fn set_drive(&mut self, drive: f32) {
+        self.drive = drive.max(0.01);
+    }
+
+    /// Set the amount of slow pitch wobble, clamped to [0.0-1.0].
+    pub fn set_wow(&mut self, wow: f32) {
+        self.wow = wow.clamp(0.0, 1.0);
+    }
+
+    /// Set the amount of fast pitch wobble, clamped to [0.0-1.0].
+    pub fn set_flutter(&mut self, flutter: f32) {
+        self.flutter = flutter.clamp(0.0, 1.0);
+    }
+
+    /// Set the cutoff of the tape loss filter in the feedback loop.
+    pub fn set_tone(&mut self, freq: f32) {
+        self.tone = freq;
+        self.tape_loss.set_freq(freq);
+    }
+
+    /// Erase the tape and restart the wow and flutter, the drift repeats from the start too.
+    pub fn reset(&mut self) {
+        self.delay_line.reset();
+        self.head_bump.reset();
+        self.tape_loss.reset();
+        self.drift.set_immediate(0.0);
+        self.wow_lfo.reset();
+        self.flutter_lfo.reset();
+        self.random = XorShift32::new(RANDOM_SEED);
+    }
+}
+
+impl Processor for TapeDelay<'_> {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+impl Preset for TapeDelay<'_> {
+    type Params = TapeDelayParams;
+
+    fn get_params(&self) -> TapeDelayParams {
+        TapeDelayParams {
+            delay: self.delay / self.sample_rate,
+            feedback: self.feedback,
+            mix: self.mix.get_mix(),
+            mix_curve: self.mix.get_curve(),
+            drive: self.drive,
+            wow: self.wow,
+            flutter: self.flutter,
+            tone: self.tone,
+        }
+    }
+
+    fn set_params(&mut self, params: &TapeDelayParams) {
+        self.set_delay(params.delay);
+        self.set_feedback(params.feedback);
+        self.set_mix_curve(params.mix_curve);
+        self.set_mix(params.mix);
+        self.set_drive(params.drive);
+        self.set_wow(params.wow);
+        self.set_flutter(params.flutter);
+        self.set_tone(params.tone);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const SAMPLE_RATE_F: f32 = 44100.0;
+
+    use super::*;
+
+    #[test]
+    fn test_tape_delay_preset() {
+        let mut buffer = [0.0; 44100];
+        let mut tape_delay = TapeDelay::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+        tape_delay.set_delay(0.5);
+        tape_delay.set_mix_curve(CrossfadeCurve::EqualPower);
+        tape_delay.set_tone(3000.0);
+        let params = tape_delay.get_params();
+        assert!((params.delay - 0.5).abs() < 1e-6);
+        assert_eq!(params.mix_curve, CrossfadeCurve::EqualPower);
+
+        let mut buffer = [0.0; 44100];
+        let mut restored = TapeDelay::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+        restored.set_params(&params);
+        assert_eq!(restored.get_params(), params);
+    }
+
+    #[test]
+    fn test_tape_delay_echo() {
+        let mut buffer = vec![0.0; 44100];
+        let mut tape_delay = TapeDelay::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+        tape_delay.set_delay(0.1);
+        tape_delay.set_mix(1.0);
+        tape_delay.set_wow(0.0);
+        tape_delay.set_flutter(0.0);
+        let output: Vec<f32> = (0..8820)
+            .map(|i| tape_delay.process(if i < 64 { 0.5 } else { 0.0 }))
+            .collect();
+        let first_echo = output.iter().position(|x| x.abs() > 0.1).unwrap();
+        assert!((first_echo as i32 - 4410).abs() < 4);
+    }
+
+    #[test]
+    fn test_tape_delay_full_feedback_bounded() {
+        let mut buffer = vec![0.0; 44100];
+        let mut tape_delay = TapeDelay::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+        tape_delay.set_feedback(1.0);
+        tape_delay.set_wow(1.0);
+        tape_delay.set_flutter(1.0);
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 220.0);
+        for _ in 0..441000 {
+            let out = tape_delay.process(oscillator.process());
+            assert!(out.abs() < 3.0);
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use core::fmt;
+
+/// Errors from the fallible `try_new` constructors, so firmware can fail gracefully at init
+/// instead of panicking or running with nonsense parameters.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// A buffer is empty or too short for the processor.
+    BufferTooSmall,
+    /// The sample rate is zero, negative or not finite.
+    InvalidSampleRate,
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BufferTooSmall => write!(f, "buffer too small"),
+            Error::InvalidSampleRate => write!(f, "invalid sample rate"),
+        }
+    }
+}
+
+pub(crate) fn check_sample_rate(sample_rate: f32) -> Result<()> {
+    if sample_rate.is_finite() && sample_rate > 0.0 {
+        Ok(())
+    } else {
+        trace!("invalid sample rate {}", sample_rate);
+        Err(Error::InvalidSampleRate)
+    }
+}
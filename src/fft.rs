@@ -0,0 +1,368 @@
+float_math!();
+
+use core::f64::consts::PI;
+use core::ops::{Add, Mul, Sub};
+
+#[cfg(all(feature = "cmsis", target_arch = "arm"))]
+use crate::cmsis::FftInstances;
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Complex {
+    pub re: f32,
+    pub im: f32,
+}
+
+impl Complex {
+    pub const fn new(re: f32, im: f32) -> Self {
+        Self { re, im }
+    }
+
+    /// From a magnitude and a phase in radians.
+    pub fn from_polar(norm: f32, arg: f32) -> Self {
+        Self::new(norm * arg.cos(), norm * arg.sin())
+    }
+
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    pub fn norm_sqr(self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+
+    /// Magnitude of the complex number.
+    pub fn norm(self) -> f32 {
+        self.norm_sqr().sqrt()
+    }
+
+    /// Phase in radians, in [-pi, pi].
+    pub fn arg(self) -> f32 {
+        self.im.atan2(self.re)
+    }
+
+    fn scale(self, factor: f32) -> Self {
+        Self::new(self.re * factor, self.im * factor)
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+// Accurate sine and cosine for building the twiddle tables, the fast approximations used for
+// audio rate math are not precise enough for large transforms.
+fn sin_cos(theta: f64) -> (f64, f64) {
+    // Reduce to [-PI, PI) around PI, sin(x) = -sin(x - PI) and cos(x) = -cos(x - PI).
+    let x = theta - PI;
+    let mut sin = 0.0;
+    let mut cos = 0.0;
+    let mut term = 1.0;
+    for n in 0..40 {
+        if n % 2 == 0 {
+            cos += if n % 4 == 0 { term } else { -term };
+        } else {
+            sin += if n % 4 == 1 { term } else { -term };
+        }
+        term *= x / (n + 1) as f64;
+    }
+    (-sin, -cos)
+}
+
+// Iterative radix-2 decimation in time transform of `data` in place.
+// `twiddles` holds the roots of unity of an order that is a multiple of the data length.
+fn transform(data: &mut [Complex], twiddles: &[Complex]) {
+    let len = data.len();
+    let bits = len.trailing_zeros();
+    if bits == 0 {
+        return;
+    }
+    for i in 0..len {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if j > i {
+            data.swap(i, j);
+        }
+    }
+    let mut size = 2;
+    while size <= len {
+        let half = size / 2;
+        let stride = twiddles.len() / size;
+        for start in (0..len).step_by(size) {
+            for k in 0..half {
+                let odd = data[start + k + half] * twiddles[k * stride];
+                let even = data[start + k];
+                data[start + k] = even + odd;
+                data[start + k + half] = even - odd;
+            }
+        }
+        size *= 2;
+    }
+}
+
+// Inverse transform with 1 / len scaling, using the conjugate symmetry of the forward transform.
+fn inverse_transform(data: &mut [Complex], twiddles: &[Complex]) {
+    for x in data.iter_mut() {
+        *x = x.conj();
+    }
+    transform(data, twiddles);
+    let scale = 1.0 / data.len() as f32;
+    for x in data.iter_mut() {
+        *x = x.conj().scale(scale);
+    }
+}
+
+/// Allocation free radix-2 FFT of a power of two size `N`.
+/// The twiddle table is calculated once on construction, so keep the instance around.
+/// With the `cmsis` feature on Arm targets, sizes supported by CMSIS-DSP use its transforms.
+pub struct Fft<const N: usize> {
+    twiddles: [Complex; N],
+    #[cfg(all(feature = "cmsis", target_arch = "arm"))]
+    cmsis: FftInstances,
+}
+
+impl<const N: usize> Fft<N> {
+    pub fn new() -> Self {
+        assert!(
+            N.is_power_of_two() && N >= 2,
+            "FFT size must be a power of two"
+        );
+        let mut twiddles = [Complex::default(); N];
+        for (k, twiddle) in twiddles.iter_mut().enumerate() {
+            let (sin, cos) = sin_cos(2.0 * PI * k as f64 / N as f64);
+            *twiddle = Complex::new(cos as f32, -sin as f32);
+        }
+        Self {
+            twiddles,
+            #[cfg(all(feature = "cmsis", target_arch = "arm"))]
+            cmsis: FftInstances::new(N),
+        }
+    }
+
+    /// Forward transform in place, unnormalized.
+    pub fn process(&self, data: &mut [Complex; N]) {
+        #[cfg(all(feature = "cmsis", target_arch = "arm"))]
+        if self.cmsis.complex(as_interleaved(data), false) {
+            return;
+        }
+        transform(data, &self.twiddles);
+    }
+
+    /// Inverse transform in place, scaled by 1 / N so it undoes `process`.
+    pub fn inverse(&self, data: &mut [Complex; N]) {
+        #[cfg(all(feature = "cmsis", target_arch = "arm"))]
+        if self.cmsis.complex(as_interleaved(data), true) {
+            return;
+        }
+        inverse_transform(data, &self.twiddles);
+    }
+
+    /// Forward transform of `N` real samples in place, computed with an `N / 2` point complex
+    /// transform. The output is packed as in CMSIS-DSP: `data[0]` is the DC bin, `data[1]` the
+    /// real Nyquist bin, followed by real and imaginary pairs for bins 1 to `N / 2 - 1`.
+    pub fn process_real(&self, data: &mut [f32; N]) {
+        #[cfg(all(feature = "cmsis", target_arch = "arm"))]
+        {
+            let mut input = *data;
+            if self.cmsis.real(&mut input, data, false) {
+                return;
+            }
+        }
+        let spectrum = as_complex(data);
+        let half = spectrum.len();
+        transform(spectrum, &self.twiddles);
+
+        let dc = spectrum[0];
+        spectrum[0] = Complex::new(dc.re + dc.im, dc.re - dc.im);
+        for k in 1..=half / 2 {
+            let (a, b) = (spectrum[k], spectrum[half - k].conj());
+            let even = (a + b).scale(0.5);
+            // (a - b) / 2i
+            let difference = a - b;
+            let odd = Complex::new(difference.im, -difference.re).scale(0.5) * self.twiddles[k];
+            spectrum[k] = even + odd;
+            if k != half - k {
+                spectrum[half - k] = (even - odd).conj();
+            }
+        }
+    }
+
+    /// Inverse of `process_real`, scaled by 1 / N.
+    pub fn inverse_real(&self, data: &mut [f32; N]) {
+        #[cfg(all(feature = "cmsis", target_arch = "arm"))]
+        {
+            let mut input = *data;
+            if self.cmsis.real(&mut input, data, true) {
+                return;
+            }
+        }
+        let spectrum = as_complex(data);
+        let half = spectrum.len();
+
+        let packed = spectrum[0];
+        spectrum[0] = Complex::new(packed.re + packed.im, packed.re - packed.im).scale(0.5);
+        for k in 1..=half / 2 {
+            let (a, b) = (spectrum[k], spectrum[half - k].conj());
+            let even = (a + b).scale(0.5);
+            let odd = (a - b).scale(0.5) * self.twiddles[k].conj();
+            // even + i * odd
+            spectrum[k] = Complex::new(even.re - odd.im, even.im + odd.re);
+            if k != half - k {
+                let (even, odd) = (even.conj(), odd.conj());
+                spectrum[half - k] = Complex::new(even.re - odd.im, even.im + odd.re);
+            }
+        }
+        inverse_transform(spectrum, &self.twiddles);
+    }
+}
+
+impl<const N: usize> Default for Fft<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Convert the packed output of `Fft::process_real` to `N / 2 + 1` bin magnitudes, DC to Nyquist.
+/// Only as many bins as fit in `magnitudes` are written.
+pub fn real_magnitudes(packed: &[f32], magnitudes: &mut [f32]) {
+    let half = packed.len() / 2;
+    for (k, magnitude) in magnitudes.iter_mut().enumerate().take(half + 1) {
+        *magnitude = match k {
+            0 => packed[0].abs(),
+            k if k == half => packed[1].abs(),
+            k => Complex::new(packed[2 * k], packed[2 * k + 1]).norm(),
+        };
+    }
+}
+
+#[cfg(all(feature = "cmsis", target_arch = "arm"))]
+fn as_interleaved(data: &mut [Complex]) -> &mut [f32] {
+    // SAFETY: Complex is repr(C) with two f32 fields, see `as_complex`.
+    unsafe { core::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut f32, data.len() * 2) }
+}
+
+// View interleaved real and imaginary samples as complex numbers.
+fn as_complex(data: &mut [f32]) -> &mut [Complex] {
+    // SAFETY: Complex is repr(C) with two f32 fields, so it has the size and alignment of two
+    // consecutive f32 and any pair of f32 is a valid value.
+    unsafe { core::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut Complex, data.len() / 2) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive_dft(input: &[f32]) -> Vec<Complex> {
+        let len = input.len();
+        (0..len)
+            .map(|k| {
+                input
+                    .iter()
+                    .enumerate()
+                    .fold(Complex::default(), |sum, (n, x)| {
+                        let theta = -2.0 * PI * (k * n) as f64 / len as f64;
+                        sum + Complex::new(
+                            (*x as f64 * theta.cos()) as f32,
+                            (*x as f64 * theta.sin()) as f32,
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_twiddles_accurate() {
+        for i in 0..64 {
+            let theta = 2.0 * PI * i as f64 / 64.0;
+            let (sin, cos) = sin_cos(theta);
+            assert!((sin - theta.sin()).abs() < 1e-12);
+            assert!((cos - theta.cos()).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_fft_matches_dft() {
+        let input: Vec<f32> = (0..64)
+            .map(|i| ((i * 7919) % 13) as f32 / 6.0 - 1.0)
+            .collect();
+        let expected = naive_dft(&input);
+
+        let fft: Fft<64> = Fft::new();
+        let mut data = [Complex::default(); 64];
+        for (x, input) in data.iter_mut().zip(input.iter()) {
+            x.re = *input;
+        }
+        fft.process(&mut data);
+        for (x, expected) in data.iter().zip(expected.iter()) {
+            assert!((*x - *expected).norm() < 1e-4);
+        }
+
+        let mut real = [0.0; 64];
+        real.copy_from_slice(&input);
+        fft.process_real(&mut real);
+        assert!((real[0] - expected[0].re).abs() < 1e-4);
+        assert!((real[1] - expected[32].re).abs() < 1e-4);
+        for k in 1..32 {
+            let bin = Complex::new(real[2 * k], real[2 * k + 1]);
+            assert!((bin - expected[k]).norm() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_fft_round_trip() {
+        let fft: Fft<1024> = Fft::new();
+        let mut data = [Complex::default(); 1024];
+        let mut real = [0.0; 1024];
+        for i in 0..1024 {
+            data[i] = Complex::new((i as f32 * 0.37).sin(), (i as f32 * 0.11).cos());
+            real[i] = (i as f32 * 0.23).sin();
+        }
+        let (original, original_real) = (data, real);
+
+        fft.process(&mut data);
+        fft.inverse(&mut data);
+        fft.process_real(&mut real);
+        fft.inverse_real(&mut real);
+        for i in 0..1024 {
+            assert!((data[i] - original[i]).norm() < 1e-4);
+            assert!((real[i] - original_real[i]).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_fft_sine_bin() {
+        let fft: Fft<256> = Fft::new();
+        let mut data = [0.0; 256];
+        for (i, x) in data.iter_mut().enumerate() {
+            *x = (2.0 * core::f32::consts::PI * 10.0 * i as f32 / 256.0).sin();
+        }
+        fft.process_real(&mut data);
+        for k in 1..128 {
+            let magnitude = Complex::new(data[2 * k], data[2 * k + 1]).norm();
+            let expected = if k == 10 { 128.0 } else { 0.0 };
+            assert!((magnitude - expected).abs() < 1e-3);
+        }
+    }
+}
@@ -1,51 +1,123 @@
+float_math!();
 use core::cmp::{max, min};
-use core::f32::consts::PI;
-use micromath::F32Ext;
+use core::f32::consts::{FRAC_1_SQRT_2, PI};
 use ordered_float::OrderedFloat;
 
+use crate::block::{dot_product, LANES};
+#[cfg(all(feature = "cmsis", target_arch = "arm"))]
+use crate::cmsis;
+use crate::control::Smoother;
 use crate::delay::DelayLine;
-
-pub struct OnePoleLowPass {
+use crate::error::{check_sample_rate, Error, Result};
+use crate::fft::Fft;
+use crate::processor::Processor;
+// Float isn't imported, its math would be ambiguous with F32Ext on f32.
+use crate::sample::{self, Sample};
+use crate::units::db_to_linear;
+use crate::windows::{self, Symmetry, Window};
+
+pub struct OnePoleLowPass<T: Sample = f32> {
     sample_rate: f32,
-    a0: f32,
-    b1: f32,
-    z1: f32,
+    a0: T,
+    z1: T::State,
 }
 
-impl OnePoleLowPass {
-    pub fn new(sample_rate: f32) -> Self {
+impl<T: Sample> OnePoleLowPass<T> {
+    pub const fn new(sample_rate: f32) -> Self {
         Self {
             sample_rate,
-            a0: 1.0,
-            b1: 0.0,
-            z1: 0.0,
+            a0: T::IDENTITY,
+            z1: T::STATE_EQUILIBRIUM,
         }
     }
 
     pub fn set_freq(&mut self, freq: f32) {
         let freq = freq / self.sample_rate;
-        self.b1 = (-2.0 * PI * freq).exp();
-        self.a0 = 1.0 - self.b1;
+        self.a0 = T::from_f32(1.0 - (-2.0 * PI * freq).exp());
+    }
+
+    pub fn process(&mut self, input: T) -> T {
+        self.z1 = T::approach(self.z1, input, self.a0);
+        T::from_state(self.z1)
+    }
+
+    /// Clear the filter state, keeping the coefficients.
+    pub fn reset(&mut self) {
+        self.z1 = T::STATE_EQUILIBRIUM;
+    }
+}
+
+impl Processor for OnePoleLowPass {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+/// DC blocker, a first order high pass with its zero at DC. Cheaper than a biquad and flat
+/// above a few times the corner, 10 Hz by default.
+pub struct DcBlocker {
+    sample_rate: f32,
+    coef: f32,
+    x1: f32,
+    y1: f32,
+}
+
+impl DcBlocker {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut blocker = Self {
+            sample_rate,
+            coef: 0.0,
+            x1: 0.0,
+            y1: 0.0,
+        };
+        blocker.set_freq(10.0);
+        blocker
+    }
+
+    /// Set the -3 dB corner in Hz.
+    pub fn set_freq(&mut self, freq: f32) {
+        self.coef = (-2.0 * PI * freq / self.sample_rate).exp();
     }
 
     pub fn process(&mut self, input: f32) -> f32 {
-        self.z1 = (input * self.a0) + (self.z1 * self.b1);
-        self.z1
+        self.y1 = input - self.x1 + self.coef * self.y1;
+        self.x1 = input;
+        self.y1
+    }
+
+    pub fn reset(&mut self) {
+        self.x1 = 0.0;
+        self.y1 = 0.0;
+    }
+}
+
+impl Processor for DcBlocker {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
     }
 }
 
-pub struct AllPassSP<'a> {
+pub struct AllPassSP<'a, T: sample::Float = f32, const N: usize = 0> {
     sample_rate: f32,
-    delay_line: DelayLine<'a>,
+    delay_line: DelayLine<'a, T, N>,
     reverb_time: f32,
     max_loop_time: f32,
     loop_time: f32,
     rollover: usize,
-    coef: f32,
+    coef: T,
 }
 
-impl<'a> AllPassSP<'a> {
-    pub fn new(sample_rate: f32, delay_line: DelayLine<'a>) -> Self {
+impl<'a, T: sample::Float, const N: usize> AllPassSP<'a, T, N> {
+    /// The delay line must hold more than 10 ms, see `try_new` to check it.
+    pub fn new(sample_rate: f32, delay_line: DelayLine<'a, T, N>) -> Self {
         let max_loop_time: f32 = delay_line.len() as f32 / sample_rate - 0.01;
         let rollover = (max_loop_time * sample_rate) as usize;
 
@@ -55,18 +127,28 @@ impl<'a> AllPassSP<'a> {
             loop_time: max_loop_time,
             max_loop_time,
             rollover,
-            coef: 0.0,
+            coef: T::EQUILIBRIUM,
             reverb_time: 0.0,
         };
         all_pass.calc_reverb();
         all_pass
     }
 
+    pub fn try_new(sample_rate: f32, delay_line: DelayLine<'a, T, N>) -> Result<Self> {
+        check_sample_rate(sample_rate)?;
+        if delay_line.len() as f32 / sample_rate <= 0.01 {
+            return Err(Error::BufferTooSmall);
+        }
+        Ok(Self::new(sample_rate, delay_line))
+    }
+
     fn calc_reverb(&mut self) {
-        self.coef = (-6.9078 * self.loop_time / self.reverb_time).exp();
+        self.coef = (T::from_f32(-6.9078) * T::from_f32(self.loop_time)
+            / T::from_f32(self.reverb_time))
+        .exp();
     }
 
-    pub fn process(&mut self, input: f32) -> f32 {
+    pub fn process(&mut self, input: T) -> T {
         let y = self.delay_line.read();
         let z = (self.coef * y) + input;
         self.delay_line.write(z);
@@ -88,24 +170,47 @@ impl<'a> AllPassSP<'a> {
         self.reverb_time = reverb_time;
         self.calc_reverb();
     }
+
+    /// Clear the delay line.
+    pub fn reset(&mut self) {
+        self.delay_line.reset();
+    }
 }
 
-pub struct AllPass<'a> {
+impl<const N: usize> Processor for AllPassSP<'_, f32, N> {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+pub struct AllPass<'a, T: sample::Float = f32, const N: usize = 0> {
     sample_rate: f32,
-    delay_line: DelayLine<'a>,
-    k1: f32,
+    delay_line: DelayLine<'a, T, N>,
+    k1: T,
 }
 
-impl<'a> AllPass<'a> {
-    pub fn new(sample_rate: f32, delay_line: DelayLine<'a>) -> Self {
+impl<'a, T: sample::Float, const N: usize> AllPass<'a, T, N> {
+    pub fn new(sample_rate: f32, delay_line: DelayLine<'a, T, N>) -> Self {
         Self {
             sample_rate,
-            k1: 0.0,
+            k1: T::EQUILIBRIUM,
             delay_line,
         }
     }
 
-    pub fn process(&mut self, input: f32) -> f32 {
+    pub fn try_new(sample_rate: f32, delay_line: DelayLine<'a, T, N>) -> Result<Self> {
+        check_sample_rate(sample_rate)?;
+        if delay_line.is_empty() {
+            return Err(Error::BufferTooSmall);
+        }
+        Ok(Self::new(sample_rate, delay_line))
+    }
+
+    pub fn process(&mut self, input: T) -> T {
         let z1 = self.delay_line.read();
         let x = (self.k1 * z1) + input;
         self.delay_line.write(x);
@@ -114,8 +219,23 @@ impl<'a> AllPass<'a> {
     }
 
     pub fn set_freq(&mut self, freq: f32) {
-        let freq = PI * freq / self.sample_rate;
-        self.k1 = (1.0 - freq) / (1.0 + freq);
+        let freq = T::PI * T::from_f32(freq) / T::from_f32(self.sample_rate);
+        self.k1 = (T::IDENTITY - freq) / (T::IDENTITY + freq);
+    }
+
+    /// Clear the delay line.
+    pub fn reset(&mut self) {
+        self.delay_line.reset();
+    }
+}
+
+impl<const N: usize> Processor for AllPass<'_, f32, N> {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
     }
 }
 
@@ -136,10 +256,15 @@ pub struct StateVariable {
     out_notch: f32,
     out_peak: f32,
     previous: f32,
+    cutoff: f32,
+    control_rate: bool,
+    freq_step: f32,
+    damp_step: f32,
+    remaining: usize,
 }
 
 impl StateVariable {
-    pub fn new(sample_rate: f32) -> StateVariable {
+    pub const fn new(sample_rate: f32) -> StateVariable {
         StateVariable {
             sample_rate,
             low_pass: 0.0,
@@ -157,26 +282,41 @@ impl StateVariable {
             out_notch: 0.0,
             out_peak: 0.0,
             previous: 0.0,
+            cutoff: 0.0,
+            control_rate: false,
+            freq_step: 0.0,
+            damp_step: 0.0,
+            remaining: 0,
         }
     }
 
     fn pass(&mut self, input: f32) {
         self.notch = input - self.damp * self.band_pass;
-        self.low_pass = self.low_pass + self.freq * self.band_pass;
+        self.low_pass += self.freq * self.band_pass;
         self.high_pass = self.notch - self.low_pass;
         self.band_pass =
             self.freq * self.high_pass + self.band_pass - self.drive * self.band_pass.powi(3);
     }
 
-    fn calc_damp(&mut self) {
-        self.damp = min(
+    // Frequency coefficient of the cutoff.
+    fn calc_freq(&self) -> f32 {
+        let freq = self.cutoff.clamp(0.0, self.sample_rate / 3.0);
+        2.0 * (PI
+            * min(
+                OrderedFloat(0.25),
+                OrderedFloat(freq / (self.sample_rate * 2.0)),
+            )
+            .0)
+            .sin()
+    }
+
+    // Damping for a frequency coefficient at the current resonance.
+    fn calc_damp(&self, freq: f32) -> f32 {
+        min(
             OrderedFloat(2.0 * (1.0 - self.resonance.powf(0.25))),
-            min(
-                OrderedFloat(2.0),
-                OrderedFloat(2.0 / self.freq - self.freq * 0.5),
-            ),
+            min(OrderedFloat(2.0), OrderedFloat(2.0 / freq - freq * 0.5)),
         )
-        .0;
+        .0
     }
 
     // pub fn process(&mut self, input: f32) {
@@ -197,6 +337,11 @@ impl StateVariable {
     // }
 
     pub fn process(&mut self, input: f32) {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            self.freq += self.freq_step;
+            self.damp += self.damp_step;
+        }
         self.pass(self.previous);
         self.pass(input);
         self.out_low_pass = self.low_pass;
@@ -207,25 +352,34 @@ impl StateVariable {
         self.previous = input;
     }
 
+    /// Control rate tick, once called the cutoff and resonance only take effect here and the
+    /// coefficients are interpolated over the next `samples` samples.
+    pub fn update_params(&mut self, samples: usize) {
+        self.control_rate = true;
+        let samples = samples.max(1);
+        let freq = self.calc_freq();
+        let damp = self.calc_damp(freq);
+        self.freq_step = (freq - self.freq) / samples as f32;
+        self.damp_step = (damp - self.damp) / samples as f32;
+        self.remaining = samples;
+    }
+
     /// Set the cutoff frequency
     pub fn set_freq(&mut self, freq: f32) {
-        let freq = freq.clamp(0.0, self.sample_rate / 3.0);
-        self.freq = 2.0
-            * (PI
-                * min(
-                    OrderedFloat(0.25),
-                    OrderedFloat(freq / (self.sample_rate * 2.0)),
-                )
-                .0)
-                .sin();
-        self.calc_damp();
+        self.cutoff = freq;
+        if !self.control_rate {
+            self.freq = self.calc_freq();
+            self.damp = self.calc_damp(self.freq);
+        }
     }
 
     /// Set filter resonance, clamped to [0.0-1.0].
     pub fn set_resonance(&mut self, resonance: f32) {
         self.resonance = resonance.clamp(0.0, 1.0);
         // Recalculate damp and drive
-        self.calc_damp();
+        if !self.control_rate {
+            self.damp = self.calc_damp(self.freq);
+        }
         self.drive = self.pre_drive * self.resonance;
     }
 
@@ -255,6 +409,1064 @@ impl StateVariable {
     pub fn get_peak(&self) -> f32 {
         self.out_peak
     }
+
+    /// Clear the integrators and outputs, keeping the parameters.
+    pub fn reset(&mut self) {
+        self.low_pass = 0.0;
+        self.high_pass = 0.0;
+        self.band_pass = 0.0;
+        self.notch = 0.0;
+        self.out_low_pass = 0.0;
+        self.out_high_pass = 0.0;
+        self.out_band_pass = 0.0;
+        self.out_notch = 0.0;
+        self.out_peak = 0.0;
+        self.previous = 0.0;
+    }
+}
+
+/// Second order IIR filter, transposed direct form II.
+/// Coefficients are calculated from the RBJ Audio EQ Cookbook at the precision of `T`.
+pub struct Biquad<T: sample::Float = f32> {
+    sample_rate: f32,
+    b0: T,
+    b1: T,
+    b2: T,
+    a1: T,
+    a2: T,
+    z1: T,
+    z2: T,
+}
+
+impl<T: sample::Float> Biquad<T> {
+    pub const fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            b0: T::IDENTITY,
+            b1: T::EQUILIBRIUM,
+            b2: T::EQUILIBRIUM,
+            a1: T::EQUILIBRIUM,
+            a2: T::EQUILIBRIUM,
+            z1: T::EQUILIBRIUM,
+            z2: T::EQUILIBRIUM,
+        }
+    }
+
+    pub fn process(&mut self, input: T) -> T {
+        let output = self.b0 * input + self.z1;
+        self.z1 = self.b1 * input - self.a1 * output + self.z2;
+        self.z2 = self.b2 * input - self.a2 * output;
+        output
+    }
+
+    /// Clear the filter state, keeping the coefficients.
+    pub fn reset(&mut self) {
+        self.z1 = T::EQUILIBRIUM;
+        self.z2 = T::EQUILIBRIUM;
+    }
+
+    // Returns cos(w0) and alpha for the given frequency and Q.
+    fn calc_w0(&self, freq: f32, q: f32) -> (T, T) {
+        let freq = T::from_f32(freq.clamp(1.0, self.sample_rate * 0.49));
+        let w0 = T::from_f32(2.0) * T::PI * freq / T::from_f32(self.sample_rate);
+        (w0.cos(), w0.sin() / T::from_f32(2.0 * q.max(0.01)))
+    }
+
+    // Amplitude of the peak and shelf filters for a gain in dB.
+    fn calc_a(gain: f32) -> T {
+        T::from_f32(10.0).powf(T::from_f32(gain) / T::from_f32(40.0))
+    }
+
+    /// Set raw coefficients, normalized by `a0`.
+    pub fn set_coefs(&mut self, b0: T, b1: T, b2: T, a0: T, a1: T, a2: T) {
+        let a0_recip = T::IDENTITY / a0;
+        self.b0 = b0 * a0_recip;
+        self.b1 = b1 * a0_recip;
+        self.b2 = b2 * a0_recip;
+        self.a1 = a1 * a0_recip;
+        self.a2 = a2 * a0_recip;
+    }
+
+    pub fn set_low_pass(&mut self, freq: f32, q: f32) {
+        let (cos_w0, alpha) = self.calc_w0(freq, q);
+        let (one, two, half) = (T::IDENTITY, T::from_f32(2.0), T::from_f32(0.5));
+        let b1 = one - cos_w0;
+        self.set_coefs(
+            b1 * half,
+            b1,
+            b1 * half,
+            one + alpha,
+            -two * cos_w0,
+            one - alpha,
+        );
+    }
+
+    pub fn set_high_pass(&mut self, freq: f32, q: f32) {
+        let (cos_w0, alpha) = self.calc_w0(freq, q);
+        let (one, two, half) = (T::IDENTITY, T::from_f32(2.0), T::from_f32(0.5));
+        let b1 = -(one + cos_w0);
+        self.set_coefs(
+            -b1 * half,
+            b1,
+            -b1 * half,
+            one + alpha,
+            -two * cos_w0,
+            one - alpha,
+        );
+    }
+
+    /// Band pass with 0 dB peak gain.
+    pub fn set_band_pass(&mut self, freq: f32, q: f32) {
+        let (cos_w0, alpha) = self.calc_w0(freq, q);
+        let (zero, one, two) = (T::EQUILIBRIUM, T::IDENTITY, T::from_f32(2.0));
+        self.set_coefs(alpha, zero, -alpha, one + alpha, -two * cos_w0, one - alpha);
+    }
+
+    pub fn set_notch(&mut self, freq: f32, q: f32) {
+        let (cos_w0, alpha) = self.calc_w0(freq, q);
+        let one = T::IDENTITY;
+        let a1 = -T::from_f32(2.0) * cos_w0;
+        self.set_coefs(one, a1, one, one + alpha, a1, one - alpha);
+    }
+
+    pub fn set_all_pass(&mut self, freq: f32, q: f32) {
+        let (cos_w0, alpha) = self.calc_w0(freq, q);
+        let one = T::IDENTITY;
+        let a1 = -T::from_f32(2.0) * cos_w0;
+        self.set_coefs(one - alpha, a1, one + alpha, one + alpha, a1, one - alpha);
+    }
+
+    /// Peaking EQ with the given gain in dB at the center frequency.
+    pub fn set_peak(&mut self, freq: f32, q: f32, gain: f32) {
+        let (cos_w0, alpha) = self.calc_w0(freq, q);
+        let a = Self::calc_a(gain);
+        let one = T::IDENTITY;
+        let a1 = -T::from_f32(2.0) * cos_w0;
+        self.set_coefs(
+            one + alpha * a,
+            a1,
+            one - alpha * a,
+            one + alpha / a,
+            a1,
+            one - alpha / a,
+        );
+    }
+
+    /// Low shelf with the given gain in dB below the corner frequency.
+    pub fn set_low_shelf(&mut self, freq: f32, q: f32, gain: f32) {
+        let (cos_w0, alpha) = self.calc_w0(freq, q);
+        let a = Self::calc_a(gain);
+        let (one, two) = (T::IDENTITY, T::from_f32(2.0));
+        let sqrt_a_alpha = two * a.sqrt() * alpha;
+        self.set_coefs(
+            a * ((a + one) - (a - one) * cos_w0 + sqrt_a_alpha),
+            two * a * ((a - one) - (a + one) * cos_w0),
+            a * ((a + one) - (a - one) * cos_w0 - sqrt_a_alpha),
+            (a + one) + (a - one) * cos_w0 + sqrt_a_alpha,
+            -two * ((a - one) + (a + one) * cos_w0),
+            (a + one) + (a - one) * cos_w0 - sqrt_a_alpha,
+        );
+    }
+
+    /// High shelf with the given gain in dB above the corner frequency.
+    pub fn set_high_shelf(&mut self, freq: f32, q: f32, gain: f32) {
+        let (cos_w0, alpha) = self.calc_w0(freq, q);
+        let a = Self::calc_a(gain);
+        let (one, two) = (T::IDENTITY, T::from_f32(2.0));
+        let sqrt_a_alpha = two * a.sqrt() * alpha;
+        self.set_coefs(
+            a * ((a + one) + (a - one) * cos_w0 + sqrt_a_alpha),
+            -two * a * ((a - one) + (a + one) * cos_w0),
+            a * ((a + one) + (a - one) * cos_w0 - sqrt_a_alpha),
+            (a + one) - (a - one) * cos_w0 + sqrt_a_alpha,
+            two * ((a - one) - (a + one) * cos_w0),
+            (a + one) - (a - one) * cos_w0 - sqrt_a_alpha,
+        );
+    }
+}
+
+impl Biquad {
+    /// Filter a block in place.
+    pub fn process_block(&mut self, block: &mut [f32]) {
+        #[cfg(all(feature = "cmsis", target_arch = "arm"))]
+        {
+            let coefs = [self.b0, self.b1, self.b2, -self.a1, -self.a2];
+            let mut state = [self.z1, self.z2];
+            cmsis::biquad_block(&coefs, &mut state, block);
+            self.z1 = state[0];
+            self.z2 = state[1];
+        }
+        #[cfg(not(all(feature = "cmsis", target_arch = "arm")))]
+        for sample in block.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
+}
+
+impl Processor for Biquad {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn process_block(&mut self, block: &mut [f32]) {
+        self.process_block(block)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+/// Biquad with coefficients that move linearly to a new design over a number of samples.
+/// Design the target at control rate with the `Biquad` setters, this avoids both per sample trig
+/// and the zipper noise of stepping the coefficients. Either pass a designed `Biquad` to
+/// `set_target`, or change `design` and let `update_params` ticks move to it.
+pub struct InterpolatedBiquad {
+    filter: Biquad,
+    design: Biquad,
+    redesigned: bool,
+    control_rate: bool,
+    target: [f32; 5],
+    steps: [f32; 5],
+    remaining: usize,
+}
+
+impl InterpolatedBiquad {
+    pub const fn new(sample_rate: f32) -> Self {
+        Self {
+            filter: Biquad::new(sample_rate),
+            design: Biquad::new(sample_rate),
+            redesigned: false,
+            control_rate: false,
+            target: [1.0, 0.0, 0.0, 0.0, 0.0],
+            steps: [0.0; 5],
+            remaining: 0,
+        }
+    }
+
+    /// Get the design to change with the `Biquad` setters. Without control rate ticks it takes
+    /// effect on the next sample, with them it's moved to over the samples of the next tick.
+    pub fn design(&mut self) -> &mut Biquad {
+        self.redesigned = true;
+        &mut self.design
+    }
+
+    /// Control rate tick, moving to a changed `design` over the next `samples` samples.
+    pub fn update_params(&mut self, samples: usize) {
+        self.control_rate = true;
+        if self.redesigned {
+            self.apply_design(samples);
+        }
+    }
+
+    fn apply_design(&mut self, samples: usize) {
+        self.redesigned = false;
+        let design = &self.design;
+        self.move_to(
+            [design.b0, design.b1, design.b2, design.a1, design.a2],
+            samples,
+        );
+    }
+
+    /// Move to the coefficients of `target` over `samples` samples, 0 jumps to them immediately.
+    /// The state of `target` is ignored.
+    pub fn set_target(&mut self, target: &Biquad, samples: usize) {
+        self.move_to(
+            [target.b0, target.b1, target.b2, target.a1, target.a2],
+            samples,
+        );
+    }
+
+    fn move_to(&mut self, target: [f32; 5], samples: usize) {
+        self.target = target;
+        self.remaining = samples;
+        if samples == 0 {
+            self.set_coefs(self.target);
+            return;
+        }
+        let current = self.coefs();
+        let recip = 1.0 / samples as f32;
+        for ((step, target), current) in self.steps.iter_mut().zip(&self.target).zip(&current) {
+            *step = (target - current) * recip;
+        }
+    }
+
+    fn coefs(&self) -> [f32; 5] {
+        let filter = &self.filter;
+        [filter.b0, filter.b1, filter.b2, filter.a1, filter.a2]
+    }
+
+    fn set_coefs(&mut self, [b0, b1, b2, a1, a2]: [f32; 5]) {
+        self.filter.b0 = b0;
+        self.filter.b1 = b1;
+        self.filter.b2 = b2;
+        self.filter.a1 = a1;
+        self.filter.a2 = a2;
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        if self.redesigned && !self.control_rate {
+            self.apply_design(0);
+        }
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            let mut coefs = self.coefs();
+            if self.remaining == 0 {
+                // Land exactly on the target instead of accumulating rounding errors.
+                coefs = self.target;
+            } else {
+                for (coef, step) in coefs.iter_mut().zip(&self.steps) {
+                    *coef += step;
+                }
+            }
+            self.set_coefs(coefs);
+        }
+        self.filter.process(input)
+    }
+
+    /// Clear the filter state, keeping the coefficients.
+    pub fn reset(&mut self) {
+        self.filter.reset();
+    }
+}
+
+impl Processor for InterpolatedBiquad {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+
+    fn update_params(&mut self, samples: usize) {
+        self.update_params(samples)
+    }
+}
+
+/// Four independent biquads processed together, one per lane, for filtering voices or channels
+/// with the same structure. Each lane copies its coefficients from a designed `Biquad`.
+pub struct Biquad4 {
+    b0: [f32; LANES],
+    b1: [f32; LANES],
+    b2: [f32; LANES],
+    a1: [f32; LANES],
+    a2: [f32; LANES],
+    z1: [f32; LANES],
+    z2: [f32; LANES],
+}
+
+impl Biquad4 {
+    pub const fn new() -> Self {
+        Self {
+            b0: [1.0; LANES],
+            b1: [0.0; LANES],
+            b2: [0.0; LANES],
+            a1: [0.0; LANES],
+            a2: [0.0; LANES],
+            z1: [0.0; LANES],
+            z2: [0.0; LANES],
+        }
+    }
+
+    pub fn process(&mut self, input: [f32; LANES]) -> [f32; LANES] {
+        let mut output = [0.0; LANES];
+        for lane in 0..LANES {
+            let x = input[lane];
+            let y = self.b0[lane] * x + self.z1[lane];
+            self.z1[lane] = self.b1[lane] * x - self.a1[lane] * y + self.z2[lane];
+            self.z2[lane] = self.b2[lane] * x - self.a2[lane] * y;
+            output[lane] = y;
+        }
+        output
+    }
+
+    pub fn process_block(&mut self, block: &mut [[f32; LANES]]) {
+        for frame in block.iter_mut() {
+            *frame = self.process(*frame);
+        }
+    }
+
+    /// Copy the coefficients of `filter` into a lane, lanes past the last are ignored.
+    pub fn set_lane(&mut self, lane: usize, filter: &Biquad) {
+        if lane < LANES {
+            self.b0[lane] = filter.b0;
+            self.b1[lane] = filter.b1;
+            self.b2[lane] = filter.b2;
+            self.a1[lane] = filter.a1;
+            self.a2[lane] = filter.a2;
+        }
+    }
+
+    /// Clear the state of every lane, keeping the coefficients.
+    pub fn reset(&mut self) {
+        self.z1 = [0.0; LANES];
+        self.z2 = [0.0; LANES];
+    }
+}
+
+impl Default for Biquad4 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Passive three knob guitar amp tone stack, a bilinear transform of the analog circuit from
+/// D. Yeh and J. Smith, "Discretization of the '59 Fender Bassman Tone Stack".
+/// The controls interact the same way the real circuit's do.
+pub struct ToneStack {
+    sample_rate: f32,
+    bass: f32,
+    mid: f32,
+    treble: f32,
+    b: [f32; 4],
+    a: [f32; 3],
+    z: [f32; 3],
+}
+
+impl ToneStack {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut tone_stack = Self {
+            sample_rate,
+            bass: 0.5,
+            mid: 0.5,
+            treble: 0.5,
+            b: [1.0, 0.0, 0.0, 0.0],
+            a: [0.0; 3],
+            z: [0.0; 3],
+        };
+        tone_stack.calc_coefs();
+        tone_stack
+    }
+
+    fn calc_coefs(&mut self) {
+        // Bassman component values.
+        const C1: f64 = 250e-12;
+        const C2: f64 = 20e-9;
+        const C3: f64 = 20e-9;
+        const R1: f64 = 250e3;
+        const R2: f64 = 1e6;
+        const R3: f64 = 25e3;
+        const R4: f64 = 56e3;
+
+        let t = self.treble as f64;
+        let m = self.mid as f64;
+        // The bass pot has a log taper.
+        let l = (3.4 * (self.bass - 1.0)).exp() as f64;
+
+        let b1 = t * C1 * R1 + m * C3 * R3 + l * (C1 * R2 + C2 * R2) + (C1 * R3 + C2 * R3);
+        let b2 = t * (C1 * C2 * R1 * R4 + C1 * C3 * R1 * R4)
+            - m * m * (C1 * C3 * R3 * R3 + C2 * C3 * R3 * R3)
+            + m * (C1 * C3 * R1 * R3 + C1 * C3 * R3 * R3 + C2 * C3 * R3 * R3)
+            + l * (C1 * C2 * R1 * R2 + C1 * C2 * R2 * R4 + C1 * C3 * R2 * R4)
+            + l * m * (C1 * C3 * R2 * R3 + C2 * C3 * R2 * R3)
+            + (C1 * C2 * R1 * R3 + C1 * C2 * R3 * R4 + C1 * C3 * R3 * R4);
+        let b3 = l * m * (C1 * C2 * C3 * R1 * R2 * R3 + C1 * C2 * C3 * R2 * R3 * R4)
+            - m * m * (C1 * C2 * C3 * R1 * R3 * R3 + C1 * C2 * C3 * R3 * R3 * R4)
+            + m * (C1 * C2 * C3 * R1 * R3 * R3 + C1 * C2 * C3 * R3 * R3 * R4)
+            + t * C1 * C2 * C3 * R1 * R3 * R4
+            - t * m * C1 * C2 * C3 * R1 * R3 * R4
+            + t * l * C1 * C2 * C3 * R1 * R2 * R4;
+        let a0 = 1.0;
+        let a1 = (C1 * R1 + C1 * R3 + C2 * R3 + C2 * R4 + C3 * R4)
+            + m * C3 * R3
+            + l * (C1 * R2 + C2 * R2);
+        let a2 = m
+            * (C1 * C3 * R1 * R3 - C2 * C3 * R3 * R4 + C1 * C3 * R3 * R3 + C2 * C3 * R3 * R3)
+            + l * m * (C1 * C3 * R2 * R3 + C2 * C3 * R2 * R3)
+            - m * m * (C1 * C3 * R3 * R3 + C2 * C3 * R3 * R3)
+            + l * (C1 * C2 * R2 * R4 + C1 * C2 * R1 * R2 + C1 * C3 * R2 * R4 + C2 * C3 * R2 * R4)
+            + (C1 * C2 * R1 * R4
+                + C1 * C3 * R1 * R4
+                + C1 * C2 * R3 * R4
+                + C1 * C2 * R1 * R3
+                + C1 * C3 * R3 * R4
+                + C2 * C3 * R3 * R4);
+        let a3 = l * m * (C1 * C2 * C3 * R1 * R2 * R3 + C1 * C2 * C3 * R2 * R3 * R4)
+            - m * m * (C1 * C2 * C3 * R1 * R3 * R3 + C1 * C2 * C3 * R3 * R3 * R4)
+            + m * (C1 * C2 * C3 * R3 * R3 * R4 + C1 * C2 * C3 * R1 * R3 * R3
+                - C1 * C2 * C3 * R1 * R3 * R4)
+            + l * C1 * C2 * C3 * R1 * R2 * R4
+            + C1 * C2 * C3 * R1 * R3 * R4;
+
+        // Bilinear transform.
+        let c = 2.0 * self.sample_rate as f64;
+        let (c2, c3) = (c * c, c * c * c);
+        let bz0 = -b1 * c - b2 * c2 - b3 * c3;
+        let bz1 = -b1 * c + b2 * c2 + 3.0 * b3 * c3;
+        let bz2 = b1 * c + b2 * c2 - 3.0 * b3 * c3;
+        let bz3 = b1 * c - b2 * c2 + b3 * c3;
+        let az0 = -a0 - a1 * c - a2 * c2 - a3 * c3;
+        let az1 = -3.0 * a0 - a1 * c + a2 * c2 + 3.0 * a3 * c3;
+        let az2 = -3.0 * a0 + a1 * c + a2 * c2 - 3.0 * a3 * c3;
+        let az3 = -a0 + a1 * c - a2 * c2 + a3 * c3;
+
+        self.b = [
+            (bz0 / az0) as f32,
+            (bz1 / az0) as f32,
+            (bz2 / az0) as f32,
+            (bz3 / az0) as f32,
+        ];
+        self.a = [(az1 / az0) as f32, (az2 / az0) as f32, (az3 / az0) as f32];
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = self.b[0] * input + self.z[0];
+        self.z[0] = self.b[1] * input - self.a[0] * output + self.z[1];
+        self.z[1] = self.b[2] * input - self.a[1] * output + self.z[2];
+        self.z[2] = self.b[3] * input - self.a[2] * output;
+        output
+    }
+
+    /// Set the bass control, clamped to [0.0-1.0].
+    pub fn set_bass(&mut self, bass: f32) {
+        self.bass = bass.clamp(0.0, 1.0);
+        self.calc_coefs();
+    }
+
+    /// Set the mid control, clamped to [0.0-1.0].
+    pub fn set_mid(&mut self, mid: f32) {
+        self.mid = mid.clamp(0.0, 1.0);
+        self.calc_coefs();
+    }
+
+    /// Set the treble control, clamped to [0.0-1.0].
+    pub fn set_treble(&mut self, treble: f32) {
+        self.treble = treble.clamp(0.0, 1.0);
+        self.calc_coefs();
+    }
+
+    pub fn get_bass(&self) -> f32 {
+        self.bass
+    }
+
+    pub fn get_mid(&self) -> f32 {
+        self.mid
+    }
+
+    pub fn get_treble(&self) -> f32 {
+        self.treble
+    }
+
+    /// Clear the filter state, keeping the controls.
+    pub fn reset(&mut self) {
+        self.z = [0.0; 3];
+    }
+}
+
+impl Processor for ToneStack {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+/// Direct form FIR filter, convolving the input with `coefs`.
+/// The delay line must be at least as long as the coefficients.
+pub struct Fir<'a, const N: usize = 0> {
+    coefs: &'a [f32],
+    delay_line: DelayLine<'a, f32, N>,
+    // Inputs are written backwards, so newest to oldest runs forwards from here in line with
+    // the coefficients.
+    index: usize,
+}
+
+impl<'a, const N: usize> Fir<'a, N> {
+    pub fn new(coefs: &'a [f32], delay_line: DelayLine<'a, f32, N>) -> Self {
+        let coefs = &coefs[..min(coefs.len(), delay_line.len())];
+        Self {
+            coefs,
+            delay_line,
+            index: 0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let len = self.delay_line.len();
+        self.index = (self.index + len - 1) % len;
+        self.delay_line[self.index] = input;
+        let history = self.delay_line.as_slice();
+        let (older, newer) = history.split_at(self.index);
+        let split = newer.len().min(self.coefs.len());
+        dot_product(self.coefs, newer) + dot_product(&self.coefs[split..], older)
+    }
+
+    /// Replace the coefficients, truncated to the delay line length.
+    pub fn set_coefs(&mut self, coefs: &'a [f32]) {
+        self.coefs = &coefs[..min(coefs.len(), self.delay_line.len())];
+    }
+
+    pub fn len(&self) -> usize {
+        self.coefs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.coefs.is_empty()
+    }
+
+    /// Group delay in samples of symmetric, linear phase, coefficients. Other responses, such
+    /// as minimum phase cabinet IRs, have no fixed delay and report 0.
+    pub fn latency_samples(&self) -> usize {
+        let len = self.coefs.len();
+        let symmetric = (0..len / 2).all(|i| self.coefs[i] == self.coefs[len - 1 - i]);
+        if symmetric {
+            len.saturating_sub(1) / 2
+        } else {
+            0
+        }
+    }
+
+    /// Clear the delay line.
+    pub fn reset(&mut self) {
+        self.delay_line.reset();
+        self.index = 0;
+    }
+}
+
+impl<const N: usize> Processor for Fir<'_, N> {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.latency_samples()
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+/// Fourth order Linkwitz-Riley crossover, splitting the input into low and high bands that sum
+/// back to an all pass response.
+pub struct LinkwitzRiley {
+    low_pass: [Biquad; 2],
+    high_pass: [Biquad; 2],
+}
+
+impl LinkwitzRiley {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut crossover = Self {
+            low_pass: [Biquad::new(sample_rate), Biquad::new(sample_rate)],
+            high_pass: [Biquad::new(sample_rate), Biquad::new(sample_rate)],
+        };
+        crossover.set_freq(1000.0);
+        crossover
+    }
+
+    /// Returns the (low, high) bands.
+    pub fn process(&mut self, input: f32) -> (f32, f32) {
+        let low = self.low_pass[0].process(input);
+        let high = self.high_pass[0].process(input);
+        (
+            self.low_pass[1].process(low),
+            self.high_pass[1].process(high),
+        )
+    }
+
+    /// Set the crossover frequency.
+    pub fn set_freq(&mut self, freq: f32) {
+        for filter in self.low_pass.iter_mut() {
+            filter.set_low_pass(freq, FRAC_1_SQRT_2);
+        }
+        for filter in self.high_pass.iter_mut() {
+            filter.set_high_pass(freq, FRAC_1_SQRT_2);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for filter in self.low_pass.iter_mut().chain(self.high_pass.iter_mut()) {
+            filter.reset();
+        }
+    }
+}
+
+/// Splits the input into `N` bands, lowest first, with Linkwitz-Riley crossovers in series. Each
+/// band is passed through all pass filters matching the crossovers above it, so the bands sum
+/// back to an all pass response with a flat magnitude. Fewer bands can be used at run time.
+pub struct BandSplit<const N: usize> {
+    bands: usize,
+    // The crossover between band i and i + 1, the last one is unused.
+    crossovers: [LinkwitzRiley; N],
+    freqs: [f32; N],
+    // All pass compensation, `all_passes[band][crossover]` for crossovers above the band.
+    all_passes: [[Biquad; N]; N],
+}
+
+impl<const N: usize> BandSplit<N> {
+    /// All `N` bands, with crossovers spaced evenly in octaves between 20 Hz and 20 kHz.
+    pub fn new(sample_rate: f32) -> Self {
+        let mut split = Self {
+            bands: N,
+            crossovers: core::array::from_fn(|_| LinkwitzRiley::new(sample_rate)),
+            freqs: [0.0; N],
+            all_passes: core::array::from_fn(|_| {
+                core::array::from_fn(|_| Biquad::new(sample_rate))
+            }),
+        };
+        for crossover in 0..N.saturating_sub(1) {
+            let freq = 20.0 * 1000.0_f32.powf((crossover + 1) as f32 / N as f32);
+            split.set_crossover_freq(crossover, freq);
+        }
+        split
+    }
+
+    /// Returns the bands, those above the number in use are silent.
+    pub fn process(&mut self, input: f32) -> [f32; N] {
+        let mut output = [0.0; N];
+        let mut rest = input;
+        let last = self.bands - 1;
+        for (band, output) in output.iter_mut().enumerate().take(self.bands) {
+            let mut signal = if band < last {
+                let (low, high) = self.crossovers[band].process(rest);
+                rest = high;
+                low
+            } else {
+                rest
+            };
+            for all_pass in &mut self.all_passes[band][(band + 1).min(last)..last] {
+                signal = all_pass.process(signal);
+            }
+            *output = signal;
+        }
+        output
+    }
+
+    /// Sum processed bands back together.
+    pub fn recombine(bands: &[f32; N]) -> f32 {
+        bands.iter().sum()
+    }
+
+    /// Set the number of bands in use, clamped to [1, N]. The crossovers keep their
+    /// frequencies, the highest band takes everything above the last one in use.
+    pub fn set_bands(&mut self, bands: usize) {
+        self.bands = bands.clamp(1, N.max(1));
+    }
+
+    pub fn get_bands(&self) -> usize {
+        self.bands
+    }
+
+    /// Set the frequency of the crossover between `crossover` and `crossover + 1`.
+    pub fn set_crossover_freq(&mut self, crossover: usize, freq: f32) {
+        if crossover + 1 >= N {
+            return;
+        }
+        self.freqs[crossover] = freq;
+        self.crossovers[crossover].set_freq(freq);
+        // The LR4 low and high outputs sum to a second order all pass with Q = 1/sqrt(2).
+        for all_pass in self.all_passes.iter_mut().take(crossover) {
+            all_pass[crossover].set_all_pass(freq, FRAC_1_SQRT_2);
+        }
+    }
+
+    pub fn get_crossover_freq(&self, crossover: usize) -> f32 {
+        self.freqs[..N.saturating_sub(1)]
+            .get(crossover)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    pub fn reset(&mut self) {
+        for crossover in self.crossovers.iter_mut() {
+            crossover.reset();
+        }
+        for all_pass in self.all_passes.iter_mut().flatten() {
+            all_pass.reset();
+        }
+    }
+}
+
+/// Bands of an `Isolator`, lowest first.
+pub const ISOLATOR_BANDS: usize = 3;
+// Most boost of an isolator band in dB, and the gain at or below which it's fully killed.
+const ISOLATOR_MAX_DB: f32 = 6.0;
+const ISOLATOR_KILL_DB: f32 = -80.0;
+
+/// DJ mixer three band isolator. A `BandSplit` splits the input into low, mid and high bands that
+/// recombine flat, each with a smoothed gain from a full kill up to +6 dB.
+pub struct Isolator {
+    split: BandSplit<ISOLATOR_BANDS>,
+    gains: [Smoother; ISOLATOR_BANDS],
+    gains_db: [f32; ISOLATOR_BANDS],
+}
+
+impl Isolator {
+    /// Crossovers at 300 Hz and 4 kHz with every band at 0 dB.
+    pub fn new(sample_rate: f32) -> Self {
+        let mut isolator = Self {
+            split: BandSplit::new(sample_rate),
+            gains: core::array::from_fn(|_| {
+                let mut gain = Smoother::new(sample_rate);
+                gain.set_immediate(1.0);
+                gain
+            }),
+            gains_db: [0.0; ISOLATOR_BANDS],
+        };
+        isolator.set_low_freq(300.0);
+        isolator.set_high_freq(4000.0);
+        isolator
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.split
+            .process(input)
+            .iter()
+            .zip(self.gains.iter_mut())
+            .map(|(band, gain)| band * gain.next())
+            .sum()
+    }
+
+    /// Set the crossover between the low and mid bands.
+    pub fn set_low_freq(&mut self, freq: f32) {
+        self.split.set_crossover_freq(0, freq);
+    }
+
+    /// Set the crossover between the mid and high bands.
+    pub fn set_high_freq(&mut self, freq: f32) {
+        self.split.set_crossover_freq(1, freq);
+    }
+
+    /// Set the gain of `band` in dB, at most +6 dB. -80 dB and below, including negative
+    /// infinity, kill the band entirely.
+    pub fn set_gain_db(&mut self, band: usize, gain: f32) {
+        if band >= ISOLATOR_BANDS {
+            return;
+        }
+        let gain = gain.min(ISOLATOR_MAX_DB);
+        self.gains_db[band] = gain;
+        let linear = if gain <= ISOLATOR_KILL_DB {
+            0.0
+        } else {
+            db_to_linear(gain)
+        };
+        self.gains[band].set_target(linear);
+    }
+
+    pub fn get_gain_db(&self, band: usize) -> f32 {
+        self.gains_db.get(band).copied().unwrap_or(0.0)
+    }
+
+    /// Clear the filters and jump to the target gains.
+    pub fn reset(&mut self) {
+        self.split.reset();
+        for gain in self.gains.iter_mut() {
+            gain.reset();
+        }
+    }
+}
+
+impl Processor for Isolator {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+/// Most notches a `FeedbackSuppressor` deploys.
+pub const MAX_NOTCHES: usize = 8;
+// Deployed notches start shallow and deepen while the feedback persists.
+const NOTCH_Q: f32 = 30.0;
+const NOTCH_START_DB: f32 = -9.0;
+const NOTCH_STEP_DB: f32 = -3.0;
+const NOTCH_MAX_DB: f32 = -24.0;
+// Quietest peak treated as feedback, in dBFS.
+const FEEDBACK_MIN_DB: f32 = -50.0;
+
+/// Automatic feedback suppressor for PA and intercom use. The output is Hann windowed and
+/// transformed every `N / 2` samples, a bin that stands far above the average spectrum for long
+/// enough is taken as howl and gets a narrow notch, deepened while it keeps ringing. Once all
+/// notches are used the oldest is moved. Sustained pure tones in the program can trigger it too,
+/// so keep the threshold high for music.
+pub struct FeedbackSuppressor<const N: usize> {
+    sample_rate: f32,
+    fft: Fft<N>,
+    window: [f32; N],
+    input: [f32; N],
+    write_index: usize,
+    counter: usize,
+    frame: [f32; N],
+    threshold: f32,
+    persistence: usize,
+    candidate_bin: usize,
+    candidate_frames: usize,
+    notches: [Biquad; MAX_NOTCHES],
+    notch_freqs: [f32; MAX_NOTCHES],
+    notch_gains: [f32; MAX_NOTCHES],
+    next_notch: usize,
+}
+
+impl<const N: usize> FeedbackSuppressor<N> {
+    /// 20 dB threshold and 200 ms persistence.
+    pub fn new(sample_rate: f32) -> Self {
+        let mut window = [0.0; N];
+        windows::fill(Window::Hann, Symmetry::Periodic, &mut window);
+        let mut suppressor = Self {
+            sample_rate,
+            fft: Fft::new(),
+            window,
+            input: [0.0; N],
+            write_index: 0,
+            counter: 0,
+            frame: [0.0; N],
+            threshold: 0.0,
+            persistence: 1,
+            candidate_bin: 0,
+            candidate_frames: 0,
+            notches: core::array::from_fn(|_| Biquad::new(sample_rate)),
+            notch_freqs: [0.0; MAX_NOTCHES],
+            notch_gains: [0.0; MAX_NOTCHES],
+            next_notch: 0,
+        };
+        suppressor.set_threshold(20.0);
+        suppressor.set_persistence(0.2);
+        suppressor
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let mut output = input;
+        for (notch, freq) in self.notches.iter_mut().zip(self.notch_freqs.iter()) {
+            if *freq > 0.0 {
+                output = notch.process(output);
+            }
+        }
+
+        self.input[self.write_index] = output;
+        self.write_index = (self.write_index + 1) % N;
+        self.counter += 1;
+        if self.counter >= N / 2 {
+            self.counter = 0;
+            self.analyze();
+        }
+        output
+    }
+
+    fn analyze(&mut self) {
+        for (i, x) in self.frame.iter_mut().enumerate() {
+            *x = self.input[(self.write_index + i) % N] * self.window[i];
+        }
+        self.fft.process_real(&mut self.frame);
+        // Power of the bins between DC and Nyquist, in place of their real parts.
+        for bin in self.frame[2..].chunks_exact_mut(2) {
+            bin[0] = bin[0] * bin[0] + bin[1] * bin[1];
+        }
+        let frame = &self.frame;
+        let power = |k: usize| frame[2 * k];
+
+        let bins = N / 2;
+        let mean = (1..bins).map(power).sum::<f32>() / (bins - 1) as f32;
+        let mut peak = 0;
+        for k in 2..bins - 1 {
+            if power(k) > power(k - 1) && power(k) >= power(k + 1) && power(k) > power(peak) {
+                peak = k;
+            }
+        }
+        // A full scale sine has a power of (N / 4)^2 in its Hann windowed bin.
+        let full_scale = (N * N) as f32 / 16.0;
+        let min_power = full_scale * db_to_linear(FEEDBACK_MIN_DB).powi(2);
+        if peak == 0 || power(peak) < mean * self.threshold || power(peak) < min_power {
+            self.candidate_frames = 0;
+            return;
+        }
+
+        if self.candidate_frames > 0 && peak.abs_diff(self.candidate_bin) <= 1 {
+            self.candidate_frames += 1;
+        } else {
+            self.candidate_frames = 1;
+        }
+        self.candidate_bin = peak;
+        if self.candidate_frames >= self.persistence {
+            self.candidate_frames = 0;
+            // Parabolic interpolation of the log power places the notch between bins.
+            let (a, b, c) = (
+                power(peak - 1).max(1e-20).ln(),
+                power(peak).ln(),
+                power(peak + 1).max(1e-20).ln(),
+            );
+            let offset = 0.5 * (a - c) / (a - 2.0 * b + c);
+            let freq = (peak as f32 + offset) * self.sample_rate / N as f32;
+            self.deploy_notch(freq);
+        }
+    }
+
+    fn deploy_notch(&mut self, freq: f32) {
+        let bin_width = self.sample_rate / N as f32;
+        let notch = match self
+            .notch_freqs
+            .iter()
+            .position(|f| *f > 0.0 && (f - freq).abs() < bin_width)
+        {
+            // Still ringing through an existing notch, deepen it.
+            Some(notch) => {
+                self.notch_gains[notch] =
+                    (self.notch_gains[notch] + NOTCH_STEP_DB).max(NOTCH_MAX_DB);
+                notch
+            }
+            None => {
+                let notch = self.next_notch;
+                self.next_notch = (self.next_notch + 1) % MAX_NOTCHES;
+                self.notch_freqs[notch] = freq;
+                self.notch_gains[notch] = NOTCH_START_DB;
+                self.notches[notch].reset();
+                notch
+            }
+        };
+        trace!(
+            "feedback notch at {} Hz, {} dB",
+            freq,
+            self.notch_gains[notch]
+        );
+        let (freq, gain) = (self.notch_freqs[notch], self.notch_gains[notch]);
+        self.notches[notch].set_peak(freq, NOTCH_Q, gain);
+    }
+
+    /// Set how far in dB a peak has to stand above the average spectrum to count as feedback.
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = db_to_linear(threshold.max(0.0)).powi(2);
+    }
+
+    /// Set how long in seconds a peak has to persist before it's notched.
+    pub fn set_persistence(&mut self, time: f32) {
+        let hop = (N / 2) as f32 / self.sample_rate;
+        self.persistence = ((time / hop) as usize).max(1);
+    }
+
+    /// Get the centre frequencies of the deployed notches in Hz, 0.0 for unused ones.
+    pub fn get_notch_freqs(&self) -> &[f32; MAX_NOTCHES] {
+        &self.notch_freqs
+    }
+
+    /// Remove every notch, for example after moving the microphones.
+    pub fn clear_notches(&mut self) {
+        self.notch_freqs = [0.0; MAX_NOTCHES];
+        self.next_notch = 0;
+    }
+
+    /// Clear the analysis and notch filter state, keeping the deployed notches.
+    pub fn reset(&mut self) {
+        self.input = [0.0; N];
+        self.counter = 0;
+        self.candidate_frames = 0;
+        for notch in self.notches.iter_mut() {
+            notch.reset();
+        }
+    }
+}
+
+impl<const N: usize> Processor for FeedbackSuppressor<N> {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
 }
 
 #[cfg(test)]
@@ -264,6 +1476,7 @@ mod tests {
     const NYQUIST: f32 = SAMPLE_RATE_F / 2.0;
 
     use super::*;
+    use crate::synthesis::{Oscillator, WaveType};
 
     use plotters::prelude::*;
     use rand::distributions::{Distribution, Uniform};
@@ -277,7 +1490,7 @@ mod tests {
             .margin(5)
             .x_label_area_size(30)
             .y_label_area_size(30)
-            .build_cartesian_2d(LogRange(0.1..NYQUIST), -51f32..11f32)
+            .build_cartesian_2d((0.1..NYQUIST).log_scale(), -51f32..11f32)
             .unwrap();
 
         chart.configure_mesh().draw().unwrap();
@@ -537,6 +1750,304 @@ mod tests {
         graph_log_log(data, "SVF Peak", "test_svf_peak.png");
     }
 
+    #[test]
+    fn test_biquad_block() {
+        let mut filter = Biquad::new(SAMPLE_RATE_F);
+        filter.set_low_pass(1000.0, 0.707);
+        let mut block_filter = Biquad::new(SAMPLE_RATE_F);
+        block_filter.set_low_pass(1000.0, 0.707);
+        let mut oscillator = Oscillator::new(WaveType::Saw, SAMPLE_RATE_F, 220.0);
+        let mut block = [0.0; 64];
+        for _ in 0..4 {
+            for x in block.iter_mut() {
+                *x = oscillator.process();
+            }
+            let expected: Vec<f32> = block.iter().map(|x| filter.process(*x)).collect();
+            block_filter.process_block(&mut block);
+            assert_eq!(&block[..], &expected[..]);
+        }
+    }
+
+    #[test]
+    fn test_all_pass_try_new() {
+        let mut buffer = [0.0; 400];
+        assert_eq!(
+            AllPassSP::try_new(SAMPLE_RATE_F, DelayLine::new(&mut buffer)).err(),
+            Some(Error::BufferTooSmall)
+        );
+        let mut buffer = [0.0; 4410];
+        assert_eq!(
+            AllPassSP::try_new(0.0, DelayLine::new(&mut buffer)).err(),
+            Some(Error::InvalidSampleRate)
+        );
+        assert!(AllPassSP::try_new(SAMPLE_RATE_F, DelayLine::new(&mut buffer)).is_ok());
+        let mut empty: [f32; 0] = [];
+        assert_eq!(
+            DelayLine::try_new(&mut empty).err(),
+            Some(Error::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn test_biquad4_lanes() {
+        let mut filters = [
+            Biquad::new(SAMPLE_RATE_F),
+            Biquad::new(SAMPLE_RATE_F),
+            Biquad::new(SAMPLE_RATE_F),
+            Biquad::new(SAMPLE_RATE_F),
+        ];
+        filters[0].set_low_pass(500.0, 0.707);
+        filters[1].set_high_pass(2000.0, 0.707);
+        filters[2].set_peak(1000.0, 2.0, 6.0);
+        let mut lanes = Biquad4::new();
+        for (lane, filter) in filters.iter().enumerate() {
+            lanes.set_lane(lane, filter);
+        }
+        let mut oscillator = Oscillator::new(WaveType::Saw, SAMPLE_RATE_F, 220.0);
+        for _ in 0..256 {
+            let x = oscillator.process();
+            let output = lanes.process([x, -x, 0.5 * x, x]);
+            let inputs = [x, -x, 0.5 * x, x];
+            for lane in 0..4 {
+                assert_eq!(output[lane], filters[lane].process(inputs[lane]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_interpolated_biquad_reaches_target() {
+        let mut target = Biquad::new(SAMPLE_RATE_F);
+        target.set_low_pass(800.0, 0.707);
+        let mut interpolated = InterpolatedBiquad::new(SAMPLE_RATE_F);
+        interpolated.set_target(&target, 64);
+        let mut oscillator = Oscillator::new(WaveType::Saw, SAMPLE_RATE_F, 220.0);
+        for _ in 0..64 {
+            assert!(interpolated.process(oscillator.process()).is_finite());
+        }
+        interpolated.reset();
+        for _ in 0..256 {
+            let x = oscillator.process();
+            assert!((interpolated.process(x) - target.process(x)).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_interpolated_biquad_design() {
+        let mut target = Biquad::new(SAMPLE_RATE_F);
+        target.set_low_pass(800.0, 0.707);
+        let mut immediate = InterpolatedBiquad::new(SAMPLE_RATE_F);
+        immediate.design().set_low_pass(800.0, 0.707);
+        let mut ticked = InterpolatedBiquad::new(SAMPLE_RATE_F);
+        ticked.update_params(32);
+        ticked.design().set_low_pass(800.0, 0.707);
+        // Unchanged until the next tick.
+        assert_eq!(ticked.process(1.0), 1.0);
+        ticked.update_params(32);
+        let mut oscillator = Oscillator::new(WaveType::Saw, SAMPLE_RATE_F, 220.0);
+        for _ in 0..32 {
+            ticked.process(oscillator.process());
+        }
+        ticked.reset();
+        for _ in 0..256 {
+            let x = oscillator.process();
+            let expected = target.process(x);
+            assert!((immediate.process(x) - expected).abs() < 1e-5);
+            assert!((ticked.process(x) - expected).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_svf_control_rate() {
+        let mut immediate = StateVariable::new(SAMPLE_RATE_F);
+        immediate.set_freq(1000.0);
+        immediate.set_resonance(0.5);
+        let mut ticked = StateVariable::new(SAMPLE_RATE_F);
+        ticked.set_freq(500.0);
+        ticked.update_params(1);
+        ticked.set_freq(1000.0);
+        ticked.set_resonance(0.5);
+        ticked.update_params(64);
+        let mut oscillator = Oscillator::new(WaveType::Saw, SAMPLE_RATE_F, 220.0);
+        for _ in 0..64 {
+            ticked.process(oscillator.process());
+        }
+        for _ in 0..4096 {
+            let x = oscillator.process();
+            immediate.process(x);
+            ticked.process(x);
+        }
+        // The states converge once the coefficients match.
+        assert!((immediate.get_low_pass() - ticked.get_low_pass()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_biquad_band_pass() {
+        let mut instant: [f32; 4096] = [0.0; 4096];
+        instant[0] = 1.0;
+        let mut filter = Biquad::new(SAMPLE_RATE_F);
+        filter.set_band_pass(1000.0, 2.0);
+        for item in &mut instant {
+            *item = filter.process(*item);
+        }
+
+        let spectrum = samples_fft_to_spectrum(
+            &instant,
+            SAMPLE_RATE,
+            FrequencyLimit::Max(NYQUIST),
+            Some(&scaling::basic::scale_20_times_log10),
+            None,
+        );
+
+        let data: Vec<(f32, f32)> = spectrum
+            .to_map(None)
+            .iter()
+            .map(|(x, y)| (*x as f32, *y))
+            .collect();
+
+        for (hz, db) in &data {
+            if (*hz - 1000.0).abs() < 20.0 {
+                assert!(db.abs() < 1.0);
+            } else if *hz < 250.0 || *hz > 4000.0 {
+                assert!(*db < -6.0);
+            }
+        }
+
+        graph_log_log(data, "Biquad Band 1000 Hz", "test_biquad_band.png");
+    }
+
+    // Steady state gain of a filter at the given frequency.
+    fn sine_gain<F: FnMut(f32) -> f32>(freq: f32, mut filter: F) -> f32 {
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, freq);
+        let mut peak = 0.0_f32;
+        for i in 0..SAMPLE_RATE {
+            let output = filter(oscillator.process());
+            if i > SAMPLE_RATE / 2 {
+                peak = peak.max(output.abs());
+            }
+        }
+        peak
+    }
+
+    #[test]
+    fn test_tone_stack() {
+        let mut tone_stack = ToneStack::new(SAMPLE_RATE_F);
+        let flat_bass = sine_gain(60.0, |x| tone_stack.process(x));
+        let mid = sine_gain(600.0, |x| tone_stack.process(x));
+        // A passive stack never adds gain and has the classic mid scoop.
+        assert!(flat_bass <= 1.0 && mid < flat_bass);
+
+        let mut tone_stack = ToneStack::new(SAMPLE_RATE_F);
+        tone_stack.set_bass(0.0);
+        assert!(sine_gain(60.0, |x| tone_stack.process(x)) < flat_bass * 0.6);
+
+        let mut tone_stack = ToneStack::new(SAMPLE_RATE_F);
+        tone_stack.set_mid(1.0);
+        assert!(sine_gain(600.0, |x| tone_stack.process(x)) > mid);
+    }
+
+    #[test]
+    fn test_fir_impulse() {
+        let coefs = [0.5, 0.25, -0.125];
+        let mut buffer = [0.0; 8];
+        let mut fir = Fir::new(&coefs, DelayLine::new(&mut buffer));
+        let output: Vec<f32> = (0..5)
+            .map(|i| fir.process(if i == 0 { 1.0 } else { 0.0 }))
+            .collect();
+        assert_eq!(output, vec![0.5, 0.25, -0.125, 0.0, 0.0]);
+        // Across the end of the buffer.
+        let output: Vec<f32> = (0..5)
+            .map(|i| fir.process(if i == 0 { 1.0 } else { 0.0 }))
+            .collect();
+        assert_eq!(output, vec![0.5, 0.25, -0.125, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_linkwitz_riley_flat_sum() {
+        for freq in [50.0, 500.0, 1000.0, 2000.0, 10000.0].iter() {
+            let mut crossover = LinkwitzRiley::new(SAMPLE_RATE_F);
+            let gain = sine_gain(*freq, |x| {
+                let (low, high) = crossover.process(x);
+                low + high
+            });
+            assert!((gain - 1.0).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_band_split_flat_sum() {
+        let mut split: BandSplit<5> = BandSplit::new(SAMPLE_RATE_F);
+        assert!((split.get_crossover_freq(0) - 79.6).abs() < 0.1);
+        assert_eq!(split.get_crossover_freq(4), 0.0);
+        for bands in 1..=5 {
+            split.set_bands(bands);
+            for freq in [30.0, 200.0, 1000.0, 5000.0, 15000.0].iter() {
+                let gain = sine_gain(*freq, |x| BandSplit::recombine(&split.process(x)));
+                assert!((gain - 1.0).abs() < 0.01, "{} {} {}", bands, freq, gain);
+            }
+        }
+
+        // Each band holds its own range.
+        split.set_bands(5);
+        let highest = sine_gain(15000.0, |x| split.process(x)[4]);
+        let lowest = sine_gain(15000.0, |x| split.process(x)[0]);
+        assert!(highest > 0.99 && lowest < 0.01);
+    }
+
+    #[test]
+    fn test_isolator() {
+        // Flat with every band at 0 dB.
+        let mut isolator = Isolator::new(SAMPLE_RATE_F);
+        for freq in [50.0, 300.0, 1000.0, 4000.0, 10000.0].iter() {
+            let gain = sine_gain(*freq, |x| isolator.process(x));
+            assert!((gain - 1.0).abs() < 0.01, "{} {}", freq, gain);
+        }
+
+        // Killing a band removes its frequencies and leaves the others.
+        isolator.set_gain_db(0, f32::NEG_INFINITY);
+        assert!(sine_gain(50.0, |x| isolator.process(x)) < 0.01);
+        assert!((sine_gain(10000.0, |x| isolator.process(x)) - 1.0).abs() < 0.01);
+        isolator.set_gain_db(0, 0.0);
+        isolator.set_gain_db(1, -100.0);
+        assert!(sine_gain(1000.0, |x| isolator.process(x)) < 0.05);
+        isolator.set_gain_db(2, 20.0);
+        assert_eq!(isolator.get_gain_db(2), 6.0);
+    }
+
+    #[test]
+    fn test_feedback_suppressor() {
+        use crate::random::{Random, XorShift32};
+
+        let mut random = XorShift32::new(1);
+        let mut suppressor: FeedbackSuppressor<1024> = FeedbackSuppressor::new(SAMPLE_RATE_F);
+        // Noise alone deploys nothing.
+        for _ in 0..SAMPLE_RATE {
+            suppressor.process(0.1 * random.next_f32_bipolar());
+        }
+        assert!(suppressor.get_notch_freqs().iter().all(|f| *f == 0.0));
+
+        // A sustained tone well above the noise is found and notched.
+        let mut howl = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 2500.0);
+        let mut peak = 0.0_f32;
+        for i in 0..SAMPLE_RATE {
+            let output =
+                suppressor.process(0.5 * howl.process() + 0.01 * random.next_f32_bipolar());
+            if i > SAMPLE_RATE * 9 / 10 {
+                peak = peak.max(output.abs());
+            }
+        }
+        let notched = suppressor.get_notch_freqs().iter().filter(|f| **f > 0.0);
+        assert!(notched.clone().count() >= 1);
+        assert!(
+            notched.clone().all(|f| (f - 2500.0).abs() < 20.0),
+            "{:?}",
+            suppressor.get_notch_freqs()
+        );
+        assert!(peak < 0.5 * db_to_linear(-12.0), "{}", peak);
+
+        suppressor.clear_notches();
+        assert!(suppressor.get_notch_freqs().iter().all(|f| *f == 0.0));
+    }
+
     #[test]
     fn test_all_pass_1() {
         let mut instant: [f32; 4096] = [0.0; 4096];
@@ -0,0 +1,298 @@
+use core::f64::consts::PI;
+
+use crate::convert::{f32_to_i16, i16_to_f32};
+use crate::delay::DelayLine;
+use crate::random::{Random, XorShift32};
+use crate::sample::Sample;
+use crate::synthesis::{WaveType, RANDOM_SEED};
+use crate::windows::cos;
+
+/// Q15 fixed point sample, 1 sign bit and 15 fractional bits covering [-1.0-1.0).
+pub type Q15 = i16;
+
+pub fn to_q15(sample: f32) -> Q15 {
+    f32_to_i16(sample)
+}
+
+pub fn from_q15(sample: Q15) -> f32 {
+    i16_to_f32(sample)
+}
+
+pub fn to_q15_slice(input: &[f32], output: &mut [Q15]) {
+    for (output, input) in output.iter_mut().zip(input.iter()) {
+        *output = to_q15(*input);
+    }
+}
+
+pub fn from_q15_slice(input: &[Q15], output: &mut [f32]) {
+    for (output, input) in output.iter_mut().zip(input.iter()) {
+        *output = from_q15(*input);
+    }
+}
+
+/// Saturating Q15 multiply with rounding.
+pub fn mul(a: Q15, b: Q15) -> Q15 {
+    ((a as i32 * b as i32 + (1 << 14)) >> 15).clamp(i16::MIN as i32, i16::MAX as i32) as Q15
+}
+
+impl Sample for Q15 {
+    // 8 extra fractional bits.
+    type State = i32;
+
+    const EQUILIBRIUM: Self = 0;
+
+    const IDENTITY: Self = i16::MAX;
+
+    const STATE_EQUILIBRIUM: i32 = 0;
+
+    fn from_f32(value: f32) -> Self {
+        to_q15(value)
+    }
+
+    fn to_f32(self) -> f32 {
+        from_q15(self)
+    }
+
+    fn add(self, other: Self) -> Self {
+        self.saturating_add(other)
+    }
+
+    fn sub(self, other: Self) -> Self {
+        self.saturating_sub(other)
+    }
+
+    fn mul(self, other: Self) -> Self {
+        mul(self, other)
+    }
+
+    fn lerp(self, other: Self, frac: f32) -> Self {
+        // The difference can exceed Q15, so interpolate in i32.
+        let frac = (frac * 65536.0) as i32;
+        (self as i32 + (((other as i32 - self as i32) * frac) >> 16)) as Q15
+    }
+
+    fn from_state(state: i32) -> Self {
+        ((state + (1 << 7)) >> 8).clamp(i16::MIN as i32, i16::MAX as i32) as Q15
+    }
+
+    fn approach(state: i32, target: Self, coef: Self) -> i32 {
+        let delta = (((target as i32) << 8) - state) as i64 * coef as i64;
+        state + ((delta + (1 << 14)) >> 15) as i32
+    }
+}
+
+impl<const N: usize> DelayLine<'_, Q15, N> {
+    /// Like `read_delayed` without floating point, the delay is in 16.16 fixed point samples.
+    pub fn read_delayed_fixed(&self, delay: u32) -> Q15 {
+        let whole = (delay >> 16) as usize;
+        let frac = (delay & 0xffff) as i32;
+        let a = self.tap(whole) as i32;
+        let b = self.tap(whole + 1) as i32;
+        (a + (((b - a) * frac) >> 16)) as Q15
+    }
+}
+
+const SINE_TABLE_BITS: u32 = 8;
+const SINE_TABLE_LEN: usize = 1 << SINE_TABLE_BITS;
+
+// One period of a sine plus a guard point for interpolation.
+const SINE_TABLE: [Q15; SINE_TABLE_LEN + 1] = {
+    let mut table = [0; SINE_TABLE_LEN + 1];
+    let mut i = 0;
+    while i <= SINE_TABLE_LEN {
+        let value = cos(2.0 * PI * i as f64 / SINE_TABLE_LEN as f64 - PI / 2.0) * 32767.0;
+        table[i] = if value < 0.0 {
+            value - 0.5
+        } else {
+            value + 0.5
+        } as Q15;
+        i += 1;
+    }
+    table
+};
+
+// Random level in Q15 drawn the same way as the floating point oscillator's.
+fn random_q15(random: &mut XorShift32) -> i32 {
+    (random.next_u32() >> 16) as i32 - 32768
+}
+
+/// Phase accumulator oscillator in Q15 for MCUs without an FPU.
+/// The band limited PolyBLEP wave types fall back to their naive versions.
+pub struct OscillatorQ15 {
+    wave_type: WaveType,
+    sample_rate: f32,
+    phase: u32,
+    phase_inc: u32,
+    amplitude: Q15,
+    seed: u32,
+    random: XorShift32,
+    // Random levels of this cycle, from and to.
+    levels: [i32; 2],
+}
+
+impl OscillatorQ15 {
+    pub fn new(wave_type: WaveType, sample_rate: f32, frequency: f32) -> Self {
+        let mut oscillator = Self {
+            wave_type,
+            sample_rate,
+            phase: 0,
+            phase_inc: 0,
+            amplitude: i16::MAX,
+            seed: RANDOM_SEED,
+            random: XorShift32::new(RANDOM_SEED),
+            levels: [0; 2],
+        };
+        oscillator.set_freq(frequency);
+        oscillator.set_seed(RANDOM_SEED);
+        oscillator
+    }
+
+    pub fn process(&mut self) -> Q15 {
+        let phase = self.phase;
+        let out = match self.wave_type {
+            WaveType::Sine => {
+                let index = (phase >> (32 - SINE_TABLE_BITS)) as usize;
+                let frac = ((phase >> (16 - SINE_TABLE_BITS)) & 0xffff) as i32;
+                let a = SINE_TABLE[index] as i32;
+                let b = SINE_TABLE[index + 1] as i32;
+                a + (((b - a) * frac) >> 16)
+            }
+            WaveType::Triangle | WaveType::PolyBLEPTri => {
+                ((2 * (phase >> 16) as i32 - 65536).abs() - 32768).min(i16::MAX as i32)
+            }
+            WaveType::Saw | WaveType::PolyBLEPSaw => i16::MAX as i32 - (phase >> 16) as i32,
+            WaveType::Ramp => (phase >> 16) as i32 - 32768,
+            WaveType::Square | WaveType::PolyBLEPSquare => {
+                if phase < 1 << 31 {
+                    i16::MAX as i32
+                } else {
+                    i16::MIN as i32
+                }
+            }
+            WaveType::RandomStep => self.levels[1],
+            WaveType::RandomSmooth => {
+                // Smoothstep with t in Q16.
+                let t = (phase >> 16) as i64;
+                let curve = (t * t * (3 * 65536 - 2 * t)) >> 32;
+                let [from, to] = self.levels;
+                from + (((to - from) as i64 * curve) >> 16) as i32
+            }
+        };
+        let (next, wrapped) = self.phase.overflowing_add(self.phase_inc);
+        self.phase = next;
+        if wrapped {
+            self.levels = [self.levels[1], random_q15(&mut self.random)];
+        }
+        mul(out.min(i16::MAX as i32) as Q15, self.amplitude)
+    }
+
+    /// Seed the random waveforms, the same seed gives the same levels as `Oscillator`. The
+    /// sequence restarts from the seed on `reset`.
+    pub fn set_seed(&mut self, seed: u32) {
+        self.seed = seed;
+        self.random = XorShift32::new(seed);
+        self.levels = [random_q15(&mut self.random), random_q15(&mut self.random)];
+    }
+
+    /// Set the frequency, calculated in floating point so call it outside the audio loop.
+    pub fn set_freq(&mut self, frequency: f32) {
+        let ratio = (frequency as f64 / self.sample_rate as f64).clamp(0.0, 0.5);
+        self.phase_inc = (ratio * 4294967296.0) as u32;
+    }
+
+    /// Set the phase increment directly, one period is 2^32.
+    pub fn set_phase_inc(&mut self, phase_inc: u32) {
+        self.phase_inc = phase_inc;
+    }
+
+    pub fn set_amplitude(&mut self, amplitude: Q15) {
+        self.amplitude = amplitude;
+    }
+
+    /// Set the phase, one period is 2^32.
+    pub fn set_phase(&mut self, phase: u32) {
+        self.phase = phase;
+    }
+
+    pub fn set_wave_type(&mut self, wave_type: WaveType) {
+        self.wave_type = wave_type;
+    }
+
+    /// Restart the waveform from phase 0.
+    pub fn reset(&mut self) {
+        self.phase = 0;
+        self.set_seed(self.seed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const SAMPLE_RATE_F: f32 = 48000.0;
+
+    use super::*;
+    use crate::synthesis::Oscillator;
+
+    #[test]
+    fn test_q15_conversion() {
+        assert_eq!(to_q15(0.5), 16384);
+        assert_eq!(from_q15(-16384), -0.5);
+        assert_eq!(mul(16384, 16384), 8192);
+        assert_eq!(mul(i16::MIN, i16::MIN), i16::MAX);
+    }
+
+    #[test]
+    fn test_delay_line_q15() {
+        let mut buffer: [Q15; 8] = [0; 8];
+        let mut delay_line = DelayLine::new(&mut buffer);
+        for x in [100, 200, 300].iter() {
+            delay_line.write(*x);
+        }
+        assert_eq!(delay_line.tap(1), 300);
+        assert_eq!(delay_line.tap(3), 100);
+        assert_eq!(delay_line.read_delayed_fixed(0x18000), 250);
+        for x in [i16::MIN, i16::MAX].iter() {
+            delay_line.write(*x);
+        }
+        assert_eq!(delay_line.tap(1), i16::MAX);
+        assert_eq!(delay_line.read_delayed(1.5), -1);
+        let mut gain = crate::mix::Gain::<Q15>::new(SAMPLE_RATE_F);
+        gain.set_linear_immediate(0.5);
+        assert_eq!(gain.process(10000), 5000);
+    }
+
+    #[test]
+    fn test_one_pole_q15_settles() {
+        let mut filter = crate::filter::OnePoleLowPass::<Q15>::new(SAMPLE_RATE_F);
+        filter.set_freq(20.0);
+        let mut output = 0;
+        for _ in 0..48000 {
+            output = filter.process(10000);
+        }
+        assert!((output as i32 - 10000).abs() <= 1, "{}", output);
+    }
+
+    #[test]
+    fn test_oscillator_q15_matches_float() {
+        for wave_type in [
+            WaveType::Sine,
+            WaveType::Triangle,
+            WaveType::Saw,
+            WaveType::Ramp,
+            WaveType::Square,
+            WaveType::RandomStep,
+            WaveType::RandomSmooth,
+        ]
+        .iter()
+        {
+            let mut fixed = OscillatorQ15::new(*wave_type, SAMPLE_RATE_F, 375.0);
+            let mut float = Oscillator::new(*wave_type, SAMPLE_RATE_F, 375.0);
+            for i in 0..1000 {
+                let (fixed, float) = (from_q15(fixed.process()), float.process());
+                // Skip the edges of the discontinuous waves where the phases may round apart.
+                if i % 128 > 1 && i % 128 < 63 || i % 128 > 65 && i % 128 < 127 {
+                    assert!((fixed - float).abs() < 2e-3, "{:?} {}", wave_type, i);
+                }
+            }
+        }
+    }
+}
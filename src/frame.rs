@@ -0,0 +1,174 @@
+//! One sample per channel at a single point in time. Stereo processors take and return `Stereo`
+//! frames through their `process_frame` methods, and frames convert to and from the `(f32, f32)`
+//! tuples used elsewhere and plain arrays. With the `dasp` feature they also convert to and from
+//! any `dasp_frame::Frame`.
+
+use core::ops::{Add, Mul, Sub};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Frame<const N: usize>(pub [f32; N]);
+
+pub type Mono = Frame<1>;
+pub type Stereo = Frame<2>;
+
+impl<const N: usize> Frame<N> {
+    pub const EQUILIBRIUM: Self = Self([0.0; N]);
+
+    pub const CHANNELS: usize = N;
+
+    /// Apply `f` to every channel.
+    pub fn map<F: FnMut(f32) -> f32>(self, f: F) -> Self {
+        Self(self.0.map(f))
+    }
+
+    /// Combine two frames channel by channel.
+    pub fn zip_map<F: FnMut(f32, f32) -> f32>(self, other: Self, mut f: F) -> Self {
+        Self(core::array::from_fn(|i| f(self.0[i], other.0[i])))
+    }
+
+    pub fn channel(&self, channel: usize) -> Option<f32> {
+        self.0.get(channel).copied()
+    }
+}
+
+impl Mono {
+    pub fn to_stereo(self) -> Stereo {
+        Frame([self.0[0]; 2])
+    }
+}
+
+impl Stereo {
+    pub fn new(left: f32, right: f32) -> Self {
+        Self([left, right])
+    }
+
+    pub fn left(&self) -> f32 {
+        self.0[0]
+    }
+
+    pub fn right(&self) -> f32 {
+        self.0[1]
+    }
+
+    /// Average of both channels.
+    pub fn to_mono(self) -> Mono {
+        Frame([(self.0[0] + self.0[1]) * 0.5])
+    }
+}
+
+impl<const N: usize> Default for Frame<N> {
+    fn default() -> Self {
+        Self::EQUILIBRIUM
+    }
+}
+
+impl<const N: usize> Add for Frame<N> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        self.zip_map(other, |a, b| a + b)
+    }
+}
+
+impl<const N: usize> Sub for Frame<N> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.zip_map(other, |a, b| a - b)
+    }
+}
+
+impl<const N: usize> Mul<f32> for Frame<N> {
+    type Output = Self;
+
+    fn mul(self, gain: f32) -> Self {
+        self.map(|x| x * gain)
+    }
+}
+
+impl<const N: usize> From<[f32; N]> for Frame<N> {
+    fn from(samples: [f32; N]) -> Self {
+        Self(samples)
+    }
+}
+
+impl<const N: usize> From<Frame<N>> for [f32; N] {
+    fn from(frame: Frame<N>) -> Self {
+        frame.0
+    }
+}
+
+impl From<f32> for Mono {
+    fn from(sample: f32) -> Self {
+        Self([sample])
+    }
+}
+
+impl From<(f32, f32)> for Stereo {
+    fn from((left, right): (f32, f32)) -> Self {
+        Self([left, right])
+    }
+}
+
+impl From<Stereo> for (f32, f32) {
+    fn from(frame: Stereo) -> Self {
+        (frame.0[0], frame.0[1])
+    }
+}
+
+#[cfg(feature = "dasp")]
+impl<const N: usize> Frame<N> {
+    /// Convert from a dasp frame of any sample type, missing channels are silent and extra
+    /// channels are dropped.
+    pub fn from_dasp<F>(frame: F) -> Self
+    where
+        F: dasp_frame::Frame,
+        F::Sample: dasp_sample::ToSample<f32>,
+    {
+        use dasp_sample::Sample;
+        let mut channels = frame.channels();
+        Self(core::array::from_fn(|_| {
+            channels.next().map_or(0.0, |sample| sample.to_sample())
+        }))
+    }
+
+    /// Convert to a dasp frame of any sample type, missing channels are silent and extra
+    /// channels are dropped.
+    pub fn to_dasp<F>(self) -> F
+    where
+        F: dasp_frame::Frame,
+        F::Sample: dasp_sample::FromSample<f32>,
+    {
+        use dasp_sample::Sample;
+        F::from_fn(|i| self.channel(i).unwrap_or(0.0).to_sample())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_arithmetic() {
+        let a = Stereo::new(1.0, -1.0);
+        let b: Stereo = (0.5, 0.5).into();
+        assert_eq!(a + b, Stereo::new(1.5, -0.5));
+        assert_eq!((a - b) * 2.0, Stereo::new(1.0, -3.0));
+        assert_eq!(a.to_mono(), Mono::from(0.0));
+        assert_eq!(Mono::from(0.25).to_stereo(), Stereo::new(0.25, 0.25));
+        assert_eq!(<(f32, f32)>::from(a), (1.0, -1.0));
+        assert_eq!(Stereo::default().channel(1), Some(0.0));
+        assert_eq!(a.channel(2), None);
+    }
+
+    #[cfg(feature = "dasp")]
+    #[test]
+    fn test_dasp_conversion() {
+        let frame = Stereo::from_dasp([16384_i16, -32768]);
+        assert_eq!(frame, Stereo::new(0.5, -1.0));
+        let samples: [i16; 2] = frame.to_dasp();
+        assert_eq!(samples, [16384, -32768]);
+        let mono: [f32; 1] = frame.to_dasp();
+        assert_eq!(mono, [0.5]);
+    }
+}
@@ -0,0 +1,167 @@
+//! Interpolation between samples for fractional delays and reading tables or recordings at
+//! fractional positions. The point functions interpolate between the two middle points of their
+//! input, the `read_*` functions read a slice at a fractional index, holding the first and last
+//! samples past the ends.
+
+use core::f32::consts::PI;
+
+float_math!();
+
+/// Straight line from `a` at 0.0 to `b` at 1.0.
+pub fn lerp(a: f32, b: f32, frac: f32) -> f32 {
+    a + (b - a) * frac
+}
+
+/// 4 point cubic Hermite (Catmull-Rom) between `y[1]` and `y[2]`, continuous in slope.
+pub fn hermite(y: [f32; 4], frac: f32) -> f32 {
+    let c1 = 0.5 * (y[2] - y[0]);
+    let c2 = y[0] - 2.5 * y[1] + 2.0 * y[2] - 0.5 * y[3];
+    let c3 = 0.5 * (y[3] - y[0]) + 1.5 * (y[1] - y[2]);
+    ((c3 * frac + c2) * frac + c1) * frac + y[1]
+}
+
+/// 4 point third order Lagrange between `y[1]` and `y[2]`, the cubic through all four points.
+pub fn lagrange(y: [f32; 4], frac: f32) -> f32 {
+    let d0 = frac + 1.0;
+    let d1 = frac;
+    let d2 = frac - 1.0;
+    let d3 = frac - 2.0;
+    -y[0] * d1 * d2 * d3 / 6.0 + y[1] * d0 * d2 * d3 / 2.0 - y[2] * d0 * d1 * d3 / 2.0
+        + y[3] * d0 * d1 * d2 / 6.0
+}
+
+pub(crate) fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-6 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+// Blackman window stretched over (-half_width, half_width).
+pub(crate) fn blackman(x: f32, half_width: f32) -> f32 {
+    if x.abs() >= half_width {
+        return 0.0;
+    }
+    let phase = PI * x / half_width;
+    0.42 + 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos()
+}
+
+/// Blackman windowed sinc between the two middle points of `y`, which should have an even
+/// length. Longer inputs pass more of the band flat at more cost.
+pub fn windowed_sinc(y: &[f32], frac: f32) -> f32 {
+    let half_width = (y.len() / 2) as f32;
+    y.iter()
+        .enumerate()
+        .map(|(i, y)| {
+            let x = i as f32 - (half_width - 1.0) - frac;
+            y * sinc(x) * blackman(x, half_width)
+        })
+        .sum()
+}
+
+// Sample at `index` which may be past either end.
+fn get(input: &[f32], index: isize) -> f32 {
+    input[index.clamp(0, input.len() as isize - 1) as usize]
+}
+
+fn split(position: f32) -> (isize, f32) {
+    // Truncation rounds toward zero, step down for negative positions.
+    let mut whole = position as isize;
+    if (whole as f32) > position {
+        whole -= 1;
+    }
+    (whole, position - whole as f32)
+}
+
+fn points(input: &[f32], position: f32) -> ([f32; 4], f32) {
+    let (whole, frac) = split(position);
+    let mut y = [0.0; 4];
+    for (i, y) in y.iter_mut().enumerate() {
+        *y = get(input, whole + i as isize - 1);
+    }
+    (y, frac)
+}
+
+/// Read `input`, which must not be empty, at a fractional index with `lerp`.
+pub fn read_linear(input: &[f32], position: f32) -> f32 {
+    let (whole, frac) = split(position);
+    lerp(get(input, whole), get(input, whole + 1), frac)
+}
+
+/// Read `input`, which must not be empty, at a fractional index with `hermite`.
+pub fn read_hermite(input: &[f32], position: f32) -> f32 {
+    let (y, frac) = points(input, position);
+    hermite(y, frac)
+}
+
+/// Read `input`, which must not be empty, at a fractional index with `lagrange`.
+pub fn read_lagrange(input: &[f32], position: f32) -> f32 {
+    let (y, frac) = points(input, position);
+    lagrange(y, frac)
+}
+
+/// Read `input`, which must not be empty, at a fractional index with `windowed_sinc` over
+/// `half_width` samples each side, at most 32.
+pub fn read_sinc(input: &[f32], position: f32, half_width: usize) -> f32 {
+    const MAX_HALF_WIDTH: usize = 32;
+    let half_width = half_width.clamp(1, MAX_HALF_WIDTH);
+    let (whole, frac) = split(position);
+    let mut y = [0.0; 2 * MAX_HALF_WIDTH];
+    let y = &mut y[..2 * half_width];
+    for (i, y) in y.iter_mut().enumerate() {
+        *y = get(input, whole + i as isize + 1 - half_width as isize);
+    }
+    windowed_sinc(y, frac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delay::DelayLine;
+
+    #[test]
+    fn test_polynomials() {
+        // Each order is exact for polynomials up to its degree.
+        let line: [f32; 8] = core::array::from_fn(|i| 2.0 * i as f32 - 3.0);
+        let quadratic: [f32; 8] = core::array::from_fn(|i| (i * i) as f32);
+        let cubic: [f32; 8] = core::array::from_fn(|i| (i * i * i) as f32 - (i * i) as f32);
+        for &position in [1.0, 2.25, 3.5, 5.9].iter() {
+            assert!((read_linear(&line, position) - (2.0 * position - 3.0)).abs() < 1e-4);
+            assert!((read_hermite(&quadratic, position) - position * position).abs() < 1e-4);
+            let expected = position * position * position - position * position;
+            assert!((read_lagrange(&cubic, position) - expected).abs() < 1e-3);
+        }
+
+        // Past the ends holds the first and last samples.
+        assert_eq!(read_linear(&line, -2.0), -3.0);
+        assert_eq!(read_hermite(&line, 20.0), 11.0);
+    }
+
+    #[test]
+    fn test_sinc() {
+        let sine: [f32; 128] = core::array::from_fn(|i| (0.3 * i as f32).sin());
+        // Whole positions return the samples.
+        assert!((read_sinc(&sine, 40.0, 8) - sine[40]).abs() < 1e-5);
+        for &position in [40.3, 64.5, 80.75].iter() {
+            let expected = (0.3_f32 * position).sin();
+            assert!((read_sinc(&sine, position, 16) - expected).abs() < 2e-3);
+            assert!((read_hermite(&sine, position) - expected).abs() < 2e-3);
+        }
+    }
+
+    #[test]
+    fn test_delay_line_reads() {
+        let mut buffer = [0.0; 64];
+        let mut delay_line = DelayLine::new(&mut buffer);
+        for i in 0..100 {
+            delay_line.write((0.2 * i as f32).sin());
+        }
+        // The newest sample is i = 99, `delay` samples back is at 100 - delay.
+        for &delay in [1.0, 4.5, 10.25, 40.8].iter() {
+            let expected = (0.2_f32 * (100.0 - delay)).sin();
+            assert!((delay_line.read_delayed_hermite(delay) - expected).abs() < 1e-3);
+            assert!((delay_line.read_delayed_lagrange(delay) - expected).abs() < 1e-3);
+        }
+    }
+}
@@ -1,37 +1,177 @@
 #![cfg_attr(not(test), no_std)]
+#[cfg(feature = "alloc")]
+extern crate alloc;
+// InstantCounter needs std on hosted targets.
+#[cfg(all(feature = "bench", not(test), not(target_os = "none")))]
+extern crate std;
+
+// Trace level event log over defmt, compiled out without the `defmt` feature.
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "defmt")]
+        defmt::trace!($($arg)*);
+    };
+}
+
+// Float math through math::F32Ext on no_std targets. Builds with std in the dependency graph,
+// tests included, resolve to the inherent f32 methods instead and leave the import unused.
+macro_rules! float_math {
+    () => {
+        #[allow(unused_imports)]
+        use crate::math::F32Ext;
+    };
+}
+
+pub mod analysis;
+#[cfg(feature = "bench")]
+pub mod bench;
+pub mod block;
+pub mod cabinet;
+#[cfg(all(feature = "cmsis", target_arch = "arm"))]
+mod cmsis;
+pub mod control;
+pub mod convert;
+pub mod cordic;
+pub mod distortion;
+pub mod dynamics;
+pub mod echo;
+pub mod error;
+pub mod fft;
 pub mod filter;
+#[cfg(feature = "fixed")]
+pub mod fixed;
+pub mod frame;
+pub mod interp;
+pub mod looper;
+mod math;
+pub mod measure;
+pub mod meter;
+pub mod mix;
+pub mod modulation;
+pub mod pitch;
+pub mod preset;
+pub mod processor;
+pub mod random;
+pub mod resample;
 pub mod reverb;
+pub mod sample;
+pub mod sigma_delta;
+pub mod spectral;
+pub mod stereo;
 pub mod synthesis;
+pub mod tuning;
+pub mod units;
+pub mod vocoder;
+pub mod voice;
+pub mod windows;
 
 pub mod delay {
-    use core::ops::{Index, IndexMut};
+    #[cfg(feature = "alloc")]
+    use alloc::{boxed::Box, vec};
+    use core::ops::{Deref, DerefMut, Index, IndexMut};
+
+    use crate::error::{Error, Result};
+    use crate::sample::Sample;
+
+    // Storage of a delay line, borrowed, boxed with the `alloc` feature or held in a
+    // heapless::Vec of capacity `N` with the `heapless` feature.
+    enum Buffer<'a, T, const N: usize> {
+        Borrowed(&'a mut [T]),
+        #[cfg(feature = "alloc")]
+        Owned(Box<[T]>),
+        #[cfg(feature = "heapless")]
+        Heapless(heapless::Vec<T, N>),
+    }
+
+    impl<T, const N: usize> Deref for Buffer<'_, T, N> {
+        type Target = [T];
+
+        fn deref(&self) -> &[T] {
+            match self {
+                Buffer::Borrowed(inner) => inner,
+                #[cfg(feature = "alloc")]
+                Buffer::Owned(inner) => inner,
+                #[cfg(feature = "heapless")]
+                Buffer::Heapless(inner) => inner,
+            }
+        }
+    }
+
+    impl<T, const N: usize> DerefMut for Buffer<'_, T, N> {
+        fn deref_mut(&mut self) -> &mut [T] {
+            match self {
+                Buffer::Borrowed(inner) => inner,
+                #[cfg(feature = "alloc")]
+                Buffer::Owned(inner) => inner,
+                #[cfg(feature = "heapless")]
+                Buffer::Heapless(inner) => inner,
+            }
+        }
+    }
 
-    pub struct DelayLine<'a> {
-        inner: &'a mut [f32],
+    /// Circular buffer of samples. Delay lines borrow their buffer, usually a static array, or
+    /// own it, boxed with the `alloc` feature or in a `heapless::Vec` with the `heapless`
+    /// feature. Effects built on delay lines such as the all-pass filters, FIRs and echoes then
+    /// have a `'static` lifetime. `N` is the capacity of a heapless buffer and stays 0 otherwise.
+    pub struct DelayLine<'a, T: Sample = f32, const N: usize = 0> {
+        inner: Buffer<'a, T, N>,
         index: usize,
     }
 
-    impl<'a> DelayLine<'a> {
-        pub fn new(inner: &'a mut [f32]) -> DelayLine {
-            DelayLine { inner, index: 0 }
+    impl<'a, T: Sample> DelayLine<'a, T> {
+        /// The buffer must not be empty, see `try_new` to check it.
+        pub const fn new(inner: &'a mut [T]) -> DelayLine<'a, T> {
+            DelayLine {
+                inner: Buffer::Borrowed(inner),
+                index: 0,
+            }
         }
 
-        pub fn process(&mut self, input: f32) -> f32 {
+        pub fn try_new(inner: &'a mut [T]) -> Result<DelayLine<'a, T>> {
+            if inner.is_empty() {
+                return Err(Error::BufferTooSmall);
+            }
+            Ok(Self::new(inner))
+        }
+    }
+
+    impl<T: Sample, const N: usize> DelayLine<'_, T, N> {
+        pub fn process(&mut self, input: T) -> T {
             let output = self.inner[self.index];
             self.index = (self.index + 1) % self.inner.len();
             self.inner[self.index] = input;
             output
         }
 
-        pub fn get(&self, index: usize) -> f32 {
+        pub fn get(&self, index: usize) -> T {
             self.inner[index % self.inner.len()]
         }
 
-        pub fn read(&self) -> f32 {
+        pub fn read(&self) -> T {
             self.inner[self.index]
         }
 
-        pub fn write(&mut self, input: f32) {
+        /// Read the sample written `delay` samples ago, clamped to [1, len].
+        /// A delay of 1 returns the most recently written sample.
+        pub fn tap(&self, delay: usize) -> T {
+            let len = self.inner.len();
+            let delay = delay.clamp(1, len);
+            self.inner[(self.index + len - delay) % len]
+        }
+
+        /// Read the sample written `delay` samples ago, linearly interpolating fractional delays.
+        /// A delay of 1.0 returns the most recently written sample.
+        pub fn read_delayed(&self, delay: f32) -> T {
+            let len = self.inner.len();
+            let delay = delay.clamp(1.0, (len.max(2) - 1) as f32);
+            let whole = delay as usize;
+            let frac = delay - whole as f32;
+            let a = self.inner[(self.index + 2 * len - whole) % len];
+            let b = self.inner[(self.index + 2 * len - whole - 1) % len];
+            a.lerp(b, frac)
+        }
+
+        pub fn write(&mut self, input: T) {
             self.inner[self.index] = input;
             self.index = (self.index + 1) % self.inner.len();
         }
@@ -39,19 +179,148 @@ pub mod delay {
         pub fn len(&self) -> usize {
             self.inner.len()
         }
+
+        pub fn is_empty(&self) -> bool {
+            self.inner.is_empty()
+        }
+
+        /// The whole buffer in memory order, for processors that keep their own write position.
+        pub fn as_slice(&self) -> &[T] {
+            &self.inner
+        }
+
+        /// Fill the buffer with silence.
+        pub fn reset(&mut self) {
+            self.inner.fill(T::EQUILIBRIUM);
+            self.index = 0;
+        }
     }
 
-    impl Index<usize> for DelayLine<'_> {
-        type Output = f32;
+    impl<const N: usize> DelayLine<'_, f32, N> {
+        /// Like `read_delayed` with cubic Hermite interpolation, smoother for modulated delays.
+        pub fn read_delayed_hermite(&self, delay: f32) -> f32 {
+            let (y, frac) = self.delayed_points(delay);
+            crate::interp::hermite(y, frac)
+        }
+
+        /// Like `read_delayed` with third order Lagrange interpolation.
+        pub fn read_delayed_lagrange(&self, delay: f32) -> f32 {
+            let (y, frac) = self.delayed_points(delay);
+            crate::interp::lagrange(y, frac)
+        }
+
+        // Four samples around `delay` from newest to oldest, `tap` holds the ends.
+        fn delayed_points(&self, delay: f32) -> ([f32; 4], f32) {
+            let delay = delay.clamp(1.0, (self.inner.len().max(2) - 1) as f32);
+            let whole = delay as usize;
+            let y = [
+                self.tap(whole - 1),
+                self.tap(whole),
+                self.tap(whole + 1),
+                self.tap(whole + 2),
+            ];
+            (y, delay - whole as f32)
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    impl<T: Sample> DelayLine<'static, T> {
+        /// Own a boxed buffer, failing if it's empty.
+        pub fn from_box(inner: Box<[T]>) -> Result<DelayLine<'static, T>> {
+            if inner.is_empty() {
+                return Err(Error::BufferTooSmall);
+            }
+            Ok(DelayLine {
+                inner: Buffer::Owned(inner),
+                index: 0,
+            })
+        }
+
+        /// Allocate a buffer of `len` samples of silence, failing if `len` is zero.
+        pub fn with_len(len: usize) -> Result<DelayLine<'static, T>> {
+            Self::from_box(vec![T::EQUILIBRIUM; len].into_boxed_slice())
+        }
+    }
+
+    #[cfg(feature = "heapless")]
+    impl<T: Sample, const N: usize> DelayLine<'static, T, N> {
+        /// Own a heapless vector, filled to its capacity with silence. Fails if `N` is 0.
+        pub fn from_heapless(mut inner: heapless::Vec<T, N>) -> Result<DelayLine<'static, T, N>> {
+            if N == 0 {
+                return Err(Error::BufferTooSmall);
+            }
+            // Can't fail when resizing up to the capacity.
+            let _ = inner.resize(N, T::EQUILIBRIUM);
+            Ok(DelayLine {
+                inner: Buffer::Heapless(inner),
+                index: 0,
+            })
+        }
+    }
+
+    impl<T: Sample, const N: usize> Index<usize> for DelayLine<'_, T, N> {
+        type Output = T;
 
         fn index(&self, index: usize) -> &Self::Output {
             &self.inner[index]
         }
     }
 
-    impl IndexMut<usize> for DelayLine<'_> {
+    impl<T: Sample, const N: usize> IndexMut<usize> for DelayLine<'_, T, N> {
         fn index_mut(&mut self, index: usize) -> &mut Self::Output {
             &mut self.inner[index]
         }
     }
+
+    #[cfg(all(test, any(feature = "alloc", feature = "heapless")))]
+    mod tests {
+        use super::*;
+        use crate::filter::Fir;
+
+        #[cfg(feature = "alloc")]
+        #[test]
+        fn test_owned_delay_line() {
+            assert_eq!(
+                DelayLine::<f32>::with_len(0).err(),
+                Some(Error::BufferTooSmall)
+            );
+            let mut owned = DelayLine::with_len(4).unwrap();
+            let mut buffer = [0.0; 4];
+            let mut borrowed = DelayLine::new(&mut buffer);
+            for x in [1.0, 2.0, 3.0, 4.0, 5.0].iter() {
+                assert_eq!(owned.process(*x), borrowed.process(*x));
+            }
+            assert_eq!(owned.tap(2), borrowed.tap(2));
+
+            // Effects taking a delay line become 'static.
+            static COEFS: [f32; 2] = [0.5, 0.5];
+            let mut fir: Fir<'static> = Fir::new(&COEFS, DelayLine::with_len(2).unwrap());
+            assert_eq!(fir.process(1.0), 0.5);
+            assert_eq!(fir.process(1.0), 1.0);
+        }
+
+        #[cfg(feature = "heapless")]
+        #[test]
+        fn test_heapless_delay_line() {
+            let empty: heapless::Vec<f32, 0> = heapless::Vec::new();
+            assert_eq!(
+                DelayLine::from_heapless(empty).err(),
+                Some(Error::BufferTooSmall)
+            );
+            let mut owned = DelayLine::from_heapless(heapless::Vec::<f32, 4>::new()).unwrap();
+            assert_eq!(owned.len(), 4);
+            let mut buffer = [0.0; 4];
+            let mut borrowed = DelayLine::new(&mut buffer);
+            for x in [1.0, 2.0, 3.0, 4.0, 5.0].iter() {
+                assert_eq!(owned.process(*x), borrowed.process(*x));
+            }
+            assert_eq!(owned.tap(2), borrowed.tap(2));
+
+            static COEFS: [f32; 2] = [0.5, 0.5];
+            let delay_line = DelayLine::from_heapless(heapless::Vec::<f32, 2>::new()).unwrap();
+            let mut fir: Fir<'static, 2> = Fir::new(&COEFS, delay_line);
+            assert_eq!(fir.process(1.0), 0.5);
+            assert_eq!(fir.process(1.0), 1.0);
+        }
+    }
 }
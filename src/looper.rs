@@ -0,0 +1,631 @@
+use crate::delay::DelayLine;
+use crate::processor::Processor;
+use crate::units::bpm_to_samples;
+
+// Default length of the loop point crossfade and the play and overdub ramps, in seconds.
+const FADE_TIME: f32 = 0.01;
+
+/// What a looper is doing.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum LooperState {
+    /// No loop recorded.
+    Empty,
+    /// Recording the first layer, which sets the loop length.
+    Recording,
+    Playing,
+    /// Playing while adding the input to the loop.
+    Overdubbing,
+    /// Loop kept but silent, playing starts again from the top.
+    Stopped,
+}
+
+// Linear ramp of `value` towards `target`.
+fn approach(value: f32, target: f32, step: f32) -> f32 {
+    if value < target {
+        (value + step).min(target)
+    } else {
+        (value - step).max(target)
+    }
+}
+
+/// Loop recorder over a delay line's buffer, whose length bounds the loop. The input is always
+/// passed through with the loop mixed in. When the first layer closes, the input that follows is
+/// crossfaded into the top of the loop so the loop point doesn't click, and overdubs and stops
+/// are ramped. With a second buffer at least as long, the last overdub can be undone.
+///
+/// The loop length can be synced to a tempo, rounding it up to whole bars. Call `restart` on
+/// the downbeats of an external clock to keep the loop aligned with it.
+pub struct Looper<'a> {
+    sample_rate: f32,
+    buffer: DelayLine<'a>,
+    undo: Option<DelayLine<'a>>,
+    max_length: usize,
+    state: LooperState,
+    length: usize,
+    position: usize,
+    // Where the first layer ends and what follows it, when waiting for a synced length.
+    record_end: usize,
+    after_record: LooperState,
+    fade: usize,
+    // Length of the loop point crossfade during the first pass, 0 after.
+    tail: usize,
+    play_gain: f32,
+    dub_gain: f32,
+    feedback: f32,
+    level: f32,
+    undo_start: usize,
+    undo_count: usize,
+    tempo: f32,
+    sync_beats: u32,
+}
+
+impl<'a> Looper<'a> {
+    /// Looper without undo.
+    pub fn new(sample_rate: f32, buffer: DelayLine<'a>) -> Self {
+        Self::build(sample_rate, buffer, None)
+    }
+
+    /// Looper keeping the loop before the last overdub in `undo`, which should be as long as
+    /// `buffer`. The longest loop is the shorter of the two.
+    pub fn with_undo(sample_rate: f32, buffer: DelayLine<'a>, undo: DelayLine<'a>) -> Self {
+        Self::build(sample_rate, buffer, Some(undo))
+    }
+
+    fn build(sample_rate: f32, buffer: DelayLine<'a>, undo: Option<DelayLine<'a>>) -> Self {
+        let max_length = undo
+            .as_ref()
+            .map_or(buffer.len(), |undo| undo.len().min(buffer.len()));
+        let mut looper = Self {
+            sample_rate,
+            buffer,
+            undo,
+            max_length,
+            state: LooperState::Empty,
+            length: 0,
+            position: 0,
+            record_end: max_length,
+            after_record: LooperState::Playing,
+            fade: 1,
+            tail: 0,
+            play_gain: 0.0,
+            dub_gain: 0.0,
+            feedback: 1.0,
+            level: 1.0,
+            undo_start: 0,
+            undo_count: 0,
+            tempo: 120.0,
+            sync_beats: 0,
+        };
+        looper.set_fade_time(FADE_TIME);
+        looper
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        match self.state {
+            LooperState::Empty => return input,
+            LooperState::Recording => {
+                self.buffer[self.position] = input;
+                self.position += 1;
+                if self.position >= self.record_end {
+                    self.close_loop(self.after_record);
+                }
+                return input;
+            }
+            _ => {}
+        }
+
+        let step = 1.0 / self.fade as f32;
+        let playing = matches!(self.state, LooperState::Playing | LooperState::Overdubbing);
+        let overdubbing = self.state == LooperState::Overdubbing;
+        self.play_gain = approach(self.play_gain, if playing { 1.0 } else { 0.0 }, step);
+        self.dub_gain = approach(self.dub_gain, if overdubbing { 1.0 } else { 0.0 }, step);
+        if self.play_gain == 0.0 && self.dub_gain == 0.0 && self.tail == 0 {
+            return input;
+        }
+
+        let old = self.buffer[self.position];
+        // On the first pass the top of the loop fades in over the input that followed it.
+        let (base, loop_gain) = if self.position < self.tail {
+            let gain = self.position as f32 / self.tail as f32;
+            (old * gain + input * (1.0 - gain), gain)
+        } else {
+            (old, 1.0)
+        };
+        let output = input + old * loop_gain * self.play_gain * self.level;
+
+        if self.dub_gain > 0.0 {
+            if self.undo_count < self.length {
+                if let Some(undo) = &mut self.undo {
+                    undo[self.position] = base;
+                    self.undo_count += 1;
+                }
+            }
+            let decay = 1.0 - self.dub_gain * (1.0 - self.feedback);
+            self.buffer[self.position] = base * decay + input * self.dub_gain * loop_gain;
+        } else if self.position < self.tail {
+            self.buffer[self.position] = base;
+        }
+
+        self.position += 1;
+        if self.position >= self.length {
+            self.position = 0;
+            self.tail = 0;
+        }
+        output
+    }
+
+    pub fn process_block(&mut self, block: &mut [f32]) {
+        for sample in block.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
+
+    /// Start recording a new loop, discarding the current one.
+    pub fn record(&mut self) {
+        self.clear();
+        self.state = LooperState::Recording;
+    }
+
+    /// Play the loop, closing the first layer or ending an overdub.
+    pub fn play(&mut self) {
+        self.change_state(LooperState::Playing);
+    }
+
+    /// Add the input to the loop, closing the first layer if recording.
+    pub fn overdub(&mut self) {
+        if self.state != LooperState::Overdubbing {
+            self.change_state(LooperState::Overdubbing);
+        }
+    }
+
+    /// Silence the loop, closing the first layer if recording.
+    pub fn stop(&mut self) {
+        self.change_state(LooperState::Stopped);
+    }
+
+    fn change_state(&mut self, state: LooperState) {
+        match self.state {
+            LooperState::Empty => {}
+            LooperState::Recording => {
+                self.after_record = state;
+                self.record_end = self.synced_length(self.position);
+                if self.position >= self.record_end {
+                    self.close_loop(state);
+                }
+            }
+            _ => self.enter(state),
+        }
+    }
+
+    fn enter(&mut self, state: LooperState) {
+        if self.state == LooperState::Stopped && self.play_gain == 0.0 {
+            self.position = 0;
+        }
+        if state == LooperState::Overdubbing {
+            self.start_undo();
+        }
+        self.state = state;
+    }
+
+    // Round a recorded length up to whole sync lengths, when it fits.
+    fn synced_length(&self, length: usize) -> usize {
+        let length = length.max(1);
+        if self.sync_beats == 0 {
+            return length;
+        }
+        let beat = bpm_to_samples(self.tempo, self.sample_rate);
+        let bar = ((self.sync_beats as f32 * beat) as usize).max(1);
+        let synced = length.div_ceil(bar) * bar;
+        if synced <= self.max_length {
+            synced
+        } else {
+            length
+        }
+    }
+
+    fn close_loop(&mut self, state: LooperState) {
+        self.length = self.position.max(1);
+        self.position = 0;
+        self.tail = self.fade.min(self.length / 2);
+        self.play_gain = 0.0;
+        self.dub_gain = 0.0;
+        self.record_end = self.max_length;
+        self.state = LooperState::Stopped;
+        self.enter(state);
+        // The first layer plays at once, the crossfade covers its start.
+        if state != LooperState::Stopped {
+            self.play_gain = 1.0;
+        }
+    }
+
+    fn start_undo(&mut self) {
+        self.undo_start = self.position;
+        self.undo_count = 0;
+    }
+
+    /// Undo the last overdub, calling it again redoes it. While recording the first layer the
+    /// loop is cleared. Does nothing without an undo buffer.
+    pub fn undo(&mut self) {
+        if self.state == LooperState::Recording {
+            self.clear();
+            return;
+        }
+        let undo = match &mut self.undo {
+            Some(undo) => undo,
+            None => return,
+        };
+        if self.state == LooperState::Overdubbing {
+            self.state = LooperState::Playing;
+        }
+        self.dub_gain = 0.0;
+        for i in 0..self.undo_count {
+            let index = (self.undo_start + i) % self.length;
+            core::mem::swap(&mut undo[index], &mut self.buffer[index]);
+        }
+    }
+
+    /// Discard the loop.
+    pub fn clear(&mut self) {
+        self.state = LooperState::Empty;
+        self.length = 0;
+        self.position = 0;
+        self.record_end = self.max_length;
+        self.after_record = LooperState::Playing;
+        self.tail = 0;
+        self.play_gain = 0.0;
+        self.dub_gain = 0.0;
+        self.undo_count = 0;
+    }
+
+    /// Jump back to the top of the loop. An overdub in progress can only be undone from here.
+    pub fn restart(&mut self) {
+        if self.length == 0 {
+            return;
+        }
+        self.position = 0;
+        self.tail = 0;
+        if self.dub_gain > 0.0 || self.state == LooperState::Overdubbing {
+            self.start_undo();
+        }
+    }
+
+    /// Set how much of the loop is kept on each overdub pass, [0-1].
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.feedback = feedback.clamp(0.0, 1.0);
+    }
+
+    /// Set the playback level of the loop, linear.
+    pub fn set_level(&mut self, level: f32) {
+        self.level = level;
+    }
+
+    /// Set the length of the loop point crossfade and play and overdub ramps, in seconds.
+    pub fn set_fade_time(&mut self, fade_time: f32) {
+        self.fade = ((fade_time * self.sample_rate) as usize).max(1);
+    }
+
+    /// Set the tempo in beats per minute used to sync the loop length.
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.tempo = bpm.max(1.0);
+    }
+
+    /// Round the loop length up to a multiple of `beats` at the tempo, recording continues
+    /// until it's reached. 0 turns sync off.
+    pub fn set_sync_beats(&mut self, beats: u32) {
+        self.sync_beats = beats;
+    }
+
+    pub fn get_state(&self) -> LooperState {
+        self.state
+    }
+
+    /// Get the loop length in samples, 0 until the first layer closes.
+    pub fn get_length(&self) -> usize {
+        self.length
+    }
+
+    /// Get the playback position in samples from the top of the loop.
+    pub fn get_position(&self) -> usize {
+        self.position
+    }
+
+    /// Get the longest loop in samples.
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    /// Discard the loop.
+    pub fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+impl Processor for Looper<'_> {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_looper() {
+        let mut buffer = [0.0; 2000];
+        let mut undo_buffer = [0.0; 2000];
+        let mut looper = Looper::with_undo(
+            1000.0,
+            DelayLine::new(&mut buffer),
+            DelayLine::new(&mut undo_buffer),
+        );
+
+        // A 500 sample loop of 1.0, the input following it crossfaded into the top.
+        looper.record();
+        for _ in 0..500 {
+            assert_eq!(looper.process(1.0), 1.0);
+        }
+        looper.play();
+        assert_eq!(looper.get_length(), 500);
+        for _ in 0..10 {
+            looper.process(1.0);
+        }
+        for _ in 0..490 {
+            assert_eq!(looper.process(0.0), 1.0);
+        }
+        for _ in 0..500 {
+            assert!((looper.process(0.0) - 1.0).abs() < 1e-6);
+        }
+
+        // Overdub one pass of 0.5, then undo and redo it.
+        looper.overdub();
+        for _ in 0..500 {
+            looper.process(0.5);
+        }
+        looper.play();
+        for _ in 0..750 {
+            looper.process(0.0);
+        }
+        assert!((looper.process(0.0) - 1.5).abs() < 1e-6);
+        looper.undo();
+        assert!((looper.process(0.0) - 1.0).abs() < 1e-6);
+        looper.undo();
+        assert!((looper.process(0.0) - 1.5).abs() < 1e-6);
+
+        // Stopping ramps out and playing starts from the top.
+        looper.stop();
+        for _ in 0..10 {
+            looper.process(0.0);
+        }
+        assert_eq!(looper.process(0.0), 0.0);
+        looper.play();
+        assert_eq!(looper.get_position(), 0);
+
+        // Synced to two beats at 120 BPM recording continues to 1000 samples.
+        looper.set_sync_beats(2);
+        looper.record();
+        for _ in 0..700 {
+            looper.process(1.0);
+        }
+        looper.play();
+        assert_eq!(looper.get_state(), LooperState::Recording);
+        for _ in 0..300 {
+            looper.process(1.0);
+        }
+        assert_eq!(looper.get_state(), LooperState::Playing);
+        assert_eq!(looper.get_length(), 1000);
+
+        // Recording stops at the longest loop.
+        looper.set_sync_beats(0);
+        looper.record();
+        for _ in 0..2000 {
+            looper.process(1.0);
+        }
+        assert_eq!(looper.get_state(), LooperState::Playing);
+        assert_eq!(looper.get_length(), looper.max_length());
+    }
+
+    // Record `len` samples of `value` on a fresh loop and play it back.
+    fn record_loop(looper: &mut Looper, len: usize, value: f32) {
+        looper.record();
+        for _ in 0..len {
+            looper.process(value);
+        }
+        looper.play();
+    }
+
+    // Process `len` samples of silence, returning the last output.
+    fn run(looper: &mut Looper, len: usize) -> f32 {
+        let mut output = 0.0;
+        for _ in 0..len {
+            output = looper.process(0.0);
+        }
+        output
+    }
+
+    #[test]
+    fn test_looper_empty() {
+        let mut buffer = [0.0; 100];
+        let mut looper = Looper::new(1000.0, DelayLine::new(&mut buffer));
+        assert_eq!(looper.max_length(), 100);
+        // Nothing to play, stop, overdub, undo or restart.
+        looper.play();
+        looper.overdub();
+        looper.stop();
+        looper.undo();
+        looper.restart();
+        assert_eq!(looper.get_state(), LooperState::Empty);
+        assert_eq!(looper.get_length(), 0);
+        assert_eq!(looper.process(0.25), 0.25);
+        let mut block = [0.5; 4];
+        looper.process_block(&mut block);
+        assert_eq!(block, [0.5; 4]);
+
+        // The shorter buffer bounds the loop.
+        let (mut buffer, mut undo) = ([0.0; 100], [0.0; 60]);
+        let looper = Looper::with_undo(
+            1000.0,
+            DelayLine::new(&mut buffer),
+            DelayLine::new(&mut undo),
+        );
+        assert_eq!(looper.max_length(), 60);
+    }
+
+    #[test]
+    fn test_looper_stop_while_recording() {
+        let mut buffer = [0.0; 200];
+        let mut looper = Looper::new(1000.0, DelayLine::new(&mut buffer));
+        looper.record();
+        for _ in 0..100 {
+            looper.process(1.0);
+        }
+        // The loop closes silent and only the input passes.
+        looper.stop();
+        assert_eq!(looper.get_state(), LooperState::Stopped);
+        assert_eq!(looper.get_length(), 100);
+        for _ in 0..100 {
+            assert_eq!(looper.process(0.25), 0.25);
+        }
+        looper.play();
+        assert_eq!(looper.get_position(), 0);
+        assert_eq!(run(&mut looper, 51), 1.0);
+    }
+
+    #[test]
+    fn test_looper_level_and_feedback() {
+        let mut buffer = [0.0; 200];
+        let mut looper = Looper::new(1000.0, DelayLine::new(&mut buffer));
+        record_loop(&mut looper, 100, 1.0);
+        run(&mut looper, 100);
+        looper.set_level(0.5);
+        assert_eq!(run(&mut looper, 51), 0.5);
+
+        // An overdub pass of silence at half feedback halves the loop.
+        run(&mut looper, 49);
+        looper.set_feedback(0.5);
+        looper.overdub();
+        run(&mut looper, 100);
+        looper.play();
+        assert!((run(&mut looper, 51) - 0.25).abs() < 1e-6);
+
+        // Feedback is clamped, so the loop never grows.
+        looper.set_feedback(2.0);
+        run(&mut looper, 49);
+        looper.overdub();
+        run(&mut looper, 100);
+        looper.play();
+        assert!((run(&mut looper, 51) - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_looper_overdub_while_recording() {
+        let mut buffer = [0.0; 200];
+        let mut looper = Looper::new(1000.0, DelayLine::new(&mut buffer));
+        looper.record();
+        for _ in 0..100 {
+            looper.process(1.0);
+        }
+        looper.overdub();
+        assert_eq!(looper.get_state(), LooperState::Overdubbing);
+        assert_eq!(looper.get_length(), 100);
+        for _ in 0..100 {
+            looper.process(0.5);
+        }
+        looper.play();
+        assert!((run(&mut looper, 51) - 1.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_looper_undo() {
+        // Undo while recording discards the loop.
+        let mut buffer = [0.0; 200];
+        let mut looper = Looper::new(1000.0, DelayLine::new(&mut buffer));
+        looper.record();
+        looper.process(1.0);
+        looper.undo();
+        assert_eq!(looper.get_state(), LooperState::Empty);
+
+        // Without an undo buffer an overdub can't be undone.
+        record_loop(&mut looper, 100, 1.0);
+        run(&mut looper, 100);
+        looper.overdub();
+        for _ in 0..100 {
+            looper.process(0.5);
+        }
+        looper.undo();
+        assert_eq!(looper.get_state(), LooperState::Overdubbing);
+        looper.play();
+        assert!((run(&mut looper, 51) - 1.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_looper_restart() {
+        let mut buffer = [0.0; 200];
+        let mut undo_buffer = [0.0; 200];
+        let mut looper = Looper::with_undo(
+            1000.0,
+            DelayLine::new(&mut buffer),
+            DelayLine::new(&mut undo_buffer),
+        );
+        record_loop(&mut looper, 100, 1.0);
+        run(&mut looper, 130);
+        assert_eq!(looper.get_position(), 30);
+        looper.restart();
+        assert_eq!(looper.get_position(), 0);
+
+        // Restarting mid overdub starts a new undo from the top, so undo only removes what was
+        // added after the restart.
+        run(&mut looper, 50);
+        looper.overdub();
+        for _ in 0..30 {
+            looper.process(0.5);
+        }
+        looper.restart();
+        for _ in 0..20 {
+            looper.process(0.5);
+        }
+        looper.undo();
+        assert_eq!(looper.get_state(), LooperState::Playing);
+        // The top of the loop is back to the recording, the overdub before the restart stays.
+        assert_eq!(looper.get_position(), 20);
+        assert!((run(&mut looper, 30) - 1.0).abs() < 1e-6);
+        assert!((run(&mut looper, 21) - 1.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_looper_sync_too_long() {
+        // Four beats at 60 BPM don't fit, so the loop keeps its recorded length.
+        let mut buffer = [0.0; 2000];
+        let mut looper = Looper::new(1000.0, DelayLine::new(&mut buffer));
+        looper.set_tempo(60.0);
+        looper.set_sync_beats(4);
+        record_loop(&mut looper, 300, 1.0);
+        assert_eq!(looper.get_state(), LooperState::Playing);
+        assert_eq!(looper.get_length(), 300);
+    }
+
+    #[test]
+    fn test_looper_fade_time_and_reset() {
+        let mut buffer = [0.0; 200];
+        let mut looper = Looper::new(1000.0, DelayLine::new(&mut buffer));
+        looper.set_fade_time(0.05);
+        record_loop(&mut looper, 200, 1.0);
+        run(&mut looper, 200);
+        // Stopping ramps out over 50 samples.
+        looper.stop();
+        assert!(run(&mut looper, 25) > 0.0);
+        assert_eq!(run(&mut looper, 26), 0.0);
+
+        Processor::reset(&mut looper);
+        assert_eq!(looper.get_state(), LooperState::Empty);
+        assert_eq!(looper.get_length(), 0);
+        assert_eq!(looper.process(0.25), 0.25);
+    }
+}
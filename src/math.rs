@@ -0,0 +1,288 @@
+//! Transcendental math for no_std targets, with the backend chosen by feature.
+//! `micromath` is fast with errors around 1e-3, `libm` is accurate to within an ulp or two and
+//! takes precedence when both are enabled. `tables` replaces sin and cos with an interpolated
+//! lookup table, accurate to about 5e-6, on top of either backend.
+
+// Hosted builds resolve to the inherent std methods, leaving the trait unused outside tests.
+#![allow(dead_code)]
+
+#[cfg(not(any(feature = "micromath", feature = "libm")))]
+compile_error!("enable the `micromath` or `libm` feature to select a math backend");
+
+/// Math methods for f32 on no_std targets, mirroring the inherent methods std provides.
+pub(crate) trait F32Ext {
+    fn sin(self) -> f32;
+    fn cos(self) -> f32;
+    fn tan(self) -> f32;
+    fn atan2(self, other: f32) -> f32;
+    fn exp(self) -> f32;
+    fn ln(self) -> f32;
+    fn log10(self) -> f32;
+    fn powf(self, n: f32) -> f32;
+    fn powi(self, n: i32) -> f32;
+    fn sqrt(self) -> f32;
+    fn round(self) -> f32;
+    fn fract(self) -> f32;
+}
+
+impl F32Ext for f32 {
+    fn sin(self) -> f32 {
+        #[cfg(feature = "tables")]
+        {
+            tables::sin(self)
+        }
+        #[cfg(not(feature = "tables"))]
+        {
+            backend::sin(self)
+        }
+    }
+
+    fn cos(self) -> f32 {
+        #[cfg(feature = "tables")]
+        {
+            tables::cos(self)
+        }
+        #[cfg(not(feature = "tables"))]
+        {
+            backend::cos(self)
+        }
+    }
+
+    fn tan(self) -> f32 {
+        backend::tan(self)
+    }
+
+    fn atan2(self, other: f32) -> f32 {
+        backend::atan2(self, other)
+    }
+
+    fn exp(self) -> f32 {
+        backend::exp(self)
+    }
+
+    fn ln(self) -> f32 {
+        backend::ln(self)
+    }
+
+    fn log10(self) -> f32 {
+        backend::log10(self)
+    }
+
+    fn powf(self, n: f32) -> f32 {
+        backend::powf(self, n)
+    }
+
+    fn powi(self, n: i32) -> f32 {
+        backend::powi(self, n)
+    }
+
+    fn sqrt(self) -> f32 {
+        backend::sqrt(self)
+    }
+
+    fn round(self) -> f32 {
+        backend::round(self)
+    }
+
+    fn fract(self) -> f32 {
+        backend::fract(self)
+    }
+}
+
+#[cfg(feature = "libm")]
+mod backend {
+    #[allow(dead_code)]
+    pub fn sin(x: f32) -> f32 {
+        libm::sinf(x)
+    }
+
+    #[allow(dead_code)]
+    pub fn cos(x: f32) -> f32 {
+        libm::cosf(x)
+    }
+
+    pub fn tan(x: f32) -> f32 {
+        libm::tanf(x)
+    }
+
+    pub fn atan2(y: f32, x: f32) -> f32 {
+        libm::atan2f(y, x)
+    }
+
+    pub fn exp(x: f32) -> f32 {
+        libm::expf(x)
+    }
+
+    pub fn ln(x: f32) -> f32 {
+        libm::logf(x)
+    }
+
+    pub fn log10(x: f32) -> f32 {
+        libm::log10f(x)
+    }
+
+    pub fn powf(x: f32, n: f32) -> f32 {
+        libm::powf(x, n)
+    }
+
+    pub fn powi(x: f32, n: i32) -> f32 {
+        libm::powf(x, n as f32)
+    }
+
+    pub fn sqrt(x: f32) -> f32 {
+        libm::sqrtf(x)
+    }
+
+    pub fn round(x: f32) -> f32 {
+        libm::roundf(x)
+    }
+
+    pub fn fract(x: f32) -> f32 {
+        x - libm::truncf(x)
+    }
+}
+
+#[cfg(all(feature = "micromath", not(feature = "libm")))]
+mod backend {
+    use micromath::F32Ext;
+
+    #[allow(dead_code)]
+    pub fn sin(x: f32) -> f32 {
+        F32Ext::sin(x)
+    }
+
+    #[allow(dead_code)]
+    pub fn cos(x: f32) -> f32 {
+        F32Ext::cos(x)
+    }
+
+    pub fn tan(x: f32) -> f32 {
+        F32Ext::tan(x)
+    }
+
+    pub fn atan2(y: f32, x: f32) -> f32 {
+        F32Ext::atan2(y, x)
+    }
+
+    pub fn exp(x: f32) -> f32 {
+        F32Ext::exp(x)
+    }
+
+    pub fn ln(x: f32) -> f32 {
+        F32Ext::ln(x)
+    }
+
+    pub fn log10(x: f32) -> f32 {
+        F32Ext::log10(x)
+    }
+
+    pub fn powf(x: f32, n: f32) -> f32 {
+        F32Ext::powf(x, n)
+    }
+
+    pub fn powi(x: f32, n: i32) -> f32 {
+        F32Ext::powi(x, n)
+    }
+
+    pub fn sqrt(x: f32) -> f32 {
+        F32Ext::sqrt(x)
+    }
+
+    pub fn round(x: f32) -> f32 {
+        F32Ext::round(x)
+    }
+
+    pub fn fract(x: f32) -> f32 {
+        F32Ext::fract(x)
+    }
+}
+
+#[cfg(feature = "tables")]
+mod tables {
+    use core::f64::consts::PI;
+
+    use crate::windows;
+
+    const SINE_TABLE_BITS: u32 = 10;
+    const SINE_TABLE_LEN: usize = 1 << SINE_TABLE_BITS;
+
+    // One period of a sine plus a guard point for interpolation.
+    static SINE_TABLE: [f32; SINE_TABLE_LEN + 1] = {
+        let mut table = [0.0; SINE_TABLE_LEN + 1];
+        let mut i = 0;
+        while i <= SINE_TABLE_LEN {
+            table[i] = windows::cos(2.0 * PI * i as f64 / SINE_TABLE_LEN as f64 - PI / 2.0) as f32;
+            i += 1;
+        }
+        table
+    };
+
+    /// Sine of `x` in periods rather than radians.
+    fn sin_periods(x: f32) -> f32 {
+        // Float to int casts saturate, so huge arguments lose accuracy but stay in range.
+        let mut t = x - x as i32 as f32;
+        if t < 0.0 {
+            t += 1.0;
+        }
+        let position = t * SINE_TABLE_LEN as f32;
+        let index = (position as usize).min(SINE_TABLE_LEN - 1);
+        let frac = position - index as f32;
+        let a = SINE_TABLE[index];
+        let b = SINE_TABLE[index + 1];
+        a + (b - a) * frac
+    }
+
+    pub fn sin(x: f32) -> f32 {
+        sin_periods(x * (0.5 / core::f32::consts::PI))
+    }
+
+    pub fn cos(x: f32) -> f32 {
+        sin_periods(x * (0.5 / core::f32::consts::PI) + 0.25)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_accuracy() {
+        let sin_tolerance = if cfg!(feature = "tables") {
+            1e-5
+        } else if cfg!(feature = "libm") {
+            1e-6
+        } else {
+            2e-3
+        };
+        // The micromath square root is a bit level approximation, off by up to about 6%.
+        let (sqrt_tolerance, log_tolerance) = if cfg!(feature = "libm") {
+            (1e-6, 1e-6)
+        } else {
+            (7e-2, 1e-4)
+        };
+        for i in -1000..1000 {
+            let x = i as f32 * 0.01;
+            assert!(
+                (F32Ext::sin(x) - x.sin()).abs() < sin_tolerance,
+                "sin {}",
+                x
+            );
+            assert!(
+                (F32Ext::cos(x) - x.cos()).abs() < sin_tolerance,
+                "cos {}",
+                x
+            );
+            let x = x.abs() + 0.01;
+            assert!(
+                (F32Ext::sqrt(x) / x.sqrt() - 1.0).abs() < sqrt_tolerance,
+                "sqrt {}",
+                x
+            );
+            assert!(
+                (F32Ext::log10(x) - x.log10()).abs() < log_tolerance,
+                "log10 {}",
+                x
+            );
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! Impulse response measurement with an exponential sine sweep, for a device measuring its own
+//! speaker and room through its microphone, e.g. for automatic EQ. Play the sweep, record the
+//! response including the tail, then `deconvolve` the recording with the same sweep.
+
+use core::f32::consts::PI;
+
+float_math!();
+
+use crate::fft::{Complex, Fft};
+
+// Fade at the end of the sweep so it doesn't stop with a click, in seconds.
+const SWEEP_FADE_TIME: f32 = 0.005;
+// Regularization of the spectral division relative to the peak sweep energy, it keeps bins
+// outside the sweep range from amplifying noise.
+const DECONVOLVE_REGULARIZATION: f32 = 1e-5;
+
+/// Exponential (Farina) sine sweep, spending equal time per octave so it has a pink spectrum and
+/// harmonic distortion lands before the linear response after deconvolution.
+pub struct Sweep {
+    sample_rate: f32,
+    start_freq: f32,
+    len: usize,
+    fade_len: usize,
+    // Frequency multiplier per sample.
+    growth: f32,
+    index: usize,
+    freq: f32,
+    phase: f32,
+}
+
+impl Sweep {
+    /// Sweep from `start_freq` to `end_freq` in Hz over `duration` seconds.
+    pub fn new(sample_rate: f32, start_freq: f32, end_freq: f32, duration: f32) -> Self {
+        let start_freq = start_freq.max(1.0);
+        let end_freq = end_freq.clamp(start_freq, sample_rate * 0.5);
+        let len = ((duration * sample_rate) as usize).max(1);
+        Self {
+            sample_rate,
+            start_freq,
+            len,
+            fade_len: ((SWEEP_FADE_TIME * sample_rate) as usize).min(len),
+            growth: ((end_freq / start_freq).ln() / len as f32).exp(),
+            index: 0,
+            freq: start_freq,
+            phase: 0.0,
+        }
+    }
+
+    /// Next sample of the sweep, silence once it's done.
+    pub fn process(&mut self) -> f32 {
+        if self.index >= self.len {
+            return 0.0;
+        }
+        let fade = ((self.len - self.index) as f32 / self.fade_len.max(1) as f32).min(1.0);
+        let output = (2.0 * PI * self.phase).sin() * fade;
+        self.phase = (self.phase + self.freq / self.sample_rate).fract();
+        self.freq *= self.growth;
+        self.index += 1;
+        output
+    }
+
+    /// Restart and write the whole sweep to the start of `buffer`, silencing the rest.
+    pub fn fill(&mut self, buffer: &mut [f32]) {
+        self.reset();
+        for sample in buffer.iter_mut() {
+            *sample = self.process();
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.index >= self.len
+    }
+
+    /// Length in samples.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn reset(&mut self) {
+        self.index = 0;
+        self.freq = self.start_freq;
+        self.phase = 0.0;
+    }
+}
+
+/// Replace `recording` with the impulse response of the system that turned `sweep` into it, by
+/// dividing their spectra. Both start at the same sample and are zero padded to `N`, which must
+/// fit the sweep plus the length of the response so its tail doesn't wrap around. `sweep` is
+/// overwritten with its spectrum.
+pub fn deconvolve<const N: usize>(fft: &Fft<N>, sweep: &mut [f32; N], recording: &mut [f32; N]) {
+    fft.process_real(sweep);
+    fft.process_real(recording);
+
+    let energy = |re: f32, im: f32| re * re + im * im;
+    let peak = sweep[2..].chunks_exact(2).fold(
+        energy(sweep[0], 0.0).max(energy(sweep[1], 0.0)),
+        |peak, bin| peak.max(energy(bin[0], bin[1])),
+    );
+    let floor = peak * DECONVOLVE_REGULARIZATION;
+
+    // R * conj(S) / (|S|^2 + floor), the DC and Nyquist bins are real.
+    recording[0] *= sweep[0] / (sweep[0] * sweep[0] + floor);
+    recording[1] *= sweep[1] / (sweep[1] * sweep[1] + floor);
+    for (r, s) in recording[2..]
+        .chunks_exact_mut(2)
+        .zip(sweep[2..].chunks_exact(2))
+    {
+        let s = Complex::new(s[0], s[1]);
+        let quotient = Complex::new(r[0], r[1]) * s.conj();
+        let scale = 1.0 / (s.norm_sqr() + floor);
+        r[0] = quotient.re * scale;
+        r[1] = quotient.im * scale;
+    }
+    fft.inverse_real(recording);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sweep() {
+        let mut sweep = Sweep::new(48000.0, 100.0, 10000.0, 0.5);
+        assert_eq!(sweep.len(), 24000);
+        // Half way through in time is half way in octaves, 1 kHz.
+        let samples: Vec<f32> = (0..24001).map(|_| sweep.process()).collect();
+        let crossings = samples[11760..12240]
+            .windows(2)
+            .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+            .count();
+        assert!((9..=11).contains(&crossings), "{}", crossings);
+        assert!(sweep.is_done());
+        assert_eq!(samples[24000], 0.0);
+        assert!(samples[23999].abs() < 0.01);
+    }
+
+    #[test]
+    fn test_deconvolve_recovers_echoes() {
+        const N: usize = 16384;
+        let fft: Fft<N> = Fft::new();
+        let mut sweep = Sweep::new(48000.0, 20.0, 22000.0, 0.25);
+        let mut reference = [0.0; N];
+        sweep.fill(&mut reference);
+
+        // The system under test, a direct sound after 10 samples and a reflection after 30.
+        let mut recording = [0.0; N];
+        for n in 0..N {
+            let delayed = |delay: usize| {
+                if n >= delay {
+                    reference[n - delay]
+                } else {
+                    0.0
+                }
+            };
+            recording[n] = 0.5 * delayed(10) - 0.25 * delayed(30);
+        }
+
+        // The response is band limited to the sweep, so the peaks are about 8% low and ring.
+        deconvolve(&fft, &mut reference, &mut recording);
+        assert!((recording[10] - 0.46).abs() < 0.02, "{}", recording[10]);
+        assert!((recording[30] + 0.23).abs() < 0.02, "{}", recording[30]);
+        for (n, x) in recording.iter().enumerate() {
+            if n != 10 && n != 30 {
+                assert!(x.abs() < 0.06, "{} {}", n, x);
+            }
+        }
+    }
+}
@@ -0,0 +1,776 @@
+float_math!();
+
+use core::f32::consts::PI;
+
+use crate::delay::DelayLine;
+use crate::filter::Biquad;
+use crate::resample::HalfbandInterpolator;
+use crate::units::{db_to_linear, linear_to_db, power_to_db, MIN_DB};
+
+/// RMS level meter, averaging over either a sliding window or an exponential time constant.
+pub struct RmsMeter<'a> {
+    window: Option<DelayLine<'a>>,
+    count: usize,
+    sum: f32,
+    coef: f32,
+    mean_square: f32,
+}
+
+impl<'a> RmsMeter<'a> {
+    /// Average over the last `time` seconds with exponential weighting.
+    pub fn new_exponential(sample_rate: f32, time: f32) -> Self {
+        Self {
+            window: None,
+            count: 0,
+            sum: 0.0,
+            coef: (-1.0 / (time.max(1e-6) * sample_rate)).exp(),
+            mean_square: 0.0,
+        }
+    }
+
+    /// Average over a sliding window the length of the delay line.
+    pub const fn new_sliding(window: DelayLine<'a>) -> Self {
+        Self {
+            window: Some(window),
+            count: 0,
+            sum: 0.0,
+            coef: 0.0,
+            mean_square: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) {
+        let square = input * input;
+        match &mut self.window {
+            Some(window) => {
+                let len = window.len();
+                self.sum += square - window.read();
+                window.write(square);
+                // Re-sum once per window so rounding errors in the running sum can't accumulate.
+                self.count += 1;
+                if self.count >= len {
+                    self.count = 0;
+                    self.sum = (1..=len).map(|delay| window.tap(delay)).sum();
+                }
+                self.mean_square = self.sum.max(0.0) / len as f32;
+            }
+            None => {
+                self.mean_square = square + self.coef * (self.mean_square - square);
+            }
+        }
+    }
+
+    pub fn process_block(&mut self, block: &[f32]) {
+        for sample in block {
+            self.process(*sample);
+        }
+    }
+
+    /// Get the linear RMS level.
+    pub fn get_rms(&self) -> f32 {
+        self.mean_square.sqrt()
+    }
+
+    /// Get the RMS level in dBFS, a full scale square wave reads 0 dB and a full scale sine -3 dB.
+    pub fn get_db(&self) -> f32 {
+        linear_to_db(self.get_rms())
+    }
+
+    /// Clear the average back to silence.
+    pub fn reset(&mut self) {
+        if let Some(window) = &mut self.window {
+            window.reset();
+        }
+        self.count = 0;
+        self.sum = 0.0;
+        self.mean_square = 0.0;
+    }
+}
+
+/// Peak meter with hold and a linear decay in dB per second, for LED and display metering.
+/// Samples at or above full scale are counted as overs until the count is read.
+pub struct PeakMeter {
+    sample_rate: f32,
+    hold: u32,
+    hold_counter: u32,
+    decay: f32,
+    peak: f32,
+    clip_count: u32,
+}
+
+impl PeakMeter {
+    /// Holds peaks for a second, then decays at 20 dB per second.
+    pub const fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            hold: sample_rate as u32,
+            hold_counter: 0,
+            decay: 20.0 / sample_rate,
+            peak: MIN_DB,
+            clip_count: 0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) {
+        let level = input.abs();
+        if level >= 1.0 {
+            self.clip_count = self.clip_count.saturating_add(1);
+        }
+        let level = linear_to_db(level);
+        if level >= self.peak {
+            self.peak = level;
+            self.hold_counter = self.hold;
+        } else if self.hold_counter > 0 {
+            self.hold_counter -= 1;
+        } else {
+            self.peak = (self.peak - self.decay).max(level);
+        }
+    }
+
+    pub fn process_block(&mut self, block: &[f32]) {
+        for sample in block {
+            self.process(*sample);
+        }
+    }
+
+    /// Set the time in seconds a peak is held before decaying.
+    pub fn set_hold(&mut self, hold: f32) {
+        self.hold = (hold.max(0.0) * self.sample_rate) as u32;
+    }
+
+    /// Set the decay rate in dB per second.
+    pub fn set_decay(&mut self, decay: f32) {
+        self.decay = decay.max(0.0) / self.sample_rate;
+    }
+
+    /// Get the displayed peak level in dBFS.
+    pub fn get_db(&self) -> f32 {
+        self.peak
+    }
+
+    /// Get the number of overs since the last call and reset the count.
+    pub fn take_clip_count(&mut self) -> u32 {
+        core::mem::replace(&mut self.clip_count, 0)
+    }
+
+    /// Drop the displayed peak to silence and clear the overs.
+    pub fn reset(&mut self) {
+        self.peak = MIN_DB;
+        self.hold_counter = 0;
+        self.clip_count = 0;
+    }
+}
+
+/// True peak meter, 4x oversamples the input with two halfband interpolators in series to catch
+/// overs between samples that a sample peak meter misses. The interpolated points lag the input
+/// by about 17 samples.
+pub struct TruePeakMeter {
+    // 1x to 2x, then 2x to 4x.
+    first: HalfbandInterpolator,
+    second: HalfbandInterpolator,
+    peak: f32,
+}
+
+impl TruePeakMeter {
+    pub const fn new() -> Self {
+        Self {
+            first: HalfbandInterpolator::new(),
+            second: HalfbandInterpolator::new(),
+            peak: 0.0,
+        }
+    }
+
+    /// Process one sample, returning the linear true peak of the interpolated points.
+    pub fn process(&mut self, input: f32) -> f32 {
+        let mut peak = 0.0_f32;
+        for x in self.first.process(input).iter() {
+            for y in self.second.process(*x).iter() {
+                peak = peak.max(y.abs());
+            }
+        }
+        self.peak = self.peak.max(peak);
+        peak
+    }
+
+    pub fn process_block(&mut self, block: &[f32]) {
+        for sample in block {
+            self.process(*sample);
+        }
+    }
+
+    /// Get the highest true peak since the last reset in dBTP.
+    pub fn get_db(&self) -> f32 {
+        linear_to_db(self.peak)
+    }
+
+    /// Clear the held maximum.
+    pub fn reset(&mut self) {
+        self.peak = 0.0;
+    }
+}
+
+impl Default for TruePeakMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Loudness histogram for the integrated measurement, bins of 0.5 LU from the absolute gate up.
+const ABSOLUTE_GATE: f32 = -70.0;
+const HISTOGRAM_BIN: f32 = 0.5;
+const HISTOGRAM_BINS: usize = 150;
+// 100 ms sub-blocks, four make a momentary block and thirty a short term block.
+const MOMENTARY_BLOCKS: usize = 4;
+const SHORT_TERM_BLOCKS: usize = 30;
+
+fn mean_square_to_lufs(mean_square: f32) -> f32 {
+    if mean_square <= 0.0 {
+        MIN_DB
+    } else {
+        -0.691 + power_to_db(mean_square)
+    }
+}
+
+/// Simplified ITU-R BS.1770 loudness meter for one or two channels.
+/// Momentary (400 ms) and short term (3 s) loudness update every 100 ms. Integrated loudness is
+/// gated as in the standard, using a histogram of block loudness instead of storing every block.
+pub struct LoudnessMeter {
+    shelf: [Biquad; 2],
+    high_pass: [Biquad; 2],
+    hop: usize,
+    counter: usize,
+    accumulator: f32,
+    sub_blocks: [f32; SHORT_TERM_BLOCKS],
+    sub_index: usize,
+    sub_count: usize,
+    histogram_count: [u32; HISTOGRAM_BINS],
+    histogram_energy: [f32; HISTOGRAM_BINS],
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut meter = Self {
+            shelf: [Biquad::new(sample_rate), Biquad::new(sample_rate)],
+            high_pass: [Biquad::new(sample_rate), Biquad::new(sample_rate)],
+            hop: (sample_rate * 0.1) as usize,
+            counter: 0,
+            accumulator: 0.0,
+            sub_blocks: [0.0; SHORT_TERM_BLOCKS],
+            sub_index: 0,
+            sub_count: 0,
+            histogram_count: [0; HISTOGRAM_BINS],
+            histogram_energy: [0.0; HISTOGRAM_BINS],
+        };
+        // K-weighting, the BS.1770 48 kHz filters re-derived for any sample rate.
+        let k = (PI * 1681.9745 / sample_rate).tan();
+        let q = 0.707_175_2;
+        let vh = 10.0_f32.powf(3.999_843_8 / 20.0);
+        let vb = vh.powf(0.499_666_78);
+        let shelf = [
+            vh + vb * k / q + k * k,
+            2.0 * (k * k - vh),
+            vh - vb * k / q + k * k,
+            1.0 + k / q + k * k,
+            2.0 * (k * k - 1.0),
+            1.0 - k / q + k * k,
+        ];
+        let k = (PI * 38.135_47 / sample_rate).tan();
+        let q = 0.500_327;
+        let a = [
+            1.0 + k / q + k * k,
+            2.0 * (k * k - 1.0),
+            1.0 - k / q + k * k,
+        ];
+        for channel in 0..2 {
+            let [b0, b1, b2, a0, a1, a2] = shelf;
+            meter.shelf[channel].set_coefs(b0, b1, b2, a0, a1, a2);
+            meter.high_pass[channel].set_coefs(1.0, -2.0, 1.0, a[0], a[1], a[2]);
+        }
+        meter
+    }
+
+    fn k_weight(&mut self, channel: usize, input: f32) -> f32 {
+        self.high_pass[channel].process(self.shelf[channel].process(input))
+    }
+
+    fn accumulate(&mut self, energy: f32) {
+        self.accumulator += energy;
+        self.counter += 1;
+        if self.counter < self.hop {
+            return;
+        }
+        self.sub_blocks[self.sub_index] = self.accumulator / self.hop as f32;
+        self.sub_index = (self.sub_index + 1) % SHORT_TERM_BLOCKS;
+        self.sub_count = (self.sub_count + 1).min(SHORT_TERM_BLOCKS);
+        self.accumulator = 0.0;
+        self.counter = 0;
+
+        // Each hop completes a 400 ms gating block with 75% overlap.
+        if self.sub_count >= MOMENTARY_BLOCKS {
+            let mean_square = self.mean_square(MOMENTARY_BLOCKS);
+            let loudness = mean_square_to_lufs(mean_square);
+            if loudness > ABSOLUTE_GATE {
+                let bin = ((loudness - ABSOLUTE_GATE) / HISTOGRAM_BIN) as usize;
+                let bin = bin.min(HISTOGRAM_BINS - 1);
+                self.histogram_count[bin] += 1;
+                self.histogram_energy[bin] += mean_square;
+            }
+        }
+    }
+
+    fn mean_square(&self, blocks: usize) -> f32 {
+        let blocks = blocks.min(self.sub_count);
+        if blocks == 0 {
+            return 0.0;
+        }
+        let sum: f32 = (1..=blocks)
+            .map(|i| self.sub_blocks[(self.sub_index + SHORT_TERM_BLOCKS - i) % SHORT_TERM_BLOCKS])
+            .sum();
+        sum / blocks as f32
+    }
+
+    pub fn process(&mut self, input: f32) {
+        let weighted = self.k_weight(0, input);
+        self.accumulate(weighted * weighted);
+    }
+
+    pub fn process_stereo(&mut self, left: f32, right: f32) {
+        let left = self.k_weight(0, left);
+        let right = self.k_weight(1, right);
+        self.accumulate(left * left + right * right);
+    }
+
+    /// Get the momentary loudness in LUFS.
+    pub fn get_momentary(&self) -> f32 {
+        mean_square_to_lufs(self.mean_square(MOMENTARY_BLOCKS))
+    }
+
+    /// Get the short term loudness in LUFS.
+    pub fn get_short_term(&self) -> f32 {
+        mean_square_to_lufs(self.mean_square(SHORT_TERM_BLOCKS))
+    }
+
+    /// Get the gated integrated loudness in LUFS since the last reset.
+    pub fn get_integrated(&self) -> f32 {
+        let gated_mean = |first_bin: usize| {
+            let count: u32 = self.histogram_count[first_bin..].iter().sum();
+            let energy: f32 = self.histogram_energy[first_bin..].iter().sum();
+            if count == 0 {
+                0.0
+            } else {
+                energy / count as f32
+            }
+        };
+        // Relative gate 10 LU below the absolute gated loudness.
+        let relative_gate = mean_square_to_lufs(gated_mean(0)) - 10.0;
+        let first_bin = ((relative_gate - ABSOLUTE_GATE).max(0.0) / HISTOGRAM_BIN) as usize;
+        mean_square_to_lufs(gated_mean(first_bin.min(HISTOGRAM_BINS - 1)))
+    }
+
+    /// Restart the integrated measurement.
+    pub fn reset_integrated(&mut self) {
+        self.histogram_count = [0; HISTOGRAM_BINS];
+        self.histogram_energy = [0.0; HISTOGRAM_BINS];
+    }
+
+    /// Restart every measurement and clear the weighting filters.
+    pub fn reset(&mut self) {
+        for filter in self.shelf.iter_mut().chain(self.high_pass.iter_mut()) {
+            filter.reset();
+        }
+        self.counter = 0;
+        self.accumulator = 0.0;
+        self.sub_blocks = [0.0; SHORT_TERM_BLOCKS];
+        self.sub_index = 0;
+        self.sub_count = 0;
+        self.reset_integrated();
+    }
+}
+
+/// Stereo correlation meter for checking mono compatibility.
+/// Reads +1.0 for identical channels, 0.0 for unrelated channels and -1.0 for channels that
+/// cancel when summed to mono.
+pub struct CorrelationMeter {
+    sample_rate: f32,
+    coef: f32,
+    product: f32,
+    left_power: f32,
+    right_power: f32,
+}
+
+impl CorrelationMeter {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut meter = Self {
+            sample_rate,
+            coef: 0.0,
+            product: 0.0,
+            left_power: 0.0,
+            right_power: 0.0,
+        };
+        meter.set_time(0.3);
+        meter
+    }
+
+    pub fn process(&mut self, left: f32, right: f32) {
+        let coef = self.coef;
+        self.product = left * right + coef * (self.product - left * right);
+        self.left_power = left * left + coef * (self.left_power - left * left);
+        self.right_power = right * right + coef * (self.right_power - right * right);
+    }
+
+    pub fn process_block(&mut self, left: &[f32], right: &[f32]) {
+        for (left, right) in left.iter().zip(right.iter()) {
+            self.process(*left, *right);
+        }
+    }
+
+    /// Set the integration time in seconds.
+    pub fn set_time(&mut self, time: f32) {
+        self.coef = (-1.0 / (time.max(1e-6) * self.sample_rate)).exp();
+    }
+
+    /// Get the correlation, [-1.0-1.0]. Silence reads 0.0.
+    pub fn get_correlation(&self) -> f32 {
+        let power = (self.left_power * self.right_power).sqrt();
+        if power > 1e-12 {
+            (self.product / power).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.product = 0.0;
+        self.left_power = 0.0;
+        self.right_power = 0.0;
+    }
+}
+
+/// Detects clipping as runs of consecutive samples at or above a threshold, since a single full
+/// scale sample is usually a legitimate peak.
+pub struct ClipDetector {
+    threshold: f32,
+    min_run: u32,
+    run: u32,
+    clip_count: u32,
+    clipped_samples: u32,
+    longest_run: u32,
+}
+
+impl ClipDetector {
+    pub const fn new() -> Self {
+        Self {
+            threshold: 0.999,
+            min_run: 3,
+            run: 0,
+            clip_count: 0,
+            clipped_samples: 0,
+            longest_run: 0,
+        }
+    }
+
+    /// Returns true while the input is clipping.
+    pub fn process(&mut self, input: f32) -> bool {
+        if input.abs() < self.threshold {
+            self.run = 0;
+            return false;
+        }
+        self.run = self.run.saturating_add(1);
+        if self.run < self.min_run {
+            return false;
+        }
+        if self.run == self.min_run {
+            trace!("clipping at {}", input);
+            self.clip_count = self.clip_count.saturating_add(1);
+            self.clipped_samples = self.clipped_samples.saturating_add(self.min_run - 1);
+        }
+        self.clipped_samples = self.clipped_samples.saturating_add(1);
+        self.longest_run = self.longest_run.max(self.run);
+        true
+    }
+
+    pub fn process_block(&mut self, block: &[f32]) {
+        for sample in block {
+            self.process(*sample);
+        }
+    }
+
+    /// Set the level in dBFS counted as full scale.
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = db_to_linear(threshold);
+    }
+
+    /// Set the number of consecutive samples at full scale counted as clipping.
+    pub fn set_min_run(&mut self, samples: u32) {
+        self.min_run = samples.max(1);
+    }
+
+    /// Get the number of clipping events.
+    pub fn get_clip_count(&self) -> u32 {
+        self.clip_count
+    }
+
+    /// Get the total number of clipped samples.
+    pub fn get_clipped_samples(&self) -> u32 {
+        self.clipped_samples
+    }
+
+    /// Get the length in samples of the longest clipping event.
+    pub fn get_longest_run(&self) -> u32 {
+        self.longest_run
+    }
+
+    /// Clear the counts.
+    pub fn reset(&mut self) {
+        self.run = 0;
+        self.clip_count = 0;
+        self.clipped_samples = 0;
+        self.longest_run = 0;
+    }
+}
+
+impl Default for ClipDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Detects extended periods below a threshold, for diagnostics and auto standby.
+pub struct SilenceDetector {
+    sample_rate: f32,
+    threshold: f32,
+    min_duration: u32,
+    duration: u32,
+    silence_count: u32,
+}
+
+impl SilenceDetector {
+    pub const fn new(sample_rate: f32) -> Self {
+        // One second, at least one sample.
+        let min_duration = sample_rate as u32;
+        Self {
+            sample_rate,
+            // -60 dB
+            threshold: 0.001,
+            min_duration: if min_duration == 0 { 1 } else { min_duration },
+            duration: 0,
+            silence_count: 0,
+        }
+    }
+
+    /// Returns true while the input has been silent for at least the minimum duration.
+    pub fn process(&mut self, input: f32) -> bool {
+        if input.abs() >= self.threshold {
+            self.duration = 0;
+            return false;
+        }
+        self.duration = self.duration.saturating_add(1);
+        if self.duration == self.min_duration {
+            self.silence_count = self.silence_count.saturating_add(1);
+        }
+        self.duration >= self.min_duration
+    }
+
+    pub fn process_block(&mut self, block: &[f32]) {
+        for sample in block {
+            self.process(*sample);
+        }
+    }
+
+    /// Set the level in dBFS below which the input counts as silent.
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = db_to_linear(threshold);
+    }
+
+    /// Set the time in seconds the input must stay below the threshold to count as silence.
+    pub fn set_min_duration(&mut self, duration: f32) {
+        self.min_duration = ((duration.max(0.0) * self.sample_rate) as u32).max(1);
+    }
+
+    pub fn is_silent(&self) -> bool {
+        self.duration >= self.min_duration
+    }
+
+    /// Get the time in seconds the input has been below the threshold.
+    pub fn get_duration(&self) -> f32 {
+        self.duration as f32 / self.sample_rate
+    }
+
+    /// Get the number of silent periods detected.
+    pub fn get_silence_count(&self) -> u32 {
+        self.silence_count
+    }
+
+    /// Restart the duration and clear the count.
+    pub fn reset(&mut self) {
+        self.duration = 0;
+        self.silence_count = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const SAMPLE_RATE_F: f32 = 44100.0;
+
+    use super::*;
+    use crate::synthesis::{Oscillator, WaveType};
+
+    #[test]
+    fn test_rms_sliding_sine() {
+        let mut buffer = vec![0.0; 4410];
+        let mut meter = RmsMeter::new_sliding(DelayLine::new(&mut buffer));
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 1000.0);
+        for _ in 0..44100 {
+            meter.process(oscillator.process());
+        }
+        assert!((meter.get_db() + 3.01).abs() < 0.05);
+        for _ in 0..4410 {
+            meter.process(0.0);
+        }
+        assert!(meter.get_rms() < 1e-3);
+    }
+
+    #[test]
+    fn test_peak_meter_hold_and_decay() {
+        let mut meter = PeakMeter::new(1000.0);
+        meter.set_hold(0.5);
+        meter.set_decay(10.0);
+        meter.process(0.5);
+        assert!((meter.get_db() + 6.02).abs() < 0.01);
+        meter.process_block(&[0.0; 500]);
+        assert!((meter.get_db() + 6.02).abs() < 0.01);
+        // One second of decay at 10 dB/s.
+        meter.process_block(&[0.0; 1000]);
+        assert!((meter.get_db() + 16.02).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_const_meters() {
+        const PEAK: PeakMeter = PeakMeter::new(1000.0);
+        const SILENCE: SilenceDetector = SilenceDetector::new(1000.0);
+        let mut meter = PEAK;
+        meter.process(0.5);
+        meter.process_block(&[0.0; 1999]);
+        // One second hold then one second of decay at the default 20 dB/s.
+        assert!((meter.get_db() + 26.02).abs() < 0.05);
+        let mut detector = SILENCE;
+        assert!(!detector.process(0.0011));
+        for _ in 0..999 {
+            assert!(!detector.process(0.0009));
+        }
+        assert!(detector.process(0.0009));
+    }
+
+    #[test]
+    fn test_peak_meter_clip_latch() {
+        let mut meter = PeakMeter::new(1000.0);
+        meter.process_block(&[0.5, 1.0, -1.2, 0.1]);
+        assert_eq!(meter.take_clip_count(), 2);
+        assert_eq!(meter.take_clip_count(), 0);
+    }
+
+    #[test]
+    fn test_true_peak_intersample() {
+        // A quarter sample rate sine sampled at 45 degrees never has a sample above 0.707.
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, SAMPLE_RATE_F / 4.0);
+        oscillator.set_phase(0.125);
+        let mut true_peak = TruePeakMeter::new();
+        let mut sample_peak = PeakMeter::new(SAMPLE_RATE_F);
+        for _ in 0..4410 {
+            let sample = oscillator.process();
+            true_peak.process(sample);
+            sample_peak.process(sample);
+        }
+        assert!((sample_peak.get_db() + 3.01).abs() < 0.1);
+        assert!(true_peak.get_db().abs() < 0.3);
+    }
+
+    #[test]
+    fn test_loudness_reference_tone() {
+        // A full scale 997 Hz sine in one channel reads -3.01 LUFS.
+        let mut meter = LoudnessMeter::new(48000.0);
+        let mut oscillator = Oscillator::new(WaveType::Sine, 48000.0, 997.0);
+        for _ in 0..(48000 * 4) {
+            meter.process(oscillator.process());
+        }
+        assert!((meter.get_momentary() + 3.01).abs() < 0.1);
+        assert!((meter.get_short_term() + 3.01).abs() < 0.1);
+        assert!((meter.get_integrated() + 3.01).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_loudness_relative_gate() {
+        // Quiet passages 20 LU down are gated out of the integrated loudness.
+        let mut meter = LoudnessMeter::new(48000.0);
+        let mut oscillator = Oscillator::new(WaveType::Sine, 48000.0, 997.0);
+        for amplitude in [0.5, 0.05, 0.5, 0.05].iter() {
+            oscillator.set_amplitude(*amplitude);
+            for _ in 0..(48000 * 4) {
+                meter.process(oscillator.process());
+            }
+        }
+        assert!((meter.get_integrated() + 9.03).abs() < 0.3);
+    }
+
+    #[test]
+    fn test_correlation() {
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 440.0);
+        let mut quadrature = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 440.0);
+        quadrature.set_phase(0.25);
+        let mut in_phase = CorrelationMeter::new(SAMPLE_RATE_F);
+        let mut inverted = CorrelationMeter::new(SAMPLE_RATE_F);
+        let mut orthogonal = CorrelationMeter::new(SAMPLE_RATE_F);
+        assert_eq!(in_phase.get_correlation(), 0.0);
+        for _ in 0..44100 {
+            let (x, y) = (oscillator.process(), quadrature.process());
+            in_phase.process(x, x);
+            inverted.process(x, -0.5 * x);
+            orthogonal.process(x, y);
+        }
+        assert!((in_phase.get_correlation() - 1.0).abs() < 1e-3);
+        assert!((inverted.get_correlation() + 1.0).abs() < 1e-3);
+        assert!(orthogonal.get_correlation().abs() < 0.05);
+    }
+
+    #[test]
+    fn test_clip_detector_runs() {
+        let mut detector = ClipDetector::new();
+        // Isolated full scale peaks are not clipping.
+        detector.process_block(&[1.0, 0.5, -1.0, 1.0, 0.2]);
+        assert_eq!(detector.get_clip_count(), 0);
+        detector.process_block(&[1.0, 1.0, 1.0, 1.0, 1.0, 0.0, -1.0, -1.0, -1.0, 0.0]);
+        assert_eq!(detector.get_clip_count(), 2);
+        assert_eq!(detector.get_clipped_samples(), 8);
+        assert_eq!(detector.get_longest_run(), 5);
+        detector.reset();
+        assert_eq!(detector.get_clip_count(), 0);
+    }
+
+    #[test]
+    fn test_silence_detector() {
+        let mut detector = SilenceDetector::new(1000.0);
+        detector.set_min_duration(0.5);
+        let mut oscillator = Oscillator::new(WaveType::Sine, 1000.0, 50.0);
+        for _ in 0..2000 {
+            assert!(!detector.process(oscillator.process()));
+        }
+        for i in 0..1000 {
+            assert_eq!(detector.process(0.0001), i >= 499);
+        }
+        assert!(detector.is_silent());
+        assert!((detector.get_duration() - 1.0).abs() < 1e-3);
+        assert!(!detector.process(0.5));
+        assert_eq!(detector.get_silence_count(), 1);
+    }
+
+    #[test]
+    fn test_rms_exponential_square() {
+        let mut meter = RmsMeter::new_exponential(SAMPLE_RATE_F, 0.05);
+        let mut oscillator = Oscillator::new(WaveType::Square, SAMPLE_RATE_F, 100.0);
+        oscillator.set_amplitude(0.5);
+        for _ in 0..44100 {
+            meter.process(oscillator.process());
+        }
+        assert!((meter.get_db() + 6.02).abs() < 0.05);
+    }
+}
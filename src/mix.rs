@@ -0,0 +1,1044 @@
+float_math!();
+
+use core::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2};
+use core::marker::PhantomData;
+
+use crate::block::scale;
+use crate::control::Smoother;
+use crate::convert::{NoiseShaping, Quantizer};
+use crate::distortion::soft_clip;
+use crate::dynamics::Gate;
+use crate::filter::{Biquad, DcBlocker};
+use crate::frame::{Frame, Stereo};
+use crate::processor::Processor;
+use crate::sample::Sample;
+use crate::stereo::{pan_gains, PanLaw};
+use crate::units::{db_to_linear, linear_to_db};
+
+/// Gain curve of a crossfade.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum CrossfadeCurve {
+    /// Gains sum to 1.0, for correlated signals such as a dry signal and a short effect.
+    Linear,
+    /// Squared gains sum to 1.0, for uncorrelated signals such as reverb or long delays.
+    EqualPower,
+}
+
+/// Crossfade between two signals.
+pub struct Crossfade {
+    curve: CrossfadeCurve,
+    position: f32,
+    gain_a: f32,
+    gain_b: f32,
+}
+
+impl Crossfade {
+    pub fn new(curve: CrossfadeCurve) -> Self {
+        let mut crossfade = Self {
+            curve,
+            position: 0.0,
+            gain_a: 1.0,
+            gain_b: 0.0,
+        };
+        crossfade.set_position(0.0);
+        crossfade
+    }
+
+    /// Mix the two signals, a position of 0.0 returns only `a`.
+    pub fn process<T: Sample>(&self, a: T, b: T) -> T {
+        a.mul(T::from_f32(self.gain_a))
+            .add(b.mul(T::from_f32(self.gain_b)))
+    }
+
+    /// Set the crossfade position, clamped to [0.0-1.0].
+    pub fn set_position(&mut self, position: f32) {
+        self.position = position.clamp(0.0, 1.0);
+        match self.curve {
+            CrossfadeCurve::Linear => {
+                self.gain_a = 1.0 - self.position;
+                self.gain_b = self.position;
+            }
+            CrossfadeCurve::EqualPower => {
+                let theta = self.position * FRAC_PI_2;
+                self.gain_a = theta.cos();
+                self.gain_b = theta.sin();
+            }
+        }
+    }
+
+    pub fn set_curve(&mut self, curve: CrossfadeCurve) {
+        self.curve = curve;
+        self.set_position(self.position);
+    }
+
+    pub fn get_curve(&self) -> CrossfadeCurve {
+        self.curve
+    }
+
+    pub fn get_position(&self) -> f32 {
+        self.position
+    }
+}
+
+/// Dry/wet mixer for effects.
+pub struct Mix {
+    crossfade: Crossfade,
+}
+
+impl Mix {
+    pub fn new(curve: CrossfadeCurve) -> Self {
+        let mut crossfade = Crossfade::new(curve);
+        crossfade.set_position(0.5);
+        Self { crossfade }
+    }
+
+    pub fn process<T: Sample>(&self, dry: T, wet: T) -> T {
+        self.crossfade.process(dry, wet)
+    }
+
+    /// Set the wet amount, clamped to [0.0-1.0].
+    pub fn set_mix(&mut self, mix: f32) {
+        self.crossfade.set_position(mix);
+    }
+
+    pub fn set_curve(&mut self, curve: CrossfadeCurve) {
+        self.crossfade.set_curve(curve);
+    }
+
+    pub fn get_curve(&self) -> CrossfadeCurve {
+        self.crossfade.get_curve()
+    }
+
+    pub fn get_mix(&self) -> f32 {
+        self.crossfade.get_position()
+    }
+}
+
+/// Gain with a linear ramp on changes to avoid zipper noise.
+/// Fixed point samples can't represent gains above 1.0, so those saturate.
+pub struct Gain<T: Sample = f32> {
+    sample: PhantomData<T>,
+    smoother: Smoother,
+}
+
+impl<T: Sample> Gain<T> {
+    pub const fn new(sample_rate: f32) -> Self {
+        Self {
+            sample: PhantomData,
+            smoother: Smoother::with_value(sample_rate, 1.0),
+        }
+    }
+
+    pub fn process(&mut self, input: T) -> T {
+        input.mul(T::from_f32(self.smoother.next()))
+    }
+
+    /// Set the gain in dB.
+    pub fn set_db(&mut self, gain: f32) {
+        self.set_linear(db_to_linear(gain));
+    }
+
+    /// Set the linear gain.
+    pub fn set_linear(&mut self, gain: f32) {
+        self.smoother.set_target(gain);
+    }
+
+    /// Jump to the target gain without ramping.
+    pub fn set_linear_immediate(&mut self, gain: f32) {
+        self.smoother.set_immediate(gain);
+    }
+
+    /// Set the time in seconds a gain change ramps over.
+    pub fn set_ramp_time(&mut self, ramp_time: f32) {
+        self.smoother.set_time(ramp_time);
+    }
+
+    /// Get the current, possibly ramping, linear gain.
+    pub fn get_linear(&self) -> f32 {
+        self.smoother.get_value()
+    }
+
+    /// Get the current gain in dB.
+    pub fn get_db(&self) -> f32 {
+        linear_to_db(self.get_linear())
+    }
+
+    /// Jump to the target gain, ending any ramp.
+    pub fn reset(&mut self) {
+        self.smoother.reset();
+    }
+}
+
+impl Processor for Gain {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+/// Volume control with equal loudness compensation for small speakers.
+/// As the volume drops below the reference level, bass and to a lesser degree treble are boosted
+/// with shelving filters to make up for the ear's reduced sensitivity at low levels.
+pub struct LoudnessVolume {
+    gain: Gain,
+    bass: Biquad,
+    treble: Biquad,
+    volume: f32,
+    reference: f32,
+    amount: f32,
+}
+
+impl LoudnessVolume {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut volume = Self {
+            gain: Gain::new(sample_rate),
+            bass: Biquad::new(sample_rate),
+            treble: Biquad::new(sample_rate),
+            volume: 0.0,
+            reference: 0.0,
+            amount: 1.0,
+        };
+        volume.calc_compensation();
+        volume
+    }
+
+    fn calc_compensation(&mut self) {
+        let below = (self.reference - self.volume).max(0.0);
+        let bass = (below * 0.4).min(15.0) * self.amount;
+        let treble = (below * 0.15).min(6.0) * self.amount;
+        self.bass.set_low_shelf(100.0, 0.707, bass);
+        self.treble.set_high_shelf(10000.0, 0.707, treble);
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let shaped = self.treble.process(self.bass.process(input));
+        self.gain.process(shaped)
+    }
+
+    /// Set the volume in dB.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+        self.gain.set_db(volume);
+        self.calc_compensation();
+    }
+
+    /// Set the volume in dB at and above which no compensation is applied, usually the level the
+    /// speaker is tuned at.
+    pub fn set_reference(&mut self, reference: f32) {
+        self.reference = reference;
+        self.calc_compensation();
+    }
+
+    /// Set the amount of compensation, clamped to [0.0-1.0].
+    pub fn set_amount(&mut self, amount: f32) {
+        self.amount = amount.clamp(0.0, 1.0);
+        self.calc_compensation();
+    }
+
+    pub fn get_volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub fn reset(&mut self) {
+        self.gain.reset();
+        self.bass.reset();
+        self.treble.reset();
+    }
+}
+
+impl Processor for LoudnessVolume {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+/// Scale `block` in place so its peak is at `target` dBFS, returning the linear gain applied.
+/// Silent blocks are left alone.
+pub fn normalize_block(block: &mut [f32], target: f32) -> f32 {
+    let peak = block.iter().fold(0.0_f32, |peak, x| peak.max(x.abs()));
+    if peak == 0.0 {
+        return 1.0;
+    }
+    let gain = db_to_linear(target) / peak;
+    scale(block, gain);
+    gain
+}
+
+// Level above which gain stage protection starts to soft clip, -3 dBFS.
+const PROTECT_KNEE: f32 = 0.707;
+
+/// Gain between two effects in a chain that meters the headroom left after it, with optional
+/// protection that soft clips peaks above -3 dBFS so they never exceed full scale. Place one
+/// between stages to spot which one overloads.
+pub struct GainStage {
+    gain: Gain,
+    protect: bool,
+    peak: f32,
+}
+
+impl GainStage {
+    /// Unity gain without protection.
+    pub const fn new(sample_rate: f32) -> Self {
+        Self {
+            gain: Gain::new(sample_rate),
+            protect: false,
+            peak: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = self.gain.process(input);
+        self.peak = self.peak.max(output.abs());
+        if self.protect && output.abs() > PROTECT_KNEE {
+            // Continuous in level and slope at the knee, reaching full scale at 3x past it.
+            let over = (output.abs() - PROTECT_KNEE) / (1.0 - PROTECT_KNEE);
+            let limited = PROTECT_KNEE + (1.0 - PROTECT_KNEE) * soft_clip(over);
+            limited.copysign(output)
+        } else {
+            output
+        }
+    }
+
+    /// Set the gain in dB.
+    pub fn set_db(&mut self, gain: f32) {
+        self.gain.set_db(gain);
+    }
+
+    /// Enable soft clipping above -3 dBFS.
+    pub fn set_protect(&mut self, enabled: bool) {
+        self.protect = enabled;
+    }
+
+    /// Get the highest level before protection since the last `reset_meter`, in dBFS.
+    pub fn get_peak_db(&self) -> f32 {
+        linear_to_db(self.peak)
+    }
+
+    /// Get the headroom left below full scale in dB, negative once the stage has overloaded.
+    pub fn get_headroom_db(&self) -> f32 {
+        -self.get_peak_db()
+    }
+
+    pub fn reset_meter(&mut self) {
+        self.peak = 0.0;
+    }
+
+    /// Jump to the target gain and clear the meter.
+    pub fn reset(&mut self) {
+        self.gain.reset();
+        self.reset_meter();
+    }
+}
+
+impl Processor for GainStage {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+/// Last node before the codec: a DC blocker, the final gain and a gentle soft clipper keeping
+/// peaks below full scale, then optionally a dithered quantizer for integer codecs. Peaks above
+/// -3 dBFS are rounded off rather than wrapping or hard clipping in the codec. NaN and infinite
+/// inputs are replaced with silence and clear the DC blocker, so a blown up filter upstream
+/// can't reach the codec. Use one per channel.
+pub struct OutputStage {
+    dc_blocker: DcBlocker,
+    dc_block: bool,
+    gain: GainStage,
+    quantizer: Quantizer,
+}
+
+impl OutputStage {
+    /// DC blocking and soft clipping on at unity gain, quantizing to 24 bits.
+    pub fn new(sample_rate: f32) -> Self {
+        let mut gain = GainStage::new(sample_rate);
+        gain.set_protect(true);
+        Self {
+            dc_blocker: DcBlocker::new(sample_rate),
+            dc_block: true,
+            gain,
+            quantizer: Quantizer::new(24),
+        }
+    }
+
+    /// Process one sample for a floating point codec.
+    pub fn process(&mut self, input: f32) -> f32 {
+        if !input.is_finite() {
+            trace!("output stage recovered from {}", input);
+            self.dc_blocker.reset();
+            return 0.0;
+        }
+        let input = if self.dc_block {
+            self.dc_blocker.process(input)
+        } else {
+            input
+        };
+        self.gain.process(input)
+    }
+
+    /// Process and quantize one sample to a right aligned integer of the configured bit depth.
+    pub fn process_int(&mut self, input: f32) -> i32 {
+        let output = self.process(input);
+        self.quantizer.process(output)
+    }
+
+    /// Process and quantize to 16 bit samples, the bit depth should be set to 16.
+    pub fn process_i16_slice(&mut self, input: &[f32], output: &mut [i16]) {
+        for (output, input) in output.iter_mut().zip(input.iter()) {
+            *output = self.process_int(*input) as i16;
+        }
+    }
+
+    /// Process and quantize to right aligned samples of the configured bit depth.
+    pub fn process_i32_slice(&mut self, input: &[f32], output: &mut [i32]) {
+        for (output, input) in output.iter_mut().zip(input.iter()) {
+            *output = self.process_int(*input);
+        }
+    }
+
+    /// Set the final gain in dB, smoothed.
+    pub fn set_db(&mut self, gain: f32) {
+        self.gain.set_db(gain);
+    }
+
+    pub fn set_dc_block(&mut self, enabled: bool) {
+        self.dc_block = enabled;
+    }
+
+    /// Enable soft clipping above -3 dBFS, without it peaks over full scale are left for the
+    /// quantizer or codec to hard clip.
+    pub fn set_clip(&mut self, enabled: bool) {
+        self.gain.set_protect(enabled);
+    }
+
+    /// Set the bit depth of the integer outputs, [8-24].
+    pub fn set_bits(&mut self, bits: u32) {
+        self.quantizer.set_bits(bits);
+    }
+
+    /// Enable TPDF dither on the integer outputs.
+    pub fn set_dither(&mut self, enabled: bool) {
+        self.quantizer.set_dither(enabled);
+    }
+
+    pub fn set_noise_shaping(&mut self, shaping: NoiseShaping) {
+        self.quantizer.set_noise_shaping(shaping);
+    }
+
+    /// Get the highest level before clipping since the last `reset_meter`, in dBFS.
+    pub fn get_peak_db(&self) -> f32 {
+        self.gain.get_peak_db()
+    }
+
+    pub fn reset_meter(&mut self) {
+        self.gain.reset_meter();
+    }
+
+    /// Clear the DC blocker and quantizer, jump to the target gain and clear the meter.
+    pub fn reset(&mut self) {
+        self.dc_blocker.reset();
+        self.gain.reset();
+        self.quantizer.reset();
+    }
+}
+
+impl Processor for OutputStage {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+/// First node after the codec: a DC blocker, an optional high pass against rumble and handling
+/// noise, a smoothed input trim and an optional noise gate. The gate follows the trim so its
+/// threshold is relative to the trimmed level. Use one per channel.
+pub struct InputStage {
+    dc_blocker: DcBlocker,
+    dc_block: bool,
+    high_pass: Biquad,
+    high_pass_freq: f32,
+    trim: Gain,
+    gate: Gate<'static>,
+    gate_enabled: bool,
+}
+
+impl InputStage {
+    /// DC blocking on at unity gain, high pass and gate off.
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            dc_blocker: DcBlocker::new(sample_rate),
+            dc_block: true,
+            high_pass: Biquad::new(sample_rate),
+            high_pass_freq: 0.0,
+            trim: Gain::new(sample_rate),
+            gate: Gate::new(sample_rate),
+            gate_enabled: false,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let mut output = input;
+        if self.dc_block {
+            output = self.dc_blocker.process(output);
+        }
+        if self.high_pass_freq > 0.0 {
+            output = self.high_pass.process(output);
+        }
+        output = self.trim.process(output);
+        if self.gate_enabled {
+            output = self.gate.process(output);
+        }
+        output
+    }
+
+    pub fn process_block(&mut self, block: &mut [f32]) {
+        for sample in block.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
+
+    pub fn set_dc_block(&mut self, enabled: bool) {
+        self.dc_block = enabled;
+    }
+
+    /// Set the high pass frequency in Hz, 0.0 turns it off.
+    pub fn set_high_pass(&mut self, freq: f32) {
+        self.high_pass_freq = freq.max(0.0);
+        if self.high_pass_freq > 0.0 {
+            self.high_pass
+                .set_high_pass(self.high_pass_freq, FRAC_1_SQRT_2);
+        }
+    }
+
+    /// Set the input trim in dB, smoothed.
+    pub fn set_trim_db(&mut self, gain: f32) {
+        self.trim.set_db(gain);
+    }
+
+    pub fn set_gate(&mut self, enabled: bool) {
+        self.gate_enabled = enabled;
+    }
+
+    /// Get the gate to set its threshold, range and timing.
+    pub fn gate(&mut self) -> &mut Gate<'static> {
+        &mut self.gate
+    }
+
+    /// Clear the filters, jump to the target trim and close the gate.
+    pub fn reset(&mut self) {
+        self.dc_blocker.reset();
+        self.high_pass.reset();
+        self.trim.reset();
+        self.gate.reset();
+    }
+}
+
+impl Processor for InputStage {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+// A channel's send to an aux bus, the smoother ramps to the combined send, fader and mute gains.
+struct AuxSend {
+    level: f32,
+    pre_fader: bool,
+    gain: Smoother,
+}
+
+// One input of a mixer, the smoothers ramp to the combined fader, pan and mute gains.
+struct MixerChannel<const AUX: usize> {
+    gain: f32,
+    pan: f32,
+    mute: bool,
+    left: Smoother,
+    right: Smoother,
+    sends: [AuxSend; AUX],
+}
+
+/// Sums `CH` mono inputs to a stereo bus with a fader, pan and mute per channel, then runs the
+/// bus through an optional insert and the master fader. Gain, pan and mute changes are ramped
+/// over 20 ms so they don't click.
+///
+/// Each channel also sends to `AUX` mono aux buses, pre or post fader, feeding shared effects
+/// such as one reverb and one delay. Their stereo returns are summed into the bus ahead of the
+/// insert. Muting a channel mutes its sends too.
+pub struct Mixer<const CH: usize, const AUX: usize = 0> {
+    channels: [MixerChannel<AUX>; CH],
+    returns: [Smoother; AUX],
+    law: PanLaw,
+    master: Smoother,
+}
+
+impl<const CH: usize, const AUX: usize> Mixer<CH, AUX> {
+    /// All channels at unity gain, centered with the constant power law and unmuted. Sends are
+    /// off and post fader, returns at unity gain.
+    pub fn new(sample_rate: f32) -> Self {
+        let mut master = Smoother::new(sample_rate);
+        master.set_immediate(1.0);
+        let mut mixer = Self {
+            channels: core::array::from_fn(|_| MixerChannel {
+                gain: 1.0,
+                pan: 0.0,
+                mute: false,
+                left: Smoother::new(sample_rate),
+                right: Smoother::new(sample_rate),
+                sends: core::array::from_fn(|_| AuxSend {
+                    level: 0.0,
+                    pre_fader: false,
+                    gain: Smoother::new(sample_rate),
+                }),
+            }),
+            returns: core::array::from_fn(|_| {
+                let mut gain = Smoother::new(sample_rate);
+                gain.set_immediate(1.0);
+                gain
+            }),
+            law: PanLaw::ConstantPower,
+            master,
+        };
+        for channel in 0..CH {
+            mixer.update(channel);
+        }
+        mixer.reset();
+        mixer
+    }
+
+    /// Mix without effects on the aux buses, which then return nothing.
+    pub fn process(&mut self, inputs: Frame<CH>) -> Stereo {
+        self.process_with_insert(inputs, |bus| bus)
+    }
+
+    /// Mix with `insert` on the master bus ahead of the master fader, for example a bus
+    /// compressor's `process_frame`. The aux buses return nothing.
+    pub fn process_with_insert<F: FnMut(Stereo) -> Stereo>(
+        &mut self,
+        inputs: Frame<CH>,
+        insert: F,
+    ) -> Stereo {
+        self.process_with_buses(inputs, |_, _| Stereo::EQUILIBRIUM, insert)
+    }
+
+    /// Mix with `effect` run on each aux bus, taking the bus index and its summed sends and
+    /// returning the stereo return, and `insert` on the master bus.
+    pub fn process_with_buses<E, F>(
+        &mut self,
+        inputs: Frame<CH>,
+        mut effect: E,
+        mut insert: F,
+    ) -> Stereo
+    where
+        E: FnMut(usize, f32) -> Stereo,
+        F: FnMut(Stereo) -> Stereo,
+    {
+        let mut bus = Stereo::EQUILIBRIUM;
+        let mut sends = [0.0; AUX];
+        for (input, channel) in inputs.0.iter().zip(self.channels.iter_mut()) {
+            bus.0[0] += input * channel.left.next();
+            bus.0[1] += input * channel.right.next();
+            for (send, aux) in sends.iter_mut().zip(channel.sends.iter_mut()) {
+                *send += input * aux.gain.next();
+            }
+        }
+        for (aux, (send, gain)) in sends.iter().zip(self.returns.iter_mut()).enumerate() {
+            bus = bus + effect(aux, *send) * gain.next();
+        }
+        insert(bus) * self.master.next()
+    }
+
+    /// Mix blocks of each channel into left and right output blocks.
+    pub fn process_block(&mut self, inputs: &[&[f32]; CH], left: &mut [f32], right: &mut [f32]) {
+        for (i, (left, right)) in left.iter_mut().zip(right.iter_mut()).enumerate() {
+            let frame = Frame(core::array::from_fn(|channel| {
+                inputs[channel].get(i).copied().unwrap_or(0.0)
+            }));
+            let output = self.process(frame);
+            *left = output.left();
+            *right = output.right();
+        }
+    }
+
+    /// Set a channel's fader in dB.
+    pub fn set_gain_db(&mut self, channel: usize, gain: f32) {
+        if let Some(strip) = self.channels.get_mut(channel) {
+            strip.gain = db_to_linear(gain);
+            self.update(channel);
+        }
+    }
+
+    /// Set a channel's pan position, clamped to [-1.0, 1.0], -1.0 being hard left.
+    pub fn set_pan(&mut self, channel: usize, pan: f32) {
+        if let Some(strip) = self.channels.get_mut(channel) {
+            strip.pan = pan.clamp(-1.0, 1.0);
+            self.update(channel);
+        }
+    }
+
+    pub fn set_mute(&mut self, channel: usize, mute: bool) {
+        if let Some(strip) = self.channels.get_mut(channel) {
+            strip.mute = mute;
+            self.update(channel);
+        }
+    }
+
+    /// Set a channel's send level to an aux bus in dB, sends start off.
+    pub fn set_send_db(&mut self, channel: usize, aux: usize, level: f32) {
+        if let Some(send) = self
+            .channels
+            .get_mut(channel)
+            .and_then(|strip| strip.sends.get_mut(aux))
+        {
+            send.level = db_to_linear(level);
+            self.update(channel);
+        }
+    }
+
+    /// Take a channel's send to an aux bus before the fader, so it keeps its level while the
+    /// fader moves, such as for monitor mixes. Sends start post fader.
+    pub fn set_send_pre_fader(&mut self, channel: usize, aux: usize, pre_fader: bool) {
+        if let Some(send) = self
+            .channels
+            .get_mut(channel)
+            .and_then(|strip| strip.sends.get_mut(aux))
+        {
+            send.pre_fader = pre_fader;
+            self.update(channel);
+        }
+    }
+
+    /// Set an aux bus' return level in dB.
+    pub fn set_return_db(&mut self, aux: usize, level: f32) {
+        if let Some(gain) = self.returns.get_mut(aux) {
+            gain.set_target(db_to_linear(level));
+        }
+    }
+
+    pub fn is_muted(&self, channel: usize) -> bool {
+        self.channels.get(channel).is_some_and(|strip| strip.mute)
+    }
+
+    pub fn set_pan_law(&mut self, law: PanLaw) {
+        self.law = law;
+        for channel in 0..CH {
+            self.update(channel);
+        }
+    }
+
+    pub fn get_pan_law(&self) -> PanLaw {
+        self.law
+    }
+
+    /// Set the master fader in dB.
+    pub fn set_master_db(&mut self, gain: f32) {
+        self.master.set_target(db_to_linear(gain));
+    }
+
+    fn update(&mut self, channel: usize) {
+        let strip = &mut self.channels[channel];
+        let gain = if strip.mute { 0.0 } else { strip.gain };
+        let (left, right) = pan_gains(self.law, strip.pan);
+        strip.left.set_target(gain * left);
+        strip.right.set_target(gain * right);
+        let on = if strip.mute { 0.0 } else { 1.0 };
+        for send in strip.sends.iter_mut() {
+            let fader = if send.pre_fader { 1.0 } else { strip.gain };
+            send.gain.set_target(send.level * fader * on);
+        }
+    }
+
+    /// Jump to the target gains.
+    pub fn reset(&mut self) {
+        for strip in self.channels.iter_mut() {
+            strip.left.reset();
+            strip.right.reset();
+            for send in strip.sends.iter_mut() {
+                send.gain.reset();
+            }
+        }
+        for gain in self.returns.iter_mut() {
+            gain.reset();
+        }
+        self.master.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthesis::{Oscillator, WaveType};
+
+    fn sine_gain(volume: &mut LoudnessVolume, freq: f32) -> f32 {
+        let mut oscillator = Oscillator::new(WaveType::Sine, 44100.0, freq);
+        let mut peak = 0.0_f32;
+        for i in 0..44100 {
+            let output = volume.process(oscillator.process());
+            if i > 22050 {
+                peak = peak.max(output.abs());
+            }
+        }
+        20.0 * peak.log10()
+    }
+
+    #[test]
+    fn test_loudness_volume() {
+        let mut volume = LoudnessVolume::new(44100.0);
+        assert!((sine_gain(&mut volume, 50.0) - sine_gain(&mut volume, 1000.0)).abs() < 0.1);
+
+        volume.set_volume(-40.0);
+        let mid = sine_gain(&mut volume, 1000.0);
+        let bass = sine_gain(&mut volume, 40.0);
+        let treble = sine_gain(&mut volume, 16000.0);
+        assert!((mid + 40.0).abs() < 1.0);
+        assert!(bass - mid > 12.0);
+        assert!(treble - mid > 4.0 && treble - mid < bass - mid);
+
+        volume.set_amount(0.0);
+        assert!((sine_gain(&mut volume, 40.0) + 40.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_crossfade_ends() {
+        for curve in [CrossfadeCurve::Linear, CrossfadeCurve::EqualPower].iter() {
+            let mut crossfade = Crossfade::new(*curve);
+            assert!((crossfade.process(1.0_f32, 2.0) - 1.0).abs() < 1e-6);
+            crossfade.set_position(1.0);
+            assert!((crossfade.process(1.0_f32, 2.0) - 2.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_gain_ramp() {
+        let mut gain = Gain::new(1000.0);
+        gain.set_ramp_time(0.01);
+        gain.set_db(-20.0);
+        let output: Vec<f32> = (0..12).map(|_| gain.process(1.0)).collect();
+        for pair in output.windows(2) {
+            assert!(pair[1] <= pair[0]);
+            assert!(pair[0] - pair[1] < 0.1);
+        }
+        assert!((output[9] - 0.1).abs() < 1e-6);
+        assert!((gain.get_db() + 20.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_gain_stage() {
+        let mut stage = GainStage::new(44100.0);
+        let mut oscillator = Oscillator::new(WaveType::Sine, 44100.0, 100.0);
+        for _ in 0..1000 {
+            stage.process(0.5 * oscillator.process());
+        }
+        assert!((stage.get_headroom_db() - 6.02).abs() < 0.05);
+
+        // 6 dB over full scale is metered and, with protection, kept below full scale.
+        stage.set_db(12.0);
+        stage.set_protect(true);
+        stage.reset();
+        let mut output_peak = 0.0_f32;
+        for _ in 0..1000 {
+            output_peak = output_peak.max(stage.process(0.5 * oscillator.process()).abs());
+        }
+        assert!((stage.get_headroom_db() + 6.0).abs() < 0.1);
+        assert!(output_peak <= 1.0 && output_peak > 0.9);
+        // Quiet signals pass untouched.
+        assert_eq!(stage.process(0.1), 0.1 * db_to_linear(12.0));
+
+        let mut block = [0.1, -0.25, 0.2];
+        assert_eq!(normalize_block(&mut block, 0.0), 4.0);
+        assert_eq!(block, [0.4, -1.0, 0.8]);
+    }
+
+    #[test]
+    fn test_output_stage() {
+        let mut stage = OutputStage::new(44100.0);
+        let mut oscillator = Oscillator::new(WaveType::Sine, 44100.0, 1000.0);
+        stage.set_db(6.0);
+        stage.reset();
+
+        // An offset is removed and a full scale sine doubled is rounded off below full scale.
+        let mut output_peak = 0.0_f32;
+        let mut sum = 0.0;
+        for n in 0..44100 {
+            let output = stage.process(0.3 + oscillator.process());
+            if n >= 22050 {
+                output_peak = output_peak.max(output.abs());
+                sum += output;
+            }
+        }
+        assert!((sum / 22050.0).abs() < 0.01);
+        assert!(output_peak <= 1.0 && output_peak > 0.9);
+        assert!(stage.get_peak_db() > 6.0);
+
+        // Integer output follows the bit depth, undithered half scale is exact.
+        stage.set_dc_block(false);
+        stage.set_db(0.0);
+        stage.set_bits(16);
+        stage.set_dither(false);
+        stage.reset();
+        let mut output = [0_i16; 2];
+        stage.process_i16_slice(&[0.5, -0.5], &mut output);
+        assert_eq!(output, [16384, -16384]);
+
+        // Non-finite input is muted without poisoning the DC blocker.
+        stage.set_dc_block(true);
+        assert_eq!(stage.process(f32::NAN), 0.0);
+        assert_eq!(stage.process(f32::INFINITY), 0.0);
+        assert_eq!(stage.process_int(f32::NAN), 0);
+        assert!(stage.process(0.5).is_finite());
+    }
+
+    #[test]
+    fn test_input_stage() {
+        let mut stage = InputStage::new(44100.0);
+        let mut rumble = Oscillator::new(WaveType::Sine, 44100.0, 20.0);
+        let mut tone = Oscillator::new(WaveType::Sine, 44100.0, 1000.0);
+        stage.set_high_pass(120.0);
+        stage.set_trim_db(6.0);
+
+        // The offset and rumble are removed and the tone is trimmed up.
+        let mut sum = 0.0;
+        let mut peak = 0.0_f32;
+        for n in 0..44100 {
+            let output = stage.process(0.2 + 0.5 * rumble.process() + 0.25 * tone.process());
+            if n >= 22050 {
+                sum += output;
+                peak = peak.max(output.abs());
+            }
+        }
+        assert!((sum / 22050.0).abs() < 0.01);
+        assert!((peak - 0.5).abs() < 0.03);
+
+        // With the gate on a quiet tone is cut and a loud one passes.
+        stage.set_gate(true);
+        stage.gate().set_threshold(-30.0);
+        stage.reset();
+        let mut quiet_peak = 0.0_f32;
+        for n in 0..22050 {
+            let output = stage.process(0.005 * tone.process());
+            if n >= 11025 {
+                quiet_peak = quiet_peak.max(output.abs());
+            }
+        }
+        assert!(quiet_peak < 1e-4);
+        peak = 0.0;
+        for n in 0..22050 {
+            let output = stage.process(0.25 * tone.process());
+            if n >= 11025 {
+                peak = peak.max(output.abs());
+            }
+        }
+        assert!(stage.gate().is_open());
+        assert!((peak - 0.5).abs() < 0.03);
+
+        // Reset jumps straight to a new trim.
+        let mut stage = InputStage::new(44100.0);
+        stage.set_dc_block(false);
+        stage.set_trim_db(-6.0);
+        stage.reset();
+        assert!((stage.process(1.0) - 0.501).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_mixer() {
+        let mut mixer: Mixer<3> = Mixer::new(44100.0);
+        mixer.set_pan(0, -1.0);
+        mixer.set_pan(1, 1.0);
+        mixer.set_gain_db(1, -6.0);
+        mixer.set_mute(2, true);
+        mixer.reset();
+        let output = mixer.process(Frame([0.5, 0.5, 1.0]));
+        assert!((output.left() - 0.5).abs() < 1e-6);
+        assert!((output.right() - 0.5 * db_to_linear(-6.0)).abs() < 1e-6);
+
+        // Unmuting ramps the third channel in over 20 ms, centered at -3 dB.
+        mixer.set_mute(2, false);
+        let mut previous = 0.0;
+        for _ in 0..882 {
+            let output = mixer.process(Frame([0.0, 0.0, 1.0]));
+            assert!(output.left() >= previous && output.left() - previous < 0.001);
+            previous = output.left();
+        }
+        assert!((previous - FRAC_1_SQRT_2).abs() < 1e-4);
+
+        // The insert runs ahead of the master fader.
+        mixer.set_master_db(-6.0);
+        mixer.reset();
+        let output = mixer.process_with_insert(Frame([1.0, 0.0, 0.0]), |bus| bus * 2.0);
+        assert!((output.left() - 2.0 * db_to_linear(-6.0)).abs() < 1e-6);
+        assert!(!mixer.is_muted(2) && !mixer.is_muted(5));
+    }
+
+    #[test]
+    fn test_mixer_aux() {
+        let mut mixer: Mixer<2, 2> = Mixer::new(44100.0);
+        // The first channel's fader is down, only its pre fader send is heard.
+        mixer.set_gain_db(0, -120.0);
+        mixer.set_send_db(0, 0, 0.0);
+        mixer.set_send_pre_fader(0, 0, true);
+        mixer.set_send_db(0, 1, 0.0);
+        mixer.set_send_db(1, 1, -6.0);
+        mixer.set_return_db(1, -6.0);
+        mixer.reset();
+
+        let mut sends = [0.0; 2];
+        let output = mixer.process_with_buses(
+            Frame([1.0, 1.0]),
+            |aux, send| {
+                sends[aux] = send;
+                Stereo::new(send, 0.0)
+            },
+            |bus| bus,
+        );
+        assert!((sends[0] - 1.0).abs() < 1e-6);
+        assert!((sends[1] - db_to_linear(-6.0)).abs() < 1e-4);
+        let dry = FRAC_1_SQRT_2 * (1.0 + db_to_linear(-120.0));
+        let wet = 1.0 + sends[1] * db_to_linear(-6.0);
+        assert!((output.left() - dry - wet).abs() < 1e-4);
+        assert!((output.right() - dry).abs() < 1e-4);
+
+        // Muting silences the sends too.
+        mixer.set_mute(0, true);
+        mixer.reset();
+        mixer.process_with_buses(
+            Frame([1.0, 0.0]),
+            |aux, send| {
+                sends[aux] = send;
+                Stereo::EQUILIBRIUM
+            },
+            |bus| bus,
+        );
+        assert_eq!(sends, [0.0; 2]);
+    }
+
+    #[test]
+    fn test_mix_center() {
+        let mut mix = Mix::new(CrossfadeCurve::Linear);
+        mix.set_mix(0.5);
+        assert!((mix.process(1.0_f32, 1.0) - 1.0).abs() < 1e-6);
+
+        mix.set_curve(CrossfadeCurve::EqualPower);
+        let dry = mix.process(1.0_f32, 0.0);
+        let wet = mix.process(0.0_f32, 1.0);
+        assert!((dry * dry + wet * wet - 1.0).abs() < 1e-4);
+    }
+}
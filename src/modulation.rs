@@ -0,0 +1,889 @@
+float_math!();
+
+use core::f32::consts::PI;
+
+use crate::control::{Smoother, SmootherMode};
+use crate::delay::DelayLine;
+use crate::filter::{Biquad, InterpolatedBiquad};
+use crate::frame::{Mono, Stereo};
+use crate::mix::{CrossfadeCurve, Mix};
+use crate::preset::Preset;
+use crate::processor::Processor;
+use crate::synthesis::{Oscillator, WaveType};
+use crate::units::{bpm_to_hz, cents_to_ratio};
+
+// Smallest delay the modulated tap is allowed to reach, in samples.
+const MIN_DELAY: f32 = 1.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct VibratoParams {
+    /// LFO rate in Hz.
+    pub rate: f32,
+    /// Peak pitch deviation in cents.
+    pub depth: f32,
+}
+
+/// Pitch vibrato, a 100% wet delay line modulated by a sine LFO.
+pub struct Vibrato<'a> {
+    sample_rate: f32,
+    delay_line: DelayLine<'a>,
+    lfo: Oscillator,
+    rate: f32,
+    depth: f32,
+    center: f32,
+    sweep: f32,
+    control_rate: bool,
+    modulation: f32,
+    modulation_step: f32,
+    remaining: usize,
+}
+
+impl<'a> Vibrato<'a> {
+    pub fn new(sample_rate: f32, delay_line: DelayLine<'a>) -> Self {
+        let rate = 5.0;
+        let mut vibrato = Self {
+            sample_rate,
+            delay_line,
+            lfo: Oscillator::new(WaveType::Sine, sample_rate, rate),
+            rate,
+            depth: 0.0,
+            center: MIN_DELAY,
+            sweep: 0.0,
+            control_rate: false,
+            modulation: 0.0,
+            modulation_step: 0.0,
+            remaining: 0,
+        };
+        vibrato.calc_sweep();
+        vibrato
+    }
+
+    fn calc_sweep(&mut self) {
+        // A sinusoidal delay sweep of A seconds at f Hz has a peak pitch ratio of 1 + 2*pi*f*A.
+        let ratio = cents_to_ratio(self.depth);
+        let sweep = (ratio - 1.0) / (2.0 * PI * self.rate) * self.sample_rate;
+        let max_sweep = (self.delay_line.len() as f32 - 2.0 - MIN_DELAY) * 0.5;
+        self.sweep = sweep.clamp(0.0, max_sweep.max(0.0));
+        self.center = self.sweep + MIN_DELAY;
+    }
+
+    /// Process one sample using the internal LFO.
+    pub fn process(&mut self, input: f32) -> f32 {
+        let modulation = if self.control_rate {
+            if self.remaining > 0 {
+                self.remaining -= 1;
+                self.modulation += self.modulation_step;
+            }
+            self.modulation
+        } else {
+            self.lfo.process()
+        };
+        self.process_modulated(input, modulation)
+    }
+
+    /// Control rate tick, once called the LFO is only read here, at the end of the next
+    /// `samples` samples, and the modulation is interpolated linearly between ticks.
+    pub fn update_params(&mut self, samples: usize) {
+        self.control_rate = true;
+        let samples = samples.max(1);
+        self.lfo.skip(samples - 1);
+        let target = self.lfo.process();
+        self.modulation_step = (target - self.modulation) / samples as f32;
+        self.remaining = samples;
+    }
+
+    /// Process one sample with an external modulation signal in [-1.0, 1.0] in place of the internal LFO.
+    pub fn process_modulated(&mut self, input: f32, modulation: f32) -> f32 {
+        let delay = self.center + self.sweep * modulation.clamp(-1.0, 1.0);
+        let output = self.delay_line.read_delayed(delay);
+        self.delay_line.write(input);
+        output
+    }
+
+    /// Set the LFO rate in Hz.
+    pub fn set_rate(&mut self, rate: f32) {
+        self.rate = rate.max(0.01);
+        self.lfo.set_freq(self.rate);
+        self.calc_sweep();
+    }
+
+    /// Set the LFO rate from a tempo in BPM and the number of beats per LFO cycle.
+    pub fn set_tempo(&mut self, bpm: f32, beats: f32) {
+        self.set_rate(bpm_to_hz(bpm, beats));
+    }
+
+    /// Set the peak pitch deviation in cents, limited by the delay line length.
+    pub fn set_depth(&mut self, cents: f32) {
+        self.depth = cents.max(0.0);
+        self.calc_sweep();
+    }
+
+    /// Average delay of the output in samples, for aligning parallel dry paths.
+    pub fn latency_samples(&self) -> usize {
+        self.center as usize
+    }
+
+    /// Restart the LFO, for syncing it to a note or beat.
+    pub fn retrigger(&mut self) {
+        self.lfo.reset();
+        self.modulation = 0.0;
+        self.remaining = 0;
+    }
+
+    /// Lock the LFO phase to a transport position in beats, see `Oscillator::lock_phase`.
+    pub fn lock_phase(&mut self, position: f32, beats: f32) {
+        self.lfo.lock_phase(position, beats);
+    }
+
+    /// Clear the delay line and restart the LFO.
+    pub fn reset(&mut self) {
+        self.delay_line.reset();
+        self.retrigger();
+    }
+}
+
+impl Processor for Vibrato<'_> {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.latency_samples()
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+
+    fn update_params(&mut self, samples: usize) {
+        self.update_params(samples)
+    }
+}
+
+impl Preset for Vibrato<'_> {
+    type Params = VibratoParams;
+
+    fn get_params(&self) -> VibratoParams {
+        VibratoParams {
+            rate: self.rate,
+            depth: self.depth,
+        }
+    }
+
+    fn set_params(&mut self, params: &VibratoParams) {
+        self.set_rate(params.rate);
+        self.set_depth(params.depth);
+    }
+}
+
+// Time constant of the gain smoothing, about a 160 Hz cutoff, low enough to round off square
+// wave edges.
+const TREMOLO_SMOOTHING_TIME: f32 = 0.001;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct TremoloParams {
+    pub wave_type: WaveType,
+    /// LFO rate in Hz.
+    pub rate: f32,
+    pub depth: f32,
+    pub phase_offset: f32,
+}
+
+/// Stereo tremolo, amplitude modulation by an LFO with an adjustable phase offset between channels.
+pub struct Tremolo {
+    lfo_left: Oscillator,
+    lfo_right: Oscillator,
+    smooth_left: Smoother,
+    smooth_right: Smoother,
+    depth: f32,
+    phase_offset: f32,
+    control_rate: bool,
+}
+
+impl Tremolo {
+    pub fn new(sample_rate: f32) -> Self {
+        let rate = 4.0;
+        let smoother = || {
+            let mut smoother = Smoother::new(sample_rate);
+            smoother.set_mode(SmootherMode::OnePole);
+            smoother.set_time(TREMOLO_SMOOTHING_TIME);
+            smoother
+        };
+        let mut tremolo = Self {
+            lfo_left: Oscillator::new(WaveType::Sine, sample_rate, rate),
+            lfo_right: Oscillator::new(WaveType::Sine, sample_rate, rate),
+            smooth_left: smoother(),
+            smooth_right: smoother(),
+            depth: 0.5,
+            phase_offset: 0.0,
+            control_rate: false,
+        };
+        tremolo.settle();
+        tremolo
+    }
+
+    // Restart the LFOs with the gain smoothing at their first gains, so the output starts at
+    // the right level instead of fading in.
+    fn settle(&mut self) {
+        self.retrigger();
+        let lfo_left = self.lfo_left.process();
+        let lfo_right = self.lfo_right.process();
+        self.retrigger();
+        self.smooth_left.set_immediate(self.gain(lfo_left));
+        self.smooth_right.set_immediate(self.gain(lfo_right));
+    }
+
+    fn gain(&self, lfo: f32) -> f32 {
+        // Map the bipolar LFO to [1.0 - depth, 1.0].
+        1.0 - self.depth * 0.5 * (1.0 - lfo)
+    }
+
+    /// Process one mono sample, returning the left channel.
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.process_stereo(input, input).0
+    }
+
+    /// Process one stereo sample, the right channel is modulated with the configured phase offset.
+    pub fn process_stereo(&mut self, left: f32, right: f32) -> (f32, f32) {
+        if !self.control_rate {
+            let lfo_left = self.lfo_left.process();
+            let lfo_right = self.lfo_right.process();
+            self.smooth_left.set_target(self.gain(lfo_left));
+            self.smooth_right.set_target(self.gain(lfo_right));
+        }
+        (
+            left * self.smooth_left.next(),
+            right * self.smooth_right.next(),
+        )
+    }
+
+    /// Control rate tick, once called the LFOs are only read here, at the end of the next
+    /// `samples` samples, and the gain smoothing carries the output between ticks.
+    pub fn update_params(&mut self, samples: usize) {
+        self.control_rate = true;
+        let samples = samples.max(1);
+        self.lfo_left.skip(samples - 1);
+        self.lfo_right.skip(samples - 1);
+        let lfo_left = self.lfo_left.process();
+        let lfo_right = self.lfo_right.process();
+        self.smooth_left.set_target(self.gain(lfo_left));
+        self.smooth_right.set_target(self.gain(lfo_right));
+    }
+
+    /// Set the LFO waveform.
+    pub fn set_wave_type(&mut self, wave_type: WaveType) {
+        self.lfo_left.set_wave_type(wave_type);
+        self.lfo_right.set_wave_type(wave_type);
+    }
+
+    /// Set the LFO rate in Hz.
+    pub fn set_rate(&mut self, rate: f32) {
+        self.lfo_left.set_freq(rate);
+        self.lfo_right.set_freq(rate);
+    }
+
+    /// Set the LFO rate from a tempo in BPM and the number of beats per LFO cycle.
+    pub fn set_tempo(&mut self, bpm: f32, beats: f32) {
+        self.set_rate(bpm_to_hz(bpm, beats));
+    }
+
+    /// Set the modulation depth, clamped to [0.0-1.0].
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    /// Set the phase offset of the right channel LFO, clamped to [0.0-1.0] of a cycle.
+    /// An offset of 0.5 gives an auto-pan style movement.
+    pub fn set_phase_offset(&mut self, offset: f32) {
+        self.phase_offset = offset.clamp(0.0, 1.0);
+        self.lfo_right
+            .set_phase((self.lfo_left.get_phase() + self.phase_offset) % 1.0);
+    }
+
+    /// Restart the LFOs, keeping the phase offset, for syncing them to a note or beat.
+    pub fn retrigger(&mut self) {
+        self.lfo_left.reset();
+        self.lfo_right.reset();
+        self.lfo_right.set_phase(self.phase_offset);
+    }
+
+    /// Lock the LFO phases to a transport position in beats, keeping the phase offset, see
+    /// `Oscillator::lock_phase`.
+    pub fn lock_phase(&mut self, position: f32, beats: f32) {
+        self.lfo_left.lock_phase(position, beats);
+        self.lfo_right
+            .lock_phase(position + self.phase_offset * beats, beats);
+    }
+
+    /// Restart the LFOs and jump the gain smoothing to the gain they start at.
+    pub fn reset(&mut self) {
+        self.settle();
+    }
+}
+
+impl Processor for Tremolo {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+
+    fn update_params(&mut self, samples: usize) {
+        self.update_params(samples)
+    }
+}
+
+impl Preset for Tremolo {
+    type Params = TremoloParams;
+
+    fn get_params(&self) -> TremoloParams {
+        TremoloParams {
+            wave_type: self.lfo_left.get_wave_type(),
+            rate: self.lfo_left.get_freq(),
+            depth: self.depth,
+            phase_offset: self.phase_offset,
+        }
+    }
+
+    fn set_params(&mut self, params: &TremoloParams) {
+        self.set_wave_type(params.wave_type);
+        self.set_rate(params.rate);
+        self.set_depth(params.depth);
+        self.set_phase_offset(params.phase_offset);
+    }
+}
+
+// Centre delay of the dimension chorus taps in seconds.
+const DIMENSION_DELAY: f32 = 0.005;
+// LFO rate in Hz and sweep in seconds of each dimension mode.
+const DIMENSION_MODES: [(f32, f32); 4] = [
+    (0.25, 0.0004),
+    (0.25, 0.0008),
+    (0.25, 0.0012),
+    (0.5, 0.0012),
+];
+
+/// The four settings of a dimension chorus, from subtle to strong.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum DimensionMode {
+    One,
+    Two,
+    Three,
+    Four,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct DimensionChorusParams {
+    pub mode: DimensionMode,
+    pub mix: f32,
+}
+
+/// Dimension style chorus for widening without obvious pitch wobble. Two taps of one delay line
+/// are swept in opposite directions by a slow triangle LFO and their difference is added to one
+/// channel and subtracted from the other. The taps' pitch changes largely cancel, and the mono
+/// sum is exactly the dry input.
+pub struct DimensionChorus<'a> {
+    sample_rate: f32,
+    delay_line: DelayLine<'a>,
+    lfo: Oscillator,
+    mode: DimensionMode,
+    center: f32,
+    sweep: f32,
+    // Linear, so the mono sum stays exactly the dry input.
+    mix: Mix,
+}
+
+impl<'a> DimensionChorus<'a> {
+    /// The delay line needs to hold 7 ms for the strongest modes.
+    pub fn new(sample_rate: f32, delay_line: DelayLine<'a>) -> Self {
+        let mut chorus = Self {
+            sample_rate,
+            delay_line,
+            lfo: Oscillator::new(WaveType::Triangle, sample_rate, 0.25),
+            mode: DimensionMode::One,
+            center: MIN_DELAY,
+            sweep: 0.0,
+            mix: Mix::new(CrossfadeCurve::Linear),
+        };
+        chorus.set_mode(DimensionMode::Two);
+        chorus
+    }
+
+    pub fn process(&mut self, input: f32) -> (f32, f32) {
+        let modulation = self.sweep * self.lfo.process();
+        self.delay_line.write(input);
+        let a = self
+            .delay_line
+            .read_delayed_hermite(self.center + modulation);
+        let b = self
+            .delay_line
+            .read_delayed_hermite(self.center - modulation);
+        let side = a - b;
+        (
+            self.mix.process(input, input + side),
+            self.mix.process(input, input - side),
+        )
+    }
+
+    pub fn process_frame(&mut self, input: Mono) -> Stereo {
+        self.process(input.0[0]).into()
+    }
+
+    /// Set the LFO rate and sweep, limited by the delay line length.
+    pub fn set_mode(&mut self, mode: DimensionMode) {
+        self.mode = mode;
+        let (rate, sweep) = DIMENSION_MODES[mode as usize];
+        self.lfo.set_freq(rate);
+        let max_delay = (self.delay_line.len() as f32 - 3.0).max(MIN_DELAY);
+        self.center = (DIMENSION_DELAY * self.sample_rate).clamp(MIN_DELAY, max_delay);
+        // Both taps stay within the delay line.
+        self.sweep = (sweep * self.sample_rate)
+            .min(self.center - MIN_DELAY)
+            .min(max_delay - self.center);
+    }
+
+    pub fn get_mode(&self) -> DimensionMode {
+        self.mode
+    }
+
+    /// Set the LFO rate from a tempo in BPM and the number of beats per LFO cycle, replacing the
+    /// mode's rate until the mode is next set.
+    pub fn set_tempo(&mut self, bpm: f32, beats: f32) {
+        self.lfo.set_tempo(bpm, beats);
+    }
+
+    /// Lock the LFO phase to a transport position in beats, see `Oscillator::lock_phase`.
+    pub fn lock_phase(&mut self, position: f32, beats: f32) {
+        self.lfo.lock_phase(position, beats);
+    }
+
+    /// Set the level of the swept taps' difference, clamped to [0.0-1.0].
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix.set_mix(mix);
+    }
+
+    /// Clear the delay line and restart the LFO.
+    pub fn reset(&mut self) {
+        self.delay_line.reset();
+        self.lfo.reset();
+    }
+}
+
+impl Preset for DimensionChorus<'_> {
+    type Params = DimensionChorusParams;
+
+    fn get_params(&self) -> DimensionChorusParams {
+        DimensionChorusParams {
+            mode: self.mode,
+            mix: self.mix.get_mix(),
+        }
+    }
+
+    fn set_params(&mut self, params: &DimensionChorusParams) {
+        self.set_mode(params.mode);
+        self.set_mix(params.mix);
+    }
+}
+
+// Time constant of the pedal smoothing, about a 20 Hz cutoff.
+const WAH_SMOOTHING_TIME: f32 = 0.008;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct WahParams {
+    /// Pedal position, 0.0 is heel down.
+    pub position: f32,
+    /// Center frequency at heel down in Hz.
+    pub min_freq: f32,
+    /// Center frequency at toe down in Hz.
+    pub max_freq: f32,
+    pub q: f32,
+}
+
+/// Wah pedal, a resonant band pass swept exponentially between the range limits by the pedal position.
+pub struct Wah {
+    filter: InterpolatedBiquad,
+    design: Biquad,
+    smoothing: Smoother,
+    position: f32,
+    min_freq: f32,
+    max_freq: f32,
+    q: f32,
+    control_rate: bool,
+}
+
+impl Wah {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut smoothing = Smoother::with_value(sample_rate, 0.5);
+        smoothing.set_mode(SmootherMode::OnePole);
+        smoothing.set_time(WAH_SMOOTHING_TIME);
+        let mut wah = Self {
+            filter: InterpolatedBiquad::new(sample_rate),
+            design: Biquad::new(sample_rate),
+            smoothing,
+            position: -1.0,
+            min_freq: 450.0,
+            max_freq: 2200.0,
+            q: 5.0,
+            control_rate: false,
+        };
+        wah.calc_filter(0.5, 0);
+        wah
+    }
+
+    // Design the filter for the position and move to it over `samples` samples.
+    fn calc_filter(&mut self, position: f32, samples: usize) {
+        self.position = position;
+        let freq = self.min_freq * (self.max_freq / self.min_freq).powf(position);
+        self.design.set_band_pass(freq, self.q);
+        self.filter.set_target(&self.design, samples);
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        // Only recalculate the filter while the smoothed pedal is moving.
+        if !self.control_rate {
+            let position = self.smoothing.next();
+            if (position - self.position).abs() > 0.0005 {
+                self.calc_filter(position, 0);
+            }
+        }
+        self.filter.process(input)
+    }
+
+    /// Control rate tick, once called the filter is only recalculated here and the coefficients
+    /// are interpolated over the next `samples` samples.
+    pub fn update_params(&mut self, samples: usize) {
+        self.control_rate = true;
+        let mut position = self.position;
+        for _ in 0..samples {
+            position = self.smoothing.next();
+        }
+        self.calc_filter(position, samples);
+    }
+
+    /// Set the pedal position, clamped to [0.0-1.0], 0.0 is heel down.
+    pub fn set_position(&mut self, position: f32) {
+        self.smoothing.set_target(position.clamp(0.0, 1.0));
+    }
+
+    /// Set the resonance of the filter.
+    pub fn set_q(&mut self, q: f32) {
+        self.q = q.max(0.5);
+        self.calc_filter(self.position, 0);
+    }
+
+    /// Set the center frequencies at heel and toe down.
+    pub fn set_range(&mut self, min_freq: f32, max_freq: f32) {
+        self.min_freq = min_freq.max(20.0);
+        self.max_freq = max_freq.max(self.min_freq);
+        self.calc_filter(self.position, 0);
+    }
+
+    /// Clear the filter state and jump to the pedal position, ending any sweep.
+    pub fn reset(&mut self) {
+        self.smoothing.reset();
+        self.calc_filter(self.smoothing.get_value(), 0);
+        self.filter.reset();
+    }
+}
+
+impl Processor for Wah {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+
+    fn update_params(&mut self, samples: usize) {
+        self.update_params(samples)
+    }
+}
+
+impl Preset for Wah {
+    type Params = WahParams;
+
+    fn get_params(&self) -> WahParams {
+        WahParams {
+            position: self.smoothing.get_target(),
+            min_freq: self.min_freq,
+            max_freq: self.max_freq,
+            q: self.q,
+        }
+    }
+
+    fn set_params(&mut self, params: &WahParams) {
+        self.set_range(params.min_freq, params.max_freq);
+        self.set_q(params.q);
+        self.set_position(params.position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const SAMPLE_RATE_F: f32 = 44100.0;
+
+    use super::*;
+
+    #[test]
+    fn test_tremolo_preset() {
+        let mut tremolo = Tremolo::new(SAMPLE_RATE_F);
+        tremolo.set_wave_type(WaveType::Triangle);
+        tremolo.set_tempo(120.0, 1.0);
+        tremolo.set_phase_offset(0.5);
+        let params = tremolo.get_params();
+        assert_eq!(params.rate, 2.0);
+
+        let mut restored = Tremolo::new(SAMPLE_RATE_F);
+        restored.set_params(&params);
+        for _ in 0..1000 {
+            assert_eq!(
+                tremolo.process_stereo(1.0, 1.0),
+                restored.process_stereo(1.0, 1.0)
+            );
+        }
+    }
+
+    #[test]
+    fn test_tremolo_reset_keeps_level() {
+        let mut tremolo = Tremolo::new(SAMPLE_RATE_F);
+        tremolo.set_depth(0.8);
+        tremolo.set_phase_offset(0.25);
+        for _ in 0..1000 {
+            tremolo.process_stereo(1.0, 1.0);
+        }
+        // The first sample after a reset is at the LFOs' starting gain, not muted.
+        tremolo.reset();
+        let (left, right) = tremolo.process_stereo(1.0, 1.0);
+        assert!((left - 0.6).abs() < 1e-3, "{}", left);
+        assert!((right - 1.0).abs() < 1e-3, "{}", right);
+
+        // A new tremolo starts the same way.
+        let (left, right) = Tremolo::new(SAMPLE_RATE_F).process_stereo(1.0, 1.0);
+        assert!((left - 0.75).abs() < 1e-3, "{}", left);
+        assert!((right - 0.75).abs() < 1e-3, "{}", right);
+    }
+
+    #[test]
+    fn test_dimension_chorus() {
+        let mut buffer = [0.0; 512];
+        let mut chorus = DimensionChorus::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+        chorus.set_mode(DimensionMode::Four);
+        chorus.set_mix(0.7);
+        let params = chorus.get_params();
+        chorus.set_params(&DimensionChorusParams {
+            mode: DimensionMode::One,
+            mix: 0.0,
+        });
+        chorus.set_params(&params);
+        assert_eq!(chorus.get_params(), params);
+
+        let mut oscillator = Oscillator::new(WaveType::PolyBLEPSaw, SAMPLE_RATE_F, 220.0);
+        let mut side = 0.0;
+        for _ in 0..44100 {
+            let input = 0.5 * oscillator.process();
+            let (left, right) = chorus.process(input);
+            // Widened, but the mono sum is untouched.
+            assert!((left + right - 2.0 * input).abs() < 1e-5);
+            side += (left - right) * (left - right);
+        }
+        assert!((side / 44100.0_f32).sqrt() > 0.05);
+    }
+
+    #[test]
+    fn test_vibrato_latency() {
+        let mut buffer: [f32; 1024] = [0.0; 1024];
+        let mut vibrato = Vibrato::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+        vibrato.set_rate(5.0);
+        vibrato.set_depth(20.0);
+        let latency = vibrato.latency_samples();
+        assert!(latency > 1);
+
+        // With a constant modulation of zero the vibrato is a pure delay of `latency` samples.
+        let mut output = [0.0; 64];
+        for (i, item) in output.iter_mut().enumerate() {
+            let input = if i == 0 { 1.0 } else { 0.0 };
+            *item = vibrato.process_modulated(input, 0.0);
+        }
+        let peak = output.iter().enumerate().fold(
+            (0, 0.0),
+            |acc, (i, x)| if *x > acc.1 { (i, *x) } else { acc },
+        );
+        assert!((peak.0 as i32 - latency as i32).abs() <= 1);
+    }
+
+    #[test]
+    fn test_vibrato_and_wah_presets() {
+        let mut buffer: [f32; 1024] = [0.0; 1024];
+        let mut vibrato = Vibrato::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+        vibrato.set_tempo(120.0, 0.5);
+        vibrato.set_depth(30.0);
+        let params = vibrato.get_params();
+        assert_eq!(params.rate, 4.0);
+        assert_eq!(params.depth, 30.0);
+        let mut restored_buffer: [f32; 1024] = [0.0; 1024];
+        let mut restored = Vibrato::new(SAMPLE_RATE_F, DelayLine::new(&mut restored_buffer));
+        restored.set_params(&params);
+        let mut oscillator = Oscillator::new(WaveType::Saw, SAMPLE_RATE_F, 220.0);
+        for _ in 0..1000 {
+            let x = oscillator.process();
+            assert_eq!(vibrato.process(x), restored.process(x));
+        }
+
+        let mut wah = Wah::new(SAMPLE_RATE_F);
+        wah.set_range(300.0, 3000.0);
+        wah.set_q(8.0);
+        wah.set_position(0.25);
+        let params = wah.get_params();
+        assert_eq!(params.position, 0.25);
+        let mut restored = Wah::new(SAMPLE_RATE_F);
+        restored.set_params(&params);
+        for _ in 0..1000 {
+            let x = oscillator.process();
+            assert_eq!(wah.process(x), restored.process(x));
+        }
+    }
+
+    #[test]
+    fn test_tremolo_square_no_clicks() {
+        let mut tremolo = Tremolo::new(SAMPLE_RATE_F);
+        tremolo.set_wave_type(WaveType::Square);
+        tremolo.set_rate(8.0);
+        tremolo.set_depth(1.0);
+        let mut last = tremolo.process(1.0);
+        let mut min = 1.0_f32;
+        for _ in 1..44100 {
+            let out = tremolo.process(1.0);
+            assert!((out - last).abs() < 0.05);
+            min = min.min(out);
+            last = out;
+        }
+        assert!(min < 0.01);
+    }
+
+    #[test]
+    fn test_tremolo_phase_offset() {
+        let mut tremolo = Tremolo::new(SAMPLE_RATE_F);
+        tremolo.set_depth(1.0);
+        tremolo.set_phase_offset(0.5);
+        let mut sum = 0.0;
+        for _ in 0..44100 {
+            let (left, right) = tremolo.process_stereo(1.0, 1.0);
+            sum += (left - right).abs();
+        }
+        assert!(sum > 1000.0);
+    }
+
+    fn sine_gain<F: FnMut(f32) -> f32>(freq: f32, mut process: F) -> f32 {
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, freq);
+        let mut peak = 0.0_f32;
+        for i in 0..22050 {
+            let output = process(oscillator.process());
+            if i > 11025 {
+                peak = peak.max(output.abs());
+            }
+        }
+        peak
+    }
+
+    #[test]
+    fn test_wah_sweep() {
+        let mut wah = Wah::new(SAMPLE_RATE_F);
+        wah.set_position(0.0);
+        let heel_low = sine_gain(450.0, |x| wah.process(x));
+        let heel_high = sine_gain(2200.0, |x| wah.process(x));
+        assert!(heel_low > 0.9 && heel_high < 0.3);
+
+        wah.set_position(1.0);
+        let toe_low = sine_gain(450.0, |x| wah.process(x));
+        let toe_high = sine_gain(2200.0, |x| wah.process(x));
+        assert!(toe_high > 0.9 && toe_low < 0.3);
+    }
+
+    #[test]
+    fn test_wah_starts_at_mid() {
+        let mut wah = Wah::new(SAMPLE_RATE_F);
+        for _ in 0..64 {
+            wah.process(0.0);
+        }
+        // Settled at the middle instead of sweeping up from heel down.
+        assert_eq!(wah.position, 0.5);
+    }
+
+    #[test]
+    fn test_wah_control_rate() {
+        let mut per_sample = Wah::new(SAMPLE_RATE_F);
+        let mut control = crate::processor::ControlRate::new(Wah::new(SAMPLE_RATE_F), 32);
+        let mut oscillator = Oscillator::new(WaveType::Saw, SAMPLE_RATE_F, 110.0);
+        let mut error: f32 = 0.0;
+        for i in 0..16384 {
+            // Sweep the pedal once both have settled.
+            if i == 8192 {
+                per_sample.set_position(1.0);
+                control.processor.set_position(1.0);
+            }
+            let x = oscillator.process();
+            let difference = (per_sample.process(x) - control.process(x)).abs();
+            if i >= 4096 {
+                error = error.max(difference);
+            }
+        }
+        assert!(error < 0.01, "{}", error);
+    }
+
+    #[test]
+    fn test_tremolo_and_vibrato_control_rate() {
+        use crate::processor::ControlRate;
+
+        let mut per_sample = Tremolo::new(SAMPLE_RATE_F);
+        let mut control = ControlRate::new(Tremolo::new(SAMPLE_RATE_F), 32);
+        let mut error: f32 = 0.0;
+        for _ in 0..44100 {
+            error = error.max((per_sample.process(1.0) - control.process(1.0)).abs());
+        }
+        assert!(error < 0.01, "{}", error);
+
+        let (mut a, mut b) = ([0.0; 1024], [0.0; 1024]);
+        let mut per_sample = Vibrato::new(SAMPLE_RATE_F, DelayLine::new(&mut a));
+        per_sample.set_depth(50.0);
+        let mut vibrato = Vibrato::new(SAMPLE_RATE_F, DelayLine::new(&mut b));
+        vibrato.set_depth(50.0);
+        let mut control = ControlRate::new(vibrato, 32);
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 220.0);
+        let mut error: f32 = 0.0;
+        for _ in 0..44100 {
+            let x = oscillator.process();
+            error = error.max((per_sample.process(x) - control.process(x)).abs());
+        }
+        assert!(error < 1e-3, "{}", error);
+    }
+
+    #[test]
+    fn test_vibrato_depth_limited() {
+        let mut buffer: [f32; 16] = [0.0; 16];
+        let mut vibrato = Vibrato::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+        vibrato.set_rate(0.1);
+        vibrato.set_depth(1200.0);
+        assert!(vibrato.latency_samples() < 16);
+        for _ in 0..44100 {
+            assert!(vibrato.process(1.0).is_finite());
+        }
+    }
+}
@@ -0,0 +1,969 @@
+float_math!();
+
+use core::f32::consts::PI;
+
+use crate::control::{Smoother, SmootherMode};
+use crate::delay::DelayLine;
+use crate::dynamics::EnvelopeFollower;
+use crate::filter::OnePoleLowPass;
+use crate::preset::Preset;
+use crate::processor::Processor;
+use crate::synthesis::{Oscillator, WaveType};
+use crate::units::{cents_to_ratio, db_to_linear, semitones_to_ratio};
+
+const MIN_DELAY: f32 = 1.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct PitchShifterParams {
+    pub semitones: f32,
+    pub cents: f32,
+    /// Crossfade window size in seconds.
+    pub window_size: f32,
+}
+
+/// Delay based pitch shifter using two crossfaded taps sweeping through a delay line.
+pub struct PitchShifter<'a> {
+    sample_rate: f32,
+    delay_line: DelayLine<'a>,
+    semitones: f32,
+    cents: f32,
+    window: f32,
+    phase: f32,
+    phase_inc: f32,
+}
+
+impl<'a> PitchShifter<'a> {
+    pub fn new(sample_rate: f32, delay_line: DelayLine<'a>) -> Self {
+        let mut shifter = Self {
+            sample_rate,
+            delay_line,
+            semitones: 0.0,
+            cents: 0.0,
+            window: 0.0,
+            phase: 0.0,
+            phase_inc: 0.0,
+        };
+        shifter.set_window_size(0.05);
+        shifter
+    }
+
+    fn calc_phase_inc(&mut self) {
+        let ratio = cents_to_ratio(self.semitones * 100.0 + self.cents);
+        // The tap delay changes by (1 - ratio) samples per sample.
+        self.phase_inc = (1.0 - ratio) / self.window;
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.phase += self.phase_inc;
+        if self.phase >= 1.0 {
+            self.phase -= 1.0;
+        } else if self.phase < 0.0 {
+            self.phase += 1.0;
+        }
+        let phase_b = (self.phase + 0.5) % 1.0;
+
+        let tap_a = self
+            .delay_line
+            .read_delayed(MIN_DELAY + self.phase * self.window);
+        let tap_b = self
+            .delay_line
+            .read_delayed(MIN_DELAY + phase_b * self.window);
+        // Complementary raised cosine windows, each tap is silent as it jumps.
+        let gain_a = 0.5 - 0.5 * (2.0 * PI * self.phase).cos();
+        self.delay_line.write(input);
+        tap_a * gain_a + tap_b * (1.0 - gain_a)
+    }
+
+    /// Set the transposition in semitones.
+    pub fn set_semitones(&mut self, semitones: f32) {
+        self.semitones = semitones;
+        self.calc_phase_inc();
+    }
+
+    /// Set the fine transposition in cents, added to the semitones.
+    pub fn set_cents(&mut self, cents: f32) {
+        self.cents = cents;
+        self.calc_phase_inc();
+    }
+
+    /// Set the crossfade window size in seconds, limited by the delay line length.
+    /// Longer windows are smoother but add latency and echo on transients.
+    pub fn set_window_size(&mut self, window: f32) {
+        let max_window = self.delay_line.len() as f32 - 2.0 - MIN_DELAY;
+        self.window = (window * self.sample_rate).clamp(1.0, max_window.max(1.0));
+        self.calc_phase_inc();
+    }
+
+    /// Average delay of the output in samples.
+    pub fn latency_samples(&self) -> usize {
+        (MIN_DELAY + self.window * 0.5) as usize
+    }
+
+    /// Clear the delay line and restart the taps.
+    pub fn reset(&mut self) {
+        self.delay_line.reset();
+        self.phase = 0.0;
+    }
+}
+
+impl Processor for PitchShifter<'_> {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.latency_samples()
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+impl Preset for PitchShifter<'_> {
+    type Params = PitchShifterParams;
+
+    fn get_params(&self) -> PitchShifterParams {
+        PitchShifterParams {
+            semitones: self.semitones,
+            cents: self.cents,
+            window_size: self.window / self.sample_rate,
+        }
+    }
+
+    fn set_params(&mut self, params: &PitchShifterParams) {
+        self.set_semitones(params.semitones);
+        self.set_cents(params.cents);
+        self.set_window_size(params.window_size);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct OctaverParams {
+    pub dry: f32,
+    pub octave_1: f32,
+    pub octave_2: f32,
+    /// Tracking filter cutoff in Hz.
+    pub tracking_freq: f32,
+}
+
+/// Analog style octave divider, generating signals one and two octaves below a monophonic input
+/// from flip-flops clocked by the input's zero crossings.
+pub struct Octaver {
+    tracking_filter: OnePoleLowPass,
+    tracking_freq: f32,
+    envelope_coef: f32,
+    envelope: f32,
+    armed: bool,
+    flip_flop_1: bool,
+    flip_flop_2: bool,
+    tone_1: OnePoleLowPass,
+    tone_2: OnePoleLowPass,
+    dry: f32,
+    octave_1: f32,
+    octave_2: f32,
+}
+
+impl Octaver {
+    pub fn new(sample_rate: f32) -> Self {
+        let tracking_freq = 400.0;
+        let mut tracking_filter = OnePoleLowPass::new(sample_rate);
+        tracking_filter.set_freq(tracking_freq);
+        let mut tone_1 = OnePoleLowPass::new(sample_rate);
+        tone_1.set_freq(1000.0);
+        let mut tone_2 = OnePoleLowPass::new(sample_rate);
+        tone_2.set_freq(500.0);
+        Self {
+            tracking_filter,
+            tracking_freq,
+            envelope_coef: (-1.0 / (0.01 * sample_rate)).exp(),
+            envelope: 0.0,
+            armed: false,
+            flip_flop_1: false,
+            flip_flop_2: false,
+            tone_1,
+            tone_2,
+            dry: 1.0,
+            octave_1: 1.0,
+            octave_2: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let tracked = self.tracking_filter.process(input);
+        let rectified = input.abs();
+        self.envelope = rectified + self.envelope_coef * (self.envelope - rectified);
+
+        // Clock the flip-flops on rising zero crossings, with hysteresis relative to the level.
+        let hysteresis = self.envelope * 0.1;
+        if tracked < -hysteresis {
+            self.armed = true;
+        } else if tracked > hysteresis && self.armed {
+            self.armed = false;
+            self.flip_flop_1 = !self.flip_flop_1;
+            if self.flip_flop_1 {
+                self.flip_flop_2 = !self.flip_flop_2;
+            }
+        }
+
+        let square_1 = if self.flip_flop_1 { 1.0 } else { -1.0 };
+        let square_2 = if self.flip_flop_2 { 1.0 } else { -1.0 };
+        let octave_1 = self.tone_1.process(square_1 * self.envelope);
+        let octave_2 = self.tone_2.process(square_2 * self.envelope);
+
+        input * self.dry + octave_1 * self.octave_1 + octave_2 * self.octave_2
+    }
+
+    /// Set the level of the unprocessed input.
+    pub fn set_dry(&mut self, level: f32) {
+        self.dry = level;
+    }
+
+    /// Set the level of the signal one octave down.
+    pub fn set_octave_1(&mut self, level: f32) {
+        self.octave_1 = level;
+    }
+
+    /// Set the level of the signal two octaves down.
+    pub fn set_octave_2(&mut self, level: f32) {
+        self.octave_2 = level;
+    }
+
+    /// Set the cutoff of the filter used to clean the input before zero crossing detection.
+    /// It should sit just above the highest fundamental to be tracked.
+    pub fn set_tracking_freq(&mut self, freq: f32) {
+        self.tracking_freq = freq;
+        self.tracking_filter.set_freq(freq);
+    }
+
+    /// Clear the filters, envelope and flip-flops.
+    pub fn reset(&mut self) {
+        self.tracking_filter.reset();
+        self.tone_1.reset();
+        self.tone_2.reset();
+        self.envelope = 0.0;
+        self.armed = false;
+        self.flip_flop_1 = false;
+        self.flip_flop_2 = false;
+    }
+}
+
+impl Processor for Octaver {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+impl Preset for Octaver {
+    type Params = OctaverParams;
+
+    fn get_params(&self) -> OctaverParams {
+        OctaverParams {
+            dry: self.dry,
+            octave_1: self.octave_1,
+            octave_2: self.octave_2,
+            tracking_freq: self.tracking_freq,
+        }
+    }
+
+    fn set_params(&mut self, params: &OctaverParams) {
+        self.set_dry(params.dry);
+        self.set_octave_1(params.octave_1);
+        self.set_octave_2(params.octave_2);
+        self.set_tracking_freq(params.tracking_freq);
+    }
+}
+
+/// Algorithm used by the pitch detector.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DetectionMode {
+    /// Average period between rising zero crossings, cheap but only reliable on clean signals.
+    ZeroCrossing,
+    /// Normalized autocorrelation, robust against harmonics and noise.
+    Autocorrelation,
+    /// Average magnitude difference function, similar to autocorrelation without multiplies.
+    Amdf,
+}
+
+/// Monophonic pitch detector for tuners and pitch tracking.
+/// Input is collected into the analysis buffer and the estimate is updated every half buffer.
+/// The buffer should hold at least two periods of the lowest frequency to be detected.
+pub struct PitchDetector<'a> {
+    sample_rate: f32,
+    buffer: DelayLine<'a>,
+    mode: DetectionMode,
+    min_freq: f32,
+    max_freq: f32,
+    threshold: f32,
+    counter: usize,
+    freq: f32,
+    confidence: f32,
+}
+
+impl<'a> PitchDetector<'a> {
+    pub fn new(sample_rate: f32, buffer: DelayLine<'a>) -> Self {
+        Self {
+            sample_rate,
+            buffer,
+            mode: DetectionMode::Autocorrelation,
+            min_freq: 50.0,
+            max_freq: 2000.0,
+            threshold: 1e-4,
+            counter: 0,
+            freq: 0.0,
+            confidence: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) {
+        self.buffer.write(input);
+        self.counter += 1;
+        if self.counter >= (self.buffer.len() / 2).max(1) {
+            self.counter = 0;
+            self.detect();
+        }
+    }
+
+    // Sample `i` of the analysis window, oldest first.
+    fn sample(&self, i: usize) -> f32 {
+        self.buffer.tap(self.buffer.len() - i)
+    }
+
+    /// Run the detection on the current buffer contents, this is done automatically by `process`.
+    pub fn detect(&mut self) {
+        let len = self.buffer.len();
+        let energy = (0..len)
+            .map(|i| self.sample(i) * self.sample(i))
+            .sum::<f32>()
+            / len as f32;
+        let (freq, confidence) = if energy < self.threshold * self.threshold {
+            (0.0, 0.0)
+        } else {
+            match self.mode {
+                DetectionMode::ZeroCrossing => self.detect_zero_crossing(),
+                DetectionMode::Autocorrelation => self.detect_lag(|detector, lag, count| {
+                    let mut cross = 0.0;
+                    let mut power = 0.0;
+                    for i in 0..count {
+                        let (a, b) = (detector.sample(i), detector.sample(i + lag));
+                        cross += a * b;
+                        power += a * a + b * b;
+                    }
+                    2.0 * cross / power.max(f32::MIN_POSITIVE)
+                }),
+                DetectionMode::Amdf => self.detect_lag(|detector, lag, count| {
+                    let mut difference = 0.0;
+                    let mut magnitude = 0.0;
+                    for i in 0..count {
+                        let (a, b) = (detector.sample(i), detector.sample(i + lag));
+                        difference += (a - b).abs();
+                        magnitude += a.abs() + b.abs();
+                    }
+                    1.0 - difference / magnitude.max(f32::MIN_POSITIVE)
+                }),
+            }
+        };
+        self.freq = freq;
+        self.confidence = confidence;
+    }
+
+    fn detect_zero_crossing(&self) -> (f32, f32) {
+        let mut first = None;
+        let mut last = 0.0;
+        let mut periods = 0;
+        let mut sum = 0.0;
+        let mut sum_squares = 0.0;
+        for i in 1..self.buffer.len() {
+            let (a, b) = (self.sample(i - 1), self.sample(i));
+            if a < 0.0 && b >= 0.0 {
+                let crossing = (i - 1) as f32 + a / (a - b);
+                if first.is_some() {
+                    let period = crossing - last;
+                    periods += 1;
+                    sum += period;
+                    sum_squares += period * period;
+                } else {
+                    first = Some(crossing);
+                }
+                last = crossing;
+            }
+        }
+        if periods == 0 {
+            return (0.0, 0.0);
+        }
+        let mean = sum / periods as f32;
+        let deviation = (sum_squares / periods as f32 - mean * mean).max(0.0).sqrt();
+        let freq = self.sample_rate / mean;
+        if freq < self.min_freq || freq > self.max_freq {
+            return (0.0, 0.0);
+        }
+        // Regular periods give a high confidence.
+        (freq, (1.0 - deviation / mean * 10.0).clamp(0.0, 1.0))
+    }
+
+    // Search lags for the best periodicity, `similarity` returns 1.0 for a perfect match.
+    fn detect_lag<F>(&self, similarity: F) -> (f32, f32)
+    where
+        F: Fn(&Self, usize, usize) -> f32,
+    {
+        let len = self.buffer.len();
+        let max_lag = ((self.sample_rate / self.min_freq) as usize).min(len / 2);
+        let min_lag = ((self.sample_rate / self.max_freq) as usize).max(1);
+        if min_lag + 2 > max_lag {
+            return (0.0, 0.0);
+        }
+        let count = len - max_lag - 1;
+        let score = |lag: usize| similarity(self, lag, count);
+
+        let mut best = f32::MIN;
+        for lag in min_lag..=max_lag {
+            best = best.max(score(lag));
+        }
+        // Take the first peak close to the best to avoid locking onto a multiple of the period.
+        let mut previous = score(min_lag - 1);
+        let mut current = score(min_lag);
+        for lag in min_lag..max_lag {
+            let next = score(lag + 1);
+            if current >= best * 0.9 && current >= previous && current >= next {
+                // Parabolic interpolation of the peak.
+                let denominator = previous - 2.0 * current + next;
+                let offset = if denominator.abs() > f32::EPSILON {
+                    (0.5 * (previous - next) / denominator).clamp(-0.5, 0.5)
+                } else {
+                    0.0
+                };
+                return (
+                    self.sample_rate / (lag as f32 + offset),
+                    current.clamp(0.0, 1.0),
+                );
+            }
+            previous = current;
+            current = next;
+        }
+        (0.0, 0.0)
+    }
+
+    pub fn set_mode(&mut self, mode: DetectionMode) {
+        self.mode = mode;
+    }
+
+    /// Set the detectable frequency range in Hz, the lowest is also limited by the buffer length.
+    pub fn set_range(&mut self, min_freq: f32, max_freq: f32) {
+        self.min_freq = min_freq.max(1.0);
+        self.max_freq = max_freq.max(self.min_freq);
+    }
+
+    /// Set the RMS level below which the input is treated as silence.
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold.max(0.0);
+    }
+
+    /// Get the detected frequency in Hz, 0.0 if no pitch was found.
+    pub fn get_freq(&self) -> f32 {
+        self.freq
+    }
+
+    /// Get the confidence of the detected frequency, [0.0-1.0].
+    pub fn get_confidence(&self) -> f32 {
+        self.confidence
+    }
+
+    /// Clear the analysis buffer and the estimate.
+    pub fn reset(&mut self) {
+        self.buffer.reset();
+        self.counter = 0;
+        self.freq = 0.0;
+        self.confidence = 0.0;
+    }
+}
+
+/// YIN pitch detector for monophonic instrument tuning, from A. de Cheveigné and H. Kawahara,
+/// "YIN, a fundamental frequency estimator for speech and music".
+/// `N` samples are analysed per estimate, finding periods up to `N / 2` samples. The analysis of
+/// one frame is spread over the following input, one lag per sample, so the cost per audio block
+/// is bounded and proportional to its length.
+pub struct Yin<const N: usize> {
+    sample_rate: f32,
+    input: [f32; N],
+    write_index: usize,
+    frame: [f32; N],
+    // Cumulative mean normalized difference, indexed by lag.
+    difference: [f32; N],
+    lag: usize,
+    running_sum: f32,
+    min_freq: f32,
+    max_freq: f32,
+    threshold: f32,
+    freq: f32,
+    probability: f32,
+}
+
+impl<const N: usize> Yin<N> {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            input: [0.0; N],
+            write_index: 0,
+            frame: [0.0; N],
+            difference: [1.0; N],
+            lag: N,
+            running_sum: 0.0,
+            min_freq: 40.0,
+            max_freq: 2000.0,
+            threshold: 0.1,
+            freq: 0.0,
+            probability: 0.0,
+        }
+    }
+
+    fn max_lag(&self) -> usize {
+        ((self.sample_rate / self.min_freq) as usize).min(N / 2)
+    }
+
+    fn min_lag(&self) -> usize {
+        ((self.sample_rate / self.max_freq) as usize).max(2)
+    }
+
+    pub fn process(&mut self, input: f32) {
+        self.input[self.write_index] = input;
+        self.write_index += 1;
+        if self.write_index == N {
+            self.write_index = 0;
+            self.frame = self.input;
+            self.difference[0] = 1.0;
+            self.lag = 1;
+            self.running_sum = 0.0;
+        }
+        self.step();
+    }
+
+    pub fn process_block(&mut self, block: &[f32]) {
+        for x in block {
+            self.process(*x);
+        }
+    }
+
+    // Calculate the difference function for the next lag, finishing the estimate when possible.
+    fn step(&mut self) {
+        let max_lag = self.max_lag();
+        if self.lag > max_lag {
+            return;
+        }
+        let lag = self.lag;
+        let window = N / 2;
+        let difference: f32 = self.frame[..window]
+            .iter()
+            .zip(self.frame[lag..lag + window].iter())
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum();
+        self.running_sum += difference;
+        self.difference[lag] = if self.running_sum > 0.0 {
+            difference * lag as f32 / self.running_sum
+        } else {
+            1.0
+        };
+        self.lag += 1;
+
+        // Absolute threshold, take the local minimum of the first dip below it.
+        let previous = lag - 1;
+        if previous > self.min_lag()
+            && self.difference[previous] < self.threshold
+            && self.difference[lag] >= self.difference[previous]
+        {
+            self.finish(previous);
+        } else if lag == max_lag {
+            // No dip below the threshold, fall back to the global minimum.
+            let min_lag = self.min_lag().min(max_lag);
+            let best = (min_lag..max_lag)
+                .min_by(|a, b| {
+                    self.difference[*a]
+                        .partial_cmp(&self.difference[*b])
+                        .unwrap_or(core::cmp::Ordering::Equal)
+                })
+                .unwrap_or(max_lag);
+            self.finish(best);
+        }
+    }
+
+    fn finish(&mut self, lag: usize) {
+        self.lag = N;
+        let value = self.difference[lag];
+        if lag < 2 || value >= 1.0 {
+            self.freq = 0.0;
+            self.probability = 0.0;
+            return;
+        }
+        // Parabolic interpolation of the minimum.
+        let (previous, next) = (self.difference[lag - 1], self.difference[lag + 1]);
+        let denominator = previous - 2.0 * value + next;
+        let offset = if denominator.abs() > f32::EPSILON {
+            (0.5 * (previous - next) / denominator).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+        self.freq = self.sample_rate / (lag as f32 + offset);
+        self.probability = (1.0 - value).clamp(0.0, 1.0);
+    }
+
+    /// Set the detectable frequency range in Hz, the lowest is also limited to `2 * sample_rate / N`.
+    pub fn set_range(&mut self, min_freq: f32, max_freq: f32) {
+        self.min_freq = min_freq.max(1.0);
+        self.max_freq = max_freq.max(self.min_freq);
+    }
+
+    /// Set the threshold of the normalized difference, lower values reject more estimates.
+    /// Typical values are [0.1-0.2].
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold.clamp(0.0, 1.0);
+    }
+
+    /// Get the detected frequency in Hz, 0.0 if no pitch was found.
+    pub fn get_freq(&self) -> f32 {
+        self.freq
+    }
+
+    /// Get the probability of the detected frequency being correct, [0.0-1.0].
+    pub fn get_probability(&self) -> f32 {
+        self.probability
+    }
+
+    /// Clear the input and the estimate.
+    pub fn reset(&mut self) {
+        self.input = [0.0; N];
+        self.write_index = 0;
+        self.lag = N;
+        self.freq = 0.0;
+        self.probability = 0.0;
+    }
+}
+
+// Input level below which the pitch follower stays silent, in dBFS.
+const FOLLOWER_MIN_DB: f32 = -50.0;
+// Fade time of the pitch follower's gate, in seconds.
+const FOLLOWER_GATE_TIME: f32 = 0.01;
+
+/// Guitar synth style voice, an oscillator following the pitch of a monophonic input found by
+/// `Yin<N>` and the level of the input. Estimates below the minimum confidence are ignored, the
+/// voice fades out when there is no confident pitch or the input is quiet. Pitch changes glide,
+/// except for the first note after silence which starts at its pitch.
+pub struct PitchFollower<const N: usize> {
+    detector: Yin<N>,
+    oscillator: Oscillator,
+    envelope: EnvelopeFollower,
+    glide: Smoother,
+    gate: Smoother,
+    min_probability: f32,
+    min_level: f32,
+    transpose: f32,
+}
+
+impl<const N: usize> PitchFollower<N> {
+    pub fn new(sample_rate: f32, wave_type: WaveType) -> Self {
+        let mut glide = Smoother::new(sample_rate);
+        glide.set_mode(SmootherMode::OnePole);
+        let mut gate = Smoother::new(sample_rate);
+        gate.set_time(FOLLOWER_GATE_TIME);
+        let mut follower = Self {
+            detector: Yin::new(sample_rate),
+            oscillator: Oscillator::new(wave_type, sample_rate, 0.0),
+            envelope: EnvelopeFollower::new(sample_rate),
+            glide,
+            gate,
+            min_probability: 0.8,
+            min_level: db_to_linear(FOLLOWER_MIN_DB),
+            transpose: 1.0,
+        };
+        follower.set_glide(0.03);
+        follower
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.detector.process(input);
+        let level = self.envelope.process(input);
+
+        let freq = self.detector.get_freq();
+        let confident = freq > 0.0 && self.detector.get_probability() >= self.min_probability;
+        if confident && level >= self.min_level {
+            if self.gate.get_target() == 0.0 {
+                self.glide.set_immediate(freq * self.transpose);
+            } else {
+                self.glide.set_target(freq * self.transpose);
+            }
+            self.gate.set_target(1.0);
+        } else if self.gate.get_target() != 0.0 {
+            self.gate.set_target(0.0);
+        }
+
+        self.oscillator.set_freq(self.glide.next());
+        self.oscillator.process() * level * self.gate.next()
+    }
+
+    /// Set the glide time in seconds.
+    pub fn set_glide(&mut self, time: f32) {
+        self.glide.set_time(time);
+    }
+
+    /// Set the probability, [0.0-1.0], below which pitch estimates are ignored.
+    pub fn set_min_confidence(&mut self, probability: f32) {
+        self.min_probability = probability.clamp(0.0, 1.0);
+    }
+
+    /// Set the interval in semitones between the input and the voice.
+    pub fn set_transpose(&mut self, semitones: f32) {
+        self.transpose = semitones_to_ratio(semitones);
+    }
+
+    pub fn set_wave_type(&mut self, wave_type: WaveType) {
+        self.oscillator.set_wave_type(wave_type);
+    }
+
+    /// Set the range of input frequencies followed in Hz.
+    pub fn set_range(&mut self, min_freq: f32, max_freq: f32) {
+        self.detector.set_range(min_freq, max_freq);
+    }
+
+    /// Get the frequency the voice is playing in Hz.
+    pub fn get_freq(&self) -> f32 {
+        self.glide.get_value()
+    }
+
+    /// Delay from the input to the voice following it, in samples.
+    pub fn latency_samples(&self) -> usize {
+        N
+    }
+
+    pub fn reset(&mut self) {
+        self.detector.reset();
+        self.oscillator.reset();
+        self.envelope.reset();
+        self.gate.set_immediate(0.0);
+    }
+}
+
+impl<const N: usize> Processor for PitchFollower<N> {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.latency_samples()
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const SAMPLE_RATE_F: f32 = 44100.0;
+
+    use super::*;
+    use crate::random::{Random, XorShift32};
+    use crate::synthesis::{Oscillator, WaveType};
+
+    fn zero_crossings(data: &[f32]) -> usize {
+        data.windows(2)
+            .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+            .count()
+    }
+
+    #[test]
+    fn test_pitch_follower() {
+        let mut follower: PitchFollower<2048> = PitchFollower::new(SAMPLE_RATE_F, WaveType::Sine);
+        follower.set_transpose(12.0);
+        let mut oscillator = Oscillator::new(WaveType::Saw, SAMPLE_RATE_F, 220.0);
+        let mut output: Vec<f32> = (0..22050)
+            .map(|_| follower.process(0.5 * oscillator.process()))
+            .collect();
+        // An octave above the input once it has locked on.
+        assert!(
+            (follower.get_freq() - 440.0).abs() < 5.0,
+            "{}",
+            follower.get_freq()
+        );
+        let crossings = zero_crossings(&output[11025..]);
+        assert!((105..=115).contains(&crossings), "{}", crossings);
+
+        // Glides to a new note.
+        oscillator.set_freq(330.0);
+        output = (0..22050)
+            .map(|_| follower.process(0.5 * oscillator.process()))
+            .collect();
+        assert!(
+            (follower.get_freq() - 660.0).abs() < 5.0,
+            "{}",
+            follower.get_freq()
+        );
+        let crossings = zero_crossings(&output[11025..]);
+        assert!((160..=170).contains(&crossings), "{}", crossings);
+
+        // Silence fades the voice out.
+        output = (0..22050).map(|_| follower.process(0.0)).collect();
+        assert!(output[11025..].iter().all(|x| x.abs() < 1e-3));
+    }
+
+    #[test]
+    fn test_pitch_shift_octave() {
+        let mut buffer: [f32; 4096] = [0.0; 4096];
+        let mut shifter = PitchShifter::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+        shifter.set_semitones(12.0);
+        shifter.set_window_size(0.03);
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 200.0);
+        let output: Vec<f32> = (0..44100)
+            .map(|_| shifter.process(oscillator.process()))
+            .collect();
+        // One second of output, expect roughly 400 cycles.
+        let crossings = zero_crossings(&output);
+        assert!(crossings > 360 && crossings < 440, "{}", crossings);
+    }
+
+    #[test]
+    fn test_octaver_divides() {
+        let mut octaver = Octaver::new(SAMPLE_RATE_F);
+        octaver.set_dry(0.0);
+        octaver.set_octave_1(1.0);
+        octaver.set_octave_2(0.0);
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 200.0);
+        let output: Vec<f32> = (0..44100)
+            .map(|_| octaver.process(oscillator.process()))
+            .collect();
+        let crossings = zero_crossings(&output);
+        assert!(crossings > 95 && crossings < 105, "{}", crossings);
+
+        octaver.set_octave_1(0.0);
+        octaver.set_octave_2(1.0);
+        let output: Vec<f32> = (0..44100)
+            .map(|_| octaver.process(oscillator.process()))
+            .collect();
+        let crossings = zero_crossings(&output);
+        assert!(crossings > 45 && crossings < 55, "{}", crossings);
+    }
+
+    #[test]
+    fn test_pitch_shifter_and_octaver_presets() {
+        let mut buffer: [f32; 4096] = [0.0; 4096];
+        let mut shifter = PitchShifter::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+        shifter.set_semitones(-5.0);
+        shifter.set_cents(12.0);
+        shifter.set_window_size(0.03);
+        let params = shifter.get_params();
+        assert!((params.window_size - 0.03).abs() < 1e-6);
+        let mut restored_buffer: [f32; 4096] = [0.0; 4096];
+        let mut restored = PitchShifter::new(SAMPLE_RATE_F, DelayLine::new(&mut restored_buffer));
+        restored.set_params(&params);
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 200.0);
+        for _ in 0..4096 {
+            let x = oscillator.process();
+            assert!((shifter.process(x) - restored.process(x)).abs() < 1e-4);
+        }
+
+        let mut octaver = Octaver::new(SAMPLE_RATE_F);
+        octaver.set_dry(0.5);
+        octaver.set_octave_2(0.8);
+        octaver.set_tracking_freq(300.0);
+        let params = octaver.get_params();
+        assert_eq!(params.tracking_freq, 300.0);
+        let mut restored = Octaver::new(SAMPLE_RATE_F);
+        restored.set_params(&params);
+        for _ in 0..4096 {
+            let x = oscillator.process();
+            assert_eq!(octaver.process(x), restored.process(x));
+        }
+    }
+
+    #[test]
+    fn test_pitch_detector_modes() {
+        for mode in [
+            DetectionMode::ZeroCrossing,
+            DetectionMode::Autocorrelation,
+            DetectionMode::Amdf,
+        ]
+        .iter()
+        {
+            let mut buffer: [f32; 2048] = [0.0; 2048];
+            let mut detector = PitchDetector::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+            detector.set_mode(*mode);
+            let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 220.0);
+            for _ in 0..8192 {
+                detector.process(oscillator.process());
+            }
+            let freq = detector.get_freq();
+            assert!((freq - 220.0).abs() < 1.0, "{:?} {}", mode, freq);
+            assert!(detector.get_confidence() > 0.8);
+        }
+    }
+
+    #[test]
+    fn test_pitch_detector_harmonics_and_silence() {
+        let mut buffer: [f32; 2048] = [0.0; 2048];
+        let mut detector = PitchDetector::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+        // A strong second harmonic must not be mistaken for the fundamental.
+        let mut fundamental = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 110.0);
+        let mut harmonic = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 220.0);
+        for _ in 0..8192 {
+            detector.process(0.3 * fundamental.process() + 0.7 * harmonic.process());
+        }
+        assert!((detector.get_freq() - 110.0).abs() < 1.0);
+
+        for _ in 0..4096 {
+            detector.process(0.0);
+        }
+        assert_eq!(detector.get_freq(), 0.0);
+        assert_eq!(detector.get_confidence(), 0.0);
+    }
+
+    #[test]
+    fn test_yin_low_e() {
+        let mut yin: Yin<2048> = Yin::new(SAMPLE_RATE_F);
+        let mut oscillator = Oscillator::new(WaveType::PolyBLEPSaw, SAMPLE_RATE_F, 82.41);
+        let mut block = [0.0; 48];
+        for _ in 0..200 {
+            for x in block.iter_mut() {
+                *x = oscillator.process();
+            }
+            yin.process_block(&block);
+        }
+        assert!((yin.get_freq() - 82.41).abs() < 0.2, "{}", yin.get_freq());
+        assert!(yin.get_probability() > 0.9);
+    }
+
+    #[test]
+    fn test_yin_noise() {
+        let mut yin: Yin<1024> = Yin::new(SAMPLE_RATE_F);
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 440.0);
+        for _ in 0..4096 {
+            yin.process(oscillator.process());
+        }
+        assert!((yin.get_freq() - 440.0).abs() < 0.5);
+
+        let mut random = XorShift32::new(1);
+        for _ in 0..4096 {
+            yin.process(random.next_f32_unit() - 0.5);
+        }
+        assert!(yin.get_probability() < 0.8);
+    }
+
+    #[test]
+    fn test_pitch_shift_unison() {
+        let mut buffer: [f32; 4096] = [0.0; 4096];
+        let mut shifter = PitchShifter::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+        let latency = shifter.latency_samples();
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 200.0);
+        let input: Vec<f32> = (0..8192).map(|_| oscillator.process()).collect();
+        let output: Vec<f32> = input.iter().map(|x| shifter.process(*x)).collect();
+        for i in 4096..8192 {
+            assert!((output[i] - input[i - latency]).abs() < 0.05);
+        }
+    }
+}
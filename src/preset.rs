@@ -0,0 +1,100 @@
+//! Capture and restore the user facing parameters of an effect as a whole, for storing presets
+//! in flash or EEPROM. Parameter structs are plain `#[repr(C)]` data with `serde` support behind
+//! the `serde` feature, so they can be written with postcard or copied as bytes. Enum fields are
+//! `#[repr(u8)]`, validate them before transmuting bytes read back from storage.
+
+/// An effect whose parameters can be saved and restored together.
+pub trait Preset {
+    type Params: Copy;
+
+    /// Capture the current parameters, in the units taken by the setters.
+    fn get_params(&self) -> Self::Params;
+
+    /// Apply all parameters as if each setter were called.
+    fn set_params(&mut self, params: &Self::Params);
+}
+
+#[cfg(all(test, any(feature = "serde", feature = "defmt")))]
+mod tests {
+    #[cfg(feature = "serde")]
+    const SAMPLE_RATE_F: f32 = 44100.0;
+
+    #[cfg(feature = "serde")]
+    use super::*;
+    use crate::distortion::{ClipType, DistortionParams};
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_postcard_round_trip() {
+        let mut distortion = crate::distortion::Distortion::new(SAMPLE_RATE_F);
+        distortion.set_gain(24.0);
+        distortion.set_clip_type(ClipType::Asymmetric);
+        distortion.set_mid(0.2);
+        let params = distortion.get_params();
+        let mut buffer = [0; 64];
+        let bytes = postcard::to_slice(&params, &mut buffer).unwrap();
+        let restored: DistortionParams = postcard::from_bytes(bytes).unwrap();
+        assert_eq!(restored, params);
+    }
+
+    #[cfg(feature = "defmt")]
+    fn assert_format<T: defmt::Format>() {}
+
+    #[cfg(feature = "defmt")]
+    #[test]
+    fn test_defmt_format() {
+        use crate::cabinet::CabSimParams;
+        use crate::convert::NoiseShaping;
+        use crate::distortion::{ExciterParams, HarmonicMode};
+        use crate::dynamics::{
+            CompressorParams, DuckerParams, GateParams, MultibandCompressorParams,
+        };
+        use crate::echo::TapeDelayParams;
+        use crate::error::Error;
+        use crate::looper::LooperState;
+        use crate::mix::CrossfadeCurve;
+        use crate::modulation::{
+            DimensionChorusParams, DimensionMode, TremoloParams, VibratoParams, WahParams,
+        };
+        use crate::pitch::{OctaverParams, PitchShifterParams};
+        use crate::stereo::{
+            AutoPanParams, HaasParams, MsChannel, PanLaw, PannerParams, StereoChannel,
+            StereoWidenerParams,
+        };
+        use crate::synthesis::WaveType;
+        use crate::units::NoteDivision;
+        use crate::vocoder::VocoderParams;
+
+        assert_format::<Error>();
+        assert_format::<CabSimParams>();
+        assert_format::<NoiseShaping>();
+        assert_format::<ClipType>();
+        assert_format::<DistortionParams>();
+        assert_format::<ExciterParams>();
+        assert_format::<HarmonicMode>();
+        assert_format::<CompressorParams>();
+        assert_format::<DuckerParams>();
+        assert_format::<GateParams>();
+        assert_format::<MultibandCompressorParams>();
+        assert_format::<TapeDelayParams>();
+        assert_format::<LooperState>();
+        assert_format::<CrossfadeCurve>();
+        assert_format::<DimensionChorusParams>();
+        assert_format::<DimensionMode>();
+        assert_format::<TremoloParams>();
+        assert_format::<VibratoParams>();
+        assert_format::<WahParams>();
+        assert_format::<OctaverParams>();
+        assert_format::<PitchShifterParams>();
+        assert_format::<AutoPanParams>();
+        assert_format::<HaasParams>();
+        assert_format::<MsChannel>();
+        assert_format::<PanLaw>();
+        assert_format::<PannerParams>();
+        assert_format::<StereoChannel>();
+        assert_format::<StereoWidenerParams>();
+        assert_format::<WaveType>();
+        assert_format::<NoteDivision>();
+        assert_format::<VocoderParams>();
+    }
+}
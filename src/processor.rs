@@ -0,0 +1,680 @@
+//! Common interface for mono audio processors, and combinators building larger processors out
+//! of smaller ones with static dispatch.
+
+use crate::control::Smoother;
+use crate::frame::Frame;
+use crate::mix::{Crossfade, CrossfadeCurve};
+use crate::preset::Preset;
+
+/// A mono audio processor.
+pub trait Processor {
+    fn process(&mut self, input: f32) -> f32;
+
+    /// Process a block in place.
+    fn process_block(&mut self, block: &mut [f32]) {
+        for sample in block.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
+
+    /// Clear internal state, keeping the parameters.
+    fn reset(&mut self) {}
+
+    /// Delay in samples the processor adds to the signal, for aligning parallel paths.
+    fn latency_samples(&self) -> usize {
+        0
+    }
+
+    /// Control rate tick, called before each run of `samples` samples, see `ControlRate`.
+    /// Processors with expensive coefficient calculations do them here and interpolate to the
+    /// new coefficients over the following samples. Processors that never get a tick keep
+    /// updating their coefficients from `process`.
+    fn update_params(&mut self, _samples: usize) {}
+}
+
+impl<P: Processor + ?Sized> Processor for &mut P {
+    fn process(&mut self, input: f32) -> f32 {
+        (**self).process(input)
+    }
+
+    fn process_block(&mut self, block: &mut [f32]) {
+        (**self).process_block(block)
+    }
+
+    fn reset(&mut self) {
+        (**self).reset()
+    }
+
+    fn latency_samples(&self) -> usize {
+        (**self).latency_samples()
+    }
+
+    fn update_params(&mut self, samples: usize) {
+        (**self).update_params(samples)
+    }
+}
+
+/// Two processors in series, build longer chains with the `chain!` macro.
+/// Blocks are run through each processor in turn, so block optimized paths are kept.
+pub struct Chain<A, B>(pub A, pub B);
+
+impl<A: Processor, B: Processor> Processor for Chain<A, B> {
+    fn process(&mut self, input: f32) -> f32 {
+        self.1.process(self.0.process(input))
+    }
+
+    fn process_block(&mut self, block: &mut [f32]) {
+        self.0.process_block(block);
+        self.1.process_block(block);
+    }
+
+    fn reset(&mut self) {
+        self.0.reset();
+        self.1.reset();
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.0.latency_samples() + self.1.latency_samples()
+    }
+
+    fn update_params(&mut self, samples: usize) {
+        self.0.update_params(samples);
+        self.1.update_params(samples);
+    }
+}
+
+/// Two processors fed the same input, with their outputs summed.
+/// For example a dry `Identity` path in parallel with a compressor gives New York compression.
+/// Paths with different `latency_samples` comb filter when summed, delay the shorter one to match.
+pub struct Parallel<A, B> {
+    pub a: A,
+    pub b: B,
+    gain_a: f32,
+    gain_b: f32,
+}
+
+impl<A, B> Parallel<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Self {
+            a,
+            b,
+            gain_a: 1.0,
+            gain_b: 1.0,
+        }
+    }
+
+    /// Set the linear gains applied to each path before summing.
+    pub fn set_gains(&mut self, gain_a: f32, gain_b: f32) {
+        self.gain_a = gain_a;
+        self.gain_b = gain_b;
+    }
+}
+
+impl<A: Processor, B: Processor> Processor for Parallel<A, B> {
+    fn process(&mut self, input: f32) -> f32 {
+        self.a.process(input) * self.gain_a + self.b.process(input) * self.gain_b
+    }
+
+    fn reset(&mut self) {
+        self.a.reset();
+        self.b.reset();
+    }
+
+    // The paths aren't aligned, the sum is reported at the later one.
+    fn latency_samples(&self) -> usize {
+        self.a.latency_samples().max(self.b.latency_samples())
+    }
+
+    fn update_params(&mut self, samples: usize) {
+        self.a.update_params(samples);
+        self.b.update_params(samples);
+    }
+}
+
+/// A processor with its output fed back to its input through a gain, delayed by one sample.
+/// For example a feedback flanger is a `Vibrato` wrapped in `Feedback`.
+pub struct Feedback<P> {
+    pub processor: P,
+    feedback: f32,
+    last: f32,
+}
+
+impl<P> Feedback<P> {
+    pub fn new(processor: P) -> Self {
+        Self {
+            processor,
+            feedback: 0.0,
+            last: 0.0,
+        }
+    }
+
+    /// Set the feedback gain, clamped to [-0.99, 0.99] to keep the loop stable around unity gain
+    /// processors.
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.feedback = feedback.clamp(-0.99, 0.99);
+    }
+
+    pub fn get_feedback(&self) -> f32 {
+        self.feedback
+    }
+}
+
+impl<P: Processor> Processor for Feedback<P> {
+    fn process(&mut self, input: f32) -> f32 {
+        self.last = self.processor.process(input + self.last * self.feedback);
+        self.last
+    }
+
+    fn reset(&mut self) {
+        self.processor.reset();
+        self.last = 0.0;
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.processor.latency_samples()
+    }
+
+    fn update_params(&mut self, samples: usize) {
+        self.processor.update_params(samples)
+    }
+}
+
+/// Runs a processor with a control rate tick every `interval` samples, so coefficients are
+/// recalculated at a fixed rate instead of per sample. Blocks are split at the ticks.
+pub struct ControlRate<P> {
+    pub processor: P,
+    interval: usize,
+    counter: usize,
+}
+
+impl<P> ControlRate<P> {
+    /// Tick every `interval` samples, at least 1. 16 to 64 samples is typical.
+    pub fn new(processor: P, interval: usize) -> Self {
+        Self {
+            processor,
+            interval: interval.max(1),
+            counter: 0,
+        }
+    }
+
+    pub fn get_interval(&self) -> usize {
+        self.interval
+    }
+}
+
+impl<P: Processor> Processor for ControlRate<P> {
+    fn process(&mut self, input: f32) -> f32 {
+        if self.counter == 0 {
+            self.processor.update_params(self.interval);
+            self.counter = self.interval;
+        }
+        self.counter -= 1;
+        self.processor.process(input)
+    }
+
+    fn process_block(&mut self, block: &mut [f32]) {
+        let mut block = block;
+        while !block.is_empty() {
+            if self.counter == 0 {
+                self.processor.update_params(self.interval);
+                self.counter = self.interval;
+            }
+            let len = self.counter.min(block.len());
+            let (run, rest) = block.split_at_mut(len);
+            self.processor.process_block(run);
+            self.counter -= len;
+            block = rest;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.processor.reset();
+        self.counter = 0;
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.processor.latency_samples()
+    }
+}
+
+/// What a bypassed processor does once the fade to the dry signal has finished.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BypassMode {
+    /// Keep processing and discard the output, so tails and envelopes are current when the
+    /// processor is switched back in.
+    KeepWarm,
+    /// Reset the processor and stop running it, saving the CPU time. Reverb and delay tails
+    /// are gone when it is switched back in.
+    Flush,
+}
+
+// Default bypass crossfade time in seconds.
+const BYPASS_FADE_TIME: f32 = 0.01;
+
+/// Click free bypass for any processor, switching crossfades linearly between the dry input and
+/// the processed signal. Processors with latency aren't compensated, the dry path is undelayed.
+pub struct Bypass<P> {
+    pub processor: P,
+    mode: BypassMode,
+    bypassed: bool,
+    flushed: bool,
+    fade: Smoother,
+    crossfade: Crossfade,
+}
+
+impl<P: Processor> Bypass<P> {
+    /// Starts active, keeping the processor warm while bypassed, with a 10 ms fade.
+    pub fn new(processor: P, sample_rate: f32) -> Self {
+        let mut fade = Smoother::new(sample_rate);
+        fade.set_time(BYPASS_FADE_TIME);
+        fade.set_immediate(1.0);
+        Self {
+            processor,
+            mode: BypassMode::KeepWarm,
+            bypassed: false,
+            flushed: false,
+            fade,
+            crossfade: Crossfade::new(CrossfadeCurve::Linear),
+        }
+    }
+
+    pub fn set_bypassed(&mut self, bypassed: bool) {
+        if bypassed == self.bypassed {
+            return;
+        }
+        self.bypassed = bypassed;
+        self.flushed = false;
+        self.fade.set_target(if bypassed { 0.0 } else { 1.0 });
+    }
+
+    /// True once bypassed, including while fading out.
+    pub fn is_bypassed(&self) -> bool {
+        self.bypassed
+    }
+
+    pub fn set_mode(&mut self, mode: BypassMode) {
+        self.mode = mode;
+    }
+
+    /// Set the crossfade time in seconds.
+    pub fn set_fade_time(&mut self, time: f32) {
+        self.fade.set_time(time);
+    }
+
+    // Fully bypassed with nothing left to run.
+    fn is_idle(&self) -> bool {
+        self.flushed && self.fade.is_settled()
+    }
+}
+
+impl<P: Processor> Processor for Bypass<P> {
+    fn process(&mut self, input: f32) -> f32 {
+        if self.is_idle() {
+            return input;
+        }
+        self.crossfade.set_position(self.fade.next());
+        let processed = self.processor.process(input);
+        if self.bypassed && self.fade.is_settled() && self.mode == BypassMode::Flush {
+            self.processor.reset();
+            self.flushed = true;
+        }
+        self.crossfade.process(input, processed)
+    }
+
+    fn process_block(&mut self, block: &mut [f32]) {
+        if self.is_idle() {
+            return;
+        }
+        if !self.bypassed && self.fade.is_settled() {
+            self.processor.process_block(block);
+            return;
+        }
+        for sample in block.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.processor.reset();
+        self.fade.reset();
+    }
+
+    fn latency_samples(&self) -> usize {
+        if self.bypassed {
+            0
+        } else {
+            self.processor.latency_samples()
+        }
+    }
+
+    fn update_params(&mut self, samples: usize) {
+        if !self.is_idle() {
+            self.processor.update_params(samples);
+        }
+    }
+}
+
+/// One mono processor per channel for multichannel codecs, from a stereo pair up to 8 channel
+/// installations. Each channel keeps its own state, parameters are linked by setting them
+/// through `set_all` or `set_params` and can be set per channel through `channel`. At least one
+/// channel is needed.
+pub struct MultiChannel<P, const CH: usize> {
+    pub processors: [P; CH],
+}
+
+impl<P: Processor, const CH: usize> MultiChannel<P, CH> {
+    // The channel count, failing the build for a wrapper without channels.
+    const CHANNELS: usize = {
+        assert!(CH > 0, "a MultiChannel needs at least one channel");
+        CH
+    };
+
+    pub fn new(processors: [P; CH]) -> Self {
+        let _ = Self::CHANNELS;
+        Self { processors }
+    }
+
+    /// Build each channel's processor from its index.
+    pub fn from_fn<F: FnMut(usize) -> P>(f: F) -> Self {
+        Self::new(core::array::from_fn(f))
+    }
+
+    pub fn process_frame(&mut self, input: Frame<CH>) -> Frame<CH> {
+        let mut channels = self.processors.iter_mut();
+        input.map(|x| channels.next().map_or(x, |processor| processor.process(x)))
+    }
+
+    /// Process a block of one channel in place.
+    pub fn process_block(&mut self, channel: usize, block: &mut [f32]) {
+        if let Some(processor) = self.processors.get_mut(channel) {
+            processor.process_block(block);
+        }
+    }
+
+    /// Process interleaved frames in place, as read from a multichannel codec. A trailing
+    /// partial frame is left unchanged.
+    pub fn process_interleaved(&mut self, interleaved: &mut [f32]) {
+        for frame in interleaved.chunks_exact_mut(CH) {
+            for (sample, processor) in frame.iter_mut().zip(self.processors.iter_mut()) {
+                *sample = processor.process(*sample);
+            }
+        }
+    }
+
+    /// Apply `f` to every channel's processor, linking a parameter across the channels.
+    pub fn set_all<F: FnMut(&mut P)>(&mut self, mut f: F) {
+        for processor in self.processors.iter_mut() {
+            f(processor);
+        }
+    }
+
+    pub fn channel(&mut self, channel: usize) -> Option<&mut P> {
+        self.processors.get_mut(channel)
+    }
+
+    /// Delay of the slowest channel in samples.
+    pub fn latency_samples(&self) -> usize {
+        self.processors
+            .iter()
+            .map(|processor| processor.latency_samples())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Control rate tick for every channel, see `Processor::update_params`.
+    pub fn update_params(&mut self, samples: usize) {
+        self.set_all(|processor| processor.update_params(samples));
+    }
+
+    pub fn reset(&mut self) {
+        self.set_all(|processor| processor.reset());
+    }
+}
+
+/// Parameters are taken from the first channel and set on all of them.
+impl<P: Processor + Preset, const CH: usize> Preset for MultiChannel<P, CH> {
+    type Params = P::Params;
+
+    fn get_params(&self) -> Self::Params {
+        let _ = Self::CHANNELS;
+        self.processors[0].get_params()
+    }
+
+    fn set_params(&mut self, params: &Self::Params) {
+        self.set_all(|processor| processor.set_params(params));
+    }
+}
+
+/// Passes the input through unchanged, the dry path of parallel routings.
+pub struct Identity;
+
+impl Processor for Identity {
+    fn process(&mut self, input: f32) -> f32 {
+        input
+    }
+
+    fn process_block(&mut self, _block: &mut [f32]) {}
+}
+
+/// Chain processors in series, `chain!(a, b, c)` is `Chain(a, Chain(b, c))`.
+#[macro_export]
+macro_rules! chain {
+    ($processor:expr $(,)?) => {
+        $processor
+    };
+    ($first:expr, $($rest:expr),+ $(,)?) => {
+        $crate::processor::Chain($first, $crate::chain!($($rest),+))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    const SAMPLE_RATE_F: f32 = 44100.0;
+
+    use super::*;
+    use crate::distortion::Distortion;
+    use crate::filter::Biquad;
+    use crate::mix::Gain;
+    use crate::synthesis::{Oscillator, WaveType};
+
+    fn make() -> (Gain, Distortion, Biquad) {
+        let mut gain = Gain::new(SAMPLE_RATE_F);
+        gain.set_db(-6.0);
+        let mut filter = Biquad::new(SAMPLE_RATE_F);
+        filter.set_low_pass(2000.0, 0.707);
+        (gain, Distortion::new(SAMPLE_RATE_F), filter)
+    }
+
+    #[test]
+    fn test_chain_matches_manual() {
+        let (gain, distortion, filter) = make();
+        let mut chain = chain!(gain, distortion, filter);
+        let (mut gain, mut distortion, mut filter) = make();
+        let mut oscillator = Oscillator::new(WaveType::Saw, SAMPLE_RATE_F, 110.0);
+        let input: Vec<f32> = (0..512).map(|_| oscillator.process()).collect();
+        let mut block = input.clone();
+        chain.process_block(&mut block);
+        for (x, y) in input.iter().zip(block.iter()) {
+            let expected = filter.process(distortion.process(gain.process(*x)));
+            assert!((expected - y).abs() < 1e-6);
+        }
+        // Borrowed processors chain too.
+        let mut borrowed = chain!(&mut chain.0, &mut chain.1);
+        borrowed.reset();
+        assert!(borrowed.process(0.0).is_finite());
+    }
+
+    #[test]
+    fn test_parallel_sums_paths() {
+        let mut gain = Gain::new(SAMPLE_RATE_F);
+        gain.set_linear_immediate(0.25);
+        let mut parallel = Parallel::new(Identity, gain);
+        assert_eq!(parallel.process(1.0), 1.25);
+        parallel.set_gains(0.5, 2.0);
+        assert_eq!(parallel.process(1.0), 1.0);
+    }
+
+    #[test]
+    fn test_feedback_impulse_response() {
+        let mut feedback = Feedback::new(Identity);
+        feedback.set_feedback(0.5);
+        assert_eq!(feedback.process(1.0), 1.0);
+        assert_eq!(feedback.process(0.0), 0.5);
+        assert_eq!(feedback.process(0.0), 0.25);
+        feedback.reset();
+        assert_eq!(feedback.process(0.0), 0.0);
+        feedback.set_feedback(2.0);
+        assert_eq!(feedback.get_feedback(), 0.99);
+    }
+
+    // Records its control rate ticks.
+    struct Ticks(Vec<usize>);
+
+    impl Processor for Ticks {
+        fn process(&mut self, input: f32) -> f32 {
+            input
+        }
+
+        fn update_params(&mut self, samples: usize) {
+            self.0.push(samples);
+        }
+    }
+
+    #[test]
+    fn test_control_rate_ticks() {
+        let mut control = ControlRate::new(Chain(Identity, Ticks(Vec::new())), 32);
+        let mut block = [0.0; 40];
+        control.process_block(&mut block);
+        control.process(0.0);
+        assert_eq!(control.processor.1 .0, [32, 32]);
+        // The second tick covers samples 32 to 63, the next one is due after 23 more.
+        for _ in 0..23 {
+            control.process(0.0);
+        }
+        assert_eq!(control.processor.1 .0.len(), 2);
+        control.process_block(&mut [0.0; 1]);
+        assert_eq!(control.processor.1 .0.len(), 3);
+    }
+
+    #[test]
+    fn test_reset_repeats_output() {
+        use crate::delay::DelayLine;
+        use crate::dynamics::Compressor;
+        use crate::modulation::{Tremolo, Vibrato};
+
+        let mut buffer = [0.0; 512];
+        let mut vibrato = Vibrato::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+        vibrato.set_depth(20.0);
+        let (_, distortion, filter) = make();
+        let mut chain = chain!(
+            distortion,
+            filter,
+            Tremolo::new(SAMPLE_RATE_F),
+            vibrato,
+            Compressor::new(SAMPLE_RATE_F)
+        );
+        let mut oscillator = Oscillator::new(WaveType::Saw, SAMPLE_RATE_F, 110.0);
+        let input: Vec<f32> = (0..2048).map(|_| oscillator.process()).collect();
+        let mut first = input.clone();
+        chain.process_block(&mut first);
+        chain.reset();
+        let mut second = input;
+        chain.process_block(&mut second);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_latency_samples() {
+        use crate::delay::DelayLine;
+        use crate::filter::Fir;
+
+        static LINEAR_PHASE: [f32; 5] = [0.1, 0.2, 0.4, 0.2, 0.1];
+        static MINIMUM_PHASE: [f32; 3] = [0.6, 0.3, 0.1];
+        let (mut a, mut b, mut c) = ([0.0; 5], [0.0; 5], [0.0; 3]);
+        let mut chain = chain!(
+            Identity,
+            Fir::new(&LINEAR_PHASE, DelayLine::new(&mut a)),
+            Fir::new(&LINEAR_PHASE, DelayLine::new(&mut b))
+        );
+        assert_eq!(chain.latency_samples(), 4);
+        // The impulse comes out at the reported latency.
+        let mut block = [0.0; 8];
+        block[0] = 1.0;
+        chain.process_block(&mut block);
+        let peak = (0..8)
+            .max_by(|i, j| block[*i].total_cmp(&block[*j]))
+            .unwrap();
+        assert_eq!(peak, 4);
+
+        let parallel = Parallel::new(chain, Fir::new(&MINIMUM_PHASE, DelayLine::new(&mut c)));
+        assert_eq!(parallel.latency_samples(), 4);
+        assert_eq!(Feedback::new(parallel.b).latency_samples(), 0);
+    }
+
+    #[test]
+    fn test_bypass_crossfades() {
+        let mut gain = Gain::new(SAMPLE_RATE_F);
+        gain.set_linear_immediate(0.0);
+        let mut bypass = Bypass::new(gain, SAMPLE_RATE_F);
+        bypass.set_fade_time(0.001);
+        assert_eq!(bypass.process(1.0), 0.0);
+
+        // Fades to the dry signal over 44 samples without jumping.
+        bypass.set_bypassed(true);
+        let mut previous = 0.0;
+        for _ in 0..44 {
+            let output = bypass.process(1.0);
+            assert!(output > previous && output - previous < 0.05);
+            previous = output;
+        }
+        assert_eq!(bypass.process(1.0), 1.0);
+        let mut block = [0.5; 8];
+        bypass.process_block(&mut block);
+        assert_eq!(block, [0.5; 8]);
+
+        bypass.set_bypassed(false);
+        bypass.process_block(&mut [1.0; 44]);
+        assert_eq!(bypass.process(1.0), 0.0);
+    }
+
+    #[test]
+    fn test_multi_channel() {
+        let mut multi: MultiChannel<Feedback<Identity>, 4> =
+            MultiChannel::from_fn(|_| Feedback::new(Identity));
+        multi.set_all(|feedback| feedback.set_feedback(0.5));
+        // Each channel keeps its own state.
+        let output = multi.process_frame(Frame([1.0, 0.0, 2.0, 0.0]));
+        assert_eq!(output, Frame([1.0, 0.0, 2.0, 0.0]));
+        let output = multi.process_frame(Frame::EQUILIBRIUM);
+        assert_eq!(output, Frame([0.5, 0.0, 1.0, 0.0]));
+
+        multi.reset();
+        multi.channel(1).unwrap().set_feedback(0.0);
+        let mut interleaved = [1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 9.0];
+        multi.process_interleaved(&mut interleaved);
+        assert_eq!(interleaved, [1.0, 1.0, 0.0, 0.0, 0.5, 0.0, 0.0, 0.0, 9.0]);
+        assert!(multi.channel(4).is_none());
+
+        // Control rate ticks reach every channel.
+        let mut multi: MultiChannel<Ticks, 2> = MultiChannel::from_fn(|_| Ticks(Vec::new()));
+        multi.update_params(32);
+        multi.update_params(16);
+        for ticks in multi.processors.iter() {
+            assert_eq!(ticks.0, [32, 16]);
+        }
+    }
+
+    #[test]
+    fn test_bypass_flush() {
+        let mut bypass = Bypass::new(Feedback::new(Identity), SAMPLE_RATE_F);
+        bypass.processor.set_feedback(0.9);
+        bypass.set_mode(BypassMode::Flush);
+        bypass.set_fade_time(0.0);
+        bypass.process(1.0);
+        bypass.set_bypassed(true);
+        assert_eq!(bypass.process(0.0), 0.0);
+        // The feedback tail was flushed, switching back in starts from silence.
+        bypass.set_bypassed(false);
+        assert_eq!(bypass.process(0.0), 0.0);
+    }
+}
@@ -0,0 +1,140 @@
+//! Small pseudo random number generators for noise, drift, jitter and dither, with no
+//! dependencies. Neither is suitable for cryptography. Seed from a hardware RNG peripheral or
+//! with `seed_from_noise` on a few ADC readings so units don't all start the same sequence.
+
+/// Source of random numbers with helpers for audio rate values.
+pub trait Random {
+    fn next_u32(&mut self) -> u32;
+
+    /// Uniform in [0.0, 1.0].
+    fn next_f32_unit(&mut self) -> f32 {
+        self.next_u32() as f32 / u32::MAX as f32
+    }
+
+    /// Uniform in [-1.0, 1.0].
+    fn next_f32_bipolar(&mut self) -> f32 {
+        self.next_f32_unit() * 2.0 - 1.0
+    }
+}
+
+/// Mix the bits of noisy readings, such as the LSBs of an unconnected ADC input, into a seed.
+pub fn seed_from_noise(samples: &[f32]) -> u32 {
+    // FNV-1a over the bit patterns.
+    samples.iter().fold(0x811c_9dc5, |hash, sample| {
+        sample
+            .to_bits()
+            .to_le_bytes()
+            .iter()
+            .fold(hash, |hash, byte| {
+                (hash ^ *byte as u32).wrapping_mul(0x0100_0193)
+            })
+    })
+}
+
+/// xorshift32, the cheapest generator, one 32 bit word of state with a period of 2^32 - 1.
+#[derive(Clone, Copy, Debug)]
+pub struct XorShift32 {
+    state: u32,
+}
+
+impl XorShift32 {
+    /// A seed of zero, which would only ever produce zero, is replaced.
+    pub const fn new(seed: u32) -> Self {
+        Self {
+            state: if seed == 0 { 0x1234_5678 } else { seed },
+        }
+    }
+}
+
+impl Default for XorShift32 {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Random for XorShift32 {
+    fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+}
+
+/// PCG32 (XSH RR), better statistics than xorshift for a 64 bit multiply per number.
+#[derive(Clone, Copy, Debug)]
+pub struct Pcg32 {
+    state: u64,
+    increment: u64,
+}
+
+impl Pcg32 {
+    const MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+
+    /// `stream` selects one of 2^63 independent sequences for the same seed.
+    pub const fn new(seed: u64, stream: u64) -> Self {
+        let increment = (stream << 1) | 1;
+        let state = increment
+            .wrapping_add(seed)
+            .wrapping_mul(Self::MULTIPLIER)
+            .wrapping_add(increment);
+        Self { state, increment }
+    }
+}
+
+impl Default for Pcg32 {
+    fn default() -> Self {
+        Self::new(0x853c_49e6_748f_ea9b, 0xda3e_39cb_94b9_5bdb)
+    }
+}
+
+impl Random for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old
+            .wrapping_mul(Self::MULTIPLIER)
+            .wrapping_add(self.increment);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_uniform<R: Random>(rng: &mut R) {
+        let mut sum = 0.0;
+        let mut min = f32::MAX;
+        let mut max = f32::MIN;
+        for _ in 0..10000 {
+            let x = rng.next_f32_bipolar();
+            sum += x;
+            min = min.min(x);
+            max = max.max(x);
+        }
+        assert!((sum / 10000.0).abs() < 0.02);
+        assert!((-1.0..-0.99).contains(&min));
+        assert!(max > 0.99 && max <= 1.0);
+    }
+
+    #[test]
+    fn test_generators() {
+        check_uniform(&mut XorShift32::new(1));
+        check_uniform(&mut Pcg32::default());
+
+        // Zero can't seed xorshift.
+        assert_ne!(XorShift32::new(0).next_u32(), 0);
+        // Reference output of the PCG32 demo program for seed 42, stream 54.
+        let mut pcg = Pcg32::new(42, 54);
+        assert_eq!(pcg.next_u32(), 0xa15c_02b7);
+        assert_eq!(pcg.next_u32(), 0x7b47_f409);
+
+        assert_ne!(
+            seed_from_noise(&[0.001, -0.002]),
+            seed_from_noise(&[0.001, -0.003])
+        );
+    }
+}
@@ -0,0 +1,760 @@
+//! Streaming sample rate conversion, for example playing 44.1 kHz samples on a 48 kHz codec.
+//! Input and output are processed in blocks of any size, the converter keeps its position
+//! between calls. The halfband types convert between rates a factor of two apart, such as
+//! 96 kHz and 48 kHz, more cheaply, and `Decimator` reduces the rate by any whole factor.
+//! `FarrowResampler` changes its ratio from one sample to the next for varispeed playback.
+
+use crate::block::dot_product;
+use crate::error::{check_sample_rate, Result};
+use crate::interp::{blackman, lerp, sinc};
+
+/// Taps of the polyphase filter, the converter delays the signal by half as many input samples.
+pub const SINC_TAPS: usize = 16;
+// Filter phases per input sample, coefficients between phases are interpolated.
+const SINC_PHASES: usize = 32;
+
+/// Interpolation used by a `Resampler`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ResampleMode {
+    /// Linear interpolation, cheap but with audible aliasing and high frequency loss.
+    Linear,
+    /// Blackman windowed sinc polyphase filter with `SINC_TAPS` taps.
+    Sinc,
+}
+
+/// Streaming sample rate converter with a fixed or slowly varying ratio.
+pub struct Resampler {
+    mode: ResampleMode,
+    // Input samples advanced per output sample.
+    step: f32,
+    // Position of the next output between the two middle samples of the history.
+    position: f32,
+    // The last `SINC_TAPS` inputs, written twice so a contiguous window can always be read.
+    history: [f32; 2 * SINC_TAPS],
+    index: usize,
+    coefs: [[f32; SINC_TAPS]; SINC_PHASES + 1],
+}
+
+impl Resampler {
+    /// The rates must be positive and finite, see `try_new` to check them.
+    pub fn new(mode: ResampleMode, input_rate: f32, output_rate: f32) -> Self {
+        let mut resampler = Self {
+            mode,
+            step: 1.0,
+            position: 0.0,
+            history: [0.0; 2 * SINC_TAPS],
+            index: 0,
+            coefs: [[0.0; SINC_TAPS]; SINC_PHASES + 1],
+        };
+        resampler.design(input_rate / output_rate);
+        resampler
+    }
+
+    pub fn try_new(mode: ResampleMode, input_rate: f32, output_rate: f32) -> Result<Self> {
+        check_sample_rate(input_rate)?;
+        check_sample_rate(output_rate)?;
+        Ok(Self::new(mode, input_rate, output_rate))
+    }
+
+    /// Set the input and output sample rates, designing the filter for them. Rates that are
+    /// zero, negative or not finite are rejected and the converter is left unchanged.
+    pub fn set_rates(&mut self, input_rate: f32, output_rate: f32) -> Result<()> {
+        check_sample_rate(input_rate)?;
+        check_sample_rate(output_rate)?;
+        self.design(input_rate / output_rate);
+        Ok(())
+    }
+
+    fn design(&mut self, step: f32) {
+        self.step = step;
+        // Band limit to the lower of the two Nyquist frequencies, with a little transition band.
+        let cutoff = 0.9 * (1.0 / self.step).min(1.0);
+        let half_width = (SINC_TAPS / 2) as f32;
+        for (phase, coefs) in self.coefs.iter_mut().enumerate() {
+            let frac = phase as f32 / SINC_PHASES as f32;
+            for (tap, coef) in coefs.iter_mut().enumerate() {
+                let x = tap as f32 - (half_width - 1.0) - frac;
+                *coef = cutoff * sinc(cutoff * x) * blackman(x, half_width);
+            }
+        }
+    }
+
+    /// Fine tune the ratio of input to output samples without redesigning the filter, for
+    /// following a drifting clock. Changes should stay within a few percent.
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.step = ratio.max(1e-3);
+    }
+
+    /// Get the ratio of input to output samples.
+    pub fn get_ratio(&self) -> f32 {
+        self.step
+    }
+
+    /// Convert as much of `input` into `output` as possible, returning the number of input
+    /// samples consumed and output samples produced. Either runs out first, pass the rest of
+    /// the input again with the next output block.
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) -> (usize, usize) {
+        let mut consumed = 0;
+        let mut produced = 0;
+        while produced < output.len() {
+            while self.position >= 1.0 {
+                match input.get(consumed) {
+                    Some(x) => self.push(*x),
+                    None => return (consumed, produced),
+                }
+                consumed += 1;
+                self.position -= 1.0;
+            }
+            output[produced] = self.interpolate();
+            produced += 1;
+            self.position += self.step;
+        }
+        (consumed, produced)
+    }
+
+    fn push(&mut self, input: f32) {
+        self.history[self.index] = input;
+        self.history[self.index + SINC_TAPS] = input;
+        self.index = (self.index + 1) % SINC_TAPS;
+    }
+
+    fn interpolate(&self) -> f32 {
+        // Oldest first.
+        let window = &self.history[self.index..self.index + SINC_TAPS];
+        match self.mode {
+            ResampleMode::Linear => lerp(
+                window[SINC_TAPS / 2 - 1],
+                window[SINC_TAPS / 2],
+                self.position,
+            ),
+            ResampleMode::Sinc => {
+                let phase = self.position * SINC_PHASES as f32;
+                let whole = (phase as usize).min(SINC_PHASES - 1);
+                let frac = phase - whole as f32;
+                let (a, b) = (&self.coefs[whole], &self.coefs[whole + 1]);
+                window
+                    .iter()
+                    .zip(a.iter().zip(b.iter()))
+                    .map(|(x, (a, b))| x * lerp(*a, *b, frac))
+                    .sum()
+            }
+        }
+    }
+
+    /// Delay of the output in input samples.
+    pub fn latency_samples(&self) -> usize {
+        SINC_TAPS / 2
+    }
+
+    /// Clear the history.
+    pub fn reset(&mut self) {
+        self.history = [0.0; 2 * SINC_TAPS];
+        self.index = 0;
+        self.position = 0.0;
+    }
+}
+
+// Non-zero side coefficients of a 47 tap Kaiser windowed halfband low-pass, from the centre tap
+// outward at odd offsets. The centre tap is 0.5 and every even offset is zero. Flat to 0.2 and
+// 80 dB down from 0.3 of the higher sample rate.
+const HALFBAND_COEFS: [f32; 12] = [
+    0.31636375,
+    -0.10039157,
+    0.054532588,
+    -0.03346171,
+    0.021137198,
+    -0.013204763,
+    0.007952739,
+    -0.00451321,
+    0.0023473979,
+    -0.0010708485,
+    0.00039050973,
+    -8.20876e-05,
+];
+const HALFBAND_TAPS: usize = 4 * HALFBAND_COEFS.len() - 1;
+
+/// Halves the sample rate, e.g. 96 kHz to 48 kHz, with a halfband low-pass evaluated only at the
+/// output rate. Delays by 23 input samples.
+pub struct HalfbandDecimator {
+    // The last `HALFBAND_TAPS + 1` inputs, written twice for contiguous reads.
+    history: [f32; 2 * (HALFBAND_TAPS + 1)],
+    index: usize,
+}
+
+impl HalfbandDecimator {
+    pub const fn new() -> Self {
+        Self {
+            history: [0.0; 2 * (HALFBAND_TAPS + 1)],
+            index: 0,
+        }
+    }
+
+    /// Take two input samples, oldest first, and return one output sample.
+    pub fn process(&mut self, input: [f32; 2]) -> f32 {
+        const LEN: usize = HALFBAND_TAPS + 1;
+        for x in input.iter() {
+            self.history[self.index] = *x;
+            self.history[self.index + LEN] = *x;
+            self.index = (self.index + 1) % LEN;
+        }
+        // Oldest first, the newest input is at `LEN - 1` and the centre tap 23 before it.
+        let window = &self.history[self.index..self.index + LEN];
+        let centre = LEN - 1 - HALFBAND_TAPS / 2;
+        let mut sum = 0.5 * window[centre];
+        for (k, coef) in HALFBAND_COEFS.iter().enumerate() {
+            sum += coef * (window[centre - 2 * k - 1] + window[centre + 2 * k + 1]);
+        }
+        sum
+    }
+
+    /// Decimate `input` into `output`, which should be half as long.
+    pub fn process_block(&mut self, input: &[f32], output: &mut [f32]) {
+        for (pair, y) in input.chunks_exact(2).zip(output.iter_mut()) {
+            *y = self.process([pair[0], pair[1]]);
+        }
+    }
+
+    /// Delay in input samples.
+    pub fn latency_samples(&self) -> usize {
+        HALFBAND_TAPS / 2
+    }
+
+    pub fn reset(&mut self) {
+        self.history = [0.0; 2 * (HALFBAND_TAPS + 1)];
+        self.index = 0;
+    }
+}
+
+impl Default for HalfbandDecimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Doubles the sample rate, e.g. 48 kHz to 96 kHz, filtering out the image with a halfband
+/// low-pass split into two phases, one of which is a plain delay. Delays by 23 output samples.
+pub struct HalfbandInterpolator {
+    // The last `2 * HALFBAND_COEFS.len()` inputs, written twice for contiguous reads.
+    history: [f32; 4 * HALFBAND_COEFS.len()],
+    index: usize,
+}
+
+impl HalfbandInterpolator {
+    pub const fn new() -> Self {
+        Self {
+            history: [0.0; 4 * HALFBAND_COEFS.len()],
+            index: 0,
+        }
+    }
+
+    /// Take one input sample and return two output samples, oldest first.
+    pub fn process(&mut self, input: f32) -> [f32; 2] {
+        const LEN: usize = 2 * HALFBAND_COEFS.len();
+        self.history[self.index] = input;
+        self.history[self.index + LEN] = input;
+        self.index = (self.index + 1) % LEN;
+        // Oldest first, the two samples either side of the centre are at `LEN / 2 - 1` and
+        // `LEN / 2`. Gain is doubled to make up for the zeros between inputs.
+        let window = &self.history[self.index..self.index + LEN];
+        let centre = LEN / 2;
+        let mut sum = 0.0;
+        for (k, coef) in HALFBAND_COEFS.iter().enumerate() {
+            sum += coef * (window[centre + k] + window[centre - 1 - k]);
+        }
+        [2.0 * sum, window[centre]]
+    }
+
+    /// Interpolate `input` into `output`, which should be twice as long.
+    pub fn process_block(&mut self, input: &[f32], output: &mut [f32]) {
+        for (x, pair) in input.iter().zip(output.chunks_exact_mut(2)) {
+            pair.copy_from_slice(&self.process(*x));
+        }
+    }
+
+    /// Delay in output samples.
+    pub fn latency_samples(&self) -> usize {
+        HALFBAND_TAPS / 2
+    }
+
+    pub fn reset(&mut self) {
+        self.history = [0.0; 4 * HALFBAND_COEFS.len()];
+        self.index = 0;
+    }
+}
+
+impl Default for HalfbandInterpolator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Taps of a `Decimator`'s anti-alias filter, which delays by half as many input samples.
+pub const DECIMATOR_TAPS: usize = 63;
+
+/// Reduces the sample rate by a whole factor `M`, e.g. to run pitch detection or an FFT on a
+/// 12 kHz stream from a 48 kHz input. A Blackman windowed sinc low-pass removes everything above
+/// 0.9 of the output Nyquist frequency and is only evaluated once per output sample.
+pub struct Decimator<const M: usize> {
+    coefs: [f32; DECIMATOR_TAPS],
+    // The last `DECIMATOR_TAPS` inputs, written twice for contiguous reads.
+    history: [f32; 2 * DECIMATOR_TAPS],
+    index: usize,
+    // Inputs since the last output.
+    count: usize,
+}
+
+impl<const M: usize> Decimator<M> {
+    pub fn new() -> Self {
+        let cutoff = 0.9 / M.max(1) as f32;
+        let half_width = (DECIMATOR_TAPS / 2 + 1) as f32;
+        let mut coefs = [0.0; DECIMATOR_TAPS];
+        for (tap, coef) in coefs.iter_mut().enumerate() {
+            let x = tap as f32 - (DECIMATOR_TAPS / 2) as f32;
+            *coef = sinc(cutoff * x) * blackman(x, half_width);
+        }
+        // Unity gain at DC.
+        let sum: f32 = coefs.iter().sum();
+        coefs.iter_mut().for_each(|coef| *coef /= sum);
+        Self {
+            coefs,
+            history: [0.0; 2 * DECIMATOR_TAPS],
+            index: 0,
+            count: 0,
+        }
+    }
+
+    /// Take one input sample, returning an output sample for every `M` inputs.
+    pub fn process(&mut self, input: f32) -> Option<f32> {
+        self.history[self.index] = input;
+        self.history[self.index + DECIMATOR_TAPS] = input;
+        self.index = (self.index + 1) % DECIMATOR_TAPS;
+        self.count += 1;
+        if self.count < M {
+            return None;
+        }
+        self.count = 0;
+        let window = &self.history[self.index..self.index + DECIMATOR_TAPS];
+        Some(dot_product(window, &self.coefs))
+    }
+
+    /// Decimate `input` into `output`, returning the number of samples written. `output` should
+    /// hold at least `input.len() / M + 1` samples, outputs past its end are dropped.
+    pub fn process_block(&mut self, input: &[f32], output: &mut [f32]) -> usize {
+        let mut written = 0;
+        for x in input {
+            if let Some(y) = self.process(*x) {
+                if let Some(out) = output.get_mut(written) {
+                    *out = y;
+                    written += 1;
+                }
+            }
+        }
+        written
+    }
+
+    /// Delay in input samples.
+    pub fn latency_samples(&self) -> usize {
+        DECIMATOR_TAPS / 2
+    }
+
+    pub fn reset(&mut self) {
+        self.history = [0.0; 2 * DECIMATOR_TAPS];
+        self.index = 0;
+        self.count = 0;
+    }
+}
+
+impl<const M: usize> Default for Decimator<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Farrow sub-filters for cubic Lagrange interpolation. Row k filters four inputs into the
+// coefficient of frac^k, so the filters stay fixed while the ratio changes.
+const FARROW_COEFS: [[f32; 4]; 4] = [
+    [0.0, 1.0, 0.0, 0.0],
+    [-1.0 / 3.0, -0.5, 1.0, -1.0 / 6.0],
+    [0.5, -1.0, 0.5, 0.0],
+    [-1.0 / 6.0, 0.5, -0.5, 1.0 / 6.0],
+];
+
+/// Resampler with a continuously variable ratio, for varispeed playback or following a drifting
+/// clock. Interpolates with a cubic Farrow structure and no anti-alias filter, so content above
+/// the output Nyquist frequency aliases when the ratio is above 1.0.
+pub struct FarrowResampler {
+    ratio: f32,
+    position: f32,
+    // The last four inputs, oldest first.
+    history: [f32; 4],
+}
+
+impl FarrowResampler {
+    pub const fn new(ratio: f32) -> Self {
+        Self {
+            ratio,
+            position: 0.0,
+            history: [0.0; 4],
+        }
+    }
+
+    /// Set the ratio of input to output samples, 2.0 plays an octave up. Takes effect from the
+    /// next output sample.
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.ratio = ratio.max(1e-3);
+    }
+
+    /// Get the ratio of input to output samples.
+    pub fn get_ratio(&self) -> f32 {
+        self.ratio
+    }
+
+    /// Convert as much of `input` into `output` as possible, returning the number of input
+    /// samples consumed and output samples produced, like `Resampler::process`.
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) -> (usize, usize) {
+        let mut consumed = 0;
+        let mut produced = 0;
+        while produced < output.len() {
+            while self.position >= 1.0 {
+                match input.get(consumed) {
+                    Some(x) => {
+                        self.history.copy_within(1.., 0);
+                        self.history[3] = *x;
+                    }
+                    None => return (consumed, produced),
+                }
+                consumed += 1;
+                self.position -= 1.0;
+            }
+            output[produced] = self.interpolate();
+            produced += 1;
+            self.position += self.ratio;
+        }
+        (consumed, produced)
+    }
+
+    fn interpolate(&self) -> f32 {
+        // Horner's rule over the sub-filter outputs, highest power first.
+        FARROW_COEFS.iter().rev().fold(0.0, |sum, filter| {
+            sum * self.position + dot_product(filter, &self.history)
+        })
+    }
+
+    /// Delay of the output in input samples, rounded up.
+    pub fn latency_samples(&self) -> usize {
+        2
+    }
+
+    pub fn reset(&mut self) {
+        self.history = [0.0; 4];
+        self.position = 0.0;
+    }
+}
+
+// Default loop gains of an `Asrc`, settling in around ten seconds with a 1024 sample buffer at
+// 48 kHz, slow enough that the pitch correction is inaudible.
+const ASRC_PROPORTIONAL: f32 = 0.02;
+const ASRC_INTEGRAL: f32 = 0.005;
+// Largest correction to the ratio, 1% is far beyond any crystal's tolerance.
+const ASRC_MAX_CORRECTION: f32 = 0.01;
+
+/// Asynchronous sample rate converter bridging two free running clocks, such as USB audio into
+/// the codec. Set the clock ratio when it's measured, from USB feedback for example, and report
+/// how full the buffer between the clock domains is once per block so a PI loop trims the ratio
+/// to keep it half full.
+pub struct Asrc {
+    pub resampler: Resampler,
+    output_rate: f32,
+    nominal: f32,
+    proportional: f32,
+    integral: f32,
+    accumulated: f32,
+    // Output samples since the last fill level update.
+    produced: usize,
+}
+
+impl Asrc {
+    /// The rates must be positive and finite, see `try_new` to check them.
+    pub fn new(mode: ResampleMode, input_rate: f32, output_rate: f32) -> Self {
+        Self {
+            resampler: Resampler::new(mode, input_rate, output_rate),
+            output_rate,
+            nominal: input_rate / output_rate,
+            proportional: ASRC_PROPORTIONAL,
+            integral: ASRC_INTEGRAL,
+            accumulated: 0.0,
+            produced: 0,
+        }
+    }
+
+    pub fn try_new(mode: ResampleMode, input_rate: f32, output_rate: f32) -> Result<Self> {
+        check_sample_rate(input_rate)?;
+        check_sample_rate(output_rate)?;
+        Ok(Self::new(mode, input_rate, output_rate))
+    }
+
+    /// Set the measured ratio of input to output samples, replacing the nominal one.
+    pub fn set_measured_ratio(&mut self, ratio: f32) {
+        self.nominal = ratio;
+        self.update_ratio(0.0);
+    }
+
+    /// Set the ratio correction per unit of fill level error and per unit of error per second.
+    pub fn set_loop_gains(&mut self, proportional: f32, integral: f32) {
+        self.proportional = proportional;
+        self.integral = integral;
+    }
+
+    /// Report the fill level of the buffer feeding `process`, 0.0 empty to 1.0 full.
+    pub fn update_fill_level(&mut self, fill: f32) {
+        let error = fill - 0.5;
+        let elapsed = self.produced as f32 / self.output_rate;
+        self.produced = 0;
+        self.accumulated = (self.accumulated + self.integral * error * elapsed)
+            .clamp(-ASRC_MAX_CORRECTION, ASRC_MAX_CORRECTION);
+        self.update_ratio(self.proportional * error);
+    }
+
+    fn update_ratio(&mut self, proportional: f32) {
+        let correction =
+            (self.accumulated + proportional).clamp(-ASRC_MAX_CORRECTION, ASRC_MAX_CORRECTION);
+        self.resampler.set_ratio(self.nominal * (1.0 + correction));
+    }
+
+    /// Get the corrected ratio of input to output samples.
+    pub fn get_ratio(&self) -> f32 {
+        self.resampler.get_ratio()
+    }
+
+    /// Convert like `Resampler::process`.
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) -> (usize, usize) {
+        let (consumed, produced) = self.resampler.process(input, output);
+        self.produced += produced;
+        (consumed, produced)
+    }
+
+    pub fn reset(&mut self) {
+        self.resampler.reset();
+        self.accumulated = 0.0;
+        self.produced = 0;
+        self.update_ratio(0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Level in dB of the error after fitting a sine of `freq` to the output.
+    fn sine_error_db(output: &[f32], freq: f32, sample_rate: f32) -> f32 {
+        let w = 2.0 * core::f32::consts::PI * freq / sample_rate;
+        let (mut s, mut c) = (0.0, 0.0);
+        for (n, y) in output.iter().enumerate() {
+            s += y * (w * n as f32).sin();
+            c += y * (w * n as f32).cos();
+        }
+        let (s, c) = (2.0 * s / output.len() as f32, 2.0 * c / output.len() as f32);
+        let error: f32 = output
+            .iter()
+            .enumerate()
+            .map(|(n, y)| {
+                let fit = s * (w * n as f32).sin() + c * (w * n as f32).cos();
+                (y - fit) * (y - fit)
+            })
+            .sum();
+        10.0 * (error / output.len() as f32).log10()
+    }
+
+    fn convert(mode: ResampleMode, freq: f32) -> Vec<f32> {
+        let mut resampler = Resampler::new(mode, 44100.0, 48000.0);
+        let input: Vec<f32> = (0..44100)
+            .map(|n| (2.0 * core::f32::consts::PI * freq * n as f32 / 44100.0).sin())
+            .collect();
+        let mut output = vec![0.0; 60000];
+        // Odd block sizes on both sides.
+        let (mut read, mut written) = (0, 0);
+        while read < input.len() {
+            let end = (read + 37).min(input.len());
+            let out_end = (written + 41).min(output.len());
+            let (consumed, produced) =
+                resampler.process(&input[read..end], &mut output[written..out_end]);
+            read += consumed;
+            written += produced;
+        }
+        output.truncate(written);
+        output
+    }
+
+    #[test]
+    fn test_resample_44k_to_48k() {
+        for &(mode, max_error) in
+            [(ResampleMode::Linear, -40.0), (ResampleMode::Sinc, -70.0)].iter()
+        {
+            let output = convert(mode, 1000.0);
+            // One second of input is one second of output.
+            assert!((output.len() as i32 - 48000).abs() <= 4, "{}", output.len());
+            let error = sine_error_db(&output[1000..47000], 1000.0, 48000.0);
+            assert!(error < max_error, "{:?} {}", mode, error);
+        }
+        // The sinc filter stays within half a dB up to 15 kHz.
+        let output = convert(ResampleMode::Sinc, 15000.0);
+        let peak = output[1000..47000]
+            .iter()
+            .fold(0.0_f32, |a, b| a.max(b.abs()));
+        assert!((peak - 1.0).abs() < 0.05, "{}", peak);
+    }
+
+    #[test]
+    fn test_resampler_rejects_invalid_rates() {
+        use crate::error::Error;
+
+        for &rate in [0.0, -48000.0, f32::NAN, f32::INFINITY].iter() {
+            assert_eq!(
+                Resampler::try_new(ResampleMode::Sinc, rate, 48000.0).err(),
+                Some(Error::InvalidSampleRate)
+            );
+            assert_eq!(
+                Asrc::try_new(ResampleMode::Sinc, 48000.0, rate).err(),
+                Some(Error::InvalidSampleRate)
+            );
+        }
+        let mut resampler = Resampler::try_new(ResampleMode::Sinc, 44100.0, 48000.0).unwrap();
+        for &rate in [0.0, -48000.0, f32::NAN, f32::INFINITY].iter() {
+            assert_eq!(
+                resampler.set_rates(48000.0, rate),
+                Err(Error::InvalidSampleRate)
+            );
+            assert_eq!(
+                resampler.set_rates(rate, 48000.0),
+                Err(Error::InvalidSampleRate)
+            );
+        }
+        // A rejected change keeps the ratio.
+        assert_eq!(resampler.get_ratio(), 44100.0 / 48000.0);
+        assert_eq!(resampler.set_rates(96000.0, 48000.0), Ok(()));
+        assert_eq!(resampler.get_ratio(), 2.0);
+        let mut output = [0.0; 8];
+        assert_eq!(resampler.process(&[1.0; 64], &mut output).1, 8);
+        assert!(output.iter().all(|y| y.is_finite()));
+    }
+
+    #[test]
+    fn test_halfband() {
+        let sine = |freq: f32, sample_rate: f32, n: usize| {
+            (2.0 * core::f32::consts::PI * freq * n as f32 / sample_rate).sin()
+        };
+
+        // 96 kHz to 48 kHz passes 1 kHz and removes 30 kHz, which would alias to 18 kHz.
+        let mut decimator = HalfbandDecimator::new();
+        let input: Vec<f32> = (0..9600).map(|n| sine(1000.0, 96000.0, n)).collect();
+        let mut output = vec![0.0; 4800];
+        decimator.process_block(&input, &mut output);
+        for (m, y) in output.iter().enumerate().skip(100) {
+            let expected = sine(1000.0, 96000.0, 2 * m + 1 - decimator.latency_samples());
+            assert!((y - expected).abs() < 1e-3, "{} {}", y, expected);
+        }
+        decimator.reset();
+        let input: Vec<f32> = (0..9600).map(|n| sine(30000.0, 96000.0, n)).collect();
+        decimator.process_block(&input, &mut output);
+        let peak = output[100..].iter().fold(0.0_f32, |a, b| a.max(b.abs()));
+        assert!(peak < 1e-3, "{}", peak);
+
+        // 48 kHz to 96 kHz fills in between the input samples.
+        let mut interpolator = HalfbandInterpolator::new();
+        let input: Vec<f32> = (0..4800).map(|n| sine(1000.0, 48000.0, n)).collect();
+        let mut output = vec![0.0; 9600];
+        interpolator.process_block(&input, &mut output);
+        for (n, y) in output.iter().enumerate().skip(100) {
+            let expected = sine(1000.0, 96000.0, n - interpolator.latency_samples());
+            assert!((y - expected).abs() < 1e-3, "{} {}", y, expected);
+        }
+    }
+
+    #[test]
+    fn test_decimator() {
+        let sine =
+            |freq: f32, n: usize| (2.0 * core::f32::consts::PI * freq * n as f32 / 48000.0).sin();
+        let mut decimator: Decimator<4> = Decimator::new();
+        let mut output = [0.0; 1200];
+
+        // 1 kHz passes at 12 kHz, delayed by the filter.
+        let input: Vec<f32> = (0..4800).map(|n| sine(1000.0, n)).collect();
+        assert_eq!(decimator.process_block(&input, &mut output), 1200);
+        for (m, y) in output.iter().enumerate().skip(50) {
+            let expected = sine(1000.0, 4 * m + 3 - decimator.latency_samples());
+            assert!((y - expected).abs() < 1e-2, "{} {}", y, expected);
+        }
+
+        // 10 kHz would alias to 2 kHz.
+        decimator.reset();
+        let input: Vec<f32> = (0..4800).map(|n| sine(10000.0, n)).collect();
+        decimator.process_block(&input, &mut output);
+        let peak = output[50..].iter().fold(0.0_f32, |a, b| a.max(b.abs()));
+        assert!(peak < 1e-3, "{}", peak);
+    }
+
+    #[test]
+    fn test_farrow_varispeed() {
+        let w = 2.0 * core::f32::consts::PI * 500.0 / 48000.0;
+        let input: Vec<f32> = (0..20000).map(|n| (w * n as f32).sin()).collect();
+        let mut resampler = FarrowResampler::new(1.0);
+        // Sweep from half to double speed one output at a time. The first output is three
+        // samples before the first input, each one advances by the ratio in use.
+        let (mut read, mut time) = (0, -3.0_f64);
+        for k in 0..10000 {
+            let ratio = 0.5 + 1.5 * k as f32 / 10000.0;
+            resampler.set_ratio(ratio);
+            let mut output = [0.0];
+            let (consumed, produced) = resampler.process(&input[read..], &mut output);
+            read += consumed;
+            assert_eq!(produced, 1);
+            if time > 2.0 {
+                let expected = (w * time as f32).sin();
+                assert!(
+                    (output[0] - expected).abs() < 1e-3,
+                    "{} {}",
+                    output[0],
+                    expected
+                );
+            }
+            time += ratio as f64;
+        }
+    }
+
+    #[test]
+    fn test_asrc_tracks_drift() {
+        use std::collections::VecDeque;
+
+        // The source runs 0.1% fast, without a measured ratio the loop has to find it.
+        const CAPACITY: usize = 1024;
+        let mut asrc = Asrc::new(ResampleMode::Linear, 48000.0, 48000.0);
+        let mut fifo: VecDeque<f32> = (0..CAPACITY / 2).map(|_| 0.0).collect();
+        let mut due = 0.0;
+        let mut output = [0.0; 48];
+        for block in 0..30000 {
+            due += 48.0 * 1.001;
+            while due >= 1.0 {
+                fifo.push_back(0.0);
+                due -= 1.0;
+            }
+            let mut written = 0;
+            while written < output.len() {
+                let (input, _) = fifo.as_slices();
+                let (consumed, produced) = asrc.process(input, &mut output[written..]);
+                fifo.drain(..consumed);
+                written += produced;
+                assert!(!fifo.is_empty() || written == output.len(), "underrun");
+            }
+            assert!(fifo.len() < CAPACITY, "overrun");
+            asrc.update_fill_level(fifo.len() as f32 / CAPACITY as f32);
+            if block > 20000 {
+                assert!((fifo.len() as i32 - CAPACITY as i32 / 2).abs() < 50);
+                assert!(
+                    (asrc.get_ratio() - 1.001).abs() < 2e-4,
+                    "{}",
+                    asrc.get_ratio()
+                );
+            }
+        }
+    }
+}
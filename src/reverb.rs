@@ -1,6 +1,4 @@
-use core::cmp::{max, min};
-use core::f32::consts::PI;
-use micromath::F32Ext;
-use ordered_float::OrderedFloat;
+//! Reverb building blocks. The all-pass filters diffusing a reverb tail live with the other
+//! filters in `filter` and are re-exported here, as `f64` versions too with the `f64` feature.
 
-use crate::delay::DelayLine;
+pub use crate::filter::{AllPass, AllPassSP};
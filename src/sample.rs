@@ -0,0 +1,284 @@
+//! Sample types the core building blocks are generic over. Processors default to `f32`, `f64`
+//! is useful for offline rendering and reference results in tests, and with the `fixed` feature
+//! Q15 samples run on cores without an FPU. Filters and reverb building blocks that need
+//! coefficients outside the fixed point range are generic over `Float` instead, implemented for
+//! `f32` and with the `f64` feature for `f64`.
+
+use core::ops::{Add, Div, Mul, Neg, Sub};
+
+/// Arithmetic a processor needs from its samples. Fixed point types saturate instead of
+/// wrapping, and `mul` treats both operands as fractions.
+pub trait Sample: Copy + PartialEq + Default {
+    /// State of recursive filters. Fixed point types keep extra fractional bits in it, so low
+    /// cutoffs settle without a dead band.
+    type State: Copy;
+
+    const EQUILIBRIUM: Self;
+
+    /// Multiplying by this leaves a sample unchanged, the largest value for fixed point types.
+    const IDENTITY: Self;
+
+    const STATE_EQUILIBRIUM: Self::State;
+
+    fn from_f32(value: f32) -> Self;
+
+    fn to_f32(self) -> f32;
+
+    fn add(self, other: Self) -> Self;
+
+    fn sub(self, other: Self) -> Self;
+
+    fn mul(self, other: Self) -> Self;
+
+    /// Linear interpolation towards `other` by `frac` in [0.0-1.0].
+    fn lerp(self, other: Self, frac: f32) -> Self {
+        self.add(other.sub(self).mul(Self::from_f32(frac)))
+    }
+
+    fn from_state(state: Self::State) -> Self;
+
+    /// Move `state` towards `target` by `coef` in [0.0-1.0], one step of a one pole low pass.
+    fn approach(state: Self::State, target: Self, coef: Self) -> Self::State;
+}
+
+impl Sample for f32 {
+    type State = f32;
+
+    const EQUILIBRIUM: Self = 0.0;
+
+    const IDENTITY: Self = 1.0;
+
+    const STATE_EQUILIBRIUM: Self = 0.0;
+
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+
+    fn to_f32(self) -> f32 {
+        self
+    }
+
+    fn add(self, other: Self) -> Self {
+        self + other
+    }
+
+    fn sub(self, other: Self) -> Self {
+        self - other
+    }
+
+    fn mul(self, other: Self) -> Self {
+        self * other
+    }
+
+    fn from_state(state: Self) -> Self {
+        state
+    }
+
+    fn approach(state: Self, target: Self, coef: Self) -> Self {
+        state + (target - state) * coef
+    }
+}
+
+impl Sample for f64 {
+    type State = f64;
+
+    const EQUILIBRIUM: Self = 0.0;
+
+    const IDENTITY: Self = 1.0;
+
+    const STATE_EQUILIBRIUM: Self = 0.0;
+
+    fn from_f32(value: f32) -> Self {
+        value as f64
+    }
+
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+
+    fn add(self, other: Self) -> Self {
+        self + other
+    }
+
+    fn sub(self, other: Self) -> Self {
+        self - other
+    }
+
+    fn mul(self, other: Self) -> Self {
+        self * other
+    }
+
+    fn from_state(state: Self) -> Self {
+        state
+    }
+
+    fn approach(state: Self, target: Self, coef: Self) -> Self {
+        state + (target - state) * coef
+    }
+}
+
+/// Floating point samples, with the math used to design coefficients at the same precision the
+/// samples are processed at.
+pub trait Float:
+    Sample
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    const PI: Self;
+
+    fn from_f64(value: f64) -> Self;
+
+    fn sin(self) -> Self;
+
+    fn cos(self) -> Self;
+
+    fn exp(self) -> Self;
+
+    fn sqrt(self) -> Self;
+
+    fn powf(self, exponent: Self) -> Self;
+}
+
+impl Float for f32 {
+    const PI: Self = core::f32::consts::PI;
+
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+
+    fn sin(self) -> Self {
+        f32_math::sin(self)
+    }
+
+    fn cos(self) -> Self {
+        f32_math::cos(self)
+    }
+
+    fn exp(self) -> Self {
+        f32_math::exp(self)
+    }
+
+    fn sqrt(self) -> Self {
+        f32_math::sqrt(self)
+    }
+
+    fn powf(self, exponent: Self) -> Self {
+        f32_math::powf(self, exponent)
+    }
+}
+
+// Out of scope of Float, so the calls resolve like in the rest of the crate.
+mod f32_math {
+    float_math!();
+
+    pub(super) fn sin(x: f32) -> f32 {
+        x.sin()
+    }
+
+    pub(super) fn cos(x: f32) -> f32 {
+        x.cos()
+    }
+
+    pub(super) fn exp(x: f32) -> f32 {
+        x.exp()
+    }
+
+    pub(super) fn sqrt(x: f32) -> f32 {
+        x.sqrt()
+    }
+
+    pub(super) fn powf(x: f32, exponent: f32) -> f32 {
+        x.powf(exponent)
+    }
+}
+
+// Double precision math always comes from libm, micromath is single precision only.
+#[cfg(feature = "f64")]
+impl Float for f64 {
+    const PI: Self = core::f64::consts::PI;
+
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+
+    fn sin(self) -> Self {
+        libm::sin(self)
+    }
+
+    fn cos(self) -> Self {
+        libm::cos(self)
+    }
+
+    fn exp(self) -> Self {
+        libm::exp(self)
+    }
+
+    fn sqrt(self) -> Self {
+        libm::sqrt(self)
+    }
+
+    fn powf(self, exponent: Self) -> Self {
+        libm::pow(self, exponent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const SAMPLE_RATE_F: f32 = 44100.0;
+
+    use super::*;
+    use crate::delay::DelayLine;
+    use crate::filter::OnePoleLowPass;
+    use crate::mix::Gain;
+
+    #[test]
+    fn test_f64_matches_f32() {
+        let mut buffer_f32 = [0.0_f32; 64];
+        let mut buffer_f64 = [0.0_f64; 64];
+        let mut delay_f32 = DelayLine::new(&mut buffer_f32);
+        let mut delay_f64 = DelayLine::new(&mut buffer_f64);
+        let mut filter_f32 = OnePoleLowPass::<f32>::new(SAMPLE_RATE_F);
+        let mut filter_f64 = OnePoleLowPass::<f64>::new(SAMPLE_RATE_F);
+        filter_f32.set_freq(1000.0);
+        filter_f64.set_freq(1000.0);
+        let mut gain_f32 = Gain::<f32>::new(SAMPLE_RATE_F);
+        let mut gain_f64 = Gain::<f64>::new(SAMPLE_RATE_F);
+        gain_f32.set_db(-6.0);
+        gain_f64.set_db(-6.0);
+        for i in 0..2000 {
+            let x = (i as f32 * 0.05).sin();
+            delay_f32.write(filter_f32.process(gain_f32.process(x)));
+            delay_f64.write(filter_f64.process(gain_f64.process(x as f64)));
+            let (a, b) = (delay_f32.read_delayed(10.5), delay_f64.read_delayed(10.5));
+            assert!((a as f64 - b).abs() < 1e-5, "{} {} {}", i, a, b);
+        }
+        assert_eq!(1.0_f64.lerp(3.0, 0.25), 1.5);
+    }
+
+    #[cfg(feature = "f64")]
+    #[test]
+    fn test_f64_filters_match_f32() {
+        use crate::filter::{AllPass, Biquad};
+
+        let mut biquad_f32 = Biquad::<f32>::new(SAMPLE_RATE_F);
+        let mut biquad_f64 = Biquad::<f64>::new(SAMPLE_RATE_F);
+        biquad_f32.set_low_shelf(200.0, 0.707, 6.0);
+        biquad_f64.set_low_shelf(200.0, 0.707, 6.0);
+        let mut buffer_f32 = [0.0_f32; 32];
+        let mut buffer_f64 = [0.0_f64; 32];
+        let mut all_pass_f32 = AllPass::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer_f32));
+        let mut all_pass_f64 = AllPass::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer_f64));
+        all_pass_f32.set_freq(1000.0);
+        all_pass_f64.set_freq(1000.0);
+        for i in 0..2000 {
+            let x = (i as f32 * 0.05).sin();
+            let a = all_pass_f32.process(biquad_f32.process(x));
+            let b = all_pass_f64.process(biquad_f64.process(x as f64));
+            assert!((a as f64 - b).abs() < 1e-3, "{} {} {}", i, a, b);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Sigma-delta modulation for driving a PWM pin or a 1 bit output as a DAC on MCUs without a
+//! codec. The modulator runs at a multiple of the audio rate and pushes its quantization noise
+//! above the audio band, where an RC filter on the pin removes it.
+
+use core::f32::consts::PI;
+
+float_math!();
+
+// Largest input the second order loop stays stable with at 1 bit.
+const MAX_INPUT: f32 = 0.9;
+// Integrator limit, so the loop recovers from overload instead of latching up.
+const MAX_INTEGRATOR: f32 = 4.0;
+// Highest audio frequency the reconstruction filter is placed for.
+const MAX_AUDIO_BANDWIDTH: f32 = 20000.0;
+
+/// Analog low-pass to put after a modulator, made of `order` equal RC stages with buffers in
+/// between.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ReconstructionFilter {
+    /// -3 dB frequency of the whole filter in Hz.
+    pub corner: f32,
+    pub order: u32,
+}
+
+impl ReconstructionFilter {
+    /// Capacitance in farads of each stage with resistors of `resistance` ohms.
+    pub fn capacitance(&self, resistance: f32) -> f32 {
+        // Cascading n equal stages lowers the corner, so each stage sits higher.
+        let order = self.order.max(1) as f32;
+        let stage_corner = self.corner / (2.0_f32.powf(1.0 / order) - 1.0).sqrt();
+        1.0 / (2.0 * PI * resistance * stage_corner)
+    }
+}
+
+/// Second order sigma-delta modulator with a 1 bit or few level quantizer. Each output is a
+/// level in [0, levels - 1], a bit for a 1 bit stream or the compare value of a PWM timer with a
+/// period of `levels - 1` counts.
+pub struct SigmaDelta {
+    levels: u16,
+    integrators: [f32; 2],
+    output: f32,
+    previous: f32,
+}
+
+impl SigmaDelta {
+    /// `levels` is clamped to at least 2, the 1 bit case.
+    pub fn new(levels: u16) -> Self {
+        Self {
+            levels: levels.max(2),
+            integrators: [0.0; 2],
+            output: 0.0,
+            previous: 0.0,
+        }
+    }
+
+    /// Run the modulator for one tick, the input is clamped to [-0.9, 0.9].
+    pub fn process_tick(&mut self, input: f32) -> u16 {
+        let input = input.clamp(-MAX_INPUT, MAX_INPUT);
+        let [first, second] = &mut self.integrators;
+        *first = (*first + input - self.output).clamp(-MAX_INTEGRATOR, MAX_INTEGRATOR);
+        *second = (*second + *first - self.output).clamp(-MAX_INTEGRATOR, MAX_INTEGRATOR);
+
+        let steps = (self.levels - 1) as f32;
+        let level = ((*second + 1.0) * 0.5 * steps).round().clamp(0.0, steps);
+        self.output = level * 2.0 / steps - 1.0;
+        level as u16
+    }
+
+    /// Modulate one audio sample into `output.len()` ticks, the oversampling ratio, linearly
+    /// interpolating from the previous sample.
+    pub fn process(&mut self, input: f32, output: &mut [u16]) {
+        let step = (input - self.previous) / output.len() as f32;
+        for (i, level) in output.iter_mut().enumerate() {
+            *level = self.process_tick(self.previous + step * (i + 1) as f32);
+        }
+        self.previous = input;
+    }
+
+    pub fn get_levels(&self) -> u16 {
+        self.levels
+    }
+
+    /// Recommend an output filter for audio at `sample_rate` modulated at `oversampling` ticks
+    /// per sample. The noise rises at 40 dB per decade, so a third order filter is needed for it
+    /// to fall overall, cornered at the top of the audio band but well below the tick rate.
+    pub fn reconstruction_filter(
+        &self,
+        sample_rate: f32,
+        oversampling: usize,
+    ) -> ReconstructionFilter {
+        let bandwidth = (0.45 * sample_rate).min(MAX_AUDIO_BANDWIDTH);
+        let tick_rate = sample_rate * oversampling as f32;
+        ReconstructionFilter {
+            corner: bandwidth.min(tick_rate * 0.05),
+            order: 3,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.integrators = [0.0; 2];
+        self.output = 0.0;
+        self.previous = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resample::Decimator;
+
+    // Three 4x decimation stages from the tick rate back to the audio rate.
+    #[derive(Default)]
+    struct Decimate64([Decimator<4>; 3]);
+
+    impl Decimate64 {
+        fn process(&mut self, input: f32) -> Option<f32> {
+            let [a, b, c] = &mut self.0;
+            a.process(input)
+                .and_then(|x| b.process(x))
+                .and_then(|x| c.process(x))
+        }
+    }
+
+    // In band signal to noise ratio in dB of a sine modulated at 64x, filtering the input and the
+    // levels with the same decimator.
+    fn in_band_snr(levels: u16, amplitude: f32) -> f32 {
+        let mut modulator = SigmaDelta::new(levels);
+        let mut input_filter = Decimate64::default();
+        let mut output_filter = Decimate64::default();
+        let steps = (levels - 1) as f32;
+        let mut ticks = [0; 64];
+        let (mut signal, mut noise) = (0.0, 0.0);
+        let mut previous = 0.0;
+        for n in 0..4800 {
+            let x = amplitude * (2.0 * PI * 1000.0 * n as f32 / 48000.0).sin();
+            modulator.process(x, &mut ticks);
+            // The same interpolation the modulator sees.
+            let step = (x - previous) / 64.0;
+            for (i, level) in ticks.iter().enumerate() {
+                let expected = input_filter.process(previous + step * (i + 1) as f32);
+                let output = output_filter.process(*level as f32 * 2.0 / steps - 1.0);
+                if let (Some(expected), Some(output), true) = (expected, output, n > 100) {
+                    signal += expected * expected;
+                    noise += (output - expected) * (output - expected);
+                }
+            }
+            previous = x;
+        }
+        10.0 * (signal / noise).log10()
+    }
+
+    #[test]
+    fn test_sigma_delta() {
+        assert!(in_band_snr(2, 0.5) > 60.0, "{}", in_band_snr(2, 0.5));
+        assert!(in_band_snr(17, 0.5) > 80.0, "{}", in_band_snr(17, 0.5));
+
+        // Overload recovers.
+        let mut modulator = SigmaDelta::new(2);
+        let mut ticks = [0; 64];
+        for _ in 0..100 {
+            modulator.process(5.0, &mut ticks);
+        }
+        modulator.process(0.0, &mut ticks);
+        for _ in 0..10 {
+            modulator.process(0.0, &mut ticks);
+        }
+        let ones = ticks.iter().filter(|level| **level == 1).count();
+        assert!((28..=36).contains(&ones), "{}", ones);
+    }
+
+    #[test]
+    fn test_reconstruction_filter() {
+        let filter = SigmaDelta::new(2).reconstruction_filter(48000.0, 64);
+        assert_eq!(filter.corner, 20000.0);
+        // Three stages of 1k need a higher stage corner than 20 kHz, so less than 8 nF.
+        let capacitance = filter.capacitance(1000.0);
+        assert!(capacitance < 1.0 / (2.0 * PI * 1000.0 * 20000.0));
+        assert!(capacitance > 3e-9);
+    }
+}
@@ -0,0 +1,584 @@
+//! Short time Fourier transform framing for spectral effects. `Stft` windows the input into
+//! overlapping frames, hands each frame's bins to the effect and overlap adds the result, so
+//! effects such as freezing, robotizing or noise reduction only deal with the bins.
+
+use core::f32::consts::TAU;
+
+float_math!();
+
+use crate::fft::{Complex, Fft};
+use crate::processor::Processor;
+use crate::units::{db_to_linear, semitones_to_ratio};
+use crate::windows::{self, Symmetry, Window};
+
+/// Overlap add STFT of `N` point frames every `HOP` samples, `HOP` dividing `N / 2`.
+/// Frames are square root Hann windowed both before the transform and after the inverse, so an
+/// effect that leaves the bins alone passes the input through `N` samples late. The effect sees
+/// the `N / 2 + 1` bins from DC to Nyquist, unnormalized, as `Fft::process_real` produces them.
+/// Smaller hops cost more but smear changes to the bins less.
+pub struct Stft<const N: usize, const HOP: usize> {
+    fft: Fft<N>,
+    window: [f32; N],
+    scale: f32,
+    input: [f32; N],
+    output: [f32; N],
+    index: usize,
+    counter: usize,
+    frame: [f32; N],
+    bins: [Complex; N],
+}
+
+impl<const N: usize, const HOP: usize> Stft<N, HOP> {
+    pub fn new() -> Self {
+        assert!(
+            HOP > 0 && HOP <= N / 2 && (N / 2) % HOP == 0,
+            "STFT hop must divide half the frame size"
+        );
+        let mut window = [0.0; N];
+        windows::fill(Window::Hann, Symmetry::Periodic, &mut window);
+        for x in window.iter_mut() {
+            *x = x.sqrt();
+        }
+        // Overlapping squared windows sum to a constant, undo it.
+        let overlap: f32 = window.iter().step_by(HOP).map(|x| x * x).sum();
+        Self {
+            fft: Fft::new(),
+            window,
+            scale: 1.0 / overlap,
+            input: [0.0; N],
+            output: [0.0; N],
+            index: 0,
+            counter: 0,
+            frame: [0.0; N],
+            bins: [Complex::default(); N],
+        }
+    }
+
+    /// Process one sample, calling `effect` with the bins of a new frame every `HOP` samples.
+    pub fn process<F: FnMut(&mut [Complex])>(&mut self, input: f32, mut effect: F) -> f32 {
+        let output = self.output[self.index];
+        self.output[self.index] = 0.0;
+        self.input[self.index] = input;
+        self.index = (self.index + 1) % N;
+        self.counter += 1;
+        if self.counter >= HOP {
+            self.counter = 0;
+            self.process_frame(&mut effect);
+        }
+        output
+    }
+
+    /// Process a block in place, see `process`.
+    pub fn process_block<F: FnMut(&mut [Complex])>(&mut self, block: &mut [f32], mut effect: F) {
+        for sample in block.iter_mut() {
+            *sample = self.process(*sample, &mut effect);
+        }
+    }
+
+    fn process_frame<F: FnMut(&mut [Complex])>(&mut self, effect: &mut F) {
+        // `index` now points at the oldest input sample.
+        for (i, x) in self.frame.iter_mut().enumerate() {
+            *x = self.input[(self.index + i) % N];
+        }
+        self.analyze();
+        effect(&mut self.bins[..=N / 2]);
+        self.synthesize();
+        for (i, x) in self.frame.iter().enumerate() {
+            self.output[(self.index + i) % N] += x;
+        }
+    }
+
+    // Window and transform `frame` into `bins`.
+    fn analyze(&mut self) {
+        for (x, window) in self.frame.iter_mut().zip(self.window.iter()) {
+            *x *= window;
+        }
+        self.fft.process_real(&mut self.frame);
+        let half = N / 2;
+        self.bins[0] = Complex::new(self.frame[0], 0.0);
+        self.bins[half] = Complex::new(self.frame[1], 0.0);
+        for k in 1..half {
+            self.bins[k] = Complex::new(self.frame[2 * k], self.frame[2 * k + 1]);
+        }
+    }
+
+    // Inverse transform `bins` into `frame`, windowed and scaled for overlap adding.
+    fn synthesize(&mut self) {
+        let half = N / 2;
+        // The real transform has no imaginary parts at DC and Nyquist.
+        self.frame[0] = self.bins[0].re;
+        self.frame[1] = self.bins[half].re;
+        for k in 1..half {
+            self.frame[2 * k] = self.bins[k].re;
+            self.frame[2 * k + 1] = self.bins[k].im;
+        }
+        self.fft.inverse_real(&mut self.frame);
+        for (x, window) in self.frame.iter_mut().zip(self.window.iter()) {
+            *x *= window * self.scale;
+        }
+    }
+
+    /// Number of bins passed to the effect, DC to Nyquist.
+    pub const fn bins(&self) -> usize {
+        N / 2 + 1
+    }
+
+    /// Delay through the STFT in samples.
+    pub fn latency_samples(&self) -> usize {
+        N
+    }
+
+    pub fn reset(&mut self) {
+        self.input = [0.0; N];
+        self.output = [0.0; N];
+        self.index = 0;
+        self.counter = 0;
+    }
+}
+
+impl<const N: usize, const HOP: usize> Default for Stft<N, HOP> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Weight of the previous frame in the smoothed bin power of `NoiseReducer`.
+const POWER_SMOOTHING: f32 = 0.7;
+
+/// Spectral subtraction noise reducer for steady noise such as hiss, hum and fan noise.
+/// Learn the noise profile from a stretch of noise alone, the input passes unchanged meanwhile.
+/// Each bin is then attenuated by the fraction of its power the profile accounts for, scaled by
+/// the over-subtraction factor. Higher factors remove more noise and more of the signal, the
+/// floor limits the attenuation, trading residual noise against the warbling "musical noise"
+/// left by deep subtraction.
+pub struct NoiseReducer<const N: usize, const HOP: usize> {
+    stft: Stft<N, HOP>,
+    noise: [f32; N],
+    power: [f32; N],
+    learning: bool,
+    learned_frames: u32,
+    over_subtraction: f32,
+    floor: f32,
+}
+
+impl<const N: usize, const HOP: usize> NoiseReducer<N, HOP> {
+    /// Over-subtraction of 2.0 and a -20 dB floor, with no profile learned.
+    pub fn new() -> Self {
+        Self {
+            stft: Stft::new(),
+            noise: [0.0; N],
+            power: [0.0; N],
+            learning: false,
+            learned_frames: 0,
+            over_subtraction: 2.0,
+            floor: db_to_linear(-20.0),
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        // Bound to locals as closures capture whole structs in edition 2018.
+        let noise = &mut self.noise;
+        let smoothed = &mut self.power;
+        let learning = self.learning;
+        let learned_frames = &mut self.learned_frames;
+        let over_subtraction = self.over_subtraction;
+        let floor_power = self.floor * self.floor;
+        self.stft.process(input, |bins| {
+            if learning {
+                // Running mean of the power in each bin.
+                *learned_frames += 1;
+                let weight = 1.0 / *learned_frames as f32;
+                for (noise, bin) in noise.iter_mut().zip(bins.iter()) {
+                    *noise += (bin.norm_sqr() - *noise) * weight;
+                }
+                return;
+            }
+            if *learned_frames == 0 {
+                return;
+            }
+            for ((noise, smoothed), bin) in
+                noise.iter().zip(smoothed.iter_mut()).zip(bins.iter_mut())
+            {
+                // Averaging over frames steadies the gains of noise bins, less musical noise.
+                let power = bin.norm_sqr();
+                *smoothed = POWER_SMOOTHING * *smoothed + (1.0 - POWER_SMOOTHING) * power;
+                let power = smoothed.max(f32::MIN_POSITIVE);
+                let gain = (1.0 - over_subtraction * noise / power)
+                    .max(floor_power)
+                    .sqrt();
+                *bin = Complex::new(bin.re * gain, bin.im * gain);
+            }
+        })
+    }
+
+    pub fn process_block(&mut self, block: &mut [f32]) {
+        for sample in block.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
+
+    /// Start or stop learning the noise profile. Starting replaces the previous profile.
+    pub fn set_learning(&mut self, learning: bool) {
+        if learning && !self.learning {
+            self.clear_profile();
+        }
+        self.learning = learning;
+    }
+
+    pub fn is_learning(&self) -> bool {
+        self.learning
+    }
+
+    /// True once a noise profile has been learned.
+    pub fn has_profile(&self) -> bool {
+        !self.learning && self.learned_frames > 0
+    }
+
+    /// Forget the noise profile, passing the input unchanged until a new one is learned.
+    pub fn clear_profile(&mut self) {
+        self.noise = [0.0; N];
+        self.learned_frames = 0;
+    }
+
+    /// Set how many times the noise profile is subtracted, 1.0 and up.
+    pub fn set_over_subtraction(&mut self, factor: f32) {
+        self.over_subtraction = factor.max(1.0);
+    }
+
+    /// Set the most a bin is attenuated in dB, as a negative level.
+    pub fn set_floor(&mut self, floor: f32) {
+        self.floor = db_to_linear(floor.min(0.0));
+    }
+
+    pub fn latency_samples(&self) -> usize {
+        self.stft.latency_samples()
+    }
+
+    /// Clear the audio, keeping the noise profile.
+    pub fn reset(&mut self) {
+        self.stft.reset();
+        self.power = [0.0; N];
+    }
+}
+
+impl<const N: usize, const HOP: usize> Default for NoiseReducer<N, HOP> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, const HOP: usize> Processor for NoiseReducer<N, HOP> {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.latency_samples()
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+// Wrap a phase to [-pi, pi].
+fn wrap_phase(phase: f32) -> f32 {
+    phase - TAU * (phase / TAU).round()
+}
+
+// Phase state of a phase vocoder, apart from the STFT so the effect closure can borrow it.
+struct VocoderState<const N: usize> {
+    magnitudes: [f32; N],
+    phases: [f32; N],
+    last_phases: [f32; N],
+    synth_phases: [f32; N],
+    last_synth_phases: [f32; N],
+}
+
+impl<const N: usize> VocoderState<N> {
+    fn new() -> Self {
+        Self {
+            magnitudes: [0.0; N],
+            phases: [0.0; N],
+            last_phases: [0.0; N],
+            synth_phases: [0.0; N],
+            last_synth_phases: [0.0; N],
+        }
+    }
+
+    // Resynthesize a frame read `hop` samples after the last one, `synthesis_hop` samples after
+    // the last frame written, with the frequencies scaled by `ratio`. Each spectral peak and the
+    // bins around it move together to the shifted peak, keeping their phases relative to the
+    // peak so the peak's shape survives (identity phase locking, Laroche and Dolson).
+    fn process(&mut self, bins: &mut [Complex], ratio: f32, hop: usize, synthesis_hop: usize) {
+        let half = bins.len() - 1;
+        for (k, bin) in bins.iter_mut().enumerate() {
+            self.magnitudes[k] = bin.norm();
+            self.phases[k] = bin.arg();
+            self.synth_phases[k] = 0.0;
+            *bin = Complex::default();
+        }
+        let magnitudes = &self.magnitudes;
+        let next_peak = |from: usize| {
+            (from..=half).find(|&k| {
+                (k.saturating_sub(2)..=(k + 2).min(half))
+                    .all(|j| j == k || magnitudes[k] > magnitudes[j])
+            })
+        };
+
+        let mut start = 0;
+        let mut peak = next_peak(0);
+        while let Some(p) = peak {
+            let next = next_peak(p + 1);
+            let end = next.map_or(half, |next| (p + next) / 2);
+            let target = (p as f32 * ratio).round() as isize;
+            if target as usize <= half {
+                let expected = TAU * (p * hop) as f32 / N as f32;
+                let deviation = wrap_phase(self.phases[p] - self.last_phases[p] - expected);
+                let advance = (expected + deviation) * ratio * synthesis_hop as f32 / hop as f32;
+                let peak_phase = wrap_phase(self.last_synth_phases[target as usize] + advance);
+                let shift = target - p as isize;
+                for k in start..=end {
+                    let out = k as isize + shift;
+                    if (0..=half as isize).contains(&out) {
+                        let phase = peak_phase + self.phases[k] - self.phases[p];
+                        let out = out as usize;
+                        bins[out] = bins[out] + Complex::from_polar(self.magnitudes[k], phase);
+                        self.synth_phases[out] = phase;
+                    }
+                }
+            }
+            start = end + 1;
+            peak = next;
+        }
+        self.last_phases[..=half].copy_from_slice(&self.phases[..=half]);
+        self.last_synth_phases[..=half].copy_from_slice(&self.synth_phases[..=half]);
+    }
+
+    fn reset(&mut self) {
+        self.last_phases = [0.0; N];
+        self.last_synth_phases = [0.0; N];
+    }
+}
+
+/// Phase vocoder pitch shifter on `Stft<N, HOP>`, cleaner than the delay based `PitchShifter`
+/// on sustained and polyphonic material at the cost of an `N` sample latency and an FFT pair
+/// every `HOP` samples, affordable on Cortex-M7 class parts. Each bin's true frequency is taken
+/// from its phase advance between frames, and each spectral peak moves to its shifted frequency
+/// with the bins around it. Transients smear over the frame, use a hop of `N / 4` or less.
+/// `stretch` changes the length of a recording instead, keeping its pitch.
+pub struct PhaseVocoder<const N: usize, const HOP: usize> {
+    stft: Stft<N, HOP>,
+    state: VocoderState<N>,
+    ratio: f32,
+}
+
+impl<const N: usize, const HOP: usize> PhaseVocoder<N, HOP> {
+    pub fn new() -> Self {
+        Self {
+            stft: Stft::new(),
+            state: VocoderState::new(),
+            ratio: 1.0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        // Bound to locals as closures capture whole structs in edition 2018.
+        let state = &mut self.state;
+        let ratio = self.ratio;
+        self.stft
+            .process(input, |bins| state.process(bins, ratio, HOP, HOP))
+    }
+
+    pub fn process_block(&mut self, block: &mut [f32]) {
+        for sample in block.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
+
+    /// Time stretch `input` by `stretch`, 2.0 plays twice as long, keeping the pitch. Writes up to
+    /// `stretch` times the input length to `output` and returns the number of samples written.
+    /// The first and last `N` samples fade in and out. Clears the audio of the pitch shifter.
+    pub fn stretch(&mut self, input: &[f32], output: &mut [f32], stretch: f32) -> usize {
+        self.reset();
+        output.fill(0.0);
+        let analysis_hop = HOP as f32 / stretch.max(1e-3);
+        let mut position = 0.0_f32;
+        let mut last_start = 0;
+        let mut written = 0;
+        loop {
+            let start = position as usize;
+            if start + N > input.len() || written + N > output.len() {
+                break;
+            }
+            self.stft.frame.copy_from_slice(&input[start..start + N]);
+            self.stft.analyze();
+            let hop = (start - last_start).max(1);
+            self.state
+                .process(&mut self.stft.bins[..=N / 2], 1.0, hop, HOP);
+            self.stft.synthesize();
+            for (y, x) in output[written..written + N]
+                .iter_mut()
+                .zip(self.stft.frame.iter())
+            {
+                *y += x;
+            }
+            written += HOP;
+            last_start = start;
+            position += analysis_hop;
+        }
+        self.reset();
+        if written == 0 {
+            0
+        } else {
+            written - HOP + N
+        }
+    }
+
+    /// Set the transposition in semitones.
+    pub fn set_semitones(&mut self, semitones: f32) {
+        self.ratio = semitones_to_ratio(semitones);
+    }
+
+    pub fn latency_samples(&self) -> usize {
+        self.stft.latency_samples()
+    }
+
+    pub fn reset(&mut self) {
+        self.stft.reset();
+        self.state.reset();
+    }
+}
+
+impl<const N: usize, const HOP: usize> Default for PhaseVocoder<N, HOP> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, const HOP: usize> Processor for PhaseVocoder<N, HOP> {
+    fn process(&mut self, input: f32) -> f32 {
+        self.process(input)
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.latency_samples()
+    }
+
+    fn reset(&mut self) {
+        self.reset()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::{Random, XorShift32};
+    use crate::synthesis::{Oscillator, WaveType};
+
+    const SAMPLE_RATE_F: f32 = 44100.0;
+
+    #[test]
+    fn test_stft_passes_through() {
+        let mut input = Oscillator::new(WaveType::PolyBLEPSaw, SAMPLE_RATE_F, 220.0);
+        let signal: Vec<f32> = (0..8192).map(|_| 0.5 * input.process()).collect();
+        let mut stft: Stft<512, 128> = Stft::new();
+        assert_eq!(stft.bins(), 257);
+        let mut output = signal.clone();
+        stft.process_block(&mut output, |bins| assert_eq!(bins.len(), 257));
+        let latency = stft.latency_samples();
+        for (x, y) in signal.iter().zip(output[latency..].iter()) {
+            assert!((x - y).abs() < 1e-4);
+        }
+
+        // Zeroing the upper bins low passes.
+        let mut stft: Stft<512, 256> = Stft::new();
+        let mut input = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 15000.0);
+        let mut peak = 0.0_f32;
+        for i in 0..8192 {
+            let output = stft.process(input.process(), |bins| {
+                for bin in bins[64..].iter_mut() {
+                    *bin = Complex::default();
+                }
+            });
+            if i > 1024 {
+                peak = peak.max(output.abs());
+            }
+        }
+        assert!(peak < 1e-3, "{}", peak);
+    }
+
+    #[test]
+    fn test_noise_reducer() {
+        let mut reducer: NoiseReducer<512, 128> = NoiseReducer::new();
+        let mut noise = XorShift32::new(1);
+        let mut sine = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 1000.0);
+        let rms = |reducer: &mut NoiseReducer<512, 128>, noise: &mut XorShift32| {
+            let output: Vec<f32> = (0..22050)
+                .map(|_| reducer.process(0.05 * noise.next_f32_bipolar()))
+                .collect();
+            (output[11025..].iter().map(|x| x * x).sum::<f32>() / 11025.0).sqrt()
+        };
+
+        // Passes unchanged without a profile and while learning.
+        let unprocessed = rms(&mut reducer, &mut noise);
+        assert!((unprocessed - 0.05 / 3.0_f32.sqrt()).abs() < 2e-3);
+        reducer.set_learning(true);
+        assert!((rms(&mut reducer, &mut noise) - unprocessed).abs() < 2e-3);
+        reducer.set_learning(false);
+        assert!(reducer.has_profile());
+
+        // Noise alone is reduced by over 10 dB.
+        let reduced = rms(&mut reducer, &mut noise);
+        assert!(reduced < 0.3 * unprocessed, "{} {}", reduced, unprocessed);
+
+        // A tone well above the noise passes.
+        let mut peak = 0.0_f32;
+        for i in 0..22050 {
+            let output = reducer.process(0.5 * sine.process() + 0.05 * noise.next_f32_bipolar());
+            if i > 11025 {
+                peak = peak.max(output.abs());
+            }
+        }
+        assert!((peak - 0.5).abs() < 0.05, "{}", peak);
+    }
+
+    fn zero_crossings(data: &[f32]) -> usize {
+        data.windows(2)
+            .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+            .count()
+    }
+
+    #[test]
+    fn test_phase_vocoder() {
+        let mut vocoder: PhaseVocoder<1024, 256> = PhaseVocoder::new();
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 220.0);
+        for &(semitones, expected) in [(12.0, 440), (-7.0, 147), (0.0, 220)].iter() {
+            vocoder.set_semitones(semitones);
+            let output: Vec<f32> = (0..88200)
+                .map(|_| vocoder.process(0.5 * oscillator.process()))
+                .collect();
+            // The last second, after the frames from before the change have passed.
+            let crossings = zero_crossings(&output[44100..]);
+            assert!(crossings.abs_diff(expected) <= 3, "{}", crossings);
+            let peak = output[44100..]
+                .iter()
+                .fold(0.0_f32, |peak, x| peak.max(x.abs()));
+            assert!((peak - 0.5).abs() < 0.1, "{}", peak);
+        }
+
+        // Twice as long at the same pitch.
+        let input: Vec<f32> = (0..22050).map(|_| 0.5 * oscillator.process()).collect();
+        let mut output = vec![0.0; 44100];
+        let written = vocoder.stretch(&input, &mut output, 2.0);
+        assert!(written.abs_diff(44100) <= 2048, "{}", written);
+        let crossings = zero_crossings(&output[1024..written - 1024]);
+        let expected = 220.0 * (written - 2048) as f32 / SAMPLE_RATE_F;
+        assert!(
+            (crossings as f32 - expected).abs() < 3.0,
+            "{} {}",
+            crossings,
+            expected
+        );
+    }
+}
@@ -0,0 +1,791 @@
+float_math!();
+
+use core::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2, PI};
+
+use crate::control::Smoother;
+use crate::delay::DelayLine;
+use crate::filter::{Biquad, InterpolatedBiquad, OnePoleLowPass};
+use crate::frame::{Mono, Stereo};
+use crate::preset::Preset;
+use crate::processor::Processor;
+use crate::synthesis::{Oscillator, WaveType};
+use crate::units::{bpm_to_hz, db_to_linear};
+
+/// Pan law, named by the level of each channel with the source panned to the center.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum PanLaw {
+    /// -3 dB at center, sin/cos equal power panning.
+    ConstantPower,
+    /// -4.5 dB at center, halfway between constant power and linear.
+    Compromise,
+    /// -6 dB at center, linear gain panning.
+    Linear,
+}
+
+/// Calculate the left and right gains for a pan position in [-1.0, 1.0], -1.0 being hard left.
+pub fn pan_gains(law: PanLaw, pan: f32) -> (f32, f32) {
+    let x = (pan.clamp(-1.0, 1.0) + 1.0) * 0.5;
+    let theta = x * FRAC_PI_2;
+    match law {
+        PanLaw::ConstantPower => (theta.cos(), theta.sin()),
+        PanLaw::Compromise => (((1.0 - x) * theta.cos()).sqrt(), (x * theta.sin()).sqrt()),
+        PanLaw::Linear => (1.0 - x, x),
+    }
+}
+
+/// Convert left and right to mid and side, scaled so that `ms_decode` restores the input.
+pub fn ms_encode(left: f32, right: f32) -> (f32, f32) {
+    ((left + right) * 0.5, (left - right) * 0.5)
+}
+
+/// Convert mid and side back to left and right.
+pub fn ms_decode(mid: f32, side: f32) -> (f32, f32) {
+    (mid + side, mid - side)
+}
+
+/// Mid/side channel selection.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum MsChannel {
+    Mid,
+    Side,
+}
+
+/// Runs a mono processor on only the mid or side of a stereo signal, such as a high shelf on the
+/// side to add air without touching the center. The other channel passes through undelayed, so
+/// the processor should have no latency.
+pub struct MsProcessor<P> {
+    pub processor: P,
+    channel: MsChannel,
+}
+
+impl<P: Processor> MsProcessor<P> {
+    pub fn new(processor: P, channel: MsChannel) -> Self {
+        Self { processor, channel }
+    }
+
+    pub fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let (mut mid, mut side) = ms_encode(left, right);
+        match self.channel {
+            MsChannel::Mid => mid = self.processor.process(mid),
+            MsChannel::Side => side = self.processor.process(side),
+        }
+        ms_decode(mid, side)
+    }
+
+    pub fn process_frame(&mut self, input: Stereo) -> Stereo {
+        self.process(input.left(), input.right()).into()
+    }
+
+    pub fn set_channel(&mut self, channel: MsChannel) {
+        self.channel = channel;
+    }
+
+    pub fn get_channel(&self) -> MsChannel {
+        self.channel
+    }
+
+    pub fn reset(&mut self) {
+        self.processor.reset();
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct PannerParams {
+    /// Pan position, -1.0 is hard left.
+    pub pan: f32,
+    pub law: PanLaw,
+}
+
+/// Mono to stereo panner.
+pub struct Panner {
+    law: PanLaw,
+    pan: f32,
+    left: f32,
+    right: f32,
+}
+
+impl Panner {
+    pub fn new(law: PanLaw) -> Self {
+        let (left, right) = pan_gains(law, 0.0);
+        Self {
+            law,
+            pan: 0.0,
+            left,
+            right,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> (f32, f32) {
+        (input * self.left, input * self.right)
+    }
+
+    pub fn process_frame(&mut self, input: Mono) -> Stereo {
+        self.process(input.0[0]).into()
+    }
+
+    /// Set the pan position, clamped to [-1.0, 1.0].
+    pub fn set_pan(&mut self, pan: f32) {
+        self.pan = pan.clamp(-1.0, 1.0);
+        let (left, right) = pan_gains(self.law, self.pan);
+        self.left = left;
+        self.right = right;
+    }
+
+    pub fn set_law(&mut self, law: PanLaw) {
+        self.law = law;
+        self.set_pan(self.pan);
+    }
+
+    pub fn get_law(&self) -> PanLaw {
+        self.law
+    }
+}
+
+impl Preset for Panner {
+    type Params = PannerParams;
+
+    fn get_params(&self) -> PannerParams {
+        PannerParams {
+            pan: self.pan,
+            law: self.law,
+        }
+    }
+
+    fn set_params(&mut self, params: &PannerParams) {
+        self.set_law(params.law);
+        self.set_pan(params.pan);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct AutoPanParams {
+    /// LFO rate in Hz.
+    pub rate: f32,
+    pub wave_type: WaveType,
+    pub depth: f32,
+    pub law: PanLaw,
+}
+
+/// Panner with the position swept by an LFO.
+pub struct AutoPan {
+    panner: Panner,
+    lfo: Oscillator,
+    depth: f32,
+}
+
+impl AutoPan {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            panner: Panner::new(PanLaw::ConstantPower),
+            lfo: Oscillator::new(WaveType::Sine, sample_rate, 1.0),
+            depth: 1.0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> (f32, f32) {
+        let pan = self.lfo.process() * self.depth;
+        self.panner.set_pan(pan);
+        self.panner.process(input)
+    }
+
+    pub fn process_frame(&mut self, input: Mono) -> Stereo {
+        self.process(input.0[0]).into()
+    }
+
+    /// Set the LFO rate in Hz.
+    pub fn set_rate(&mut self, rate: f32) {
+        self.lfo.set_freq(rate);
+    }
+
+    /// Set the LFO rate from a tempo in BPM and the number of beats per LFO cycle.
+    pub fn set_tempo(&mut self, bpm: f32, beats: f32) {
+        self.set_rate(bpm_to_hz(bpm, beats));
+    }
+
+    /// Set the LFO waveform.
+    pub fn set_wave_type(&mut self, wave_type: WaveType) {
+        self.lfo.set_wave_type(wave_type);
+    }
+
+    /// Set the width of the sweep, clamped to [0.0-1.0].
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    pub fn set_law(&mut self, law: PanLaw) {
+        self.panner.set_law(law);
+    }
+
+    /// Restart the LFO, for syncing it to a note or beat.
+    pub fn retrigger(&mut self) {
+        self.lfo.reset();
+    }
+
+    /// Lock the LFO phase to a transport position in beats, see `Oscillator::lock_phase`.
+    pub fn lock_phase(&mut self, position: f32, beats: f32) {
+        self.lfo.lock_phase(position, beats);
+    }
+
+    pub fn reset(&mut self) {
+        self.retrigger();
+    }
+}
+
+impl Preset for AutoPan {
+    type Params = AutoPanParams;
+
+    fn get_params(&self) -> AutoPanParams {
+        AutoPanParams {
+            rate: self.lfo.get_freq(),
+            wave_type: self.lfo.get_wave_type(),
+            depth: self.depth,
+            law: self.panner.get_law(),
+        }
+    }
+
+    fn set_params(&mut self, params: &AutoPanParams) {
+        self.set_rate(params.rate);
+        self.set_wave_type(params.wave_type);
+        self.set_depth(params.depth);
+        self.set_law(params.law);
+    }
+}
+
+// Time constant of the energy averages used by the correlation clamp, in seconds.
+const WIDENER_AVERAGE_TIME: f32 = 0.1;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct StereoWidenerParams {
+    pub width: f32,
+    pub bass_mono: bool,
+    /// Bass mono frequency in Hz.
+    pub bass_mono_freq: f32,
+    pub min_correlation: f32,
+}
+
+/// Mid/side stereo widener with optional bass mono and a correlation safety clamp.
+pub struct StereoWidener {
+    width: Smoother,
+    bass_mono: bool,
+    bass_mono_freq: f32,
+    side_low_pass: OnePoleLowPass,
+    min_correlation: f32,
+    average_coef: f32,
+    mid_energy: f32,
+    side_energy: f32,
+}
+
+impl StereoWidener {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut side_low_pass = OnePoleLowPass::new(sample_rate);
+        side_low_pass.set_freq(120.0);
+        Self {
+            width: Smoother::with_value(sample_rate, 1.0),
+            bass_mono: false,
+            bass_mono_freq: 120.0,
+            side_low_pass,
+            min_correlation: -1.0,
+            average_coef: (-1.0 / (WIDENER_AVERAGE_TIME * sample_rate)).exp(),
+            mid_energy: 0.0,
+            side_energy: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let (mid, mut side) = ms_encode(left, right);
+        if self.bass_mono {
+            side -= self.side_low_pass.process(side);
+        }
+
+        let mut width = self.width.next();
+        if self.min_correlation > -1.0 {
+            self.mid_energy = mid * mid + self.average_coef * (self.mid_energy - mid * mid);
+            self.side_energy = side * side + self.average_coef * (self.side_energy - side * side);
+            // For uncorrelated mid and side the output correlation is (M - w^2 S) / (M + w^2 S),
+            // solve for the largest width w that keeps it above the minimum.
+            let c = self.min_correlation;
+            let limit = self.mid_energy * (1.0 - c);
+            let side_energy = self.side_energy * (1.0 + c);
+            if width * width * side_energy > limit {
+                width = (limit / side_energy).sqrt();
+            }
+        }
+
+        ms_decode(mid, side * width)
+    }
+
+    pub fn process_frame(&mut self, input: Stereo) -> Stereo {
+        self.process(input.left(), input.right()).into()
+    }
+
+    /// Set the stereo width, clamped to [0.0-2.0], smoothed. 0.0 is mono, 1.0 leaves the input
+    /// unchanged.
+    pub fn set_width(&mut self, width: f32) {
+        self.width.set_target(width.clamp(0.0, 2.0));
+    }
+
+    /// Enable removing the side signal below the bass mono frequency.
+    pub fn set_bass_mono(&mut self, enabled: bool) {
+        self.bass_mono = enabled;
+    }
+
+    /// Set the frequency below which the output is mono.
+    pub fn set_bass_mono_freq(&mut self, freq: f32) {
+        self.bass_mono_freq = freq;
+        self.side_low_pass.set_freq(freq);
+    }
+
+    /// Set the lowest output correlation the width is allowed to cause, clamped to [-1.0, 1.0].
+    /// -1.0 disables the safety clamp.
+    pub fn set_min_correlation(&mut self, correlation: f32) {
+        self.min_correlation = correlation.clamp(-1.0, 1.0);
+    }
+
+    /// Clear the bass mono filter and the correlation averages, and jump to the target width.
+    pub fn reset(&mut self) {
+        self.width.reset();
+        self.side_low_pass.reset();
+        self.mid_energy = 0.0;
+        self.side_energy = 0.0;
+    }
+}
+
+impl Preset for StereoWidener {
+    type Params = StereoWidenerParams;
+
+    fn get_params(&self) -> StereoWidenerParams {
+        StereoWidenerParams {
+            width: self.width.get_target(),
+            bass_mono: self.bass_mono,
+            bass_mono_freq: self.bass_mono_freq,
+            min_correlation: self.min_correlation,
+        }
+    }
+
+    fn set_params(&mut self, params: &StereoWidenerParams) {
+        self.set_width(params.width);
+        self.set_bass_mono(params.bass_mono);
+        self.set_bass_mono_freq(params.bass_mono_freq);
+        self.set_min_correlation(params.min_correlation);
+    }
+}
+
+/// Left or right channel of a stereo pair.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum StereoChannel {
+    Left,
+    Right,
+}
+
+// Range of Haas delays, shorter sounds like comb filtering and longer like an echo.
+const HAAS_MIN_DELAY: f32 = 0.005;
+const HAAS_MAX_DELAY: f32 = 0.035;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct HaasParams {
+    /// Delay in seconds.
+    pub delay: f32,
+    pub delayed_channel: StereoChannel,
+    /// Boost of the delayed channel in dB.
+    pub compensation: f32,
+}
+
+/// Haas effect widener, a mono source with one channel delayed by 5 to 35 ms. The ear hears the
+/// source as wide but pulled toward the early channel, boosting the delayed channel recentres it.
+/// Summed to mono the delay comb filters, the mono check mode outputs that sum on both channels.
+pub struct Haas<'a> {
+    sample_rate: f32,
+    delay_line: DelayLine<'a>,
+    delay: usize,
+    delayed_channel: StereoChannel,
+    compensation: f32,
+    gain: f32,
+    mono_check: bool,
+}
+
+impl<'a> Haas<'a> {
+    /// The delay line needs to hold 35 ms for the full range of delays.
+    pub fn new(sample_rate: f32, delay_line: DelayLine<'a>) -> Self {
+        let mut haas = Self {
+            sample_rate,
+            delay_line,
+            delay: 1,
+            delayed_channel: StereoChannel::Right,
+            compensation: 0.0,
+            gain: 1.0,
+            mono_check: false,
+        };
+        haas.set_delay(0.015);
+        haas.set_compensation(2.0);
+        haas
+    }
+
+    pub fn process(&mut self, input: f32) -> (f32, f32) {
+        self.delay_line.write(input);
+        let delayed = self.delay_line.tap(self.delay + 1) * self.gain;
+        let (left, right) = match self.delayed_channel {
+            StereoChannel::Left => (delayed, input),
+            StereoChannel::Right => (input, delayed),
+        };
+        if self.mono_check {
+            let mono = (left + right) * 0.5;
+            (mono, mono)
+        } else {
+            (left, right)
+        }
+    }
+
+    pub fn process_frame(&mut self, input: Mono) -> Stereo {
+        self.process(input.0[0]).into()
+    }
+
+    /// Set the delay in seconds, clamped to [0.005-0.035] and the delay line length.
+    pub fn set_delay(&mut self, delay: f32) {
+        let delay = delay.clamp(HAAS_MIN_DELAY, HAAS_MAX_DELAY) * self.sample_rate;
+        self.delay = (delay as usize).min(self.delay_line.len().saturating_sub(1));
+    }
+
+    pub fn set_delayed_channel(&mut self, channel: StereoChannel) {
+        self.delayed_channel = channel;
+    }
+
+    /// Set the boost of the delayed channel in dB, clamped to [0.0-6.0].
+    pub fn set_compensation(&mut self, compensation: f32) {
+        self.compensation = compensation.clamp(0.0, 6.0);
+        self.gain = db_to_linear(self.compensation);
+    }
+
+    /// Enable outputting the mono sum on both channels, to check how the widening collapses.
+    pub fn set_mono_check(&mut self, enabled: bool) {
+        self.mono_check = enabled;
+    }
+
+    /// Clear the delay line.
+    pub fn reset(&mut self) {
+        self.delay_line.reset();
+    }
+}
+
+impl Preset for Haas<'_> {
+    type Params = HaasParams;
+
+    fn get_params(&self) -> HaasParams {
+        HaasParams {
+            delay: self.delay as f32 / self.sample_rate,
+            delayed_channel: self.delayed_channel,
+            compensation: self.compensation,
+        }
+    }
+
+    fn set_params(&mut self, params: &HaasParams) {
+        self.set_delay(params.delay);
+        self.set_delayed_channel(params.delayed_channel);
+        self.set_compensation(params.compensation);
+    }
+}
+
+// Spherical head model of the binaural panner.
+const HEAD_RADIUS: f32 = 0.0875;
+const SPEED_OF_SOUND: f32 = 343.0;
+// Head shadow on the far ear at 90 degrees, a high shelf cut in dB.
+const HEAD_SHADOW_FREQ: f32 = 1500.0;
+const HEAD_SHADOW_DB: f32 = -12.0;
+// Time over which position changes glide, in seconds.
+const BINAURAL_SMOOTHING_TIME: f32 = 0.02;
+
+/// Headphone panner placing a mono source by azimuth with the interaural time difference of a
+/// spherical head and a high shelf for the shadow on the far ear. Cheaper than HRTF convolution
+/// and without its front/back cues, but more natural than level panning.
+pub struct BinauralPanner<'a> {
+    sample_rate: f32,
+    delay_line: DelayLine<'a>,
+    azimuth: f32,
+    // Interaural time difference in samples, positive delays the left ear.
+    itd: Smoother,
+    left_shadow: InterpolatedBiquad,
+    right_shadow: InterpolatedBiquad,
+}
+
+impl<'a> BinauralPanner<'a> {
+    /// The delay line needs at least 1 ms for the largest time difference.
+    pub fn new(sample_rate: f32, delay_line: DelayLine<'a>) -> Self {
+        let mut itd = Smoother::new(sample_rate);
+        itd.set_time(BINAURAL_SMOOTHING_TIME);
+        let mut panner = Self {
+            sample_rate,
+            delay_line,
+            azimuth: 0.0,
+            itd,
+            left_shadow: InterpolatedBiquad::new(sample_rate),
+            right_shadow: InterpolatedBiquad::new(sample_rate),
+        };
+        panner.set_azimuth(0.0);
+        panner.reset();
+        panner
+    }
+
+    pub fn process(&mut self, input: f32) -> (f32, f32) {
+        self.delay_line.write(input);
+        let itd = self.itd.next();
+        let left = self.delay_line.read_delayed_hermite(1.0 + itd.max(0.0));
+        let right = self.delay_line.read_delayed_hermite(1.0 - itd.min(0.0));
+        (
+            self.left_shadow.process(left),
+            self.right_shadow.process(right),
+        )
+    }
+
+    pub fn process_frame(&mut self, input: Mono) -> Stereo {
+        self.process(input.0[0]).into()
+    }
+
+    /// Set the direction in degrees, clamped to [-90.0, 90.0]. -90.0 is hard left, 0.0 straight
+    /// ahead.
+    pub fn set_azimuth(&mut self, azimuth: f32) {
+        self.azimuth = azimuth.clamp(-90.0, 90.0);
+        let theta = self.azimuth * PI / 180.0;
+        // Woodworth's formula for the extra path around the head to the far ear.
+        let itd = HEAD_RADIUS / SPEED_OF_SOUND * (theta + theta.sin()) * self.sample_rate;
+        let max_itd = (self.delay_line.len() as f32 - 3.0).max(0.0);
+        self.itd.set_target(itd.clamp(-max_itd, max_itd));
+
+        self.set_shadows((BINAURAL_SMOOTHING_TIME * self.sample_rate) as usize);
+    }
+
+    // Move the head shadow filters to the current azimuth over `samples` samples.
+    fn set_shadows(&mut self, samples: usize) {
+        let shadow = (self.azimuth * PI / 180.0).sin();
+        let mut target = Biquad::new(self.sample_rate);
+        target.set_high_shelf(
+            HEAD_SHADOW_FREQ,
+            FRAC_1_SQRT_2,
+            HEAD_SHADOW_DB * shadow.max(0.0),
+        );
+        self.left_shadow.set_target(&target, samples);
+        let gain = HEAD_SHADOW_DB * (-shadow).max(0.0);
+        target.set_high_shelf(HEAD_SHADOW_FREQ, FRAC_1_SQRT_2, gain);
+        self.right_shadow.set_target(&target, samples);
+    }
+
+    pub fn get_azimuth(&self) -> f32 {
+        self.azimuth
+    }
+
+    /// Clear the delay line and filters and jump to the current position.
+    pub fn reset(&mut self) {
+        self.delay_line.reset();
+        self.itd.reset();
+        self.left_shadow.reset();
+        self.right_shadow.reset();
+        self.set_shadows(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const SAMPLE_RATE_F: f32 = 44100.0;
+
+    use super::*;
+    use crate::mix::Gain;
+
+    fn db(gain: f32) -> f32 {
+        20.0 * gain.log10()
+    }
+
+    #[test]
+    fn test_stereo_presets() {
+        let mut auto_pan = AutoPan::new(SAMPLE_RATE_F);
+        auto_pan.set_rate(0.5);
+        auto_pan.set_law(PanLaw::Linear);
+        let mut restored = AutoPan::new(SAMPLE_RATE_F);
+        restored.set_params(&auto_pan.get_params());
+        assert_eq!(restored.get_params(), auto_pan.get_params());
+
+        let mut widener = StereoWidener::new(SAMPLE_RATE_F);
+        widener.set_width(1.5);
+        widener.set_bass_mono(true);
+        widener.set_bass_mono_freq(200.0);
+        let mut restored = StereoWidener::new(SAMPLE_RATE_F);
+        restored.set_params(&widener.get_params());
+        assert_eq!(restored.get_params().bass_mono_freq, 200.0);
+        assert_eq!(restored.process(1.0, -0.5), widener.process(1.0, -0.5));
+    }
+
+    #[test]
+    fn test_pan_law_center() {
+        let (left, right) = pan_gains(PanLaw::ConstantPower, 0.0);
+        assert!((db(left) + 3.01).abs() < 0.05);
+        assert!((left - right).abs() < 1e-6);
+        let (left, _) = pan_gains(PanLaw::Compromise, 0.0);
+        assert!((db(left) + 4.52).abs() < 0.05);
+        let (left, _) = pan_gains(PanLaw::Linear, 0.0);
+        assert!((db(left) + 6.02).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_pan_hard_left() {
+        for law in [PanLaw::ConstantPower, PanLaw::Compromise, PanLaw::Linear].iter() {
+            let mut panner = Panner::new(*law);
+            panner.set_pan(-1.0);
+            let (left, right) = panner.process(1.0);
+            assert!((left - 1.0).abs() < 1e-6);
+            assert!(right.abs() < 1e-6);
+
+            let mut restored = Panner::new(PanLaw::Linear);
+            restored.set_params(&panner.get_params());
+            assert_eq!(restored.get_law(), *law);
+            assert_eq!(restored.process(1.0), (left, right));
+        }
+    }
+
+    #[test]
+    fn test_widener_mono() {
+        let mut widener = StereoWidener::new(SAMPLE_RATE_F);
+        widener.set_width(0.0);
+        // Width changes are smoothed.
+        let (left, right) = widener.process(1.0, -0.5);
+        assert!((left - right).abs() > 1.0);
+        for _ in 0..882 {
+            widener.process(1.0, -0.5);
+        }
+        let (left, right) = widener.process(1.0, -0.5);
+        assert!((left - right).abs() < 1e-6);
+        assert!((left - 0.25).abs() < 1e-6);
+
+        widener.set_width(1.0);
+        widener.reset();
+        let (left, right) = widener.process(1.0, -0.5);
+        assert!((left - 1.0).abs() < 1e-6);
+        assert!((right + 0.5).abs() < 1e-6);
+        let frame = widener.process_frame(Stereo::new(1.0, -0.5));
+        assert!((frame.left() - 1.0).abs() < 1e-6);
+        assert!((frame.right() + 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_widener_correlation_clamp() {
+        let mut widener = StereoWidener::new(SAMPLE_RATE_F);
+        widener.set_width(2.0);
+        widener.set_min_correlation(0.0);
+        let mut left_osc = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 440.0);
+        let mut right_osc = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 440.0);
+        right_osc.set_phase(0.2);
+        let (mut lr, mut ll, mut rr) = (0.0, 0.0, 0.0);
+        for i in 0..44100 {
+            let (left, right) = widener.process(left_osc.process(), right_osc.process());
+            if i > 22050 {
+                lr += left * right;
+                ll += left * left;
+                rr += right * right;
+            }
+        }
+        assert!(lr / (ll * rr).sqrt() > -0.05);
+    }
+
+    #[test]
+    fn test_auto_pan_constant_power() {
+        let mut auto_pan = AutoPan::new(SAMPLE_RATE_F);
+        auto_pan.set_rate(2.0);
+        for _ in 0..44100 {
+            let (left, right) = auto_pan.process(1.0);
+            assert!((left * left + right * right - 1.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_mid_side() {
+        let (mid, side) = ms_encode(0.75, -0.25);
+        assert_eq!((mid, side), (0.25, 0.5));
+        assert_eq!(ms_decode(mid, side), (0.75, -0.25));
+
+        // Muting the side leaves the mono sum in both channels, muting the mid the difference.
+        let mut gain = Gain::new(SAMPLE_RATE_F);
+        gain.set_linear_immediate(0.0);
+        let mut side_only = MsProcessor::new(gain, MsChannel::Side);
+        assert_eq!(side_only.process(0.75, -0.25), (0.25, 0.25));
+        side_only.set_channel(MsChannel::Mid);
+        assert_eq!(side_only.process(0.75, -0.25), (0.5, -0.5));
+    }
+
+    #[test]
+    fn test_haas() {
+        let mut buffer = [0.0; 2048];
+        let mut haas = Haas::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+        haas.set_delay(0.01);
+        haas.set_compensation(0.0);
+        haas.set_delayed_channel(StereoChannel::Left);
+        // The impulse comes out of the right straight away and the left 441 samples later.
+        let mut outputs = [(0.0, 0.0); 500];
+        for (i, output) in outputs.iter_mut().enumerate() {
+            *output = haas.process(if i == 0 { 1.0 } else { 0.0 });
+        }
+        assert_eq!(outputs[0], (0.0, 1.0));
+        assert_eq!(outputs[441], (1.0, 0.0));
+        assert_eq!(
+            outputs
+                .iter()
+                .filter(|(l, r)| *l != 0.0 || *r != 0.0)
+                .count(),
+            2
+        );
+
+        // Out of range delays are clamped.
+        haas.set_delay(1.0);
+        assert!((haas.get_params().delay - 0.035).abs() < 1e-4);
+
+        haas.set_mono_check(true);
+        let (left, right) = haas.process(1.0);
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_binaural_panner() {
+        let mut buffer = [0.0; 64];
+        let mut panner = BinauralPanner::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+        let impulse_response = |panner: &mut BinauralPanner| {
+            panner.reset();
+            let mut outputs = [(0.0, 0.0); 64];
+            for (i, output) in outputs.iter_mut().enumerate() {
+                *output = panner.process(if i == 0 { 1.0 } else { 0.0 });
+            }
+            outputs
+        };
+        let peak = |outputs: &[f32]| {
+            let max = outputs.iter().fold(0.0_f32, |a, b| a.max(b.abs()));
+            outputs.iter().position(|x| x.abs() == max).unwrap()
+        };
+
+        let centre = impulse_response(&mut panner);
+        assert!(centre.iter().all(|(l, r)| l == r));
+
+        // Hard right reaches the left ear about 0.66 ms later and duller.
+        panner.set_azimuth(90.0);
+        let outputs = impulse_response(&mut panner);
+        let (left, right): (Vec<f32>, Vec<f32>) = outputs.iter().cloned().unzip();
+        assert_eq!(peak(&right), 0);
+        assert!((27..=31).contains(&peak(&left)), "{}", peak(&left));
+        let energy = |x: &[f32]| x.iter().map(|x| x * x).sum::<f32>();
+        assert!(energy(&left) < 0.5 * energy(&right));
+    }
+}
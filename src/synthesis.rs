@@ -1,9 +1,20 @@
-use micromath::F32Ext;
+float_math!();
 
 use core::f32::consts::PI;
+
+use crate::block::LANES;
+use crate::cordic::SinCos;
+use crate::random::{Random, XorShift32};
+use crate::tuning::Tuning;
+use crate::units::bpm_to_hz;
+
 const TWO_PI: f32 = PI * 2.0;
 const TWO_PI_RECIP: f32 = 1.0 / TWO_PI;
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
 pub enum WaveType {
     Sine,
     Triangle,
@@ -13,6 +24,54 @@ pub enum WaveType {
     PolyBLEPTri,
     PolyBLEPSaw,
     PolyBLEPSquare,
+    /// A new random level each cycle, sample and hold.
+    RandomStep,
+    /// Random levels each cycle joined by smooth curves, interpolated noise.
+    RandomSmooth,
+}
+
+// Default seed of the random waveforms, see `Oscillator::set_seed`.
+pub(crate) const RANDOM_SEED: u32 = 0x2f6b_3a91;
+
+// Levels of the random waveforms, moving from `from` to `to` over a cycle.
+#[derive(Clone, Copy)]
+struct RandomLevels {
+    seed: u32,
+    random: XorShift32,
+    from: f32,
+    to: f32,
+    // The first levels are drawn on the first sample, so oscillators can be const.
+    primed: bool,
+}
+
+impl RandomLevels {
+    const fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            random: XorShift32::new(seed),
+            from: 0.0,
+            to: 0.0,
+            primed: false,
+        }
+    }
+
+    fn prime(&mut self) {
+        if !self.primed {
+            self.from = self.random.next_f32_bipolar();
+            self.to = self.random.next_f32_bipolar();
+            self.primed = true;
+        }
+    }
+
+    fn next_cycle(&mut self) {
+        self.from = self.to;
+        self.to = self.random.next_f32_bipolar();
+    }
+
+    // Restart the sequence from the seed.
+    fn reset(&mut self) {
+        *self = Self::new(self.seed);
+    }
 }
 
 /// Implemented based on code from
@@ -25,74 +84,66 @@ pub struct Oscillator {
     phase: f32,
     phase_inc: f32,
     last: f32,
+    levels: RandomLevels,
 }
 
 impl Oscillator {
-    pub fn new(wave_type: WaveType, sample_rate: f32, frequency: f32) -> Self {
-        let mut sine = Self {
-            wave_type: wave_type,
+    pub const fn new(wave_type: WaveType, sample_rate: f32, frequency: f32) -> Self {
+        Self {
+            wave_type,
             sample_rate,
             amplitude: 1.0,
             frequency,
             phase: 0.0,
-            phase_inc: 0.0,
+            phase_inc: TWO_PI * frequency / sample_rate,
             last: 0.0,
-        };
-        sine.calc_phase_inc();
-        sine
+            levels: RandomLevels::new(RANDOM_SEED),
+        }
     }
 
     /// Processes the waveform to be generated, returning one sample. This should be called once per sample period.
     pub fn process(&mut self) -> f32 {
-        let out = match self.wave_type {
-            WaveType::Sine => self.phase.sin(),
-            WaveType::Triangle => {
-                let t = (self.phase * TWO_PI_RECIP * 2.0) - 1.0;
-                2.0 * (t.abs() - 0.5)
-            }
-            WaveType::Saw => -1.0 * ((self.phase * TWO_PI_RECIP * 2.0) - 1.0),
-            WaveType::Ramp => (self.phase * TWO_PI_RECIP * 2.0) - 1.0,
-            WaveType::Square => {
-                if self.phase < PI {
-                    1.0
-                } else {
-                    -1.0
-                }
-            }
-            WaveType::PolyBLEPTri => {
-                let t = self.phase * TWO_PI_RECIP;
-                let mut out = if self.phase < PI { 1.0 } else { -1.0 };
-                out += poly_blep(self.phase_inc, t);
-                out -= poly_blep(self.phase_inc, (t + 0.5) % 1.0);
-                // Leaky Integrator:
-                // y[n] = A + x[n] + (1 - A) * y[n-1]
-                out = self.phase_inc * out + (1.0 - self.phase_inc) * self.last;
-                self.last = out;
-                out
-            }
-            WaveType::PolyBLEPSaw => {
-                let t = self.phase * TWO_PI_RECIP;
-                let mut out = (2.0 * t) - 1.0;
-                out -= poly_blep(self.phase_inc, t);
-                out *= -1.0;
-                out
-            }
-            WaveType::PolyBLEPSquare => {
-                let t = self.phase * TWO_PI_RECIP;
-                let mut out = if self.phase < PI { 1.0 } else { -1.0 };
-                out += poly_blep(self.phase_inc, t);
-                out -= poly_blep(self.phase_inc, (t + 0.5) % 1.0);
-                out
-            }
-        };
+        self.levels.prime();
+        let out = waveform(
+            self.wave_type,
+            self.phase,
+            self.phase_inc,
+            &mut self.last,
+            &self.levels,
+        );
+        self.advance();
+        out * self.amplitude
+    }
+
+    /// Processes one sample like `process`, computing sine waves with `sin_cos`, for example a
+    /// hardware CORDIC. Other waveforms don't need trigonometry and are unaffected.
+    pub fn process_with<S: SinCos>(&mut self, sin_cos: &mut S) -> f32 {
+        if self.wave_type != WaveType::Sine {
+            return self.process();
+        }
+        let out = sin_cos.sin_cos(self.phase).0;
+        self.advance();
+        out * self.amplitude
+    }
+
+    /// Move on `samples` samples without computing the waveform, for an LFO read at control
+    /// rate.
+    pub fn skip(&mut self, samples: usize) {
+        self.levels.prime();
+        for _ in 0..samples {
+            self.advance();
+        }
+    }
+
+    fn advance(&mut self) {
         self.phase += self.phase_inc;
         if self.phase > TWO_PI {
             self.phase -= TWO_PI;
+            self.levels.next_cycle();
         }
-        out * self.amplitude
     }
 
-    fn calc_phase_inc(&mut self) {
+    const fn calc_phase_inc(&mut self) {
         self.phase_inc = TWO_PI * self.frequency / self.sample_rate;
     }
 
@@ -102,6 +153,33 @@ impl Oscillator {
         self.calc_phase_inc();
     }
 
+    /// Set the frequency to a MIDI note number, fractional for bends, in a tuning.
+    pub fn set_note(&mut self, note: f32, tuning: &Tuning) {
+        self.set_freq(tuning.freq(note));
+    }
+
+    pub fn get_freq(&self) -> f32 {
+        self.frequency
+    }
+
+    /// Set the frequency from a tempo in BPM and the number of beats per cycle, see
+    /// `NoteDivision::beats`.
+    pub fn set_tempo(&mut self, bpm: f32, beats: f32) {
+        self.set_freq(bpm_to_hz(bpm, beats));
+    }
+
+    /// Lock the phase to a transport position in beats, for a cycle of `beats` beats starting
+    /// on beat 0.0. Call on each beat or bar from the sequencer or beat clock to keep a tempo
+    /// synced LFO aligned, or with 0.0 on transport start. A cycle of zero or fewer beats is
+    /// ignored.
+    pub fn lock_phase(&mut self, position: f32, beats: f32) {
+        if beats <= 0.0 {
+            return;
+        }
+        let phase = (position / beats).fract();
+        self.set_phase(if phase < 0.0 { phase + 1.0 } else { phase });
+    }
+
     /// Set the amplitude.
     pub fn set_amplitude(&mut self, amplitude: f32) {
         self.amplitude = amplitude;
@@ -111,6 +189,223 @@ impl Oscillator {
     pub fn set_phase(&mut self, phase: f32) {
         self.phase = phase.clamp(0.0, 1.0) * TWO_PI;
     }
+
+    /// Get the phase, in the range 0.0-1.0.
+    pub fn get_phase(&self) -> f32 {
+        self.phase * TWO_PI_RECIP
+    }
+
+    /// Set the waveform.
+    pub fn set_wave_type(&mut self, wave_type: WaveType) {
+        self.wave_type = wave_type;
+    }
+
+    pub fn get_wave_type(&self) -> WaveType {
+        self.wave_type
+    }
+
+    /// Seed the random waveforms, so LFOs on different parameters or units don't move together.
+    /// The sequence restarts from the seed on `reset`.
+    pub fn set_seed(&mut self, seed: u32) {
+        self.levels = RandomLevels::new(seed);
+    }
+
+    /// Restart the waveform from phase 0.0, for example on a new note.
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+        self.last = 0.0;
+        self.levels.reset();
+    }
+}
+
+/// Four oscillators sharing a waveform, processed together for voices of a polyphonic synth.
+pub struct Oscillator4 {
+    wave_type: WaveType,
+    sample_rate: f32,
+    amplitude: [f32; LANES],
+    phase: [f32; LANES],
+    phase_inc: [f32; LANES],
+    last: [f32; LANES],
+    levels: [RandomLevels; LANES],
+}
+
+impl Oscillator4 {
+    pub fn new(wave_type: WaveType, sample_rate: f32, frequencies: [f32; LANES]) -> Self {
+        let mut oscillators = Self {
+            wave_type,
+            sample_rate,
+            amplitude: [1.0; LANES],
+            phase: [0.0; LANES],
+            phase_inc: [0.0; LANES],
+            last: [0.0; LANES],
+            levels: core::array::from_fn(|lane| {
+                RandomLevels::new(RANDOM_SEED.wrapping_add(lane as u32))
+            }),
+        };
+        for (lane, frequency) in frequencies.iter().enumerate() {
+            oscillators.set_freq(lane, *frequency);
+        }
+        oscillators
+    }
+
+    /// Processes one sample of each oscillator.
+    pub fn process(&mut self) -> [f32; LANES] {
+        let mut out = [0.0; LANES];
+        for (lane, out) in out.iter_mut().enumerate() {
+            self.levels[lane].prime();
+            *out = waveform(
+                self.wave_type,
+                self.phase[lane],
+                self.phase_inc[lane],
+                &mut self.last[lane],
+                &self.levels[lane],
+            ) * self.amplitude[lane];
+            self.phase[lane] += self.phase_inc[lane];
+            if self.phase[lane] > TWO_PI {
+                self.phase[lane] -= TWO_PI;
+                self.levels[lane].next_cycle();
+            }
+        }
+        out
+    }
+
+    pub fn process_block(&mut self, block: &mut [[f32; LANES]]) {
+        for frame in block.iter_mut() {
+            *frame = self.process();
+        }
+    }
+
+    /// Set the frequency of one oscillator, lanes past the last are ignored.
+    pub fn set_freq(&mut self, lane: usize, frequency: f32) {
+        if lane < LANES {
+            self.phase_inc[lane] = TWO_PI * frequency / self.sample_rate;
+        }
+    }
+
+    /// Set the amplitude of one oscillator, lanes past the last are ignored.
+    pub fn set_amplitude(&mut self, lane: usize, amplitude: f32) {
+        if lane < LANES {
+            self.amplitude[lane] = amplitude;
+        }
+    }
+
+    /// Set the phase of one oscillator to value, clamped to 0.0-1.0.
+    pub fn set_phase(&mut self, lane: usize, phase: f32) {
+        if lane < LANES {
+            self.phase[lane] = phase.clamp(0.0, 1.0) * TWO_PI;
+        }
+    }
+
+    /// Set the waveform of all oscillators.
+    pub fn set_wave_type(&mut self, wave_type: WaveType) {
+        self.wave_type = wave_type;
+    }
+
+    /// Seed the random waveforms, each lane with the next seed from `seed`.
+    pub fn set_seed(&mut self, seed: u32) {
+        for (lane, levels) in self.levels.iter_mut().enumerate() {
+            *levels = RandomLevels::new(seed.wrapping_add(lane as u32));
+        }
+    }
+
+    /// Restart one oscillator from phase 0.0, lanes past the last are ignored.
+    pub fn reset_lane(&mut self, lane: usize) {
+        if lane < LANES {
+            self.phase[lane] = 0.0;
+            self.last[lane] = 0.0;
+            self.levels[lane].reset();
+        }
+    }
+
+    /// Restart all oscillators from phase 0.0.
+    pub fn reset(&mut self) {
+        self.phase = [0.0; LANES];
+        self.last = [0.0; LANES];
+        for levels in self.levels.iter_mut() {
+            levels.reset();
+        }
+    }
+}
+
+// Sample of a waveform at `phase` in radians, `last` holds the state of the integrated waves and
+// `levels` the random levels of this cycle.
+fn waveform(
+    wave_type: WaveType,
+    phase: f32,
+    phase_inc: f32,
+    last: &mut f32,
+    levels: &RandomLevels,
+) -> f32 {
+    match wave_type {
+        WaveType::Sine => phase.sin(),
+        WaveType::Triangle => {
+            let t = (phase * TWO_PI_RECIP * 2.0) - 1.0;
+            2.0 * (t.abs() - 0.5)
+        }
+        WaveType::Saw => -((phase * TWO_PI_RECIP * 2.0) - 1.0),
+        WaveType::Ramp => (phase * TWO_PI_RECIP * 2.0) - 1.0,
+        WaveType::Square => {
+            if phase < PI {
+                1.0
+            } else {
+                -1.0
+            }
+        }
+        WaveType::PolyBLEPTri => {
+            let t = phase * TWO_PI_RECIP;
+            let mut out = if phase < PI { 1.0 } else { -1.0 };
+            out += poly_blep(phase_inc, t);
+            out -= poly_blep(phase_inc, (t + 0.5) % 1.0);
+            // Leaky Integrator:
+            // y[n] = A + x[n] + (1 - A) * y[n-1]
+            out = phase_inc * out + (1.0 - phase_inc) * *last;
+            *last = out;
+            out
+        }
+        WaveType::PolyBLEPSaw => {
+            let t = phase * TWO_PI_RECIP;
+            let mut out = (2.0 * t) - 1.0;
+            out -= poly_blep(phase_inc, t);
+            out *= -1.0;
+            out
+        }
+        WaveType::PolyBLEPSquare => {
+            let t = phase * TWO_PI_RECIP;
+            let mut out = if phase < PI { 1.0 } else { -1.0 };
+            out += poly_blep(phase_inc, t);
+            out -= poly_blep(phase_inc, (t + 0.5) % 1.0);
+            out
+        }
+        WaveType::RandomStep => levels.to,
+        WaveType::RandomSmooth => {
+            // Smoothstep, level with no jump in slope where the cycles meet.
+            let t = phase * TWO_PI_RECIP;
+            levels.from + (levels.to - levels.from) * t * t * (3.0 - 2.0 * t)
+        }
+    }
+}
+
+/// Uniform white noise in [-amplitude, amplitude].
+pub struct WhiteNoise {
+    random: XorShift32,
+    amplitude: f32,
+}
+
+impl WhiteNoise {
+    pub const fn new(seed: u32) -> Self {
+        Self {
+            random: XorShift32::new(seed),
+            amplitude: 1.0,
+        }
+    }
+
+    pub fn process(&mut self) -> f32 {
+        self.random.next_f32_bipolar() * self.amplitude
+    }
+
+    pub fn set_amplitude(&mut self, amplitude: f32) {
+        self.amplitude = amplitude;
+    }
 }
 
 // Polynomial bandlimited step calculator
@@ -136,6 +431,67 @@ mod tests {
 
     use plotters::prelude::*;
 
+    #[test]
+    fn test_const_oscillator() {
+        const OSCILLATOR: Oscillator = Oscillator::new(WaveType::Saw, SAMPLE_RATE_F, 440.0);
+        let mut oscillator = OSCILLATOR;
+        let mut reference = Oscillator::new(WaveType::Saw, SAMPLE_RATE_F, 440.0);
+        for _ in 0..1000 {
+            assert_eq!(oscillator.process(), reference.process());
+        }
+    }
+
+    #[test]
+    fn test_oscillator_tempo_sync() {
+        use crate::units::NoteDivision;
+
+        let mut lfo = Oscillator::new(WaveType::Ramp, SAMPLE_RATE_F, 1.0);
+        lfo.set_tempo(120.0, NoteDivision::Quarter.beats());
+        assert_eq!(lfo.get_freq(), 2.0);
+
+        // Two beat cycles, beat 5.5 is three quarters of the way through one.
+        lfo.lock_phase(5.5, NoteDivision::Half.beats());
+        assert!((lfo.get_phase() - 0.75).abs() < 1e-6);
+        lfo.lock_phase(-0.5, 2.0);
+        assert!((lfo.get_phase() - 0.75).abs() < 1e-6);
+        lfo.lock_phase(1.0, 0.0);
+        assert!((lfo.get_phase() - 0.75).abs() < 1e-6);
+        // Half a beat later a two beat LFO has advanced a quarter cycle.
+        lfo.set_tempo(120.0, NoteDivision::Half.beats());
+        for _ in 0..11025 {
+            lfo.process();
+        }
+        assert!(lfo.get_phase() < 1e-3 || lfo.get_phase() > 1.0 - 1e-3);
+    }
+
+    #[test]
+    fn test_oscillator_with_cordic() {
+        let mut cordic = crate::cordic::Cordic;
+        let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 9876.5);
+        let mut reference = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 9876.5);
+        for _ in 0..10000 {
+            assert!((oscillator.process_with(&mut cordic) - reference.process()).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_oscillator4_matches_scalar() {
+        let frequencies = [110.0, 220.0, 440.0, 1234.5];
+        for wave_type in [WaveType::Sine, WaveType::PolyBLEPTri, WaveType::PolyBLEPSaw].iter() {
+            let mut bank = Oscillator4::new(*wave_type, SAMPLE_RATE_F, frequencies);
+            let mut oscillators =
+                frequencies.map(|f| Oscillator::new(*wave_type, SAMPLE_RATE_F, f));
+            bank.set_amplitude(2, 0.5);
+            oscillators[2].set_amplitude(0.5);
+            for _ in 0..1000 {
+                let output = bank.process();
+                for lane in 0..4 {
+                    assert_eq!(output[lane], oscillators[lane].process());
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_sine() {
         let mut oscillator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 1.5);
@@ -326,4 +682,58 @@ mod tests {
             ))
             .unwrap();
     }
+
+    #[test]
+    fn test_random() {
+        // 100 Hz, 441 samples per cycle.
+        let mut step = Oscillator::new(WaveType::RandomStep, SAMPLE_RATE_F, 100.0);
+        let mut smooth = Oscillator::new(WaveType::RandomSmooth, SAMPLE_RATE_F, 100.0);
+        let mut runs = vec![0];
+        let mut level = step.process();
+        let mut previous = smooth.process();
+        for _ in 0..44100 {
+            let next = step.process();
+            assert!((-1.0..=1.0).contains(&next));
+            if next == level {
+                *runs.last_mut().unwrap() += 1;
+            } else {
+                runs.push(0);
+            }
+            level = next;
+            // Smooth random never jumps.
+            let output = smooth.process();
+            assert!((output - previous).abs() < 0.01);
+            previous = output;
+        }
+        // Each level is held for a whole cycle.
+        assert!(runs.len() >= 99 && runs.len() <= 101);
+        assert!(runs[1..runs.len() - 1]
+            .iter()
+            .all(|run| (439..=441).contains(run)));
+
+        // Seeded sequences repeat after a reset and differ between seeds.
+        step.set_seed(7);
+        step.reset();
+        let first: Vec<f32> = (0..2000).map(|_| step.process()).collect();
+        step.reset();
+        let second: Vec<f32> = (0..2000).map(|_| step.process()).collect();
+        assert_eq!(first, second);
+        step.set_seed(8);
+        assert_ne!(step.process(), first[0]);
+    }
+
+    #[test]
+    fn test_white_noise() {
+        let mut noise = WhiteNoise::new(1);
+        noise.set_amplitude(0.5);
+        let mut sum_squares = 0.0;
+        for _ in 0..48000 {
+            let x = noise.process();
+            assert!(x.abs() <= 0.5);
+            sum_squares += x * x;
+        }
+        // Uniform noise has an RMS of amplitude / sqrt(3).
+        let rms = (sum_squares / 48000.0_f32).sqrt();
+        assert!((rms - 0.5 / 3.0_f32.sqrt()).abs() < 0.005);
+    }
 }
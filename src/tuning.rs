@@ -0,0 +1,138 @@
+//! Tunings mapping MIDI note numbers to frequencies for non equal temperaments. A 12 note scale
+//! of cent offsets repeats every octave, such as the historical temperaments or a 12 note Scala
+//! file, while a table gives every note its own frequency for scales that don't repeat at the
+//! octave. Fractional notes from pitch bends move smoothly in pitch between the neighbouring notes.
+
+float_math!();
+
+use crate::units::{cents_to_ratio, note_to_freq};
+
+/// Number of MIDI notes in a full tuning table.
+pub const NOTES: usize = 128;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Scale<'a> {
+    /// 12 tone equal temperament.
+    Equal,
+    /// Offsets in cents from equal temperament of each note from the root, repeating every octave.
+    Octave([f32; 12]),
+    /// Frequency in Hz of each MIDI note, the reference and root are ignored.
+    Table(&'a [f32; NOTES]),
+}
+
+/// Note to frequency mapping of a scale, with the pitch of A4 and the root note of octave
+/// repeating scales adjustable.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Tuning<'a> {
+    scale: Scale<'a>,
+    reference: f32,
+    root: u8,
+}
+
+impl<'a> Tuning<'a> {
+    /// A4 at 440 Hz with octave scales rooted on C.
+    pub const fn new(scale: Scale<'a>) -> Self {
+        Self {
+            scale,
+            reference: 440.0,
+            root: 0,
+        }
+    }
+
+    /// Octave scale from Scala style pitches of the degrees above the root in cents, the first
+    /// degree is the root itself at 0.0.
+    pub fn from_cents(cents: [f32; 12]) -> Self {
+        let mut offsets = [0.0; 12];
+        for (i, (offset, cents)) in offsets.iter_mut().zip(cents.iter()).enumerate() {
+            *offset = cents - 100.0 * i as f32;
+        }
+        Self::new(Scale::Octave(offsets))
+    }
+
+    /// Frequency in Hz of a MIDI note number, fractional notes are interpolated in pitch.
+    pub fn freq(&self, note: f32) -> f32 {
+        let note = note.clamp(0.0, (NOTES - 1) as f32);
+        let whole = (note as usize).min(NOTES - 2);
+        let frac = note - whole as f32;
+        match self.scale {
+            Scale::Equal => self.equal(note),
+            Scale::Octave(offsets) => {
+                let offset = |note: usize| offsets[(note + 12 - self.root as usize % 12) % 12];
+                let cents = offset(whole) + (offset(whole + 1) - offset(whole)) * frac;
+                self.equal(note) * cents_to_ratio(cents)
+            }
+            Scale::Table(table) => {
+                let (a, b) = (table[whole], table[whole + 1]);
+                a * (b / a).powf(frac)
+            }
+        }
+    }
+
+    fn equal(&self, note: f32) -> f32 {
+        note_to_freq(note) * self.reference / 440.0
+    }
+
+    /// Set the pitch of A4 in Hz.
+    pub fn set_reference(&mut self, freq: f32) {
+        self.reference = freq;
+    }
+
+    /// Set the note, [0-11] from C, the first offset of an octave scale applies to.
+    pub fn set_root(&mut self, root: u8) {
+        self.root = root % 12;
+    }
+
+    pub fn set_scale(&mut self, scale: Scale<'a>) {
+        self.scale = scale;
+    }
+
+    pub fn get_scale(&self) -> Scale<'a> {
+        self.scale
+    }
+}
+
+impl Default for Tuning<'_> {
+    fn default() -> Self {
+        Self::new(Scale::Equal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::ratio_to_cents;
+
+    #[test]
+    fn test_tuning() {
+        let equal = Tuning::default();
+        assert!((equal.freq(69.0) - 440.0).abs() < 1e-3);
+        assert!((equal.freq(81.0) - 880.0).abs() < 1e-2);
+
+        // Just intonation on C, the major third and fifth are pure.
+        let mut just = Tuning::from_cents([
+            0.0, 111.73, 203.91, 315.64, 386.31, 498.04, 590.22, 701.96, 813.69, 884.36, 1017.6,
+            1088.27,
+        ]);
+        let c4 = just.freq(60.0);
+        assert!((just.freq(64.0) / c4 - 1.25).abs() < 1e-4);
+        assert!((just.freq(67.0) / c4 - 1.5).abs() < 1e-4);
+        assert!((just.freq(72.0) / c4 - 2.0).abs() < 1e-4);
+        // A bend half way moves half way in cents.
+        let bent = ratio_to_cents(just.freq(64.5) / c4);
+        assert!((bent - (386.31 + 498.04) / 2.0).abs() < 0.01);
+
+        // Moving the root to D makes D to F# pure instead.
+        just.set_root(2);
+        assert!((just.freq(66.0) / just.freq(62.0) - 1.25).abs() < 1e-4);
+        just.set_reference(415.0);
+        assert!((just.freq(66.0) / just.freq(62.0) - 1.25).abs() < 1e-4);
+
+        // A table of quarter tones, 24 notes to the octave.
+        let table: [f32; NOTES] =
+            core::array::from_fn(|note| 440.0 * 2.0_f32.powf((note as f32 - 69.0) / 24.0));
+        let quarter = Tuning::new(Scale::Table(&table));
+        assert!((quarter.freq(93.0) - 880.0).abs() < 1e-2);
+        assert!((quarter.freq(69.5) - 440.0 * 2.0_f32.powf(0.5 / 24.0)).abs() < 1e-3);
+        assert_eq!(quarter.freq(200.0), table[NOTES - 1]);
+    }
+}
@@ -0,0 +1,165 @@
+//! Conversions between the units parameters are given in and the values processors work with.
+//! Levels in dB are amplitude dB, 0 dB is a linear gain of 1.0. Conversions without
+//! transcendental math are `const fn` so they can set up statics.
+
+use core::f32::consts::{LN_2, PI};
+
+float_math!();
+
+/// Lowest level returned by `linear_to_db`, for silence.
+pub const MIN_DB: f32 = -200.0;
+
+pub fn db_to_linear(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+/// Amplitude in dB, silence and negative values give `MIN_DB`.
+pub fn linear_to_db(linear: f32) -> f32 {
+    20.0 * linear.max(1e-10).log10()
+}
+
+/// Power, such as a mean square, in dB. Silence gives `MIN_DB`.
+pub fn power_to_db(power: f32) -> f32 {
+    10.0 * power.max(1e-20).log10()
+}
+
+pub const fn ms_to_samples(ms: f32, sample_rate: f32) -> f32 {
+    ms * sample_rate / 1000.0
+}
+
+pub const fn samples_to_ms(samples: f32, sample_rate: f32) -> f32 {
+    samples * 1000.0 / sample_rate
+}
+
+/// Angular frequency in radians per sample.
+pub const fn hz_to_radians(freq: f32, sample_rate: f32) -> f32 {
+    2.0 * PI * freq / sample_rate
+}
+
+pub const fn radians_to_hz(radians: f32, sample_rate: f32) -> f32 {
+    radians * sample_rate / (2.0 * PI)
+}
+
+/// Frequency ratio of an interval, 1200 cents to an octave.
+pub fn cents_to_ratio(cents: f32) -> f32 {
+    2.0_f32.powf(cents / 1200.0)
+}
+
+pub fn semitones_to_ratio(semitones: f32) -> f32 {
+    cents_to_ratio(semitones * 100.0)
+}
+
+pub fn ratio_to_cents(ratio: f32) -> f32 {
+    1200.0 * ratio.ln() / LN_2
+}
+
+pub fn ratio_to_semitones(ratio: f32) -> f32 {
+    ratio_to_cents(ratio) / 100.0
+}
+
+/// Frequency in Hz of a MIDI note number in 12 tone equal temperament, A4 (69) at 440 Hz.
+/// Fractional notes are bent between the neighbouring notes.
+pub fn note_to_freq(note: f32) -> f32 {
+    440.0 * semitones_to_ratio(note - 69.0)
+}
+
+/// MIDI note number, usually fractional, of a frequency in Hz.
+pub fn freq_to_note(freq: f32) -> f32 {
+    69.0 + ratio_to_semitones(freq / 440.0)
+}
+
+/// Length of a beat in samples at a tempo in BPM.
+pub const fn bpm_to_samples(bpm: f32, sample_rate: f32) -> f32 {
+    60.0 * sample_rate / bpm
+}
+
+/// Tempo in BPM of a beat lasting `samples` samples.
+pub const fn samples_to_bpm(samples: f32, sample_rate: f32) -> f32 {
+    60.0 * sample_rate / samples
+}
+
+/// Rate in Hz of a cycle lasting `beats` beats at a tempo in BPM.
+pub const fn bpm_to_hz(bpm: f32, beats: f32) -> f32 {
+    bpm / (60.0 * beats)
+}
+
+/// Note lengths for tempo synced rates, a beat being a quarter note.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum NoteDivision {
+    FourBars,
+    TwoBars,
+    Whole,
+    Half,
+    DottedQuarter,
+    Quarter,
+    QuarterTriplet,
+    DottedEighth,
+    Eighth,
+    EighthTriplet,
+    Sixteenth,
+    SixteenthTriplet,
+    ThirtySecond,
+}
+
+impl NoteDivision {
+    /// Length in beats, bars being four beats.
+    pub const fn beats(self) -> f32 {
+        match self {
+            NoteDivision::FourBars => 16.0,
+            NoteDivision::TwoBars => 8.0,
+            NoteDivision::Whole => 4.0,
+            NoteDivision::Half => 2.0,
+            NoteDivision::DottedQuarter => 1.5,
+            NoteDivision::Quarter => 1.0,
+            NoteDivision::QuarterTriplet => 2.0 / 3.0,
+            NoteDivision::DottedEighth => 0.75,
+            NoteDivision::Eighth => 0.5,
+            NoteDivision::EighthTriplet => 1.0 / 3.0,
+            NoteDivision::Sixteenth => 0.25,
+            NoteDivision::SixteenthTriplet => 1.0 / 6.0,
+            NoteDivision::ThirtySecond => 0.125,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const SAMPLE_RATE_F: f32 = 48000.0;
+
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        assert!((db_to_linear(-6.0) - 0.501).abs() < 1e-3);
+        assert!((linear_to_db(db_to_linear(-12.5)) + 12.5).abs() < 1e-4);
+        assert_eq!(linear_to_db(0.0), MIN_DB);
+        assert_eq!(linear_to_db(-1.0), MIN_DB);
+        assert_eq!(power_to_db(0.0), MIN_DB);
+        assert!((power_to_db(0.01) + 20.0).abs() < 1e-4);
+
+        const DELAY: f32 = ms_to_samples(10.0, SAMPLE_RATE_F);
+        assert_eq!(DELAY, 480.0);
+        assert_eq!(samples_to_ms(DELAY, SAMPLE_RATE_F), 10.0);
+        assert!(
+            (radians_to_hz(hz_to_radians(1000.0, SAMPLE_RATE_F), SAMPLE_RATE_F) - 1000.0).abs()
+                < 1e-3
+        );
+
+        assert!((semitones_to_ratio(12.0) - 2.0).abs() < 1e-5);
+        assert!((note_to_freq(60.0) - 261.626).abs() < 1e-2);
+        assert!((freq_to_note(note_to_freq(64.5)) - 64.5).abs() < 1e-4);
+        assert!((ratio_to_semitones(1.5) - 7.02).abs() < 1e-2);
+        assert!((ratio_to_cents(cents_to_ratio(-35.0)) + 35.0).abs() < 1e-3);
+
+        assert_eq!(bpm_to_samples(120.0, SAMPLE_RATE_F), 24000.0);
+        assert_eq!(samples_to_bpm(24000.0, SAMPLE_RATE_F), 120.0);
+        assert_eq!(bpm_to_hz(120.0, 4.0), 0.5);
+        assert_eq!(
+            bpm_to_hz(120.0, NoteDivision::DottedEighth.beats()),
+            8.0 / 3.0
+        );
+    }
+}
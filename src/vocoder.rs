@@ -0,0 +1,221 @@
+float_math!();
+
+use crate::dynamics::EnvelopeFollower;
+use crate::filter::Biquad;
+use crate::preset::Preset;
+use crate::synthesis::{Oscillator, WaveType};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct VocoderParams {
+    /// Center frequency of the lowest band in Hz.
+    pub min_freq: f32,
+    /// Center frequency of the highest band in Hz.
+    pub max_freq: f32,
+    /// Internal carrier frequency in Hz.
+    pub carrier_freq: f32,
+    pub carrier_wave_type: WaveType,
+    /// Envelope attack time in seconds.
+    pub attack: f32,
+    /// Envelope release time in seconds.
+    pub release: f32,
+}
+
+/// Channel vocoder with `BANDS` log-spaced bands.
+/// The modulator's per-band envelopes are imposed on the matching bands of the carrier,
+/// either the internal oscillator or an external signal.
+pub struct Vocoder<const BANDS: usize> {
+    sample_rate: f32,
+    analysis: [Biquad; BANDS],
+    synthesis: [Biquad; BANDS],
+    envelopes: [EnvelopeFollower; BANDS],
+    carrier: Oscillator,
+    min_freq: f32,
+    max_freq: f32,
+    attack: f32,
+    release: f32,
+}
+
+impl<const BANDS: usize> Vocoder<BANDS> {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut vocoder = Self {
+            sample_rate,
+            analysis: core::array::from_fn(|_| Biquad::new(sample_rate)),
+            synthesis: core::array::from_fn(|_| Biquad::new(sample_rate)),
+            envelopes: core::array::from_fn(|_| EnvelopeFollower::new(sample_rate)),
+            carrier: Oscillator::new(WaveType::PolyBLEPSaw, sample_rate, 110.0),
+            min_freq: 100.0,
+            max_freq: 8000.0,
+            attack: 0.0,
+            release: 0.0,
+        };
+        vocoder.calc_bands();
+        vocoder.set_attack(0.002);
+        vocoder.set_release(0.02);
+        vocoder
+    }
+
+    fn calc_bands(&mut self) {
+        let max_freq = self.max_freq.min(self.sample_rate * 0.45);
+        let ratio = if BANDS > 1 {
+            (max_freq / self.min_freq).powf(1.0 / (BANDS - 1) as f32)
+        } else {
+            2.0
+        };
+        // Bands meet at their -3 dB points.
+        let q = ratio.sqrt() / (ratio - 1.0);
+        let mut freq = self.min_freq;
+        for (analysis, synthesis) in self.analysis.iter_mut().zip(self.synthesis.iter_mut()) {
+            analysis.set_band_pass(freq, q);
+            synthesis.set_band_pass(freq, q);
+            freq *= ratio;
+        }
+    }
+
+    /// Process one sample of the modulator using the internal oscillator as the carrier.
+    pub fn process(&mut self, modulator: f32) -> f32 {
+        let carrier = self.carrier.process();
+        self.process_external(modulator, carrier)
+    }
+
+    /// Process one sample of the modulator with an external carrier.
+    pub fn process_external(&mut self, modulator: f32, carrier: f32) -> f32 {
+        let mut output = 0.0;
+        for ((analysis, synthesis), envelope) in self
+            .analysis
+            .iter_mut()
+            .zip(self.synthesis.iter_mut())
+            .zip(self.envelopes.iter_mut())
+        {
+            let level = envelope.process(analysis.process(modulator));
+            output += synthesis.process(carrier) * level;
+        }
+        output
+    }
+
+    /// Set the center frequencies of the lowest and highest bands.
+    pub fn set_freq_range(&mut self, min_freq: f32, max_freq: f32) {
+        self.min_freq = min_freq.max(20.0);
+        self.max_freq = max_freq.max(self.min_freq * 2.0);
+        self.calc_bands();
+    }
+
+    /// Set the internal carrier frequency.
+    pub fn set_carrier_freq(&mut self, freq: f32) {
+        self.carrier.set_freq(freq);
+    }
+
+    /// Set the internal carrier waveform.
+    pub fn set_carrier_wave_type(&mut self, wave_type: WaveType) {
+        self.carrier.set_wave_type(wave_type);
+    }
+
+    /// Set the attack time of the band envelope followers in seconds.
+    pub fn set_attack(&mut self, attack: f32) {
+        self.attack = attack;
+        for envelope in self.envelopes.iter_mut() {
+            envelope.set_attack(attack);
+        }
+    }
+
+    /// Set the release time of the band envelope followers in seconds.
+    pub fn set_release(&mut self, release: f32) {
+        self.release = release;
+        for envelope in self.envelopes.iter_mut() {
+            envelope.set_release(release);
+        }
+    }
+
+    /// Clear the band filters and envelopes and restart the carrier.
+    pub fn reset(&mut self) {
+        for filter in self.analysis.iter_mut().chain(self.synthesis.iter_mut()) {
+            filter.reset();
+        }
+        for envelope in self.envelopes.iter_mut() {
+            envelope.reset();
+        }
+        self.carrier.reset();
+    }
+}
+
+impl<const BANDS: usize> Preset for Vocoder<BANDS> {
+    type Params = VocoderParams;
+
+    fn get_params(&self) -> VocoderParams {
+        VocoderParams {
+            min_freq: self.min_freq,
+            max_freq: self.max_freq,
+            carrier_freq: self.carrier.get_freq(),
+            carrier_wave_type: self.carrier.get_wave_type(),
+            attack: self.attack,
+            release: self.release,
+        }
+    }
+
+    fn set_params(&mut self, params: &VocoderParams) {
+        self.set_freq_range(params.min_freq, params.max_freq);
+        self.set_carrier_freq(params.carrier_freq);
+        self.set_carrier_wave_type(params.carrier_wave_type);
+        self.set_attack(params.attack);
+        self.set_release(params.release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const SAMPLE_RATE_F: f32 = 44100.0;
+
+    use super::*;
+
+    #[test]
+    fn test_vocoder_silent_modulator() {
+        let mut vocoder: Vocoder<16> = Vocoder::new(SAMPLE_RATE_F);
+        for _ in 0..4410 {
+            assert_eq!(vocoder.process(0.0), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_vocoder_follows_modulator_band() {
+        let mut vocoder: Vocoder<8> = Vocoder::new(SAMPLE_RATE_F);
+        let mut modulator = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 1000.0);
+        let mut carrier = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 1000.0);
+        let mut energy_in_band = 0.0;
+        for _ in 0..44100 {
+            let out = vocoder.process_external(modulator.process(), carrier.process());
+            energy_in_band += out * out;
+        }
+
+        // A carrier away from the modulated band is mostly suppressed.
+        let mut vocoder: Vocoder<8> = Vocoder::new(SAMPLE_RATE_F);
+        let mut carrier = Oscillator::new(WaveType::Sine, SAMPLE_RATE_F, 6000.0);
+        let mut energy_out_of_band = 0.0;
+        for _ in 0..44100 {
+            let out = vocoder.process_external(modulator.process(), carrier.process());
+            energy_out_of_band += out * out;
+        }
+        assert!(energy_in_band > 5.0 * energy_out_of_band);
+    }
+
+    #[test]
+    fn test_vocoder_preset() {
+        let mut vocoder: Vocoder<8> = Vocoder::new(SAMPLE_RATE_F);
+        vocoder.set_freq_range(200.0, 5000.0);
+        vocoder.set_carrier_freq(220.0);
+        vocoder.set_carrier_wave_type(WaveType::PolyBLEPSquare);
+        vocoder.set_release(0.05);
+        let params = vocoder.get_params();
+        assert_eq!(params.attack, 0.002);
+        assert_eq!(params.carrier_wave_type, WaveType::PolyBLEPSquare);
+
+        let mut restored: Vocoder<8> = Vocoder::new(SAMPLE_RATE_F);
+        restored.set_params(&params);
+        let mut modulator = Oscillator::new(WaveType::Saw, SAMPLE_RATE_F, 110.0);
+        for _ in 0..4410 {
+            let x = modulator.process();
+            assert_eq!(vocoder.process(x), restored.process(x));
+        }
+    }
+}
@@ -0,0 +1,456 @@
+//! Polyphonic voice allocation. A `VoiceAllocator` hands MIDI notes to a fixed array of voices,
+//! reusing idle voices first and asking a `StealPolicy` which one to take over once all of them
+//! are sounding. Voices are told when they are stolen and when their release has finished, so a
+//! sampler can fade a stolen voice out quickly instead of cutting it. MPE expression is routed
+//! by channel to the voices that have a `NoteExpression`.
+
+use crate::control::NoteExpression;
+
+// MIDI channels, MPE gives every sounding note its own.
+const CHANNELS: usize = 16;
+
+/// A synth or sampler voice played by a `VoiceAllocator`.
+pub trait Voice {
+    /// Start `note` at MIDI `velocity`.
+    fn note_on(&mut self, note: u8, velocity: u8);
+
+    /// Start the release.
+    fn note_off(&mut self);
+
+    /// True while the voice is sounding, including its release.
+    fn is_active(&self) -> bool;
+
+    /// Called before a sounding voice is given a new note, `note_on` follows straight away.
+    fn on_steal(&mut self) {}
+
+    /// Called once a released voice has gone silent.
+    fn on_release_done(&mut self) {}
+
+    /// The voice's per note expression, for voices that respond to MPE.
+    fn expression(&mut self) -> Option<&mut NoteExpression> {
+        None
+    }
+}
+
+// Last expression messages of a channel, applied to notes started on it later.
+#[derive(Clone, Copy)]
+struct ChannelExpression {
+    bend: u16,
+    pressure: u8,
+    timbre: u8,
+}
+
+/// What the allocator knows about a voice.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct VoiceState {
+    pub channel: u8,
+    pub note: u8,
+    /// True while the key is held.
+    pub held: bool,
+    /// True while the voice is sounding, including its release.
+    pub active: bool,
+    /// Order the voice was last started in, higher is newer.
+    pub age: u32,
+}
+
+/// Chooses the voice to steal for a new note when every voice is sounding.
+pub trait StealPolicy {
+    /// Return the index of the voice to steal.
+    fn choose(&mut self, voices: &[VoiceState]) -> usize;
+}
+
+// Released voices first, then by age picked with `newer`.
+fn choose_by_age(voices: &[VoiceState], newer: bool) -> usize {
+    voices
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, voice)| {
+            let age = if newer {
+                u32::MAX - voice.age
+            } else {
+                voice.age
+            };
+            (voice.held, age)
+        })
+        .map_or(0, |(index, _)| index)
+}
+
+/// Steal the oldest released voice, or the oldest held one if none are released.
+pub struct StealOldest;
+
+impl StealPolicy for StealOldest {
+    fn choose(&mut self, voices: &[VoiceState]) -> usize {
+        choose_by_age(voices, false)
+    }
+}
+
+/// Steal the newest released voice, or the newest held one, so held chords keep sounding.
+pub struct StealNewest;
+
+impl StealPolicy for StealNewest {
+    fn choose(&mut self, voices: &[VoiceState]) -> usize {
+        choose_by_age(voices, true)
+    }
+}
+
+/// Plays notes on `N` voices, at least one. Call `update` once per block so voices whose release
+/// has finished become idle.
+pub struct VoiceAllocator<V, S, const N: usize> {
+    voices: [V; N],
+    states: [VoiceState; N],
+    policy: S,
+    age: u32,
+    channels: [ChannelExpression; CHANNELS],
+}
+
+impl<V: Voice, S: StealPolicy, const N: usize> VoiceAllocator<V, S, N> {
+    // The voice count, failing the build for an allocator without voices.
+    const VOICES: usize = {
+        assert!(N > 0, "a VoiceAllocator needs at least one voice");
+        N
+    };
+
+    pub fn new(voices: [V; N], policy: S) -> Self {
+        let _ = Self::VOICES;
+        Self {
+            voices,
+            states: [VoiceState {
+                channel: 0,
+                note: 0,
+                held: false,
+                active: false,
+                age: 0,
+            }; N],
+            policy,
+            age: 0,
+            channels: [ChannelExpression {
+                bend: 8192,
+                pressure: 0,
+                timbre: 64,
+            }; CHANNELS],
+        }
+    }
+
+    /// Start a note, returning the index of the voice playing it. Idle voices are used first,
+    /// then the policy picks one to steal. The voice's expression starts from the last values
+    /// sent on `channel`.
+    pub fn note_on(&mut self, channel: u8, note: u8, velocity: u8) -> usize {
+        self.update();
+        let index = match self.states.iter().position(|state| !state.active) {
+            Some(index) => index,
+            None => {
+                let index = self.policy.choose(&self.states).min(Self::VOICES - 1);
+                trace!(
+                    "voice {} stolen from note {} for note {}",
+                    index,
+                    self.states[index].note,
+                    note
+                );
+                self.voices[index].on_steal();
+                index
+            }
+        };
+        self.age = self.next_age();
+        self.states[index] = VoiceState {
+            channel,
+            note,
+            held: true,
+            active: true,
+            age: self.age,
+        };
+        let voice = &mut self.voices[index];
+        voice.note_on(note, velocity);
+        let values = self.channels[channel as usize % CHANNELS];
+        if let Some(expression) = voice.expression() {
+            expression.set_bend(values.bend);
+            expression.set_pressure(values.pressure);
+            expression.set_timbre(values.timbre);
+            expression.start_note();
+        }
+        index
+    }
+
+    // Count up the ages, renumbering the voices in start order from 1 before the counter wraps
+    // so the policies keep comparing them correctly.
+    fn next_age(&mut self) -> u32 {
+        if self.age == u32::MAX {
+            let mut ages = [0; N];
+            for (age, state) in ages.iter_mut().zip(self.states.iter()) {
+                *age = 1 + self
+                    .states
+                    .iter()
+                    .filter(|other| other.age < state.age)
+                    .count() as u32;
+            }
+            for (state, age) in self.states.iter_mut().zip(ages.iter()) {
+                state.age = *age;
+            }
+            self.age = N as u32;
+        }
+        self.age + 1
+    }
+
+    // Pass expression to the sounding voices on `channel`, including released ones.
+    fn route(&mut self, channel: u8, mut apply: impl FnMut(&mut NoteExpression)) {
+        for (voice, state) in self.voices.iter_mut().zip(self.states.iter()) {
+            if state.active && state.channel == channel {
+                if let Some(expression) = voice.expression() {
+                    apply(expression);
+                }
+            }
+        }
+    }
+
+    /// Route a 14 bit pitch bend message on `channel`, see `NoteExpression::set_bend`.
+    pub fn set_bend(&mut self, channel: u8, value: u16) {
+        self.channels[channel as usize % CHANNELS].bend = value;
+        self.route(channel, |expression| expression.set_bend(value));
+    }
+
+    /// Route a 7 bit channel pressure message on `channel`.
+    pub fn set_pressure(&mut self, channel: u8, value: u8) {
+        self.channels[channel as usize % CHANNELS].pressure = value;
+        self.route(channel, |expression| expression.set_pressure(value));
+    }
+
+    /// Route a 7 bit CC 74 message on `channel`.
+    pub fn set_timbre(&mut self, channel: u8, value: u8) {
+        self.channels[channel as usize % CHANNELS].timbre = value;
+        self.route(channel, |expression| expression.set_timbre(value));
+    }
+
+    /// Release the voices holding `note` on `channel`.
+    pub fn note_off(&mut self, channel: u8, note: u8) {
+        for (voice, state) in self.voices.iter_mut().zip(self.states.iter_mut()) {
+            if state.held && state.channel == channel && state.note == note {
+                state.held = false;
+                voice.note_off();
+            }
+        }
+    }
+
+    /// Release every held voice.
+    pub fn all_notes_off(&mut self) {
+        for (voice, state) in self.voices.iter_mut().zip(self.states.iter_mut()) {
+            if state.held {
+                state.held = false;
+                voice.note_off();
+            }
+        }
+    }
+
+    /// Free the released voices that have gone silent.
+    pub fn update(&mut self) {
+        for (voice, state) in self.voices.iter_mut().zip(self.states.iter_mut()) {
+            if state.active && !state.held && !voice.is_active() {
+                state.active = false;
+                voice.on_release_done();
+            }
+        }
+    }
+
+    /// Get the number of voices whose key is held.
+    pub fn held_count(&self) -> usize {
+        self.states.iter().filter(|state| state.held).count()
+    }
+
+    pub fn voices(&self) -> &[V; N] {
+        &self.voices
+    }
+
+    /// Access the voices, for rendering them or changing their parameters.
+    pub fn voices_mut(&mut self) -> &mut [V; N] {
+        &mut self.voices
+    }
+
+    pub fn states(&self) -> &[VoiceState; N] {
+        &self.states
+    }
+
+    pub fn set_policy(&mut self, policy: S) {
+        self.policy = policy;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Counts down its release one `tick` at a time and records the hooks.
+    #[derive(Default)]
+    struct TestVoice {
+        note: u8,
+        release: u32,
+        held: bool,
+        steals: u32,
+        releases_done: u32,
+    }
+
+    impl TestVoice {
+        fn tick(&mut self) {
+            if !self.held {
+                self.release = self.release.saturating_sub(1);
+            }
+        }
+    }
+
+    impl Voice for TestVoice {
+        fn note_on(&mut self, note: u8, _velocity: u8) {
+            self.note = note;
+            self.held = true;
+            self.release = 2;
+        }
+
+        fn note_off(&mut self) {
+            self.held = false;
+        }
+
+        fn is_active(&self) -> bool {
+            self.held || self.release > 0
+        }
+
+        fn on_steal(&mut self) {
+            self.steals += 1;
+        }
+
+        fn on_release_done(&mut self) {
+            self.releases_done += 1;
+        }
+    }
+
+    #[test]
+    fn test_allocator_steals_oldest() {
+        let mut allocator: VoiceAllocator<TestVoice, _, 2> =
+            VoiceAllocator::new(Default::default(), StealOldest);
+        assert_eq!(allocator.note_on(0, 60, 100), 0);
+        assert_eq!(allocator.note_on(0, 64, 100), 1);
+        assert_eq!(allocator.held_count(), 2);
+
+        // Both held, the oldest is stolen.
+        assert_eq!(allocator.note_on(0, 67, 100), 0);
+        assert_eq!(allocator.voices()[0].steals, 1);
+        assert_eq!(allocator.voices()[0].note, 67);
+
+        // A released voice is stolen before an older held one.
+        allocator.note_off(0, 67);
+        assert_eq!(allocator.note_on(0, 71, 100), 0);
+        assert_eq!(allocator.voices()[0].steals, 2);
+
+        // Once the release has finished the voice is idle and reused without stealing.
+        allocator.note_off(0, 71);
+        for voice in allocator.voices_mut().iter_mut() {
+            voice.tick();
+            voice.tick();
+        }
+        allocator.update();
+        assert_eq!(allocator.voices()[0].releases_done, 1);
+        assert!(!allocator.states()[0].active);
+        assert_eq!(allocator.note_on(0, 72, 100), 0);
+        assert_eq!(allocator.voices()[0].steals, 2);
+    }
+
+    struct MpeVoice {
+        expression: NoteExpression,
+        active: bool,
+    }
+
+    impl Voice for MpeVoice {
+        fn note_on(&mut self, _note: u8, _velocity: u8) {
+            self.active = true;
+        }
+
+        fn note_off(&mut self) {
+            self.active = false;
+        }
+
+        fn is_active(&self) -> bool {
+            self.active
+        }
+
+        fn expression(&mut self) -> Option<&mut NoteExpression> {
+            Some(&mut self.expression)
+        }
+    }
+
+    #[test]
+    fn test_allocator_routes_expression() {
+        let voices = core::array::from_fn(|_| MpeVoice {
+            expression: NoteExpression::new(44100.0),
+            active: false,
+        });
+        let mut allocator: VoiceAllocator<MpeVoice, _, 2> =
+            VoiceAllocator::new(voices, StealOldest);
+        // Sent before the note on, the note starts from it.
+        allocator.set_bend(1, 12288);
+        allocator.note_on(1, 60, 100);
+        allocator.note_on(2, 64, 100);
+        assert_eq!(allocator.voices()[0].expression.get_bend(), 24.0);
+        assert_eq!(allocator.voices()[1].expression.get_bend(), 0.0);
+
+        // Only the voice on the message's channel follows it.
+        allocator.set_timbre(2, 127);
+        for voice in allocator.voices_mut().iter_mut() {
+            voice.expression.set_smoothing_time(0.0);
+            voice.expression.tick();
+        }
+        assert_eq!(allocator.voices()[0].expression.get_timbre(), 64.0 / 127.0);
+        assert_eq!(allocator.voices()[1].expression.get_timbre(), 1.0);
+    }
+
+    #[test]
+    fn test_allocator_fingered_portamento() {
+        use crate::control::{Glide, GlideMode};
+
+        let mut allocator: VoiceAllocator<TestVoice, _, 2> =
+            VoiceAllocator::new(Default::default(), StealOldest);
+        let mut glide = Glide::new(1000.0);
+        glide.set_mode(GlideMode::Legato);
+        let mut play = |allocator: &mut VoiceAllocator<TestVoice, StealOldest, 2>, note: u8| {
+            glide.note_on(note as f32, allocator.held_count() > 0);
+            allocator.note_on(0, note, 100);
+            glide.next()
+        };
+        assert_eq!(play(&mut allocator, 60), 60.0);
+        // Played while 60 is held, so it glides.
+        assert!(play(&mut allocator, 67) < 61.0);
+        allocator.all_notes_off();
+        assert_eq!(play(&mut allocator, 72), 72.0);
+    }
+
+    #[test]
+    fn test_allocator_age_wraps() {
+        let mut allocator: VoiceAllocator<TestVoice, _, 2> =
+            VoiceAllocator::new(Default::default(), StealOldest);
+        allocator.age = u32::MAX - 1;
+        allocator.note_on(0, 60, 100);
+        allocator.note_on(0, 64, 100);
+        // The counter is renumbered instead of wrapping, so 60 is still the oldest.
+        assert_eq!(allocator.note_on(0, 67, 100), 0);
+        assert!(allocator.states()[0].age > allocator.states()[1].age);
+        assert_eq!(allocator.note_on(0, 72, 100), 1);
+
+        let mut allocator: VoiceAllocator<TestVoice, _, 2> =
+            VoiceAllocator::new(Default::default(), StealNewest);
+        allocator.age = u32::MAX - 1;
+        allocator.note_on(0, 60, 100);
+        allocator.note_on(0, 64, 100);
+        assert_eq!(allocator.note_on(0, 67, 100), 1);
+    }
+
+    #[test]
+    fn test_allocator_steals_newest() {
+        let mut allocator: VoiceAllocator<TestVoice, _, 3> =
+            VoiceAllocator::new(Default::default(), StealNewest);
+        for note in [60, 64, 67].iter() {
+            allocator.note_on(0, *note, 100);
+        }
+        assert_eq!(allocator.note_on(0, 72, 100), 2);
+        assert_eq!(allocator.note_on(0, 74, 100), 2);
+
+        // The same note on another channel is a separate voice.
+        allocator.note_off(1, 60);
+        assert_eq!(allocator.held_count(), 3);
+        allocator.all_notes_off();
+        assert_eq!(allocator.held_count(), 0);
+    }
+}
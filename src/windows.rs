@@ -0,0 +1,173 @@
+use core::f64::consts::PI;
+
+/// Window function shapes.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Window {
+    Rectangular,
+    /// Raised cosine reaching zero at the ends, a good general purpose analysis window.
+    Hann,
+    /// Raised cosine on a pedestal, lower first sidelobe than Hann but slower sidelobe falloff.
+    Hamming,
+    /// Four term Blackman-Harris with sidelobes below -92 dB, for high dynamic range spectra.
+    BlackmanHarris,
+    /// Kaiser window with shape parameter beta, trading main lobe width against sidelobe level.
+    /// A beta of 0.0 is rectangular, 8.6 is similar to Blackman.
+    Kaiser(f32),
+}
+
+/// Symmetry of a generated window.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Symmetry {
+    /// First and last values are equal, for FIR filter design.
+    Symmetric,
+    /// One period of a window of length + 1, for FFT analysis and overlap add.
+    Periodic,
+}
+
+// Cosine evaluated with a Taylor series so tables can be generated at compile time.
+pub(crate) const fn cos(x: f64) -> f64 {
+    // Reduce to [-PI, PI], flooring by hand as f64::floor isn't const.
+    let turns = x / (2.0 * PI) + 0.5;
+    let mut whole = turns as i64 as f64;
+    if whole > turns {
+        whole -= 1.0;
+    }
+    let x = x - whole * 2.0 * PI;
+    let mut sum = 0.0;
+    let mut term = 1.0;
+    let mut n = 0;
+    while n < 40 {
+        sum += term;
+        term *= -x * x / ((n + 1) * (n + 2)) as f64;
+        n += 2;
+    }
+    sum
+}
+
+// Zeroth order modified Bessel function of the first kind.
+const fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let mut k = 1;
+    while k < 50 {
+        let factor = x / (2.0 * k as f64);
+        term *= factor * factor;
+        sum += term;
+        k += 1;
+    }
+    sum
+}
+
+const fn sqrt(x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    // Newton's method from a guess above the root.
+    let mut guess = if x > 1.0 { x } else { 1.0 };
+    let mut i = 0;
+    while i < 64 {
+        guess = 0.5 * (guess + x / guess);
+        i += 1;
+    }
+    guess
+}
+
+/// Value of sample `index` of a window of `len` samples.
+pub const fn value(window: Window, symmetry: Symmetry, index: usize, len: usize) -> f32 {
+    let span = match symmetry {
+        Symmetry::Symmetric => len as f64 - 1.0,
+        Symmetry::Periodic => len as f64,
+    };
+    if span <= 0.0 {
+        return 1.0;
+    }
+    let position = index as f64 / span;
+    let theta = 2.0 * PI * position;
+    let value = match window {
+        Window::Rectangular => 1.0,
+        Window::Hann => 0.5 - 0.5 * cos(theta),
+        Window::Hamming => 0.54 - 0.46 * cos(theta),
+        Window::BlackmanHarris => {
+            0.35875 - 0.48829 * cos(theta) + 0.14128 * cos(2.0 * theta) - 0.01168 * cos(3.0 * theta)
+        }
+        Window::Kaiser(beta) => {
+            let beta = beta as f64;
+            let x = 2.0 * position - 1.0;
+            bessel_i0(beta * sqrt(1.0 - x * x)) / bessel_i0(beta)
+        }
+    };
+    value as f32
+}
+
+/// Fill `output` with a window the length of the slice.
+pub fn fill(window: Window, symmetry: Symmetry, output: &mut [f32]) {
+    let len = output.len();
+    for (i, x) in output.iter_mut().enumerate() {
+        *x = value(window, symmetry, i, len);
+    }
+}
+
+/// Generate a window table, usable in a const to place it in flash.
+pub const fn table<const N: usize>(window: Window, symmetry: Symmetry) -> [f32; N] {
+    let mut output = [0.0; N];
+    let mut i = 0;
+    while i < N {
+        output[i] = value(window, symmetry, i, N);
+        i += 1;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HANN: [f32; 64] = table(Window::Hann, Symmetry::Periodic);
+
+    #[test]
+    fn test_const_math() {
+        for i in -40..40 {
+            let x = i as f64 * 0.37;
+            assert!((cos(x) - x.cos()).abs() < 1e-12);
+            assert!((sqrt(x.abs()) - x.abs().sqrt()).abs() < 1e-12);
+        }
+        // I0(1) and I0(5)
+        assert!((bessel_i0(1.0) - 1.2660658777520082).abs() < 1e-12);
+        assert!((bessel_i0(5.0) - 27.239871823604442).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_window_shapes() {
+        let mut window = [0.0; 65];
+        fill(Window::Hann, Symmetry::Symmetric, &mut window);
+        assert!(window[0].abs() < 1e-7 && window[64].abs() < 1e-7);
+        assert!((window[32] - 1.0).abs() < 1e-7);
+
+        fill(Window::Hamming, Symmetry::Symmetric, &mut window);
+        assert!((window[0] - 0.08).abs() < 1e-6);
+
+        fill(Window::BlackmanHarris, Symmetry::Symmetric, &mut window);
+        assert!(window[0] < 1e-4);
+        assert!((window[32] - 1.0).abs() < 1e-6);
+
+        fill(Window::Kaiser(0.0), Symmetry::Symmetric, &mut window);
+        assert!(window.iter().all(|x| (*x - 1.0).abs() < 1e-7));
+        fill(Window::Kaiser(8.6), Symmetry::Symmetric, &mut window);
+        assert!(window[0] < 2e-3 && (window[32] - 1.0).abs() < 1e-6);
+
+        for i in 0..32 {
+            assert!((window[i] - window[64 - i]).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_periodic_table() {
+        let mut window = [0.0; 64];
+        fill(Window::Hann, Symmetry::Periodic, &mut window);
+        assert_eq!(window, HANN);
+        // Periodic Hann windows at 50% overlap sum to a constant.
+        for i in 0..32 {
+            assert!((HANN[i] + HANN[i + 32] - 1.0).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Rendering processors and synth voices to WAV files, so changes can be listened to as well as
+//! plotted. Shared by the integration tests and the examples, std only.
+#![allow(dead_code)]
+
+use std::f32::consts::PI;
+use std::fs;
+use std::path::PathBuf;
+
+use embedded_dsp::processor::Processor;
+use rand::distributions::{Distribution, Uniform};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Standard input signals, all peaking at 0.5 to leave headroom for boosts.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TestSignal {
+    Silence,
+    /// A single sample at the start.
+    Impulse,
+    /// Sine at a frequency in Hz.
+    Sine(f32),
+    /// Exponential sine sweep between two frequencies in Hz over the whole signal.
+    Sweep(f32, f32),
+    /// Uniform white noise from a fixed seed, the same every run.
+    Noise,
+}
+
+pub fn test_signal(signal: TestSignal, sample_rate: f32, len: usize) -> Vec<f32> {
+    match signal {
+        TestSignal::Silence => vec![0.0; len],
+        TestSignal::Impulse => (0..len).map(|i| if i == 0 { 0.5 } else { 0.0 }).collect(),
+        TestSignal::Sine(freq) => (0..len)
+            .map(|i| 0.5 * (2.0 * PI * freq * i as f32 / sample_rate).sin())
+            .collect(),
+        TestSignal::Sweep(start, end) => {
+            let duration = len as f32 / sample_rate;
+            let ratio = (end / start).ln();
+            (0..len)
+                .map(|i| {
+                    let t = i as f32 / sample_rate;
+                    let phase =
+                        2.0 * PI * start * duration / ratio * ((t / duration * ratio).exp() - 1.0);
+                    0.5 * phase.sin()
+                })
+                .collect()
+        }
+        TestSignal::Noise => {
+            let mut rng = StdRng::seed_from_u64(0);
+            let range = Uniform::new_inclusive(-0.5, 0.5);
+            (0..len).map(|_| range.sample(&mut rng)).collect()
+        }
+    }
+}
+
+/// Run a signal through a processor one block at a time, as firmware would.
+pub fn render<P: Processor>(processor: &mut P, input: &[f32], block_len: usize) -> Vec<f32> {
+    let mut output = input.to_vec();
+    for block in output.chunks_mut(block_len.max(1)) {
+        processor.process_block(block);
+    }
+    output
+}
+
+/// Directory the renders are written to, target/wav, created if missing.
+pub fn wav_dir() -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("wav");
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+pub fn wav_path(name: &str) -> PathBuf {
+    wav_dir().join(name).with_extension("wav")
+}
+
+/// Write mono 32 bit float samples.
+pub fn write_wav(name: &str, samples: &[f32], sample_rate: f32) -> PathBuf {
+    let path = wav_path(name);
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: sample_rate as u32,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+    for sample in samples {
+        writer.write_sample(*sample).unwrap();
+    }
+    writer.finalize().unwrap();
+    path
+}
+
+/// Render a test signal through a processor to target/wav/`name`.wav, returning the output.
+pub fn render_wav<P: Processor>(
+    name: &str,
+    processor: &mut P,
+    signal: TestSignal,
+    sample_rate: f32,
+    seconds: f32,
+) -> Vec<f32> {
+    let input = test_signal(signal, sample_rate, (seconds * sample_rate) as usize);
+    let output = render(processor, &input, 48);
+    write_wav(name, &output, sample_rate);
+    output
+}
+
+/// Render a synth voice, anything producing one sample per call, to target/wav/`name`.wav.
+pub fn render_voice_wav<F: FnMut() -> f32>(
+    name: &str,
+    mut voice: F,
+    sample_rate: f32,
+    seconds: f32,
+) -> Vec<f32> {
+    let output: Vec<f32> = (0..(seconds * sample_rate) as usize)
+        .map(|_| voice())
+        .collect();
+    write_wav(name, &output, sample_rate);
+    output
+}
+
+/// Read back a render, for checking files written by the helpers.
+pub fn read_wav(name: &str) -> (Vec<f32>, u32) {
+    let mut reader = hound::WavReader::open(wav_path(name)).unwrap();
+    let sample_rate = reader.spec().sample_rate;
+    let samples = reader.samples::<f32>().map(|x| x.unwrap()).collect();
+    (samples, sample_rate)
+}
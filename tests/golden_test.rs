@@ -0,0 +1,170 @@
+//! Golden file regression tests. Each block renders the same reference input and is compared
+//! against its stored output in tests/golden, so changes to algorithms or math backends that
+//! audibly change the output fail instead of passing silently.
+//!
+//! After an intended change, listen to the new renders in target/wav and then regenerate the
+//! golden files with `UPDATE_GOLDEN=1 cargo test --test golden_test`.
+
+mod common;
+
+use std::path::PathBuf;
+
+use common::*;
+use embedded_dsp::delay::DelayLine;
+use embedded_dsp::distortion::{Distortion, Exciter};
+use embedded_dsp::dynamics::Compressor;
+use embedded_dsp::echo::TapeDelay;
+use embedded_dsp::filter::{AllPass, Biquad, Fir, OnePoleLowPass, ToneStack};
+use embedded_dsp::mix::Gain;
+use embedded_dsp::modulation::{Tremolo, Vibrato, Wah};
+use embedded_dsp::pitch::{Octaver, PitchShifter};
+use embedded_dsp::processor::Processor;
+
+const SAMPLE_RATE_F: f32 = 48000.0;
+const BLOCK_LEN: usize = 48;
+// Allowed error relative to the golden output. Loose enough for the differences between the
+// micromath and libm backends, tight enough to catch changes to the algorithms.
+const DEFAULT_TOLERANCE_DB: f32 = -40.0;
+
+// Impulse, then a sweep over the audio band, then noise.
+fn reference_input() -> Vec<f32> {
+    let mut input = test_signal(TestSignal::Impulse, SAMPLE_RATE_F, 512);
+    input.extend(test_signal(
+        TestSignal::Sweep(20.0, 20000.0),
+        SAMPLE_RATE_F,
+        2048,
+    ));
+    input.extend(test_signal(TestSignal::Noise, SAMPLE_RATE_F, 1024));
+    input
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(name)
+        .with_extension("wav")
+}
+
+fn read_golden(name: &str) -> Option<Vec<f32>> {
+    let mut reader = hound::WavReader::open(golden_path(name)).ok()?;
+    Some(reader.samples::<f32>().map(|x| x.unwrap()).collect())
+}
+
+fn write_golden(name: &str, samples: &[f32]) {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: SAMPLE_RATE_F as u32,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(golden_path(name), spec).unwrap();
+    for sample in samples {
+        writer.write_sample(*sample).unwrap();
+    }
+    writer.finalize().unwrap();
+}
+
+fn rms(samples: impl Iterator<Item = f32>) -> f32 {
+    let (sum, count) = samples.fold((0.0, 0), |(sum, count), x| (sum + x * x, count + 1));
+    (sum / count.max(1) as f32).sqrt()
+}
+
+/// Render the reference input through `processor` and compare with the golden output.
+fn check_golden<P: Processor>(name: &str, mut processor: P, tolerance_db: f32) {
+    let output = render(&mut processor, &reference_input(), BLOCK_LEN);
+    // Keep the latest render for listening to failures.
+    write_wav(&format!("golden_{}", name), &output, SAMPLE_RATE_F);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        write_golden(name, &output);
+        return;
+    }
+    let golden = read_golden(name).unwrap_or_else(|| {
+        panic!(
+            "missing golden file for {}, create it with UPDATE_GOLDEN=1",
+            name
+        )
+    });
+    assert_eq!(output.len(), golden.len(), "{} length changed", name);
+    let error = rms(output.iter().zip(golden.iter()).map(|(x, y)| x - y));
+    let error_db = 20.0
+        * (error / rms(golden.iter().copied()).max(1e-10))
+            .max(1e-10)
+            .log10();
+    assert!(
+        error_db < tolerance_db,
+        "{} differs from its golden output by {} dB",
+        name,
+        error_db
+    );
+}
+
+#[test]
+fn test_golden_filters() {
+    let mut biquad = Biquad::new(SAMPLE_RATE_F);
+    biquad.set_peak(1000.0, 2.0, 6.0);
+    check_golden("biquad_peak", biquad, DEFAULT_TOLERANCE_DB);
+
+    let mut one_pole = OnePoleLowPass::new(SAMPLE_RATE_F);
+    one_pole.set_freq(500.0);
+    check_golden("one_pole_low_pass", one_pole, DEFAULT_TOLERANCE_DB);
+
+    check_golden(
+        "tone_stack",
+        ToneStack::new(SAMPLE_RATE_F),
+        DEFAULT_TOLERANCE_DB,
+    );
+
+    let mut buffer = [0.0; 64];
+    let mut all_pass = AllPass::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+    all_pass.set_freq(2000.0);
+    check_golden("all_pass", all_pass, DEFAULT_TOLERANCE_DB);
+
+    let coefs = [0.25, 0.5, 0.25];
+    let mut buffer = [0.0; 3];
+    let fir = Fir::new(&coefs, DelayLine::new(&mut buffer));
+    check_golden("fir", fir, DEFAULT_TOLERANCE_DB);
+}
+
+#[test]
+fn test_golden_distortion_and_dynamics() {
+    let mut distortion = Distortion::new(SAMPLE_RATE_F);
+    distortion.set_gain(0.7);
+    check_golden("distortion", distortion, DEFAULT_TOLERANCE_DB);
+    check_golden("exciter", Exciter::new(SAMPLE_RATE_F), DEFAULT_TOLERANCE_DB);
+    check_golden(
+        "compressor",
+        Compressor::new(SAMPLE_RATE_F),
+        DEFAULT_TOLERANCE_DB,
+    );
+    let mut gain = Gain::new(SAMPLE_RATE_F);
+    gain.set_db(-6.0);
+    check_golden("gain", gain, DEFAULT_TOLERANCE_DB);
+}
+
+#[test]
+fn test_golden_modulation_and_delay() {
+    check_golden("tremolo", Tremolo::new(SAMPLE_RATE_F), DEFAULT_TOLERANCE_DB);
+
+    let mut wah = Wah::new(SAMPLE_RATE_F);
+    wah.set_position(1.0);
+    check_golden("wah", wah, DEFAULT_TOLERANCE_DB);
+
+    let mut buffer = [0.0; 1024];
+    let mut vibrato = Vibrato::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+    vibrato.set_depth(50.0);
+    check_golden("vibrato", vibrato, DEFAULT_TOLERANCE_DB);
+
+    let mut buffer = [0.0; 4096];
+    let mut tape_delay = TapeDelay::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+    tape_delay.set_delay(0.02);
+    tape_delay.set_feedback(0.5);
+    check_golden("tape_delay", tape_delay, DEFAULT_TOLERANCE_DB);
+
+    let mut buffer = [0.0; 4096];
+    let mut pitch_shifter = PitchShifter::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+    pitch_shifter.set_semitones(7.0);
+    check_golden("pitch_shifter", pitch_shifter, DEFAULT_TOLERANCE_DB);
+
+    check_golden("octaver", Octaver::new(SAMPLE_RATE_F), DEFAULT_TOLERANCE_DB);
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use common::*;
+use embedded_dsp::chain;
+use embedded_dsp::distortion::Distortion;
+use embedded_dsp::filter::Biquad;
+use embedded_dsp::synthesis::{Oscillator, WaveType};
+
+const SAMPLE_RATE_F: f32 = 44100.0;
+
+#[test]
+fn test_render_processor_wav() {
+    let mut filter = Biquad::new(SAMPLE_RATE_F);
+    filter.set_low_pass(3000.0, 0.707);
+    let mut processor = chain!(Distortion::new(SAMPLE_RATE_F), filter);
+    let output = render_wav(
+        "distortion_sweep",
+        &mut processor,
+        TestSignal::Sweep(20.0, 20000.0),
+        SAMPLE_RATE_F,
+        1.0,
+    );
+    let (samples, sample_rate) = read_wav("distortion_sweep");
+    assert_eq!(sample_rate, 44100);
+    assert_eq!(samples, output);
+    assert!(samples.iter().any(|x| x.abs() > 0.1));
+}
+
+#[test]
+fn test_render_voice_wav() {
+    let mut oscillator = Oscillator::new(WaveType::PolyBLEPSaw, SAMPLE_RATE_F, 110.0);
+    let output = render_voice_wav("saw_voice", || oscillator.process(), SAMPLE_RATE_F, 0.5);
+    assert_eq!(output.len(), 22050);
+    assert_eq!(
+        test_signal(TestSignal::Impulse, SAMPLE_RATE_F, 4),
+        [0.5, 0.0, 0.0, 0.0]
+    );
+}
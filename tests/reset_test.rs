@@ -0,0 +1,584 @@
+//! Every stateful type's `reset` clears its state while keeping its parameters, so rendering
+//! the same input after each reset repeats the first render exactly. Smoothed parameters jump to
+//! their targets on a reset, so the first render also starts from one.
+
+mod common;
+
+use common::*;
+use embedded_dsp::analysis::{
+    BeatTracker, DcNoiseAnalyzer, OnsetDetector, SpectrumAnalyzer, VoiceActivityDetector,
+};
+use embedded_dsp::cabinet::CabSim;
+use embedded_dsp::control::{CcMap, SlewLimiter, Smoother, SoftTakeover};
+use embedded_dsp::delay::DelayLine;
+use embedded_dsp::distortion::{Distortion, Exciter};
+use embedded_dsp::dynamics::{Compressor, Ducker, EnvelopeFollower, MultibandCompressor};
+use embedded_dsp::echo::TapeDelay;
+use embedded_dsp::filter::{
+    AllPass, AllPassSP, Biquad, Biquad4, Fir, LinkwitzRiley, OnePoleLowPass, StateVariable,
+    ToneStack,
+};
+use embedded_dsp::meter::{CorrelationMeter, LoudnessMeter, PeakMeter, RmsMeter, SilenceDetector};
+use embedded_dsp::mix::{Gain, LoudnessVolume};
+use embedded_dsp::modulation::{Tremolo, Vibrato, Wah};
+use embedded_dsp::pitch::{DetectionMode, Octaver, PitchDetector, PitchShifter, Yin};
+use embedded_dsp::processor::Processor;
+use embedded_dsp::stereo::{AutoPan, StereoWidener};
+use embedded_dsp::synthesis::{Oscillator, Oscillator4, WaveType};
+use embedded_dsp::vocoder::Vocoder;
+
+const SAMPLE_RATE_F: f32 = 48000.0;
+const BLOCK_LEN: usize = 48;
+
+// A tone, then a sweep over the audio band, then noise.
+fn reference_input() -> Vec<f32> {
+    let mut input = test_signal(TestSignal::Sine(220.0), SAMPLE_RATE_F, 2048);
+    input.extend(test_signal(
+        TestSignal::Sweep(20.0, 20000.0),
+        SAMPLE_RATE_F,
+        2048,
+    ));
+    input.extend(test_signal(TestSignal::Noise, SAMPLE_RATE_F, 2048));
+    input
+}
+
+/// Reset `state` and run it through `run` twice, resetting between the runs. Both runs must
+/// match and make some output.
+fn check_repeats<S>(
+    name: &str,
+    state: &mut S,
+    mut run: impl FnMut(&mut S) -> Vec<f32>,
+    reset: impl Fn(&mut S),
+) {
+    reset(state);
+    let first = run(state);
+    reset(state);
+    let second = run(state);
+    assert!(first.iter().any(|x| *x != 0.0), "{} made no output", name);
+    assert_eq!(first, second, "{} changed after reset", name);
+}
+
+fn check_reset<P: Processor>(name: &str, mut processor: P) {
+    let input = reference_input();
+    check_repeats(
+        name,
+        &mut processor,
+        |processor| render(processor, &input, BLOCK_LEN),
+        |processor| processor.reset(),
+    );
+}
+
+#[test]
+fn test_reset_filters() {
+    let mut biquad = Biquad::new(SAMPLE_RATE_F);
+    biquad.set_peak(1000.0, 2.0, 6.0);
+    check_reset("biquad", biquad);
+
+    let mut one_pole = OnePoleLowPass::new(SAMPLE_RATE_F);
+    one_pole.set_freq(500.0);
+    check_reset("one_pole_low_pass", one_pole);
+
+    check_reset("tone_stack", ToneStack::new(SAMPLE_RATE_F));
+
+    let mut buffer = [0.0; 64];
+    let mut all_pass = AllPass::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+    all_pass.set_freq(2000.0);
+    check_reset("all_pass", all_pass);
+
+    let mut buffer = [0.0; 2048];
+    let mut all_pass_sp = AllPassSP::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+    all_pass_sp.set_freq(0.02);
+    all_pass_sp.set_reverb_time(1.0);
+    check_reset("all_pass_sp", all_pass_sp);
+
+    let coefs = [0.25, 0.5, 0.25];
+    let mut buffer = [0.0; 3];
+    check_reset("fir", Fir::new(&coefs, DelayLine::new(&mut buffer)));
+}
+
+#[test]
+fn test_reset_multi_output_filters() {
+    let input = reference_input();
+
+    let mut state_variable = StateVariable::new(SAMPLE_RATE_F);
+    state_variable.set_freq(800.0);
+    state_variable.set_resonance(0.5);
+    check_repeats(
+        "state_variable",
+        &mut state_variable,
+        |filter| {
+            input
+                .iter()
+                .map(|x| {
+                    filter.process(*x);
+                    filter.get_low_pass() + filter.get_band_pass()
+                })
+                .collect()
+        },
+        StateVariable::reset,
+    );
+
+    let mut crossover = LinkwitzRiley::new(SAMPLE_RATE_F);
+    crossover.set_freq(500.0);
+    check_repeats(
+        "linkwitz_riley",
+        &mut crossover,
+        |crossover| {
+            input
+                .iter()
+                .flat_map(|x| {
+                    let (low, high) = crossover.process(*x);
+                    [low, high]
+                })
+                .collect()
+        },
+        LinkwitzRiley::reset,
+    );
+
+    let mut biquad4 = Biquad4::new();
+    let mut design = Biquad::new(SAMPLE_RATE_F);
+    for lane in 0..4 {
+        design.set_low_pass(500.0 * (lane + 1) as f32, 0.707);
+        biquad4.set_lane(lane, &design);
+    }
+    check_repeats(
+        "biquad4",
+        &mut biquad4,
+        |filter| input.iter().flat_map(|x| filter.process([*x; 4])).collect(),
+        Biquad4::reset,
+    );
+}
+
+#[test]
+fn test_reset_dynamics_and_distortion() {
+    let mut distortion = Distortion::new(SAMPLE_RATE_F);
+    distortion.set_gain(0.7);
+    check_reset("distortion", distortion);
+    check_reset("exciter", Exciter::new(SAMPLE_RATE_F));
+
+    let mut compressor = Compressor::new(SAMPLE_RATE_F);
+    compressor.set_threshold(-20.0);
+    compressor.set_auto_release(true);
+    check_reset("compressor", compressor);
+
+    let mut multiband = MultibandCompressor::new(SAMPLE_RATE_F, 3);
+    multiband.set_threshold(-20.0);
+    check_reset("multiband_compressor", multiband);
+
+    let input = reference_input();
+    let mut envelope = EnvelopeFollower::new(SAMPLE_RATE_F);
+    check_repeats(
+        "envelope_follower",
+        &mut envelope,
+        |envelope| input.iter().map(|x| envelope.process(*x)).collect(),
+        EnvelopeFollower::reset,
+    );
+
+    let mut ducker = Ducker::new(SAMPLE_RATE_F);
+    ducker.set_threshold(-20.0);
+    check_repeats(
+        "ducker",
+        &mut ducker,
+        |ducker| {
+            input
+                .iter()
+                .map(|x| ducker.process(0.5, *x) + ducker.get_gain_reduction())
+                .collect()
+        },
+        Ducker::reset,
+    );
+}
+
+#[test]
+fn test_reset_mix_and_cabinet() {
+    let mut gain = Gain::new(SAMPLE_RATE_F);
+    gain.set_db(-6.0);
+    check_reset("gain", gain);
+
+    let mut volume = LoudnessVolume::new(SAMPLE_RATE_F);
+    volume.set_volume(-20.0);
+    check_reset("loudness_volume", volume);
+
+    let impulse_response = [0.5, 0.3, -0.2, 0.1];
+    let mut buffer = [0.0; 4];
+    check_reset(
+        "cab_sim",
+        CabSim::new(&impulse_response, DelayLine::new(&mut buffer)),
+    );
+}
+
+#[test]
+fn test_reset_modulation_and_delay() {
+    let mut tremolo = Tremolo::new(SAMPLE_RATE_F);
+    tremolo.set_depth(1.0);
+    tremolo.set_rate(7.0);
+    check_reset("tremolo", tremolo);
+
+    let mut wah = Wah::new(SAMPLE_RATE_F);
+    wah.set_position(1.0);
+    check_reset("wah", wah);
+
+    let mut buffer = [0.0; 1024];
+    let mut vibrato = Vibrato::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+    vibrato.set_depth(50.0);
+    check_reset("vibrato", vibrato);
+
+    let mut buffer = [0.0; 4096];
+    let mut tape_delay = TapeDelay::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+    tape_delay.set_delay(0.02);
+    tape_delay.set_feedback(0.5);
+    check_reset("tape_delay", tape_delay);
+
+    let mut buffer = [0.0; 4096];
+    let mut pitch_shifter = PitchShifter::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+    pitch_shifter.set_semitones(7.0);
+    check_reset("pitch_shifter", pitch_shifter);
+
+    check_reset("octaver", Octaver::new(SAMPLE_RATE_F));
+}
+
+#[test]
+fn test_retrigger_restarts_lfos() {
+    // Only the LFO restarts, the delay line keeps its contents.
+    let input = reference_input();
+    let mut buffer = [0.0; 1024];
+    let mut vibrato = Vibrato::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+    vibrato.set_depth(50.0);
+    let first = render(&mut vibrato, &input, BLOCK_LEN);
+    vibrato.retrigger();
+    let second = render(&mut vibrato, &input, BLOCK_LEN);
+    assert_ne!(first, second);
+    vibrato.reset();
+    assert_eq!(render(&mut vibrato, &input, BLOCK_LEN), first);
+
+    // A retriggered tremolo modulates a constant input the same way again.
+    let mut tremolo = Tremolo::new(SAMPLE_RATE_F);
+    tremolo.set_rate(5.0);
+    let ones = [1.0; 4800];
+    render(&mut tremolo, &ones, BLOCK_LEN);
+    tremolo.retrigger();
+    let first = render(&mut tremolo, &ones, BLOCK_LEN);
+    tremolo.retrigger();
+    let second = render(&mut tremolo, &ones, BLOCK_LEN);
+    assert_eq!(first, second);
+
+    let mut auto_pan = AutoPan::new(SAMPLE_RATE_F);
+    auto_pan.set_rate(5.0);
+    let pan = |auto_pan: &mut AutoPan| -> Vec<f32> {
+        (0..4800)
+            .flat_map(|_| {
+                let (left, right) = auto_pan.process(1.0);
+                [left, right]
+            })
+            .collect()
+    };
+    let first = pan(&mut auto_pan);
+    auto_pan.retrigger();
+    assert_eq!(pan(&mut auto_pan), first);
+    auto_pan.reset();
+    assert_eq!(pan(&mut auto_pan), first);
+}
+
+#[test]
+fn test_reset_stereo() {
+    let input = reference_input();
+    let mut widener = StereoWidener::new(SAMPLE_RATE_F);
+    widener.set_width(1.5);
+    widener.set_bass_mono(true);
+    check_repeats(
+        "stereo_widener",
+        &mut widener,
+        |widener| {
+            input
+                .iter()
+                .flat_map(|x| {
+                    let (left, right) = widener.process(*x, 0.5 * *x);
+                    [left, right]
+                })
+                .collect()
+        },
+        StereoWidener::reset,
+    );
+}
+
+#[test]
+fn test_reset_oscillators() {
+    let mut oscillator = Oscillator::new(WaveType::Saw, SAMPLE_RATE_F, 110.0);
+    check_repeats(
+        "oscillator",
+        &mut oscillator,
+        |oscillator| (0..4800).map(|_| oscillator.process()).collect(),
+        Oscillator::reset,
+    );
+
+    let mut random = Oscillator::new(WaveType::RandomStep, SAMPLE_RATE_F, 50.0);
+    random.set_seed(7);
+    check_repeats(
+        "random_oscillator",
+        &mut random,
+        |oscillator| (0..4800).map(|_| oscillator.process()).collect(),
+        Oscillator::reset,
+    );
+
+    let mut oscillators =
+        Oscillator4::new(WaveType::Sine, SAMPLE_RATE_F, [110.0, 220.0, 330.0, 440.0]);
+    let run = |oscillators: &mut Oscillator4| -> Vec<f32> {
+        (0..4800).flat_map(|_| oscillators.process()).collect()
+    };
+    check_repeats("oscillator4", &mut oscillators, run, Oscillator4::reset);
+
+    // Resetting one lane restarts only that lane.
+    oscillators.reset();
+    let first = run(&mut oscillators);
+    oscillators.reset_lane(2);
+    let second = run(&mut oscillators);
+    for (frame, (a, b)) in first.chunks(4).zip(second.chunks(4)).enumerate() {
+        assert_eq!(a[2], b[2], "lane 2 differs at frame {}", frame);
+    }
+    assert_ne!(first, second);
+}
+
+#[test]
+fn test_reset_vocoder() {
+    let input = reference_input();
+    let mut vocoder: Vocoder<8> = Vocoder::new(SAMPLE_RATE_F);
+    vocoder.set_carrier_freq(110.0);
+    check_repeats(
+        "vocoder",
+        &mut vocoder,
+        |vocoder| input.iter().map(|x| vocoder.process(*x)).collect(),
+        Vocoder::reset,
+    );
+}
+
+#[test]
+fn test_reset_controls() {
+    let steps = [0.0, 1.0, 0.25, 0.75, 0.5];
+
+    let mut slew = SlewLimiter::new(SAMPLE_RATE_F);
+    slew.set_rise(10.0);
+    slew.set_fall(5.0);
+    check_repeats(
+        "slew_limiter",
+        &mut slew,
+        |slew| {
+            steps
+                .iter()
+                .flat_map(|x| (0..2400).map(move |_| *x))
+                .map(|x| slew.process(x))
+                .collect()
+        },
+        SlewLimiter::reset,
+    );
+
+    // A smoother jumps to its target, a CC map to the value of its current position.
+    let mut smoother = Smoother::new(SAMPLE_RATE_F);
+    smoother.set_target(1.0);
+    smoother.next();
+    smoother.reset();
+    assert_eq!(smoother.get_value(), 1.0);
+    assert!(smoother.is_settled());
+
+    let mut cc = CcMap::new(SAMPLE_RATE_F, 100.0, 200.0);
+    cc.set_cc(127);
+    cc.next();
+    cc.reset();
+    assert_eq!(cc.next(), 200.0);
+
+    // A soft takeover forgets the pot and hands it control again on its next reading.
+    let mut takeover = SoftTakeover::new();
+    takeover.set_value(0.5);
+    assert_eq!(takeover.process(0.9), None);
+    assert!(!takeover.is_picked_up());
+    takeover.reset();
+    assert!(takeover.is_picked_up());
+    assert_eq!(takeover.get_value(), 0.5);
+    assert_eq!(takeover.process(0.9), Some(0.9));
+}
+
+#[test]
+fn test_reset_meters() {
+    let input = reference_input();
+
+    let mut peak = PeakMeter::new(SAMPLE_RATE_F);
+    peak.process_block(&[2.0, -0.5]);
+    peak.reset();
+    assert!(peak.get_db() < -100.0);
+    assert_eq!(peak.take_clip_count(), 0);
+
+    let mut exponential = RmsMeter::new_exponential(SAMPLE_RATE_F, 0.3);
+    check_repeats(
+        "rms_meter",
+        &mut exponential,
+        |meter| {
+            input
+                .iter()
+                .map(|x| {
+                    meter.process(*x);
+                    meter.get_rms()
+                })
+                .collect()
+        },
+        RmsMeter::reset,
+    );
+    let mut buffer = [0.0; 480];
+    let mut sliding = RmsMeter::new_sliding(DelayLine::new(&mut buffer));
+    sliding.process_block(&input);
+    sliding.reset();
+    assert_eq!(sliding.get_rms(), 0.0);
+    sliding.process_block(&[0.5; 480]);
+    assert!((sliding.get_rms() - 0.5).abs() < 1e-3);
+
+    let mut loudness = LoudnessMeter::new(SAMPLE_RATE_F);
+    let measure = |meter: &mut LoudnessMeter| -> Vec<f32> {
+        let mut readings = Vec::new();
+        for block in input.chunks(480) {
+            for x in block.iter() {
+                meter.process(*x);
+            }
+            readings.push(meter.get_momentary());
+            readings.push(meter.get_integrated());
+        }
+        readings
+    };
+    let first = measure(&mut loudness);
+    // Resetting the integrated loudness alone keeps the momentary window.
+    loudness.reset_integrated();
+    loudness.reset();
+    assert_eq!(measure(&mut loudness), first);
+
+    let mut correlation = CorrelationMeter::new(SAMPLE_RATE_F);
+    correlation.process_block(&input, &input);
+    assert!(correlation.get_correlation() > 0.9);
+    correlation.reset();
+    correlation.process_block(&input[..2048], &input[..2048]);
+    let fresh = {
+        let mut meter = CorrelationMeter::new(SAMPLE_RATE_F);
+        meter.process_block(&input[..2048], &input[..2048]);
+        meter.get_correlation()
+    };
+    assert_eq!(correlation.get_correlation(), fresh);
+
+    let mut silence = SilenceDetector::new(SAMPLE_RATE_F);
+    silence.set_min_duration(0.01);
+    silence.process_block(&[0.0; 4800]);
+    assert!(silence.is_silent());
+    silence.reset();
+    assert!(!silence.is_silent());
+    assert_eq!(silence.get_duration(), 0.0);
+    assert_eq!(silence.get_silence_count(), 0);
+}
+
+#[test]
+fn test_reset_analysis() {
+    let input = reference_input();
+
+    let mut spectrum: SpectrumAnalyzer<512, 8> = SpectrumAnalyzer::new(SAMPLE_RATE_F);
+    check_repeats(
+        "spectrum_analyzer",
+        &mut spectrum,
+        |analyzer| {
+            input
+                .chunks(512)
+                .flat_map(|block| {
+                    analyzer.process_block(block);
+                    *analyzer.get_bands()
+                })
+                .collect()
+        },
+        SpectrumAnalyzer::reset,
+    );
+
+    let mut onsets = OnsetDetector::new(SAMPLE_RATE_F);
+    check_repeats(
+        "onset_detector",
+        &mut onsets,
+        |detector| {
+            input
+                .iter()
+                .map(|x| detector.process(*x) as u8 as f32 + detector.get_detection())
+                .collect()
+        },
+        OnsetDetector::reset,
+    );
+
+    // Clicks at 120 BPM.
+    let clicks: Vec<f32> = (0..4 * 48000)
+        .map(|n| if n % 24000 < 48 { 0.8 } else { 0.0 })
+        .collect();
+    let mut beats = BeatTracker::new(SAMPLE_RATE_F);
+    check_repeats(
+        "beat_tracker",
+        &mut beats,
+        |tracker| {
+            clicks
+                .iter()
+                .map(|x| tracker.process(*x) as u8 as f32 + tracker.get_phase())
+                .collect()
+        },
+        BeatTracker::reset,
+    );
+
+    let mut dc = DcNoiseAnalyzer::new(SAMPLE_RATE_F);
+    dc.set_dc_time(0.01);
+    let offset: Vec<f32> = input.iter().map(|x| x + 0.1).collect();
+    dc.process_block(&offset);
+    assert!(dc.get_dc_offset() > 0.05);
+    dc.reset();
+    assert_eq!(dc.get_dc_offset(), 0.0);
+    assert_eq!(dc.get_noise_floor(), None);
+
+    let mut voice = VoiceActivityDetector::new(SAMPLE_RATE_F);
+    check_repeats(
+        "voice_activity_detector",
+        &mut voice,
+        |detector| {
+            input
+                .chunks(480)
+                .map(|block| detector.process_block(block) as u8 as f32 + detector.get_confidence())
+                .collect()
+        },
+        VoiceActivityDetector::reset,
+    );
+}
+
+#[test]
+fn test_reset_pitch_detection() {
+    let tone = test_signal(TestSignal::Sine(220.0), SAMPLE_RATE_F, 4800);
+
+    let mut buffer = [0.0; 1024];
+    let mut detector = PitchDetector::new(SAMPLE_RATE_F, DelayLine::new(&mut buffer));
+    detector.set_mode(DetectionMode::Autocorrelation);
+    for x in tone.iter() {
+        detector.process(*x);
+    }
+    detector.detect();
+    assert!((detector.get_freq() - 220.0).abs() < 5.0);
+    detector.reset();
+    assert_eq!(detector.get_freq(), 0.0);
+    assert_eq!(detector.get_confidence(), 0.0);
+    // The window was cleared too, so silence detects nothing.
+    detector.detect();
+    assert_eq!(detector.get_freq(), 0.0);
+
+    let mut yin: Yin<1024> = Yin::new(SAMPLE_RATE_F);
+    yin.process_block(&tone);
+    assert!((yin.get_freq() - 220.0).abs() < 5.0);
+    yin.reset();
+    assert_eq!(yin.get_freq(), 0.0);
+    assert_eq!(yin.get_probability(), 0.0);
+}
+
+#[cfg(feature = "fixed")]
+#[test]
+fn test_reset_fixed_oscillator() {
+    use embedded_dsp::fixed::OscillatorQ15;
+
+    let mut oscillator = OscillatorQ15::new(WaveType::Saw, SAMPLE_RATE_F, 110.0);
+    check_repeats(
+        "oscillator_q15",
+        &mut oscillator,
+        |oscillator| (0..4800).map(|_| oscillator.process() as f32).collect(),
+        OscillatorQ15::reset,
+    );
+}